@@ -0,0 +1,730 @@
+use crate::mailer::SmtpSettings;
+use crate::models::archive::ArchivedEntry;
+use crate::models::audit::{record_change, snapshot, AuditEntry, AuditFilter};
+use crate::models::badge::{AttendanceEntry, Badge};
+use crate::models::classroom::{ClassroomDetails, Closure, NewClosure, OpeningHours, RoomRequest, Salle};
+use crate::models::composition::{CompositionRules, CompositionViolation};
+use crate::models::conflict::{AssignmentConflict, Conflict, ConflictOverride, NewConflict};
+use crate::models::demo::{DemoOptions, DemoSummary};
+use crate::models::etudiant::Etudiant;
+use crate::models::expertise::{JuryProfile, ReviewerCandidate, ReviewerRequest};
+use crate::models::honoraria::{HonorariaRates, HonorariaSummary, PaymentDetails};
+use crate::models::invite::{Invitee, NewInvitee};
+use crate::models::invite_soutenance::{InvitationCounts, InviteSoutenance};
+use crate::models::journal::OperationSummary;
+use crate::models::jury::{Jury, NewJury};
+use crate::models::jury_soutenance::{JuryRoleOption, JurySoutenance};
+use crate::models::notification::{NotificationPreference, PendingNotification, RecipientKind};
+use crate::models::occupancy::{RoomAgenda, UtilisationReport};
+use crate::models::outbox::{NotificationPreview, OutboxMessage, OutboxStatus};
+use crate::models::pfe::Pfe;
+use crate::models::reminder::Reminder;
+use crate::models::search::SearchResult;
+use crate::models::session::{AnneeUniversitaire, Session, SessionClosing};
+use crate::models::settings::{get_setting, set_setting};
+use crate::models::soutenance::{Soutenance, SoutenancePlan};
+use crate::models::specialite::Specialite;
+use crate::models::template::{EffectiveTemplate, Language, Rendered, StoredTemplate, TemplateInput, TemplateKind};
+use crate::models::user::{api_user, User};
+use crate::models::workload::{JuryCaps, JuryWorkload, SwapSuggestion};
+use crate::commands::{
+    archive, audit, badge, classroom, composition, conflict, demo, etudiant, expertise, honoraria, invite,
+    invite_soutenance, journal, jury, jury_soutenance, notification, occupancy, outbox, pfe, reminder, search, session,
+    soutenance, specialite, template, user, workload,
+};
+use crate::query::{Page, QueryOptions};
+use crate::AppState;
+use rand::RngCore;
+use rusqlite::Connection;
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::schema::{InstanceType, Schema, SingleOrVec};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+use tiny_http::{Header, Request, Response, Server};
+
+const ENABLED_KEY: &str = "api_enabled";
+const ADDRESS_KEY: &str = "api_address";
+/// Only a hash of the token is kept; the token itself is shown once, when generated.
+const TOKEN_KEY: &str = "api_token_sha256";
+const DEFAULT_ADDRESS: &str = "127.0.0.1:8787";
+const WORKERS: usize = 4;
+const MAX_BODY_BYTES: u64 = 8 * 1024 * 1024;
+
+/// The server currently listening, if any.
+static RUNNING: Mutex<Option<Running>> = Mutex::new(None);
+
+struct Running {
+    server: Arc<Server>,
+    stopped: Arc<AtomicBool>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl Running {
+    /// Lets the requests in progress finish, then closes the listening socket.
+    fn stop(self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        for _ in 0..self.workers.len() {
+            self.server.unblock();
+        }
+        for worker in self.workers {
+            let _ = worker.join();
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct ApiSettings {
+    pub enabled: bool,
+    /// Interface and port to listen on, such as `127.0.0.1:8787`. Anything other than a
+    /// loopback address exposes the planning to the network.
+    pub address: String,
+    #[serde(default, skip_deserializing)]
+    pub has_token: bool,
+    #[serde(default, skip_deserializing)]
+    pub running: bool,
+}
+
+pub fn load_settings(conn: &Connection) -> Result<ApiSettings, String> {
+    Ok(ApiSettings {
+        enabled: get_setting(conn, ENABLED_KEY)?.as_deref() == Some("1"),
+        address: get_setting(conn, ADDRESS_KEY)?.unwrap_or_else(|| DEFAULT_ADDRESS.to_string()),
+        has_token: get_setting(conn, TOKEN_KEY)?.is_some(),
+        running: RUNNING.lock().map(|running| running.is_some()).unwrap_or(false),
+    })
+}
+
+fn save_settings(conn: &Connection, settings: &ApiSettings) -> Result<(), String> {
+    let address = settings.address.trim();
+    address
+        .parse::<SocketAddr>()
+        .map_err(|e| format!("Invalid address {}: {}", address, e))?;
+    set_setting(conn, ENABLED_KEY, Some(if settings.enabled { "1" } else { "0" }))?;
+    set_setting(conn, ADDRESS_KEY, Some(address))
+}
+
+fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Compares in constant time, so response times do not leak how much of a guess matched.
+fn same(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Starts the server when it is enabled and a token exists, stopping the one already
+/// running. Called at launch and whenever the settings change.
+pub fn restart(app: &AppHandle) -> Result<(), String> {
+    let settings = app.state::<AppState>().db.read(load_settings)?;
+    let mut running = RUNNING.lock().map_err(|e| e.to_string())?;
+    if let Some(previous) = running.take() {
+        previous.stop();
+    }
+    if !settings.enabled || !settings.has_token {
+        return Ok(());
+    }
+    let server = Arc::new(bind(&settings.address)?);
+    let stopped = Arc::new(AtomicBool::new(false));
+    let workers = (0..WORKERS)
+        .map(|_| {
+            let (server, stopped, app) = (server.clone(), stopped.clone(), app.clone());
+            std::thread::spawn(move || loop {
+                match server.recv() {
+                    Ok(request) => handle(&app, request),
+                    Err(_) if stopped.load(Ordering::SeqCst) => break,
+                    Err(e) => eprintln!("API server: {}", e),
+                }
+            })
+        })
+        .collect();
+    *running = Some(Running { server, stopped, workers });
+    Ok(())
+}
+
+/// The previous server releases its port a moment after it is dropped, so binding to
+/// the same address again is retried for a little while.
+fn bind(address: &str) -> Result<Server, String> {
+    let mut attempts = 0;
+    loop {
+        match Server::http(address) {
+            Ok(server) => return Ok(server),
+            Err(_) if attempts < 20 => {
+                attempts += 1;
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            Err(e) => return Err(format!("Cannot listen on {}: {}", address, e)),
+        }
+    }
+}
+
+fn handle(app: &AppHandle, mut request: Request) {
+    let (status, body) = match respond(app, &mut request) {
+        Ok(value) => (200, value),
+        Err((status, message)) => (status, json!({ "error": message })),
+    };
+    let header = Header::from_bytes("Content-Type", "application/json; charset=utf-8").expect("valid header");
+    let response = Response::from_string(body.to_string()).with_status_code(status).with_header(header);
+    if let Err(e) = request.respond(response) {
+        eprintln!("API server: {}", e);
+    }
+}
+
+fn respond(app: &AppHandle, request: &mut Request) -> Result<Value, (u16, String)> {
+    let method = request.method().as_str().to_uppercase();
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let path = path.trim_end_matches('/');
+    if method == "GET" && path == "/openapi.json" {
+        return Ok(openapi());
+    }
+
+    let state = app.state::<AppState>();
+    let presented = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Authorization"))
+        .and_then(|header| header.value.as_str().strip_prefix("Bearer "))
+        .map(|token| hash_token(token.trim()));
+    let expected = state.db.read(|conn| get_setting(conn, TOKEN_KEY)).map_err(|e| (500, e))?;
+    match (presented, expected) {
+        (Some(presented), Some(expected)) if same(&presented, &expected) => {}
+        _ => return Err((401, "Missing or invalid bearer token".to_string())),
+    }
+
+    let routes = routes();
+    let (route, params) = routes
+        .iter()
+        .filter(|route| route.method == method)
+        .filter_map(|route| route.matches(path).map(|params| (route, params)))
+        .max_by_key(|(route, _)| route.literal_segments())
+        .ok_or_else(|| (404, format!("No endpoint for {} {}", method, path)))?;
+
+    let mut body = String::new();
+    request
+        .as_reader()
+        .take(MAX_BODY_BYTES)
+        .read_to_string(&mut body)
+        .map_err(|e| (400, format!("Unreadable body: {}", e)))?;
+    let mut args = match body.trim() {
+        "" => Map::new(),
+        body => match serde_json::from_str(body) {
+            Ok(Value::Object(args)) => args,
+            Ok(_) => return Err((400, "The body must be a JSON object of arguments".to_string())),
+            Err(e) => return Err((400, format!("Invalid JSON body: {}", e))),
+        },
+    };
+
+    // Query and path values arrive as text: keep them as text where a string is
+    // expected, read them as JSON otherwise.
+    let mut gen = generator();
+    let schema = (route.args)(&mut gen);
+    let text_args = |name: &str| {
+        let Schema::Object(object) = &schema else {
+            return false;
+        };
+        object
+            .object
+            .as_ref()
+            .and_then(|object| object.properties.get(name))
+            .is_some_and(|property| is_text(property, &gen))
+    };
+    let raw = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (decode(name), decode(value))
+        })
+        .chain(params);
+    for (name, value) in raw {
+        let value = if text_args(&name) {
+            Value::String(value)
+        } else {
+            serde_json::from_str(&value).unwrap_or(Value::String(value))
+        };
+        args.insert(name, value);
+    }
+
+    // `call!` blocks on the command, so it runs on this thread, as the API user.
+    AppState::acting_as(api_user(), || (route.handler)(args, state)).map_err(|e| {
+        let status = if e.to_lowercase().contains("not found") { 404 } else { 400 };
+        (status, e)
+    })
+}
+
+fn decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = |byte: u8| (byte as char).to_digit(16);
+                match (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                    (Some(high), Some(low)) => {
+                        decoded.push((high * 16 + low) as u8);
+                        i += 2;
+                    }
+                    _ => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Whether values matching `schema` are strings, following references and nullable
+/// wrappers.
+fn is_text(schema: &Schema, gen: &SchemaGenerator) -> bool {
+    let Schema::Object(object) = schema else {
+        return false;
+    };
+    if let Some(reference) = &object.reference {
+        let name = reference.rsplit('/').next().unwrap_or_default();
+        return gen.definitions().get(name).is_some_and(|schema| is_text(schema, gen));
+    }
+    match &object.instance_type {
+        Some(SingleOrVec::Single(kind)) => **kind == InstanceType::String,
+        Some(SingleOrVec::Vec(kinds)) => kinds.contains(&InstanceType::String),
+        None => object.subschemas.as_ref().is_some_and(|subschemas| {
+            [&subschemas.all_of, &subschemas.any_of]
+                .into_iter()
+                .flatten()
+                .flatten()
+                .any(|schema| is_text(schema, gen))
+        }),
+    }
+}
+
+fn generator() -> SchemaGenerator {
+    SchemaGenerator::new(SchemaSettings::openapi3())
+}
+
+type Handler = fn(Map<String, Value>, State<AppState>) -> Result<Value, String>;
+
+/// An endpoint and the command it runs. Arguments are read by name from the JSON body,
+/// the query string and the path, as `invoke` passes them.
+struct Route {
+    method: &'static str,
+    path: &'static str,
+    command: &'static str,
+    args: fn(&mut SchemaGenerator) -> Schema,
+    response: fn(&mut SchemaGenerator) -> Schema,
+    handler: Handler,
+}
+
+impl Route {
+    /// The `{name}` segments of the path, when it matches.
+    fn matches(&self, path: &str) -> Option<Vec<(String, String)>> {
+        let expected: Vec<&str> = self.path.split('/').collect();
+        let actual: Vec<&str> = path.split('/').collect();
+        if expected.len() != actual.len() {
+            return None;
+        }
+        let mut params = Vec::new();
+        for (expected, actual) in expected.into_iter().zip(actual) {
+            match expected.strip_prefix('{').and_then(|name| name.strip_suffix('}')) {
+                Some(name) => params.push((name.to_string(), decode(actual))),
+                None if expected == actual => {}
+                None => return None,
+            }
+        }
+        Some(params)
+    }
+
+    /// Fixed segments win over `{name}` ones, so `/classrooms/utilisation` is not read as
+    /// the classroom `utilisation`.
+    fn literal_segments(&self) -> usize {
+        self.path.split('/').filter(|segment| !segment.starts_with('{')).count()
+    }
+
+    fn path_params(&self) -> Vec<&'static str> {
+        self.path
+            .split('/')
+            .filter_map(|segment| segment.strip_prefix('{').and_then(|name| name.strip_suffix('}')))
+            .collect()
+    }
+}
+
+/// What a command returns, as the JSON of a successful response.
+trait Reply {
+    fn reply(self) -> Result<Value, String>;
+}
+
+impl<T: Serialize> Reply for Result<T, String> {
+    fn reply(self) -> Result<Value, String> {
+        serde_json::to_value(self?).map_err(|e| e.to_string())
+    }
+}
+
+impl<T: Serialize> Reply for Vec<T> {
+    fn reply(self) -> Result<Value, String> {
+        serde_json::to_value(self).map_err(|e| e.to_string())
+    }
+}
+
+macro_rules! call {
+    ([pure] $command:path, $state:ident, $($arg:ident),*) => {{
+        let _ = $state;
+        $command($($arg),*)
+    }};
+    ([] $command:path, $state:ident, $($arg:ident),*) => {
+        tauri::async_runtime::block_on($command($($arg,)* $state))
+    };
+}
+
+/// Declares the endpoints: method, path, command with the arguments it takes besides
+/// the state, and what it returns on success. `[pure]` marks commands without state.
+macro_rules! routes {
+    ($($method:ident $path:literal => $([$pure:ident])? $module:ident::$command:ident($($arg:ident: $ty:ty),*) -> $ret:ty;)*) => {
+        fn routes() -> Vec<Route> {
+            vec![$(
+                Route {
+                    method: stringify!($method),
+                    path: $path,
+                    command: stringify!($command),
+                    args: |gen| {
+                        #[derive(JsonSchema)]
+                        #[allow(dead_code)]
+                        struct Args {
+                            $($arg: $ty),*
+                        }
+                        Args::json_schema(gen)
+                    },
+                    response: |gen| gen.subschema_for::<$ret>(),
+                    handler: |args, state| {
+                        #[derive(Deserialize)]
+                        #[serde(deny_unknown_fields)]
+                        struct Args {
+                            $($arg: $ty),*
+                        }
+                        let Args { $($arg),* } = serde_json::from_value(Value::Object(args))
+                            .map_err(|e| format!("Invalid arguments: {}", e))?;
+                        call!([$($pure)?] $module::$command, state, $($arg),*).reply()
+                    },
+                },
+            )*]
+        }
+    };
+}
+
+// Commands that import or export a file at a given path are left out: the path would be
+// read or written on this machine, on behalf of whoever holds the token.
+routes! {
+    GET "/archive" => archive::get_archive(entity_type: Option<String>) -> Vec<ArchivedEntry>;
+    POST "/archive/restore" => archive::restore_archived(entity_type: String, ids: Vec<i32>) -> usize;
+    POST "/archive/purge" => archive::purge_archive(older_than_days: Option<i64>) -> usize;
+    GET "/settings/archive-retention" => archive::get_archive_retention_days() -> i64;
+    PUT "/settings/archive-retention" => archive::set_archive_retention_days(days: i64) -> ();
+
+    GET "/audit" => audit::get_audit_log(filter: Option<AuditFilter>) -> Vec<AuditEntry>;
+
+    GET "/soutenances/{soutenance_id}/badges" => badge::get_soutenance_badges(soutenance_id: i32) -> Vec<Badge>;
+    POST "/soutenances/{soutenance_id}/check-in" => badge::check_in_badge(token: String, soutenance_id: i32) -> AttendanceEntry;
+    GET "/soutenances/{soutenance_id}/attendance" => badge::get_attendance_list(soutenance_id: i32) -> Vec<AttendanceEntry>;
+    POST "/badges/rotate-key" => badge::rotate_badge_key() -> ();
+
+    POST "/classrooms" => classroom::create_classroom(name: String, details: Option<ClassroomDetails>) -> i32;
+    GET "/classrooms" => classroom::get_all_classrooms(options: Option<QueryOptions>) -> Page<Salle>;
+    GET "/classrooms/{id}" => classroom::get_classroom(id: i32) -> Salle;
+    PUT "/classrooms/{id}" => classroom::update_classroom(id: i32, name: String, details: Option<ClassroomDetails>) -> ();
+    DELETE "/classrooms" => classroom::delete_classrooms(ids: Vec<i32>) -> ();
+    GET "/classrooms/{salle_id}/soutenances" => classroom::get_classroom_soutenances(salle_id: i32, session_id: Option<i32>) -> Vec<i32>;
+    GET "/classrooms/{classroom_id}/opening-hours" => classroom::get_classroom_opening_hours(classroom_id: i32) -> Vec<OpeningHours>;
+    PUT "/classrooms/{classroom_id}/opening-hours" => classroom::set_classroom_opening_hours(classroom_id: i32, hours: Vec<OpeningHours>) -> ();
+    GET "/classrooms/{classroom_id}/closures" => classroom::get_classroom_closures(classroom_id: i32) -> Vec<Closure>;
+    POST "/classrooms/closures" => classroom::add_classroom_closure(closure: NewClosure) -> i32;
+    DELETE "/classrooms/closures/{id}" => classroom::delete_classroom_closure(id: i32) -> ();
+    POST "/classrooms/available" => classroom::find_available_classrooms(request: RoomRequest) -> Vec<Salle>;
+    GET "/classrooms/{classroom_id}/agenda" => occupancy::get_classroom_agenda(classroom_id: i32, date_from: String, date_to: String, session_id: Option<i32>) -> RoomAgenda;
+    GET "/classrooms/utilisation" => occupancy::get_classroom_utilisation(date_from: String, date_to: String, classroom_ids: Option<Vec<i32>>, session_id: Option<i32>) -> UtilisationReport;
+
+    GET "/specialites/{specialite_id}/composition-rules" => composition::get_composition_rules(specialite_id: i32) -> CompositionRules;
+    PUT "/composition-rules" => composition::set_composition_rules(rules: CompositionRules) -> ();
+    GET "/soutenances/{soutenance_id}/composition" => composition::get_soutenance_composition(soutenance_id: i32) -> Vec<CompositionViolation>;
+    GET "/composition-violations" => composition::get_composition_violations(specialite_id: Option<i32>, session_id: Option<i32>) -> Vec<CompositionViolation>;
+
+    GET "/conflicts" => conflict::get_jury_conflicts(jury_id: Option<i32>) -> Vec<Conflict>;
+    POST "/conflicts" => conflict::declare_jury_conflict(conflict: NewConflict) -> i32;
+    DELETE "/conflicts/{id}" => conflict::delete_jury_conflict(id: i32) -> ();
+    GET "/assignment-conflicts" => conflict::get_assignment_conflicts(session_id: Option<i32>) -> Vec<AssignmentConflict>;
+    GET "/conflict-overrides" => conflict::get_conflict_overrides(soutenance_id: Option<i32>) -> Vec<ConflictOverride>;
+
+    POST "/demo-data" => demo::generate_demo_data(options: DemoOptions) -> DemoSummary;
+    GET "/demo-data" => demo::get_demo_data() -> DemoSummary;
+    DELETE "/demo-data" => demo::purge_demo_data() -> usize;
+
+    POST "/students" => etudiant::create_student(firstname: String, lastname: String, address: String, specialite_id: i32, soutenance_id: Option<i32>, session_id: Option<i32>, email: Option<String>) -> i32;
+    GET "/students/{id}" => etudiant::get_student(id: i32) -> Etudiant;
+    PUT "/students/{id}" => etudiant::update_student(id: i32, firstname: String, lastname: String, address: String, specialite_id: i32, soutenance_id: i32, email: Option<String>) -> Etudiant;
+    DELETE "/students" => etudiant::delete_students(ids: Vec<i32>) -> ();
+    PUT "/students/{id}/grade" => etudiant::set_student_grade(id: i32, grade: Option<f64>) -> Etudiant;
+    PUT "/students/{id}/supervisor" => etudiant::set_student_supervisor(id: i32, supervisor_id: Option<i32>) -> Etudiant;
+    GET "/departments/{department_id}/students" => etudiant::get_students_by_department(department_id: i32, session_id: Option<i32>, options: Option<QueryOptions>) -> Page<Etudiant>;
+    GET "/specialites/{specialite_id}/students" => etudiant::get_specialite_students(specialite_id: i32, session_id: Option<i32>, options: Option<QueryOptions>) -> Page<Etudiant>;
+
+    GET "/juries/{jury_id}/profile" => expertise::get_jury_profile(jury_id: i32) -> JuryProfile;
+    PUT "/juries/{jury_id}/profile" => expertise::set_jury_profile(jury_id: i32, profile: JuryProfile) -> JuryProfile;
+    POST "/reviewers/suggest" => expertise::suggest_reviewers(request: ReviewerRequest) -> Vec<ReviewerCandidate>;
+
+    GET "/juries/{jury_id}/payment" => honoraria::get_jury_payment(jury_id: i32) -> PaymentDetails;
+    PUT "/juries/{jury_id}/payment" => honoraria::set_jury_payment(jury_id: i32, payment: PaymentDetails) -> ();
+    GET "/honoraria/rates" => honoraria::get_honoraria_rates() -> HonorariaRates;
+    PUT "/honoraria/rates" => honoraria::set_honoraria_rates(rates: HonorariaRates) -> ();
+    GET "/honoraria" => honoraria::compute_honoraria(session_id: Option<i32>) -> HonorariaSummary;
+
+    POST "/invitees" => invite::create_invite(invite: NewInvitee) -> i32;
+    GET "/invitees" => invite::get_all_invite(options: Option<QueryOptions>) -> Page<Invitee>;
+    GET "/invitees/{id}" => invite::get_invite(id: i32) -> Invitee;
+    PUT "/invitees/{id}" => invite::update_invite(id: i32, invite: NewInvitee) -> String;
+    DELETE "/invitees" => invite::delete_invite(ids: Vec<i32>) -> ();
+    GET "/invitees/{invite_id}/soutenances" => invite::get_invite_soutenances(invite_id: i32, session_id: Option<i32>) -> Vec<i32>;
+
+    POST "/soutenances/{soutenance_id}/invitees/{invite_id}" => invite_soutenance::create_invite_soutenance(invite_id: i32, soutenance_id: i32) -> ();
+    GET "/soutenances/{soutenance_id}/invitees/{invite_id}" => invite_soutenance::get_invite_soutenance(invite_id: i32, soutenance_id: i32) -> InviteSoutenance;
+    PUT "/soutenances/{soutenance_id}/invitees/{invite_id}" => invite_soutenance::update_invite_soutenance(invite_id: i32, soutenance_id: i32) -> ();
+    DELETE "/soutenances/{soutenance_id}/invitees/{invite_id}" => invite_soutenance::delete_invite_soutenance(invite_id: i32, soutenance_id: i32) -> ();
+    POST "/soutenances/{soutenance_id}/invitees/{invite_id}/token" => invite_soutenance::regenerate_rsvp_token(invite_id: i32, soutenance_id: i32) -> String;
+    GET "/soutenances/{soutenance_id}/invitations" => invite_soutenance::get_soutenance_invitations(soutenance_id: i32) -> Vec<InviteSoutenance>;
+    GET "/soutenances/{soutenance_id}/invitations/counts" => invite_soutenance::get_invitation_counts(soutenance_id: i32) -> InvitationCounts;
+    POST "/invitations/respond" => invite_soutenance::respond_to_invitation(token: String, accepted: bool) -> InviteSoutenance;
+    POST "/invitations/check-in" => invite_soutenance::check_in_invitation(token: String) -> InviteSoutenance;
+
+    POST "/journal/undo" => journal::undo() -> Option<OperationSummary>;
+    POST "/journal/redo" => journal::redo() -> Option<OperationSummary>;
+    GET "/journal" => journal::get_operation_history() -> Vec<OperationSummary>;
+
+    POST "/juries" => jury::create_jury(jury: NewJury) -> i32;
+    GET "/juries" => jury::get_all_jury(options: Option<QueryOptions>) -> Page<Jury>;
+    GET "/juries/{id}" => jury::get_jury(id: i32) -> Jury;
+    PUT "/juries/{id}" => jury::update_jury(id: i32, jury: NewJury) -> String;
+    DELETE "/juries" => jury::delete_jury(ids: Vec<i32>) -> ();
+    GET "/juries/{jury_id}/soutenances" => jury::get_jury_soutenances(jury_id: i32, session_id: Option<i32>) -> Vec<(i32, String)>;
+
+    GET "/jury-roles" => [pure] jury_soutenance::get_jury_roles() -> Vec<JuryRoleOption>;
+    POST "/soutenances/{soutenance_id}/juries/{jury_id}" => jury_soutenance::create_jury_soutenance(jury_id: i32, soutenance_id: i32, role: String, justification: Option<String>) -> ();
+    GET "/soutenances/{soutenance_id}/juries/{jury_id}" => jury_soutenance::get_jury_soutenance(jury_id: i32, soutenance_id: i32) -> JurySoutenance;
+    PUT "/soutenances/{soutenance_id}/juries/{jury_id}" => jury_soutenance::update_jury_soutenance(jury_id: i32, soutenance_id: i32, role: String) -> ();
+    DELETE "/soutenances/{soutenance_id}/juries/{jury_id}" => jury_soutenance::delete_jury_soutenance(jury_id: i32, soutenance_id: i32) -> ();
+
+    GET "/notifications/pending" => notification::get_pending_notifications(soutenance_id: Option<i32>) -> Vec<PendingNotification>;
+    POST "/notifications/pending/flush" => notification::flush_pending_notifications() -> usize;
+    DELETE "/soutenances/{soutenance_id}/notifications/pending" => notification::discard_pending_notifications(soutenance_id: i32) -> usize;
+    GET "/notification-preferences/{recipient_kind}/{recipient_id}" => notification::get_notification_preference(recipient_kind: RecipientKind, recipient_id: i32) -> NotificationPreference;
+    PUT "/notification-preferences/{recipient_kind}/{recipient_id}" => notification::set_notification_preference(recipient_kind: RecipientKind, recipient_id: i32, muted: bool) -> ();
+
+    GET "/smtp" => outbox::get_smtp_settings() -> SmtpSettings;
+    PUT "/smtp" => outbox::set_smtp_settings(settings: SmtpSettings) -> ();
+    POST "/smtp/test" => outbox::send_test_email(to: String) -> ();
+    GET "/soutenances/{soutenance_id}/notifications" => outbox::preview_soutenance_notifications(soutenance_id: i32) -> Vec<NotificationPreview>;
+    POST "/soutenances/{soutenance_id}/notifications" => outbox::enqueue_soutenance_notifications(soutenance_id: i32) -> usize;
+    DELETE "/soutenances/{soutenance_id}/notifications" => outbox::cancel_soutenance_notifications(soutenance_id: i32) -> usize;
+    GET "/outbox" => outbox::get_outbox(soutenance_id: Option<i32>, status: Option<OutboxStatus>) -> Vec<OutboxMessage>;
+    POST "/outbox/{id}/cancel" => outbox::cancel_outbox_message(id: i32) -> ();
+    POST "/outbox/{id}/resend" => outbox::resend_outbox_message(id: i32) -> i32;
+    POST "/outbox/flush" => outbox::flush_outbox() -> usize;
+
+    POST "/pfes" => pfe::create_pfe(specialite_id: i32, title: Option<String>, resume: Option<String>, company: Option<String>) -> i32;
+    GET "/pfes/{id}" => pfe::get_pfe(id: i32) -> Pfe;
+    PUT "/pfes/{id}" => pfe::update_pfe(id: i32, specialite_id: i32, title: Option<String>, resume: Option<String>, company: Option<String>) -> ();
+    DELETE "/pfes/{id}" => pfe::delete_pfe(id: i32) -> ();
+
+    GET "/reminders/offsets" => reminder::get_reminder_offsets() -> Vec<i64>;
+    PUT "/reminders/offsets" => reminder::set_reminder_offsets(offsets: Vec<i64>) -> ();
+    GET "/reminders" => reminder::get_upcoming_reminders(soutenance_id: Option<i32>) -> Vec<Reminder>;
+    POST "/reminders/{id}/cancel" => reminder::cancel_reminder(id: i32) -> ();
+    DELETE "/soutenances/{soutenance_id}/reminders" => reminder::cancel_soutenance_reminders(soutenance_id: i32) -> usize;
+
+    GET "/search" => search::global_search(query: String, kinds: Option<Vec<String>>, limit: Option<i64>) -> Vec<SearchResult>;
+
+    POST "/annees" => session::create_annee_universitaire(label: String, start_date: Option<String>, end_date: Option<String>) -> i32;
+    GET "/annees" => session::get_all_annees_universitaires() -> Vec<AnneeUniversitaire>;
+    POST "/sessions" => session::create_session(annee_id: i32, kind: String) -> i32;
+    GET "/sessions" => session::get_all_sessions(annee_id: Option<i32>) -> Vec<Session>;
+    GET "/sessions/active" => session::get_active_session() -> Option<Session>;
+    PUT "/sessions/active" => session::set_active_session(session_id: i32) -> Session;
+    POST "/sessions/{session_id}/close" => session::close_session(session_id: i32, next_session_id: Option<i32>) -> SessionClosing;
+
+    POST "/soutenances" => soutenance::create_soutenance(date: Option<String>, hour: Option<String>, specialite_id: i32, classroom_id: Option<i32>, pfe: Option<String>, session_id: Option<i32>, expected_attendees: Option<i32>) -> Soutenance;
+    POST "/soutenances/with-participants" => soutenance::create_soutenance_with_participants(plan: SoutenancePlan) -> Soutenance;
+    GET "/soutenances/{id}" => soutenance::get_soutenance(id: i32) -> Soutenance;
+    PUT "/soutenances/{id}" => soutenance::update_soutenance(id: i32, date: Option<String>, hour: Option<String>, specialite_id: i32, classroom_id: Option<i32>, pfe: Option<String>) -> ();
+    DELETE "/soutenances/{id}" => soutenance::delete_soutenance(id: i32) -> ();
+    GET "/soutenances/{soutenance_id}/students" => soutenance::get_soutenance_students(soutenance_id: i32) -> Vec<i32>;
+    GET "/soutenances/{soutenance_id}/juries" => soutenance::get_soutenance_jurys(soutenance_id: i32) -> Vec<(i32, String)>;
+    GET "/soutenances/{soutenance_id}/invitees" => soutenance::get_soutenance_invites(soutenance_id: i32) -> Vec<i32>;
+    GET "/specialites/{specialite_id}/soutenances" => soutenance::get_specialite_soutenances(specialite_id: i32, session_id: Option<i32>, options: Option<QueryOptions>) -> Page<Soutenance>;
+    GET "/settings/defence-duration" => soutenance::get_defence_duration() -> i32;
+    PUT "/settings/defence-duration" => soutenance::set_defence_duration(minutes: i32) -> ();
+
+    POST "/specialites" => specialite::create_specialite() -> i32;
+    GET "/specialites/{id}" => specialite::get_specialite(id: i32) -> Specialite;
+    DELETE "/specialites/{id}" => specialite::delete_specialite(id: i32) -> ();
+    GET "/specialites/{specialite_id}/pfes" => specialite::get_specialite_pfes(specialite_id: i32) -> Vec<i32>;
+
+    GET "/templates" => template::get_templates(kind: Option<TemplateKind>) -> Vec<StoredTemplate>;
+    GET "/templates/effective" => template::get_effective_template(kind: TemplateKind, language: Language, specialite_id: Option<i32>) -> EffectiveTemplate;
+    PUT "/templates" => template::save_template(template: TemplateInput) -> i32;
+    DELETE "/templates/{id}" => template::delete_template(id: i32) -> ();
+    POST "/templates/validate" => [pure] template::validate_template(kind: TemplateKind, language: Language, subject: String, body: String) -> Vec<String>;
+    POST "/templates/preview" => template::preview_template(kind: TemplateKind, language: Language, specialite_id: Option<i32>, subject: Option<String>, body: Option<String>) -> Rendered;
+    GET "/templates/variables" => [pure] template::get_template_variables(kind: TemplateKind) -> Vec<String>;
+    GET "/settings/template-language" => template::get_template_language() -> Language;
+    PUT "/settings/template-language" => template::set_template_language(language: Language) -> ();
+
+    POST "/users" => user::create_user(name: String, email: String, password: String) -> i32;
+    GET "/users/{id}" => user::get_user(id: i32) -> User;
+    PUT "/users/{id}" => user::update_user(id: i32, name: String, email: String, password: String) -> ();
+    DELETE "/users/{id}" => user::delete_user(id: i32) -> ();
+
+    GET "/workload" => workload::get_jury_workload(session_id: Option<i32>) -> Vec<JuryWorkload>;
+    GET "/workload/rebalancing" => workload::suggest_jury_rebalancing(session_id: Option<i32>) -> Vec<SwapSuggestion>;
+    POST "/soutenances/{soutenance_id}/jury-swap" => workload::apply_jury_swap(soutenance_id: i32, from_jury_id: i32, to_jury_id: i32) -> ();
+    GET "/jury-caps" => workload::get_jury_caps(jury_id: Option<i32>) -> JuryCaps;
+    PUT "/jury-caps" => workload::set_jury_caps(jury_id: Option<i32>, caps: JuryCaps) -> ();
+}
+
+/// OpenAPI 3 description of every endpoint, built from the argument and result types.
+pub fn openapi() -> Value {
+    let mut gen = generator();
+    let mut paths = Map::new();
+    for route in routes() {
+        let args = (route.args)(&mut gen);
+        let response = (route.response)(&mut gen);
+        let path_params = route.path_params();
+        let Schema::Object(mut args) = args else {
+            continue;
+        };
+        let object = args.object();
+        let required = object.required.clone();
+        let mut parameters = Vec::new();
+        let mut body = Map::new();
+        for (name, schema) in std::mem::take(&mut object.properties) {
+            let location = if path_params.contains(&name.as_str()) {
+                "path"
+            } else if route.method == "GET" {
+                "query"
+            } else {
+                body.insert(name, json!(schema));
+                continue;
+            };
+            parameters.push(json!({
+                "name": name,
+                "in": location,
+                "required": location == "path" || required.contains(&name),
+                "schema": schema,
+            }));
+        }
+        let mut operation = json!({
+            "operationId": route.command,
+            "parameters": parameters,
+            "responses": {
+                "200": { "description": "Success", "content": { "application/json": { "schema": response } } },
+                "400": { "$ref": "#/components/responses/Error" },
+                "401": { "$ref": "#/components/responses/Error" },
+                "404": { "$ref": "#/components/responses/Error" },
+            },
+        });
+        if !body.is_empty() {
+            let body_required: Vec<&String> = required.iter().filter(|name| body.contains_key(*name)).collect();
+            operation["requestBody"] = json!({
+                "required": !body_required.is_empty(),
+                "content": { "application/json": { "schema": {
+                    "type": "object",
+                    "properties": body,
+                    "required": body_required,
+                    "additionalProperties": false,
+                } } },
+            });
+        }
+        let entry = paths.entry(route.path.to_string()).or_insert_with(|| json!({}));
+        entry[route.method.to_lowercase()] = operation;
+    }
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Gestion des soutenances",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Arguments not in the path go in a JSON body, or in the query string for GET. \
+                Query values that are not strings are JSON, e.g. `options={\"page\":1}`.",
+        },
+        "servers": [{ "url": "/" }],
+        "security": [{ "bearer": [] }],
+        "paths": paths,
+        "components": {
+            "schemas": gen.definitions(),
+            "securitySchemes": { "bearer": { "type": "http", "scheme": "bearer" } },
+            "responses": {
+                "Error": {
+                    "description": "The command failed or the request was refused",
+                    "content": { "application/json": { "schema": {
+                        "type": "object",
+                        "properties": { "error": { "type": "string" } },
+                        "required": ["error"],
+                    } } },
+                },
+            },
+        },
+    })
+}
+
+#[tauri::command]
+pub async fn get_api_settings(state: State<'_, AppState>) -> Result<ApiSettings, String> {
+    state.read(load_settings).await
+}
+
+/// Saves the API settings and restarts the server with them.
+#[tauri::command]
+pub async fn set_api_settings(
+    settings: ApiSettings,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<ApiSettings, String> {
+    state.write(move |tx, actor| {
+        let keys = [ENABLED_KEY, ADDRESS_KEY].map(|name| [("key", name.to_string().into())]);
+        let before = keys
+            .iter()
+            .map(|key| snapshot(tx, "settings", key))
+            .collect::<Result<Vec<_>, _>>()?;
+        save_settings(tx, &settings)?;
+        for (key, before) in keys.iter().zip(before) {
+            record_change(tx, actor, "settings", key, before)?;
+        }
+        Ok(())
+    })
+    .await?;
+    crate::blocking(move || restart(&app)).await?;
+    state.read(load_settings).await
+}
+
+/// Replaces the API token and returns the new one. It cannot be read back later, and the
+/// previous token stops working at once.
+#[tauri::command]
+pub async fn regenerate_api_token(app: AppHandle, state: State<'_, AppState>) -> Result<String, String> {
+    let mut bytes = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    let token: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    let hashed = hash_token(&token);
+    state
+        .write(move |tx, actor| {
+            let key = [("key", TOKEN_KEY.to_string().into())];
+            let before = snapshot(tx, "settings", &key)?;
+            set_setting(tx, TOKEN_KEY, Some(&hashed))?;
+            record_change(tx, actor, "settings", &key, before)
+        })
+        .await?;
+    // The server only runs once a token exists.
+    crate::blocking(move || restart(&app)).await?;
+    Ok(token)
+}
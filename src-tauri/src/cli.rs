@@ -0,0 +1,224 @@
+//! Headless entry point for scripting administration tasks, e.g. from cron. Opens the same
+//! database as the desktop app; changes are audited without a user.
+
+use crate::models::template::{self, Language};
+use crate::models::demo::{self, DemoOptions};
+use crate::models::{conflict, etudiant, notification, outbox, reminder, soutenance};
+use crate::{backup_to, get_db_path, open_database};
+use rusqlite::Connection;
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "Usage: gestion-soutenances-cli [--db PATH] <command> [options]
+
+Commands:
+  import-students <file.csv> [--session ID]
+      Create the students listed in a CSV file with the columns firstname, lastname,
+      specialite_id and optionally address, email and soutenance_id.
+  export-planning <file.csv> [--session ID]
+      Write the planning of a session, one line per soutenance.
+  pv <directory> [--session ID] [--soutenance ID] [--language fr|en|ar]
+      Write the procès-verbal of each soutenance as pv-<id>.html.
+  backup <file.db>
+      Write a consistent copy of the database.
+  scheduler
+      Run one pass of the background jobs: batched notifications, reminders and the outbox.
+  conflicts [--session ID]
+      List jury assignments that go against a declared conflict.
+  demo-data [--seed N] [--students N] [--classrooms N] [--juries N] [--start YYYY-MM-DD] [--session ID]
+      Fill a session with realistic fake students, juries, rooms, PFEs and a graded
+      planning. The same seed gives the same data; --students is per specialité.
+  purge-demo-data
+      Remove everything created by demo-data.
+
+The session defaults to the active one.";
+
+/// Arguments left once the options a command accepts are taken out.
+struct Args {
+    positional: Vec<String>,
+    options: Vec<(String, String)>,
+}
+
+impl Args {
+    fn parse(args: Vec<String>, accepted: &[&str]) -> Result<Args, String> {
+        let mut parsed = Args {
+            positional: Vec::new(),
+            options: Vec::new(),
+        };
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(name) if accepted.contains(&name) => {
+                    let value = args.next().ok_or_else(|| format!("--{} needs a value", name))?;
+                    parsed.options.push((name.to_string(), value));
+                }
+                Some(name) => return Err(format!("Unknown option --{}", name)),
+                None => parsed.positional.push(arg),
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn option(&self, name: &str) -> Option<&str> {
+        self.options.iter().rev().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    fn id(&self, name: &str) -> Result<Option<i32>, String> {
+        self.number(name)
+    }
+
+    fn number<T: std::str::FromStr>(&self, name: &str) -> Result<Option<T>, String> {
+        self.option(name)
+            .map(|v| v.parse().map_err(|_| format!("--{} expects a number, got '{}'", name, v)))
+            .transpose()
+    }
+
+    /// The only positional argument, named `what` in the error message.
+    fn single(&self, what: &str) -> Result<&str, String> {
+        match self.positional.as_slice() {
+            [value] => Ok(value),
+            [] => Err(format!("Missing {}", what)),
+            _ => Err(format!("Unexpected argument '{}'", self.positional[1])),
+        }
+    }
+
+    fn none(&self) -> Result<(), String> {
+        match self.positional.first() {
+            Some(arg) => Err(format!("Unexpected argument '{}'", arg)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Runs the command line and returns the process exit code.
+pub fn main(args: Vec<String>) -> i32 {
+    match run(args) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+fn run(mut args: Vec<String>) -> Result<(), String> {
+    let mut db_path = None;
+    if args.first().map(String::as_str) == Some("--db") {
+        if args.len() < 2 {
+            return Err(USAGE.to_string());
+        }
+        db_path = Some(PathBuf::from(args.remove(1)));
+        args.remove(0);
+    }
+    if args.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    let command = args.remove(0);
+    if command == "help" || command == "--help" || command == "-h" {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    let accepted: &[&str] = match command.as_str() {
+        "import-students" | "export-planning" | "conflicts" => &["session"],
+        "pv" => &["session", "soutenance", "language"],
+        "demo-data" => &["seed", "students", "classrooms", "juries", "start", "session"],
+        "backup" | "scheduler" | "purge-demo-data" => &[],
+        _ => return Err(format!("Unknown command '{}'\n\n{}", command, USAGE)),
+    };
+    let args = Args::parse(args, accepted)?;
+    let mut conn = open_database(&db_path.unwrap_or_else(get_db_path));
+    conn.busy_timeout(std::time::Duration::from_secs(5)).map_err(|e| e.to_string())?;
+
+    match command.as_str() {
+        "import-students" => {
+            let (path, session_id) = (args.single("CSV file")?, args.id("session")?);
+            let count = in_transaction(&mut conn, |tx| etudiant::import_students_csv(tx, &None, path, session_id))?;
+            println!("Imported {} students", count);
+        }
+        "export-planning" => {
+            let count = soutenance::write_planning_csv(&conn, args.single("output file")?, args.id("session")?)?;
+            println!("Exported {} soutenances", count);
+        }
+        "pv" => export_pvs(&conn, &args)?,
+        "backup" => {
+            let path = args.single("output file")?;
+            backup_to(&conn, Path::new(path))?;
+            println!("Backed up to {}", path);
+        }
+        "scheduler" => {
+            args.none()?;
+            let batched = notification::flush_pending(&conn, false)?;
+            let reminders = reminder::process_due(&conn)?;
+            let sent = outbox::process_due(&conn)?;
+            println!("Queued {} notifications and {} reminders, processed {} emails", batched, reminders, sent);
+        }
+        "conflicts" => {
+            args.none()?;
+            let conflicts = conflict::session_conflicts(&conn, args.id("session")?)?;
+            if conflicts.is_empty() {
+                println!("No conflicts");
+            }
+            for c in conflicts {
+                let status = match c.justification {
+                    Some(justification) => format!("overridden: {}", justification),
+                    None => "unresolved".to_string(),
+                };
+                println!(
+                    "soutenance {} / jury {}: {} ({})",
+                    c.soutenance_id,
+                    c.jury_id,
+                    c.reasons.join("; "),
+                    status
+                );
+            }
+        }
+        "demo-data" => {
+            args.none()?;
+            let options = DemoOptions {
+                seed: args.number("seed")?.unwrap_or(0),
+                students_per_specialite: args.number("students")?,
+                specialite_ids: None,
+                classrooms: args.number("classrooms")?,
+                juries: args.number("juries")?,
+                start_date: args.option("start").map(str::to_string),
+                session_id: args.id("session")?,
+            };
+            let summary = in_transaction(&mut conn, |tx| demo::generate_demo_data(tx, &None, options))?;
+            println!(
+                "Created {} students, {} juries, {} classrooms, {} PFEs and {} soutenances",
+                summary.students, summary.juries, summary.classrooms, summary.pfes, summary.soutenances
+            );
+        }
+        "purge-demo-data" => {
+            args.none()?;
+            let purged = in_transaction(&mut conn, |tx| demo::purge_demo_data(tx, &None))?;
+            println!("Removed {} demo rows", purged);
+        }
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+/// Runs `f` in a transaction committed only if it succeeds.
+fn in_transaction<T>(conn: &mut Connection, f: impl FnOnce(&Connection) -> Result<T, String>) -> Result<T, String> {
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let value = f(&tx)?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(value)
+}
+
+fn export_pvs(conn: &Connection, args: &Args) -> Result<(), String> {
+    let directory = Path::new(args.single("output directory")?);
+    std::fs::create_dir_all(directory).map_err(|e| format!("Failed to create {}: {}", directory.display(), e))?;
+    let language = args.option("language").map(Language::parse).transpose()?;
+    let ids = match args.id("soutenance")? {
+        Some(id) => vec![id],
+        None => soutenance::session_soutenance_ids(conn, args.id("session")?)?,
+    };
+    for id in &ids {
+        let (pv, context) = template::pv_source(conn, *id, language).map_err(|e| format!("Soutenance {}: {}", id, e))?;
+        let path = directory.join(format!("pv-{}.html", id));
+        template::write_pv(&path.to_string_lossy(), &pv, &context).map_err(|e| format!("Soutenance {}: {}", id, e))?;
+    }
+    println!("Wrote {} PVs to {}", ids.len(), directory.display());
+    Ok(())
+}
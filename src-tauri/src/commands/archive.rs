@@ -0,0 +1,37 @@
+use crate::models::archive::{self, ArchivedEntry};
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub async fn get_archive(
+    entity_type: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<ArchivedEntry>, String> {
+    state.read(move |conn| archive::get_archive(conn, entity_type)).await
+}
+
+#[tauri::command]
+pub async fn restore_archived(entity_type: String, ids: Vec<i32>, state: State<'_, AppState>) -> Result<usize, String> {
+    state
+        .write(move |tx, actor| archive::restore_archived(tx, actor, entity_type, ids))
+        .await
+}
+
+#[tauri::command]
+pub async fn purge_archive(older_than_days: Option<i64>, state: State<'_, AppState>) -> Result<usize, String> {
+    state
+        .write(move |tx, actor| archive::purge_archive(tx, actor, older_than_days))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_archive_retention_days(state: State<'_, AppState>) -> Result<i64, String> {
+    state.read(archive::get_archive_retention_days).await
+}
+
+#[tauri::command]
+pub async fn set_archive_retention_days(days: i64, state: State<'_, AppState>) -> Result<(), String> {
+    state
+        .write(move |tx, actor| archive::set_archive_retention_days(tx, actor, days))
+        .await
+}
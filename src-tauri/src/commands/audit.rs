@@ -0,0 +1,19 @@
+use crate::models::audit::{self, AuditEntry, AuditFilter};
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub async fn get_audit_log(filter: Option<AuditFilter>, state: State<'_, AppState>) -> Result<Vec<AuditEntry>, String> {
+    state.read(move |conn| audit::get_audit_log(conn, filter)).await
+}
+
+#[tauri::command]
+pub async fn export_audit_log_csv(
+    path: String,
+    filter: Option<AuditFilter>,
+    state: State<'_, AppState>,
+) -> Result<usize, String> {
+    state
+        .read(move |conn| audit::export_audit_log_csv(conn, &path, filter))
+        .await
+}
@@ -0,0 +1,58 @@
+use crate::models::badge::{self, AttendanceEntry, Badge};
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub async fn get_soutenance_badges(soutenance_id: i32, state: State<'_, AppState>) -> Result<Vec<Badge>, String> {
+    state
+        .write(move |tx, _| badge::get_soutenance_badges(tx, soutenance_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn export_soutenance_badges(
+    path: String,
+    soutenance_id: i32,
+    state: State<'_, AppState>,
+) -> Result<usize, String> {
+    state
+        .write(move |tx, _| badge::export_soutenance_badges(tx, path, soutenance_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn check_in_badge(
+    token: String,
+    soutenance_id: i32,
+    state: State<'_, AppState>,
+) -> Result<AttendanceEntry, String> {
+    state
+        .write(move |tx, actor| badge::check_in_badge(tx, actor, token, soutenance_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_attendance_list(
+    soutenance_id: i32,
+    state: State<'_, AppState>,
+) -> Result<Vec<AttendanceEntry>, String> {
+    state
+        .read(move |conn| badge::get_attendance_list(conn, soutenance_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn export_attendance_csv(
+    path: String,
+    soutenance_id: i32,
+    state: State<'_, AppState>,
+) -> Result<usize, String> {
+    state
+        .read(move |conn| badge::export_attendance_csv(conn, path, soutenance_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn rotate_badge_key(state: State<'_, AppState>) -> Result<(), String> {
+    state.write(move |tx, _| badge::rotate_badge_key(tx)).await
+}
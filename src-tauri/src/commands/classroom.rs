@@ -0,0 +1,109 @@
+use crate::models::classroom::{self, ClassroomDetails, Closure, NewClosure, OpeningHours, RoomRequest, Salle};
+use crate::query::{Page, QueryOptions};
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub async fn create_classroom(
+    name: String,
+    details: Option<ClassroomDetails>,
+    state: State<'_, AppState>,
+) -> Result<i32, String> {
+    state
+        .write(move |tx, actor| classroom::create_classroom(tx, actor, name, details))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_classroom(id: i32, state: State<'_, AppState>) -> Result<Salle, String> {
+    state.read(move |conn| classroom::get_classroom(conn, id)).await
+}
+
+#[tauri::command]
+pub async fn update_classroom(
+    id: i32,
+    name: String,
+    details: Option<ClassroomDetails>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .write(move |tx, actor| classroom::update_classroom(tx, actor, id, name, details))
+        .await
+}
+
+#[tauri::command]
+pub async fn delete_classrooms(ids: Vec<i32>, state: State<'_, AppState>) -> Result<(), String> {
+    state
+        .write(move |tx, actor| classroom::delete_classrooms(tx, actor, ids))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_all_classrooms(
+    options: Option<QueryOptions>,
+    state: State<'_, AppState>,
+) -> Result<Page<Salle>, String> {
+    state
+        .read(move |conn| classroom::get_all_classrooms(conn, options))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_classroom_soutenances(
+    salle_id: i32,
+    session_id: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<Vec<i32>, String> {
+    state
+        .read(move |conn| classroom::get_classroom_soutenances(conn, salle_id, session_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_classroom_opening_hours(
+    classroom_id: i32,
+    state: State<'_, AppState>,
+) -> Result<Vec<OpeningHours>, String> {
+    state
+        .read(move |conn| classroom::get_classroom_opening_hours(conn, classroom_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn set_classroom_opening_hours(
+    classroom_id: i32,
+    hours: Vec<OpeningHours>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .write(move |tx, actor| classroom::set_classroom_opening_hours(tx, actor, classroom_id, hours))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_classroom_closures(classroom_id: i32, state: State<'_, AppState>) -> Result<Vec<Closure>, String> {
+    state
+        .read(move |conn| classroom::get_classroom_closures(conn, classroom_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn add_classroom_closure(closure: NewClosure, state: State<'_, AppState>) -> Result<i32, String> {
+    state
+        .write(move |tx, actor| classroom::add_classroom_closure(tx, actor, closure))
+        .await
+}
+
+#[tauri::command]
+pub async fn delete_classroom_closure(id: i32, state: State<'_, AppState>) -> Result<(), String> {
+    state
+        .write(move |tx, actor| classroom::delete_classroom_closure(tx, actor, id))
+        .await
+}
+
+#[tauri::command]
+pub async fn find_available_classrooms(request: RoomRequest, state: State<'_, AppState>) -> Result<Vec<Salle>, String> {
+    state
+        .read(move |conn| classroom::find_available_classrooms(conn, request))
+        .await
+}
@@ -0,0 +1,38 @@
+use crate::models::composition::{self, CompositionRules, CompositionViolation};
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub async fn get_composition_rules(specialite_id: i32, state: State<'_, AppState>) -> Result<CompositionRules, String> {
+    state
+        .read(move |conn| composition::get_composition_rules(conn, specialite_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn set_composition_rules(rules: CompositionRules, state: State<'_, AppState>) -> Result<(), String> {
+    state
+        .write(move |tx, actor| composition::set_composition_rules(tx, actor, rules))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_soutenance_composition(
+    soutenance_id: i32,
+    state: State<'_, AppState>,
+) -> Result<Vec<CompositionViolation>, String> {
+    state
+        .read(move |conn| composition::get_soutenance_composition(conn, soutenance_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_composition_violations(
+    specialite_id: Option<i32>,
+    session_id: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<Vec<CompositionViolation>, String> {
+    state
+        .read(move |conn| composition::get_composition_violations(conn, specialite_id, session_id))
+        .await
+}
@@ -0,0 +1,44 @@
+use crate::models::conflict::{self, AssignmentConflict, Conflict, ConflictOverride, NewConflict};
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub async fn get_jury_conflicts(jury_id: Option<i32>, state: State<'_, AppState>) -> Result<Vec<Conflict>, String> {
+    state
+        .read(move |conn| conflict::get_jury_conflicts(conn, jury_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn declare_jury_conflict(conflict: NewConflict, state: State<'_, AppState>) -> Result<i32, String> {
+    state
+        .write(move |tx, actor| conflict::declare_jury_conflict(tx, actor, conflict))
+        .await
+}
+
+#[tauri::command]
+pub async fn delete_jury_conflict(id: i32, state: State<'_, AppState>) -> Result<(), String> {
+    state
+        .write(move |tx, actor| conflict::delete_jury_conflict(tx, actor, id))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_assignment_conflicts(
+    session_id: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<Vec<AssignmentConflict>, String> {
+    state
+        .read(move |conn| conflict::get_assignment_conflicts(conn, session_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_conflict_overrides(
+    soutenance_id: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<Vec<ConflictOverride>, String> {
+    state
+        .read(move |conn| conflict::get_conflict_overrides(conn, soutenance_id))
+        .await
+}
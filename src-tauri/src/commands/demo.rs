@@ -0,0 +1,20 @@
+use crate::models::demo::{self, DemoOptions, DemoSummary};
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub async fn generate_demo_data(options: DemoOptions, state: State<'_, AppState>) -> Result<DemoSummary, String> {
+    state
+        .write(move |tx, actor| demo::generate_demo_data(tx, actor, options))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_demo_data(state: State<'_, AppState>) -> Result<DemoSummary, String> {
+    state.read(demo::get_demo_data).await
+}
+
+#[tauri::command]
+pub async fn purge_demo_data(state: State<'_, AppState>) -> Result<usize, String> {
+    state.write(demo::purge_demo_data).await
+}
@@ -0,0 +1,98 @@
+use crate::models::etudiant::{self, Etudiant, NewStudent};
+use crate::query::{Page, QueryOptions};
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn create_student(
+    firstname: String,
+    lastname: String,
+    address: String,
+    specialite_id: i32,
+    soutenance_id: Option<i32>,
+    session_id: Option<i32>,
+    email: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<i32, String> {
+    let student = NewStudent {
+        firstname,
+        lastname,
+        address,
+        specialite_id,
+        soutenance_id,
+        session_id,
+        email,
+    };
+    state.write(move |tx, actor| etudiant::create_student(tx, actor, &student)).await
+}
+
+#[tauri::command]
+pub async fn import_students_csv(
+    path: String,
+    session_id: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<usize, String> {
+    state.write(move |tx, actor| etudiant::import_students_csv(tx, actor, &path, session_id)).await
+}
+
+#[tauri::command]
+pub async fn get_student(id: i32, state: State<'_, AppState>) -> Result<Etudiant, String> {
+    state.read(move |conn| etudiant::get_student(conn, id)).await
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn update_student(
+    id: i32,
+    firstname: String,
+    lastname: String,
+    address: String,
+    specialite_id: i32,
+    soutenance_id: i32,
+    email: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Etudiant, String> {
+    state.write(move |tx, actor| {
+        etudiant::update_student(tx, actor, id, firstname, lastname, address, specialite_id, soutenance_id, email)
+    }).await
+}
+
+#[tauri::command]
+pub async fn delete_students(ids: Vec<i32>, state: State<'_, AppState>) -> Result<(), String> {
+    state.write(move |tx, actor| etudiant::delete_students(tx, actor, ids)).await
+}
+
+#[tauri::command]
+pub async fn get_students_by_department(
+    department_id: i32,
+    session_id: Option<i32>,
+    options: Option<QueryOptions>,
+    state: State<'_, AppState>,
+) -> Result<Page<Etudiant>, String> {
+    state.read(move |conn| etudiant::get_students_by_department(conn, department_id, session_id, options)).await
+}
+
+#[tauri::command]
+pub async fn get_specialite_students(
+    specialite_id: i32,
+    session_id: Option<i32>,
+    options: Option<QueryOptions>,
+    state: State<'_, AppState>,
+) -> Result<Page<Etudiant>, String> {
+    state.read(move |conn| etudiant::get_specialite_students(conn, specialite_id, session_id, options)).await
+}
+
+#[tauri::command]
+pub async fn set_student_grade(id: i32, grade: Option<f64>, state: State<'_, AppState>) -> Result<Etudiant, String> {
+    state.write(move |tx, actor| etudiant::set_student_grade(tx, actor, id, grade)).await
+}
+
+#[tauri::command]
+pub async fn set_student_supervisor(
+    id: i32,
+    supervisor_id: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<Etudiant, String> {
+    state.write(move |tx, actor| etudiant::set_student_supervisor(tx, actor, id, supervisor_id)).await
+}
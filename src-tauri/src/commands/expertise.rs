@@ -0,0 +1,29 @@
+use crate::models::expertise::{self, JuryProfile, ReviewerCandidate, ReviewerRequest};
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub async fn get_jury_profile(jury_id: i32, state: State<'_, AppState>) -> Result<JuryProfile, String> {
+    state.read(move |conn| expertise::get_jury_profile(conn, jury_id)).await
+}
+
+#[tauri::command]
+pub async fn set_jury_profile(
+    jury_id: i32,
+    profile: JuryProfile,
+    state: State<'_, AppState>,
+) -> Result<JuryProfile, String> {
+    state
+        .write(move |tx, actor| expertise::set_jury_profile(tx, actor, jury_id, profile))
+        .await
+}
+
+#[tauri::command]
+pub async fn suggest_reviewers(
+    request: ReviewerRequest,
+    state: State<'_, AppState>,
+) -> Result<Vec<ReviewerCandidate>, String> {
+    state
+        .read(move |conn| expertise::suggest_reviewers(conn, request))
+        .await
+}
@@ -0,0 +1,83 @@
+use crate::models::honoraria::{self, HonorariaRates, HonorariaSummary, PaymentDetails};
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub async fn get_jury_payment(jury_id: i32, state: State<'_, AppState>) -> Result<PaymentDetails, String> {
+    state.read(move |conn| honoraria::get_jury_payment(conn, jury_id)).await
+}
+
+#[tauri::command]
+pub async fn set_jury_payment(jury_id: i32, payment: PaymentDetails, state: State<'_, AppState>) -> Result<(), String> {
+    state
+        .write(move |tx, actor| honoraria::set_jury_payment(tx, actor, jury_id, payment))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_honoraria_rates(state: State<'_, AppState>) -> Result<HonorariaRates, String> {
+    state.read(honoraria::get_honoraria_rates).await
+}
+
+#[tauri::command]
+pub async fn set_honoraria_rates(rates: HonorariaRates, state: State<'_, AppState>) -> Result<(), String> {
+    state
+        .write(move |tx, actor| honoraria::set_honoraria_rates(tx, actor, rates))
+        .await
+}
+
+#[tauri::command]
+pub async fn compute_honoraria(
+    session_id: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<HonorariaSummary, String> {
+    state
+        .read(move |conn| honoraria::compute_honoraria(conn, session_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn export_honoraria_csv(
+    path: String,
+    session_id: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<usize, String> {
+    state
+        .read(move |conn| honoraria::export_honoraria_csv(conn, path, session_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn export_honoraria_pdf(
+    path: String,
+    session_id: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .read(move |conn| honoraria::export_honoraria_pdf(conn, path, session_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn export_payment_statement_csv(
+    path: String,
+    jury_id: i32,
+    session_id: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<usize, String> {
+    state
+        .read(move |conn| honoraria::export_payment_statement_csv(conn, path, jury_id, session_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn export_payment_statement_pdf(
+    path: String,
+    jury_id: i32,
+    session_id: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .read(move |conn| honoraria::export_payment_statement_pdf(conn, path, jury_id, session_id))
+        .await
+}
@@ -0,0 +1,49 @@
+use crate::models::invite::{self, Invitee, NewInvitee};
+use crate::query::{Page, QueryOptions};
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub async fn create_invite(invite: NewInvitee, state: State<'_, AppState>) -> Result<i32, String> {
+    state
+        .write(move |tx, actor| invite::create_invite(tx, actor, invite))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_all_invite(
+    options: Option<QueryOptions>,
+    state: State<'_, AppState>,
+) -> Result<Page<Invitee>, String> {
+    state.read(move |conn| invite::get_all_invite(conn, options)).await
+}
+
+#[tauri::command]
+pub async fn get_invite(id: i32, state: State<'_, AppState>) -> Result<Invitee, String> {
+    state.read(move |conn| invite::get_invite(conn, id)).await
+}
+
+#[tauri::command]
+pub async fn update_invite(id: i32, invite: NewInvitee, state: State<'_, AppState>) -> Result<String, String> {
+    state
+        .write(move |tx, actor| invite::update_invite(tx, actor, id, invite))
+        .await
+}
+
+#[tauri::command]
+pub async fn delete_invite(ids: Vec<i32>, state: State<'_, AppState>) -> Result<(), String> {
+    state
+        .write(move |tx, actor| invite::delete_invite(tx, actor, ids))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_invite_soutenances(
+    invite_id: i32,
+    session_id: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<Vec<i32>, String> {
+    state
+        .read(move |conn| invite::get_invite_soutenances(conn, invite_id, session_id))
+        .await
+}
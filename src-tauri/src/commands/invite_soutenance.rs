@@ -0,0 +1,93 @@
+use crate::models::invite_soutenance::{self, InvitationCounts, InviteSoutenance};
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub async fn create_invite_soutenance(
+    invite_id: i32,
+    soutenance_id: i32,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .write(move |tx, actor| invite_soutenance::create_invite_soutenance(tx, actor, invite_id, soutenance_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_invite_soutenance(
+    invite_id: i32,
+    soutenance_id: i32,
+    state: State<'_, AppState>,
+) -> Result<InviteSoutenance, String> {
+    state
+        .read(move |conn| invite_soutenance::get_invite_soutenance(conn, invite_id, soutenance_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn update_invite_soutenance(
+    invite_id: i32,
+    soutenance_id: i32,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .write(move |tx, actor| invite_soutenance::update_invite_soutenance(tx, actor, invite_id, soutenance_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn delete_invite_soutenance(
+    invite_id: i32,
+    soutenance_id: i32,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .write(move |tx, actor| invite_soutenance::delete_invite_soutenance(tx, actor, invite_id, soutenance_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_soutenance_invitations(
+    soutenance_id: i32,
+    state: State<'_, AppState>,
+) -> Result<Vec<InviteSoutenance>, String> {
+    state
+        .read(move |conn| invite_soutenance::get_soutenance_invitations(conn, soutenance_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_invitation_counts(soutenance_id: i32, state: State<'_, AppState>) -> Result<InvitationCounts, String> {
+    state
+        .read(move |conn| invite_soutenance::get_invitation_counts(conn, soutenance_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn respond_to_invitation(
+    token: String,
+    accepted: bool,
+    state: State<'_, AppState>,
+) -> Result<InviteSoutenance, String> {
+    state
+        .write(move |tx, actor| invite_soutenance::respond_to_invitation(tx, actor, token, accepted))
+        .await
+}
+
+#[tauri::command]
+pub async fn check_in_invitation(token: String, state: State<'_, AppState>) -> Result<InviteSoutenance, String> {
+    state
+        .write(move |tx, actor| invite_soutenance::check_in_invitation(tx, actor, token))
+        .await
+}
+
+#[tauri::command]
+pub async fn regenerate_rsvp_token(
+    invite_id: i32,
+    soutenance_id: i32,
+    state: State<'_, AppState>,
+) -> Result<String, String> {
+    state
+        .write(move |tx, actor| invite_soutenance::regenerate_rsvp_token(tx, actor, invite_id, soutenance_id))
+        .await
+}
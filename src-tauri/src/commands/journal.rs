@@ -0,0 +1,21 @@
+use crate::models::journal::{self, OperationSummary};
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub async fn undo(state: State<'_, AppState>) -> Result<Option<OperationSummary>, String> {
+    state.write(journal::undo).await
+}
+
+#[tauri::command]
+pub async fn redo(state: State<'_, AppState>) -> Result<Option<OperationSummary>, String> {
+    state.write(journal::redo).await
+}
+
+#[tauri::command]
+pub async fn get_operation_history(state: State<'_, AppState>) -> Result<Vec<OperationSummary>, String> {
+    let actor = state.actor();
+    state
+        .read(move |conn| journal::get_operation_history(conn, &actor))
+        .await
+}
@@ -0,0 +1,42 @@
+use crate::models::jury::{self, Jury, NewJury};
+use crate::query::{Page, QueryOptions};
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub async fn create_jury(jury: NewJury, state: State<'_, AppState>) -> Result<i32, String> {
+    state.write(move |tx, actor| jury::create_jury(tx, actor, jury)).await
+}
+
+#[tauri::command]
+pub async fn get_all_jury(options: Option<QueryOptions>, state: State<'_, AppState>) -> Result<Page<Jury>, String> {
+    state.read(move |conn| jury::get_all_jury(conn, options)).await
+}
+
+#[tauri::command]
+pub async fn get_jury(id: i32, state: State<'_, AppState>) -> Result<Jury, String> {
+    state.read(move |conn| jury::get_jury(conn, id)).await
+}
+
+#[tauri::command]
+pub async fn update_jury(id: i32, jury: NewJury, state: State<'_, AppState>) -> Result<String, String> {
+    state
+        .write(move |tx, actor| jury::update_jury(tx, actor, id, jury))
+        .await
+}
+
+#[tauri::command]
+pub async fn delete_jury(ids: Vec<i32>, state: State<'_, AppState>) -> Result<(), String> {
+    state.write(move |tx, actor| jury::delete_jury(tx, actor, ids)).await
+}
+
+#[tauri::command]
+pub async fn get_jury_soutenances(
+    jury_id: i32,
+    session_id: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<Vec<(i32, String)>, String> {
+    state
+        .read(move |conn| jury::get_jury_soutenances(conn, jury_id, session_id))
+        .await
+}
@@ -0,0 +1,57 @@
+use crate::models::jury_soutenance::{self, JuryRoleOption, JurySoutenance};
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub fn get_jury_roles() -> Vec<JuryRoleOption> {
+    jury_soutenance::get_jury_roles()
+}
+
+#[tauri::command]
+pub async fn create_jury_soutenance(
+    jury_id: i32,
+    soutenance_id: i32,
+    role: String,
+    justification: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .write(move |tx, actor| {
+            jury_soutenance::create_jury_soutenance(tx, actor, jury_id, soutenance_id, role, justification)
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn get_jury_soutenance(
+    jury_id: i32,
+    soutenance_id: i32,
+    state: State<'_, AppState>,
+) -> Result<JurySoutenance, String> {
+    state
+        .read(move |conn| jury_soutenance::get_jury_soutenance(conn, jury_id, soutenance_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn update_jury_soutenance(
+    jury_id: i32,
+    soutenance_id: i32,
+    role: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .write(move |tx, actor| jury_soutenance::update_jury_soutenance(tx, actor, jury_id, soutenance_id, role))
+        .await
+}
+
+#[tauri::command]
+pub async fn delete_jury_soutenance(
+    jury_id: i32,
+    soutenance_id: i32,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .write(move |tx, actor| jury_soutenance::delete_jury_soutenance(tx, actor, jury_id, soutenance_id))
+        .await
+}
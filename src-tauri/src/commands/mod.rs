@@ -0,0 +1,27 @@
+pub mod archive;
+pub mod audit;
+pub mod badge;
+pub mod classroom;
+pub mod composition;
+pub mod conflict;
+pub mod demo;
+pub mod etudiant;
+pub mod expertise;
+pub mod honoraria;
+pub mod invite;
+pub mod invite_soutenance;
+pub mod journal;
+pub mod jury;
+pub mod jury_soutenance;
+pub mod notification;
+pub mod occupancy;
+pub mod outbox;
+pub mod pfe;
+pub mod reminder;
+pub mod search;
+pub mod session;
+pub mod soutenance;
+pub mod specialite;
+pub mod template;
+pub mod user;
+pub mod workload;
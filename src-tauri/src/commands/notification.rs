@@ -0,0 +1,50 @@
+use crate::models::notification::{self, NotificationPreference, PendingNotification, RecipientKind};
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub async fn get_pending_notifications(
+    soutenance_id: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<Vec<PendingNotification>, String> {
+    state
+        .read(move |conn| notification::get_pending_notifications(conn, soutenance_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn flush_pending_notifications(state: State<'_, AppState>) -> Result<usize, String> {
+    state.detached(notification::flush_pending_notifications).await
+}
+
+#[tauri::command]
+pub async fn discard_pending_notifications(soutenance_id: i32, state: State<'_, AppState>) -> Result<usize, String> {
+    state
+        .write(move |tx, _| notification::discard_pending_notifications(tx, soutenance_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_notification_preference(
+    recipient_kind: RecipientKind,
+    recipient_id: i32,
+    state: State<'_, AppState>,
+) -> Result<NotificationPreference, String> {
+    state
+        .read(move |conn| notification::get_notification_preference(conn, recipient_kind, recipient_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn set_notification_preference(
+    recipient_kind: RecipientKind,
+    recipient_id: i32,
+    muted: bool,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .write(move |tx, actor| {
+            notification::set_notification_preference(tx, actor, recipient_kind, recipient_id, muted)
+        })
+        .await
+}
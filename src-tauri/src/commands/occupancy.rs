@@ -0,0 +1,29 @@
+use crate::models::occupancy::{self, RoomAgenda, UtilisationReport};
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub async fn get_classroom_agenda(
+    classroom_id: i32,
+    date_from: String,
+    date_to: String,
+    session_id: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<RoomAgenda, String> {
+    state
+        .read(move |conn| occupancy::get_classroom_agenda(conn, classroom_id, date_from, date_to, session_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_classroom_utilisation(
+    date_from: String,
+    date_to: String,
+    classroom_ids: Option<Vec<i32>>,
+    session_id: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<UtilisationReport, String> {
+    state
+        .read(move |conn| occupancy::get_classroom_utilisation(conn, date_from, date_to, classroom_ids, session_id))
+        .await
+}
@@ -0,0 +1,77 @@
+use crate::mailer::SmtpSettings;
+use crate::models::outbox::{self, NotificationPreview, OutboxMessage, OutboxStatus};
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub async fn get_smtp_settings(state: State<'_, AppState>) -> Result<SmtpSettings, String> {
+    state.read(outbox::get_smtp_settings).await
+}
+
+#[tauri::command]
+pub async fn set_smtp_settings(settings: SmtpSettings, state: State<'_, AppState>) -> Result<(), String> {
+    state
+        .write(move |tx, actor| outbox::set_smtp_settings(tx, actor, settings))
+        .await
+}
+
+#[tauri::command]
+pub async fn send_test_email(to: String, state: State<'_, AppState>) -> Result<(), String> {
+    // The connection goes back to the pool before talking to the server.
+    let config = state.read(outbox::smtp_config).await?;
+    crate::blocking(move || outbox::send_test_email(&config, &to)).await
+}
+
+#[tauri::command]
+pub async fn preview_soutenance_notifications(
+    soutenance_id: i32,
+    state: State<'_, AppState>,
+) -> Result<Vec<NotificationPreview>, String> {
+    state
+        .read(move |conn| outbox::preview_soutenance_notifications(conn, soutenance_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn enqueue_soutenance_notifications(soutenance_id: i32, state: State<'_, AppState>) -> Result<usize, String> {
+    state
+        .write(move |tx, actor| outbox::enqueue_soutenance_notifications(tx, actor, soutenance_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_outbox(
+    soutenance_id: Option<i32>,
+    status: Option<OutboxStatus>,
+    state: State<'_, AppState>,
+) -> Result<Vec<OutboxMessage>, String> {
+    state
+        .read(move |conn| outbox::get_outbox(conn, soutenance_id, status))
+        .await
+}
+
+#[tauri::command]
+pub async fn cancel_outbox_message(id: i32, state: State<'_, AppState>) -> Result<(), String> {
+    state
+        .write(move |tx, actor| outbox::cancel_outbox_message(tx, actor, id))
+        .await
+}
+
+#[tauri::command]
+pub async fn cancel_soutenance_notifications(soutenance_id: i32, state: State<'_, AppState>) -> Result<usize, String> {
+    state
+        .write(move |tx, actor| outbox::cancel_soutenance_notifications(tx, actor, soutenance_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn resend_outbox_message(id: i32, state: State<'_, AppState>) -> Result<i32, String> {
+    state
+        .write(move |tx, actor| outbox::resend_outbox_message(tx, actor, id))
+        .await
+}
+
+#[tauri::command]
+pub async fn flush_outbox(state: State<'_, AppState>) -> Result<usize, String> {
+    state.detached(outbox::flush_outbox).await
+}
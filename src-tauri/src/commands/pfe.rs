@@ -0,0 +1,40 @@
+use crate::models::pfe::{self, Pfe};
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub async fn create_pfe(
+    specialite_id: i32,
+    title: Option<String>,
+    resume: Option<String>,
+    company: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<i32, String> {
+    state
+        .write(move |tx, actor| pfe::create_pfe(tx, actor, specialite_id, title, resume, company))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_pfe(id: i32, state: State<'_, AppState>) -> Result<Pfe, String> {
+    state.read(move |conn| pfe::get_pfe(conn, id)).await
+}
+
+#[tauri::command]
+pub async fn update_pfe(
+    id: i32,
+    specialite_id: i32,
+    title: Option<String>,
+    resume: Option<String>,
+    company: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .write(move |tx, actor| pfe::update_pfe(tx, actor, id, specialite_id, title, resume, company))
+        .await
+}
+
+#[tauri::command]
+pub async fn delete_pfe(id: i32, state: State<'_, AppState>) -> Result<(), String> {
+    state.write(move |tx, actor| pfe::delete_pfe(tx, actor, id)).await
+}
@@ -0,0 +1,39 @@
+use crate::models::reminder::{self, Reminder};
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub async fn get_reminder_offsets(state: State<'_, AppState>) -> Result<Vec<i64>, String> {
+    state.read(reminder::get_reminder_offsets).await
+}
+
+#[tauri::command]
+pub async fn set_reminder_offsets(offsets: Vec<i64>, state: State<'_, AppState>) -> Result<(), String> {
+    state
+        .write(move |tx, actor| reminder::set_reminder_offsets(tx, actor, offsets))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_upcoming_reminders(
+    soutenance_id: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<Vec<Reminder>, String> {
+    state
+        .write(move |tx, _| reminder::get_upcoming_reminders(tx, soutenance_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn cancel_reminder(id: i32, state: State<'_, AppState>) -> Result<(), String> {
+    state
+        .write(move |tx, actor| reminder::cancel_reminder(tx, actor, id))
+        .await
+}
+
+#[tauri::command]
+pub async fn cancel_soutenance_reminders(soutenance_id: i32, state: State<'_, AppState>) -> Result<usize, String> {
+    state
+        .write(move |tx, actor| reminder::cancel_soutenance_reminders(tx, actor, soutenance_id))
+        .await
+}
@@ -0,0 +1,15 @@
+use crate::models::search::{self, SearchResult};
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub async fn global_search(
+    query: String,
+    kinds: Option<Vec<String>>,
+    limit: Option<i64>,
+    state: State<'_, AppState>,
+) -> Result<Vec<SearchResult>, String> {
+    state
+        .read(move |conn| search::global_search(conn, query, kinds, limit))
+        .await
+}
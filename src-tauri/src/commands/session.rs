@@ -0,0 +1,55 @@
+use crate::models::session::{self, AnneeUniversitaire, Session, SessionClosing};
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub async fn create_annee_universitaire(
+    label: String,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<i32, String> {
+    state
+        .write(move |tx, actor| session::create_annee_universitaire(tx, actor, label, start_date, end_date))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_all_annees_universitaires(state: State<'_, AppState>) -> Result<Vec<AnneeUniversitaire>, String> {
+    state.read(session::get_all_annees_universitaires).await
+}
+
+#[tauri::command]
+pub async fn create_session(annee_id: i32, kind: String, state: State<'_, AppState>) -> Result<i32, String> {
+    state
+        .write(move |tx, actor| session::create_session(tx, actor, annee_id, kind))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_all_sessions(annee_id: Option<i32>, state: State<'_, AppState>) -> Result<Vec<Session>, String> {
+    state.read(move |conn| session::get_all_sessions(conn, annee_id)).await
+}
+
+#[tauri::command]
+pub async fn get_active_session(state: State<'_, AppState>) -> Result<Option<Session>, String> {
+    state.read(session::get_active_session).await
+}
+
+#[tauri::command]
+pub async fn set_active_session(session_id: i32, state: State<'_, AppState>) -> Result<Session, String> {
+    state
+        .write(move |tx, actor| session::set_active_session(tx, actor, session_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn close_session(
+    session_id: i32,
+    next_session_id: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<SessionClosing, String> {
+    state
+        .write(move |tx, actor| session::close_session(tx, actor, session_id, next_session_id))
+        .await
+}
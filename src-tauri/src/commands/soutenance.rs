@@ -0,0 +1,131 @@
+use crate::models::soutenance::{self, Soutenance, SoutenancePlan};
+use crate::query::{Page, QueryOptions};
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn create_soutenance(
+    date: Option<String>,
+    hour: Option<String>,
+    specialite_id: i32,
+    classroom_id: Option<i32>,
+    pfe: Option<String>,
+    session_id: Option<i32>,
+    expected_attendees: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<Soutenance, String> {
+    state
+        .write(move |tx, actor| {
+            soutenance::create_soutenance(
+                tx,
+                actor,
+                date,
+                hour,
+                specialite_id,
+                classroom_id,
+                pfe,
+                session_id,
+                expected_attendees,
+            )
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn create_soutenance_with_participants(
+    plan: SoutenancePlan,
+    state: State<'_, AppState>,
+) -> Result<Soutenance, String> {
+    state
+        .write(move |tx, actor| soutenance::create_soutenance_with_participants(tx, actor, plan))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_soutenance(id: i32, state: State<'_, AppState>) -> Result<Soutenance, String> {
+    state.read(move |conn| soutenance::get_soutenance(conn, id)).await
+}
+
+#[tauri::command]
+pub async fn update_soutenance(
+    id: i32,
+    date: Option<String>,
+    hour: Option<String>,
+    specialite_id: i32,
+    classroom_id: Option<i32>,
+    pfe: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .write(move |tx, actor| {
+            soutenance::update_soutenance(tx, actor, id, date, hour, specialite_id, classroom_id, pfe)
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn delete_soutenance(id: i32, state: State<'_, AppState>) -> Result<(), String> {
+    state
+        .write(move |tx, actor| soutenance::delete_soutenance(tx, actor, id))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_soutenance_students(soutenance_id: i32, state: State<'_, AppState>) -> Result<Vec<i32>, String> {
+    state
+        .read(move |conn| soutenance::get_soutenance_students(conn, soutenance_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_soutenance_jurys(
+    soutenance_id: i32,
+    state: State<'_, AppState>,
+) -> Result<Vec<(i32, String)>, String> {
+    state
+        .read(move |conn| soutenance::get_soutenance_jurys(conn, soutenance_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_soutenance_invites(soutenance_id: i32, state: State<'_, AppState>) -> Result<Vec<i32>, String> {
+    state
+        .read(move |conn| soutenance::get_soutenance_invites(conn, soutenance_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_specialite_soutenances(
+    specialite_id: i32,
+    session_id: Option<i32>,
+    options: Option<QueryOptions>,
+    state: State<'_, AppState>,
+) -> Result<Page<Soutenance>, String> {
+    state
+        .read(move |conn| soutenance::get_specialite_soutenances(conn, specialite_id, session_id, options))
+        .await
+}
+
+#[tauri::command]
+pub async fn export_planning_csv(
+    path: String,
+    session_id: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<usize, String> {
+    state
+        .read(move |conn| soutenance::export_planning_csv(conn, path, session_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_defence_duration(state: State<'_, AppState>) -> Result<i32, String> {
+    state.read(soutenance::get_defence_duration).await
+}
+
+#[tauri::command]
+pub async fn set_defence_duration(minutes: i32, state: State<'_, AppState>) -> Result<(), String> {
+    state
+        .write(move |tx, actor| soutenance::set_defence_duration(tx, actor, minutes))
+        .await
+}
@@ -0,0 +1,32 @@
+use crate::models::specialite::{self, Specialite};
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub async fn create_specialite(state: State<'_, AppState>) -> Result<i32, String> {
+    state.write(specialite::create_specialite).await
+}
+
+#[tauri::command]
+pub async fn get_specialite(id: i32, state: State<'_, AppState>) -> Result<Specialite, String> {
+    state.read(move |conn| specialite::get_specialite(conn, id)).await
+}
+
+#[tauri::command]
+pub async fn update_specialite(_id: i32, _state: State<'_, AppState>) -> Result<(), String> {
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn delete_specialite(id: i32, state: State<'_, AppState>) -> Result<(), String> {
+    state
+        .write(move |tx, actor| specialite::delete_specialite(tx, actor, id))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_specialite_pfes(specialite_id: i32, state: State<'_, AppState>) -> Result<Vec<i32>, String> {
+    state
+        .read(move |conn| specialite::get_specialite_pfes(conn, specialite_id))
+        .await
+}
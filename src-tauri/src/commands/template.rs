@@ -0,0 +1,73 @@
+use crate::models::template::{self, EffectiveTemplate, Language, Rendered, StoredTemplate, TemplateInput, TemplateKind};
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub async fn get_templates(
+    kind: Option<TemplateKind>,
+    state: State<'_, AppState>,
+) -> Result<Vec<StoredTemplate>, String> {
+    state.read(move |conn| template::get_templates(conn, kind)).await
+}
+
+#[tauri::command]
+pub async fn get_effective_template(
+    kind: TemplateKind,
+    language: Language,
+    specialite_id: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<EffectiveTemplate, String> {
+    state.read(move |conn| template::get_effective_template(conn, kind, language, specialite_id)).await
+}
+
+#[tauri::command]
+pub async fn save_template(template: TemplateInput, state: State<'_, AppState>) -> Result<i32, String> {
+    state.write(move |tx, actor| template::save_template(tx, actor, template)).await
+}
+
+#[tauri::command]
+pub async fn delete_template(id: i32, state: State<'_, AppState>) -> Result<(), String> {
+    state.write(move |tx, actor| template::delete_template(tx, actor, id)).await
+}
+
+#[tauri::command]
+pub fn validate_template(kind: TemplateKind, language: Language, subject: String, body: String) -> Vec<String> {
+    template::validate_template(kind, language, subject, body)
+}
+
+#[tauri::command]
+pub async fn preview_template(
+    kind: TemplateKind,
+    language: Language,
+    specialite_id: Option<i32>,
+    subject: Option<String>,
+    body: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Rendered, String> {
+    state.read(move |conn| template::preview_template(conn, kind, language, specialite_id, subject, body)).await
+}
+
+#[tauri::command]
+pub fn get_template_variables(kind: TemplateKind) -> Vec<String> {
+    template::get_template_variables(kind)
+}
+
+#[tauri::command]
+pub async fn get_template_language(state: State<'_, AppState>) -> Result<Language, String> {
+    state.read(template::get_template_language).await
+}
+
+#[tauri::command]
+pub async fn set_template_language(language: Language, state: State<'_, AppState>) -> Result<(), String> {
+    state.write(move |tx, actor| template::set_template_language(tx, actor, language)).await
+}
+
+#[tauri::command]
+pub async fn export_pv(
+    path: String,
+    soutenance_id: i32,
+    language: Option<Language>,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state.read(move |conn| template::export_pv(conn, path, soutenance_id, language)).await
+}
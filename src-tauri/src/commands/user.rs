@@ -0,0 +1,68 @@
+use crate::models::user::{self, User};
+use crate::db::lock;
+use crate::AppState;
+use serde::Serialize;
+use tauri::State;
+
+#[derive(Serialize)]
+pub struct LoginResponse {
+    access_token: String,
+    message: String,
+}
+
+#[tauri::command]
+pub async fn login(email: String, password: String, state: State<'_, AppState>) -> Result<LoginResponse, String> {
+    let logged_in = state.read(move |conn| user::authenticate(conn, &email, &password)).await?;
+    if let Some(current_user) = logged_in {
+        let token = current_user.token.clone();
+        *lock(&state.current_user) = Some(current_user);
+        Ok(LoginResponse {
+            access_token: token,
+            message: "Login successful".to_string(),
+        })
+    } 
+    else {
+        //Err("Invalid credentials".to_string())
+        Ok(LoginResponse {
+            access_token: "".to_string(),
+            message: "Invalid credentials".to_string(),
+        })
+    }
+}
+
+#[tauri::command]
+pub async fn logout(state: State<'_, AppState>) -> Result<(), String> {
+    *lock(&state.current_user) = None;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn create_user(
+    name: String,
+    email: String,
+    password: String,
+    state: State<'_, AppState>,
+) -> Result<i32, String> {
+    state.write(move |tx, actor| user::create_user(tx, actor, name, email, password)).await
+}
+
+#[tauri::command]
+pub async fn get_user(id: i32, state: State<'_, AppState>) -> Result<User, String> {
+    state.read(move |conn| user::get_user(conn, id)).await
+}
+
+#[tauri::command]
+pub async fn update_user(
+    id: i32,
+    name: String,
+    email: String,
+    password: String,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state.write(move |tx, actor| user::update_user(tx, actor, id, name, email, password)).await
+}
+
+#[tauri::command]
+pub async fn delete_user(id: i32, state: State<'_, AppState>) -> Result<(), String> {
+    state.write(move |tx, actor| user::delete_user(tx, actor, id)).await
+}
@@ -0,0 +1,47 @@
+use crate::models::workload::{self, JuryCaps, JuryWorkload, SwapSuggestion};
+use crate::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub async fn get_jury_workload(
+    session_id: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<Vec<JuryWorkload>, String> {
+    state
+        .read(move |conn| workload::get_jury_workload(conn, session_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn suggest_jury_rebalancing(
+    session_id: Option<i32>,
+    state: State<'_, AppState>,
+) -> Result<Vec<SwapSuggestion>, String> {
+    state
+        .read(move |conn| workload::suggest_jury_rebalancing(conn, session_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn apply_jury_swap(
+    soutenance_id: i32,
+    from_jury_id: i32,
+    to_jury_id: i32,
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    state
+        .write(move |tx, actor| workload::apply_jury_swap(tx, actor, soutenance_id, from_jury_id, to_jury_id))
+        .await
+}
+
+#[tauri::command]
+pub async fn get_jury_caps(jury_id: Option<i32>, state: State<'_, AppState>) -> Result<JuryCaps, String> {
+    state.read(move |conn| workload::get_jury_caps(conn, jury_id)).await
+}
+
+#[tauri::command]
+pub async fn set_jury_caps(jury_id: Option<i32>, caps: JuryCaps, state: State<'_, AppState>) -> Result<(), String> {
+    state
+        .write(move |tx, actor| workload::set_jury_caps(tx, actor, jury_id, caps))
+        .await
+}
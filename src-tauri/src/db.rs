@@ -0,0 +1,122 @@
+use rusqlite::Connection;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+/// Read connections kept open at most; one more caller waits for the first to come back.
+const READERS: usize = 4;
+/// How long a connection waits on another one's write lock before giving up.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Locks `mutex` even when a thread panicked while holding it. Whatever it guards is only
+/// left half-updated inside a transaction, which the panic already rolled back.
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The app's connections to one database in WAL mode: a single writer, so mutations never
+/// race each other, and a pool of read-only connections that keep working while it writes.
+#[derive(Clone)]
+pub struct Database {
+    inner: Arc<Pool>,
+}
+
+struct Pool {
+    path: PathBuf,
+    writer: Mutex<Connection>,
+    idle: Mutex<Idle>,
+    returned: Condvar,
+}
+
+struct Idle {
+    connections: Vec<Connection>,
+    open: usize,
+}
+
+/// A read connection on loan, handed back to the pool when dropped.
+struct Reader<'a> {
+    pool: &'a Pool,
+    conn: Option<Connection>,
+}
+
+impl Drop for Reader<'_> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            lock(&self.pool.idle).connections.push(conn);
+            self.pool.returned.notify_one();
+        }
+    }
+}
+
+impl Database {
+    /// Wraps `writer`, a connection to `path` already opened by `open_database`.
+    pub fn new(path: &Path, writer: Connection) -> Self {
+        Database {
+            inner: Arc::new(Pool {
+                path: path.to_path_buf(),
+                writer: Mutex::new(writer),
+                idle: Mutex::new(Idle {
+                    connections: Vec::new(),
+                    open: 0,
+                }),
+                returned: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Runs `f` on a read-only connection, outside of any transaction.
+    pub fn read<T>(&self, f: impl FnOnce(&Connection) -> Result<T, String>) -> Result<T, String> {
+        let reader = self.reader()?;
+        f(reader.conn.as_ref().expect("reader holds a connection"))
+    }
+
+    /// Runs `f` in a transaction on the write connection; nothing is kept if it fails.
+    pub fn write<T>(&self, f: impl FnOnce(&Connection) -> Result<T, String>) -> Result<T, String> {
+        let mut conn = lock(&self.inner.writer);
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let value = f(&tx)?;
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(value)
+    }
+
+    /// Runs `f` on a connection of its own, for jobs that open their own transactions and
+    /// may take a while, like sending mail, so they hold up neither readers nor the writer.
+    pub fn detached<T>(&self, f: impl FnOnce(&Connection) -> Result<T, String>) -> Result<T, String> {
+        let conn = self.open()?;
+        f(&conn)
+    }
+
+    fn reader(&self) -> Result<Reader<'_>, String> {
+        let pool = &*self.inner;
+        let mut idle = lock(&pool.idle);
+        loop {
+            if let Some(conn) = idle.connections.pop() {
+                return Ok(Reader { pool, conn: Some(conn) });
+            }
+            if idle.open < READERS {
+                idle.open += 1;
+                drop(idle);
+                let opened = self.open().and_then(|conn| {
+                    conn.pragma_update(None, "query_only", true)
+                        .map_err(|e| e.to_string())?;
+                    Ok(conn)
+                });
+                if opened.is_err() {
+                    lock(&pool.idle).open -= 1;
+                    pool.returned.notify_one();
+                }
+                return opened
+                    .map(|conn| Reader { pool, conn: Some(conn) })
+                    .map_err(|e| format!("Failed to open a read connection: {}", e));
+            }
+            idle = pool.returned.wait(idle).unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+
+    /// A new connection that waits for the writer instead of failing while it holds the lock.
+    fn open(&self) -> Result<Connection, String> {
+        let conn = Connection::open(&self.inner.path).map_err(|e| e.to_string())?;
+        conn.busy_timeout(BUSY_TIMEOUT).map_err(|e| e.to_string())?;
+        Ok(conn)
+    }
+}
@@ -0,0 +1,151 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn csv_line(fields: &[String]) -> String {
+    fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(",")
+}
+
+/// Writes a header line followed by one line per row; returns the number of rows written.
+pub fn write_csv(path: &str, headers: &[&str], rows: &[Vec<String>]) -> Result<usize, String> {
+    let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+    let mut out = BufWriter::new(file);
+    let headers: Vec<String> = headers.iter().map(|h| h.to_string()).collect();
+    writeln!(out, "{}", csv_line(&headers)).map_err(|e| e.to_string())?;
+    for row in rows {
+        writeln!(out, "{}", csv_line(row)).map_err(|e| e.to_string())?;
+    }
+    out.flush().map_err(|e| e.to_string())?;
+    Ok(rows.len())
+}
+
+/// Reads a CSV file into rows of fields. Handles quoted fields, doubled quotes, CRLF line
+/// endings and a leading byte order mark.
+pub fn read_csv(path: &str) -> Result<Vec<Vec<String>>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let text = text.strip_prefix('\u{feff}').unwrap_or(&text);
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(format!("Unterminated quoted field in {}", path));
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    Ok(rows)
+}
+
+pub fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const PDF_LINES_PER_PAGE: usize = 60;
+
+/// PDF string literal in WinAnsi encoding; characters outside Latin-1 become '?'.
+fn pdf_text(value: &str) -> Vec<u8> {
+    let mut out = vec![b'('];
+    for c in value.chars() {
+        match c {
+            '(' | ')' | '\\' => out.extend([b'\\', c as u8]),
+            c if (c as u32) < 0x20 => out.push(b' '),
+            c if (c as u32) < 0x80 || (0xA0..0x100).contains(&(c as u32)) => out.push(c as u32 as u8),
+            _ => out.push(b'?'),
+        }
+    }
+    out.push(b')');
+    out
+}
+
+/// Writes a plain A4 document: a title, then `lines` in a fixed-width font so columns
+/// padded with spaces stay aligned. Long documents flow onto further pages.
+pub fn write_pdf(path: &str, title: &str, lines: &[String]) -> Result<(), String> {
+    let pages: Vec<&[String]> = if lines.is_empty() {
+        vec![&[]]
+    } else {
+        lines.chunks(PDF_LINES_PER_PAGE).collect()
+    };
+    // Objects 1-3 are the catalog, the page tree and the font; each page then takes
+    // two objects, the page and its content stream.
+    let mut objects: Vec<Vec<u8>> = vec![
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            (0..pages.len()).map(|i| format!("{} 0 R", 4 + 2 * i)).collect::<Vec<_>>().join(" "),
+            pages.len()
+        )
+        .into_bytes(),
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Courier /Encoding /WinAnsiEncoding >>".to_vec(),
+    ];
+    for (index, page) in pages.iter().enumerate() {
+        let mut content = b"BT\n".to_vec();
+        let mut y = 800;
+        if index == 0 {
+            content.extend(format!("/F1 14 Tf 50 {} Td ", y).into_bytes());
+            content.extend(pdf_text(title));
+            content.extend(b" Tj ET\nBT\n");
+            y -= 30;
+        }
+        content.extend(format!("/F1 9 Tf 11 TL 50 {} Td\n", y).into_bytes());
+        for line in page.iter() {
+            content.extend(pdf_text(line));
+            content.extend(b" Tj T*\n");
+        }
+        content.extend(b"ET");
+        objects.push(
+            format!("<< /Type /Page /Parent 2 0 R /MediaBox [0 0 595 842] /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>", 5 + 2 * index)
+                .into_bytes(),
+        );
+        let mut stream = format!("<< /Length {} >>\nstream\n", content.len()).into_bytes();
+        stream.extend(content);
+        stream.extend(b"\nendstream");
+        objects.push(stream);
+    }
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend(format!("{} 0 obj\n", index + 1).into_bytes());
+        pdf.extend(object);
+        pdf.extend(b"\nendobj\n");
+    }
+    let xref = pdf.len();
+    pdf.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).into_bytes());
+    for offset in offsets {
+        pdf.extend(format!("{:010} 00000 n \n", offset).into_bytes());
+    }
+    pdf.extend(format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref).into_bytes());
+    std::fs::write(path, pdf).map_err(|e| format!("Failed to write {}: {}", path, e))
+}
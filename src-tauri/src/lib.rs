@@ -1,35 +1,116 @@
-mod models;
+mod api;
+pub mod cli;
+mod commands;
+mod db;
+mod export;
+mod mailer;
+pub mod models;
+pub mod query;
+mod templating;
 
-use models::{etudiant, invite, jury, jury_soutenance, invite_soutenance, pfe, classroom, soutenance, specialite, user};
+use db::Database;
+use models::{invite_soutenance, search, session, user};
 use rusqlite::Connection;
-use serde::Serialize;
+use std::cell::RefCell;
 use std::sync::Mutex;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tauri::State;
-use uuid::Uuid;
 
-#[derive(Serialize)]
-struct LoginResponse {
-    access_token: String,
-    message: String,
+pub struct AppState {
+    pub db: Database,
+    pub current_user: Mutex<Option<user::CurrentUser>>,
 }
 
-pub struct AppState {
-    pub db: Mutex<Connection>,
+thread_local! {
+    /// Who mutations made on this thread are attributed to instead of the signed-in user.
+    static ACTING_AS: RefCell<Option<user::CurrentUser>> = const { RefCell::new(None) };
 }
 
-fn get_db_path() -> PathBuf {
+impl AppState {
+    /// The user the current mutation is attributed to.
+    pub fn actor(&self) -> Option<user::CurrentUser> {
+        ACTING_AS
+            .with_borrow(|acting| acting.clone())
+            .or_else(|| db::lock(&self.current_user).clone())
+    }
+
+    /// Runs `f` on behalf of `user` rather than whoever is signed in to the app. Only holds
+    /// on this thread, so the commands `f` runs must be blocked on, not spawned.
+    pub fn acting_as<T>(user: user::CurrentUser, f: impl FnOnce() -> T) -> T {
+        let previous = ACTING_AS.replace(Some(user));
+        let value = f();
+        ACTING_AS.set(previous);
+        value
+    }
+
+    /// Runs `f` on a read connection from the pool, outside of any transaction.
+    pub async fn read<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&Connection) -> Result<T, String> + Send + 'static,
+    ) -> Result<T, String> {
+        let db = self.db.clone();
+        blocking(move || db.read(f)).await
+    }
+
+    /// Runs `f` in a transaction on behalf of the signed-in user; nothing is kept if it fails.
+    pub async fn write<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&Connection, &Option<user::CurrentUser>) -> Result<T, String> + Send + 'static,
+    ) -> Result<T, String> {
+        let (db, actor) = (self.db.clone(), self.actor());
+        blocking(move || db.write(|tx| f(tx, &actor))).await
+    }
+
+    /// Runs `f` on a connection of its own, for jobs that manage their own transactions.
+    pub async fn detached<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&Connection) -> Result<T, String> + Send + 'static,
+    ) -> Result<T, String> {
+        let db = self.db.clone();
+        blocking(move || db.detached(f)).await
+    }
+}
+
+/// Runs `f` on the blocking thread pool, so slow queries or I/O never stall the async runtime
+/// that serves the other commands. A panic comes back as an error.
+pub(crate) async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, String> + Send + 'static,
+) -> Result<T, String> {
+    tauri::async_runtime::spawn_blocking(f)
+        .await
+        .map_err(|e| format!("Background task failed: {}", e))?
+}
+
+/// `CREATE TABLE IF NOT EXISTS` leaves tables from older databases untouched, so columns
+/// introduced later are added here when they are missing.
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) -> rusqlite::Result<()> {
+    let exists = conn
+        .prepare(&format!("SELECT 1 FROM pragma_table_info('{}') WHERE name = ?1", table))?
+        .exists([column])?;
+    if !exists {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+    }
+    Ok(())
+}
+
+pub(crate) fn get_db_path() -> PathBuf {
     let mut path = dirs::data_dir().expect("Failed to get data directory");
     path.push("gestion_soutenances_db");
     path.push("gestion_soutenances.db");
     path
 }
 
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    let db_path = get_db_path();
-    std::fs::create_dir_all(db_path.parent().unwrap()).expect("Failed to create db directory");
-    let conn = Connection::open(&db_path).expect("Failed to open database");
+/// Opens the database at `path`, creating or migrating the schema as needed. Shared by the
+/// desktop app and the command-line tool.
+pub fn open_database(path: &Path) -> Connection {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).expect("Failed to create db directory");
+    }
+    let conn = Connection::open(path).expect("Failed to open database");
+    // Readers keep working while a write is in progress, instead of waiting for it.
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))
+        .expect("Failed to enable WAL mode");
+    conn.busy_timeout(std::time::Duration::from_secs(5)).expect("Failed to set the busy timeout");
 
     conn.execute("CREATE TABLE IF NOT EXISTS users (id INTEGER PRIMARY KEY AUTOINCREMENT, username TEXT NOT NULL, email TEXT NOT NULL UNIQUE, password TEXT NOT NULL)", []).expect("Failed to create users table");
     
@@ -39,6 +120,7 @@ pub fn run() {
         WHERE NOT EXISTS (SELECT 1 FROM users WHERE email = 'admin@example.com')",
         [],
     ).expect("Failed to insert admin user");
+    user::hash_clear_passwords(&conn).expect("Failed to hash stored passwords");
     
     conn.execute("CREATE TABLE IF NOT EXISTS etudiant (id INTEGER PRIMARY KEY AUTOINCREMENT, firstname TEXT NOT NULL, lastname TEXT NOT NULL, address TEXT NOT NULL, specialite_id INTEGER NOT NULL, soutenance_id INTEGER)", []).expect("Failed to create etudiant table");    
     
@@ -147,97 +229,522 @@ pub fn run() {
     
     conn.execute("CREATE TABLE IF NOT EXISTS invite_soutenance (invite_id INTEGER, soutenance_id INTEGER, PRIMARY KEY (invite_id, soutenance_id))", []).expect("Failed to create invite_soutenance table");
 
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS annee_universitaire (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            label TEXT NOT NULL UNIQUE,
+            start_date TEXT,
+            end_date TEXT
+        )",
+        [],
+    )
+    .expect("Failed to create annee_universitaire table");
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS session (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            annee_id INTEGER NOT NULL,
+            kind TEXT NOT NULL,
+            closed INTEGER NOT NULL DEFAULT 0,
+            closed_at TEXT,
+            UNIQUE (annee_id, kind)
+        )",
+        [],
+    )
+    .expect("Failed to create session table");
+
+    conn.execute("CREATE TABLE IF NOT EXISTS settings (key TEXT PRIMARY KEY, value TEXT)", []).expect("Failed to create settings table");
+
+    add_column_if_missing(&conn, "etudiant", "session_id", "INTEGER").expect("Failed to add etudiant.session_id");
+    add_column_if_missing(&conn, "etudiant", "grade", "REAL").expect("Failed to add etudiant.grade");
+    add_column_if_missing(&conn, "soutenance", "session_id", "INTEGER").expect("Failed to add soutenance.session_id");
+    for table in ["jury", "invite", "etudiant", "classroom"] {
+        add_column_if_missing(&conn, table, "deleted_at", "TEXT").expect("Failed to add deleted_at column");
+    }
+    for column in ["title", "resume", "company"] {
+        add_column_if_missing(&conn, "pfe", column, "TEXT").expect("Failed to add pfe column");
+    }
+    add_column_if_missing(&conn, "etudiant", "supervisor_id", "INTEGER").expect("Failed to add etudiant.supervisor_id");
+    add_column_if_missing(&conn, "etudiant", "email", "TEXT").expect("Failed to add etudiant.email");
+    add_column_if_missing(&conn, "jury", "max_per_day", "INTEGER").expect("Failed to add jury.max_per_day");
+    add_column_if_missing(&conn, "jury", "max_per_session", "INTEGER").expect("Failed to add jury.max_per_session");
+    add_column_if_missing(&conn, "jury", "keywords", "TEXT NOT NULL DEFAULT '[]'").expect("Failed to add jury.keywords");
+    add_column_if_missing(&conn, "jury", "department", "TEXT").expect("Failed to add jury.department");
+    add_column_if_missing(&conn, "jury", "grade", "TEXT").expect("Failed to add jury.grade");
+    add_column_if_missing(&conn, "jury", "external", "INTEGER NOT NULL DEFAULT 0").expect("Failed to add jury.external");
+    add_column_if_missing(&conn, "jury", "affiliation", "TEXT").expect("Failed to add jury.affiliation");
+    add_column_if_missing(&conn, "jury", "payment_reference", "TEXT").expect("Failed to add jury.payment_reference");
+    add_column_if_missing(&conn, "jury", "honorarium_rate", "REAL").expect("Failed to add jury.honorarium_rate");
+    add_column_if_missing(&conn, "jury", "travel_rate", "REAL").expect("Failed to add jury.travel_rate");
+    add_column_if_missing(&conn, "classroom", "capacity", "INTEGER").expect("Failed to add classroom.capacity");
+    add_column_if_missing(&conn, "classroom", "building", "TEXT").expect("Failed to add classroom.building");
+    add_column_if_missing(&conn, "classroom", "floor", "TEXT").expect("Failed to add classroom.floor");
+    add_column_if_missing(&conn, "classroom", "equipment", "TEXT NOT NULL DEFAULT '[]'").expect("Failed to add classroom.equipment");
+    add_column_if_missing(&conn, "invite_soutenance", "status", "TEXT NOT NULL DEFAULT 'pending'").expect("Failed to add invite_soutenance.status");
+    add_column_if_missing(&conn, "invite_soutenance", "rsvp_token", "TEXT").expect("Failed to add invite_soutenance.rsvp_token");
+    add_column_if_missing(&conn, "invite_soutenance", "responded_at", "TEXT").expect("Failed to add invite_soutenance.responded_at");
+    add_column_if_missing(&conn, "invite_soutenance", "checked_in_at", "TEXT").expect("Failed to add invite_soutenance.checked_in_at");
+    invite_soutenance::ensure_rsvp_tokens(&conn).expect("Failed to issue RSVP tokens");
+    conn.execute("CREATE UNIQUE INDEX IF NOT EXISTS invite_soutenance_token ON invite_soutenance (rsvp_token)", [])
+        .expect("Failed to index RSVP tokens");
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS classroom_opening_hours (
+            classroom_id INTEGER NOT NULL,
+            weekday INTEGER NOT NULL,
+            opens TEXT NOT NULL,
+            closes TEXT NOT NULL,
+            PRIMARY KEY (classroom_id, weekday)
+        )",
+        [],
+    )
+    .expect("Failed to create classroom_opening_hours table");
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS classroom_closure (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            classroom_id INTEGER NOT NULL,
+            start_date TEXT NOT NULL,
+            end_date TEXT NOT NULL,
+            reason TEXT
+        )",
+        [],
+    )
+    .expect("Failed to create classroom_closure table");
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            user_id INTEGER,
+            user_email TEXT,
+            timestamp TEXT NOT NULL DEFAULT (datetime('now')),
+            action TEXT NOT NULL,
+            entity_type TEXT NOT NULL,
+            entity_id TEXT NOT NULL,
+            before_json TEXT,
+            after_json TEXT,
+            changes_json TEXT NOT NULL
+        )",
+        [],
+    )
+    .expect("Failed to create audit_log table");
+
+    // The audit log is append-only: rows can be inserted but never rewritten or removed.
+    conn.execute_batch(
+        "CREATE TRIGGER IF NOT EXISTS audit_log_no_update BEFORE UPDATE ON audit_log
+        BEGIN SELECT RAISE(ABORT, 'audit_log is append-only'); END;
+        CREATE TRIGGER IF NOT EXISTS audit_log_no_delete BEFORE DELETE ON audit_log
+        BEGIN SELECT RAISE(ABORT, 'audit_log is append-only'); END;
+        CREATE INDEX IF NOT EXISTS audit_log_entity ON audit_log (entity_type, entity_id);
+        CREATE INDEX IF NOT EXISTS audit_log_user ON audit_log (user_id, timestamp);",
+    )
+    .expect("Failed to create audit_log triggers");
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS operation_journal (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_token TEXT NOT NULL,
+            user_id INTEGER,
+            label TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            undone INTEGER NOT NULL DEFAULT 0
+        )",
+        [],
+    )
+    .expect("Failed to create operation_journal table");
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS operation_journal_entry (
+            operation_id INTEGER NOT NULL,
+            seq INTEGER NOT NULL,
+            entity_type TEXT NOT NULL,
+            key_json TEXT NOT NULL,
+            before_json TEXT,
+            after_json TEXT,
+            PRIMARY KEY (operation_id, seq)
+        )",
+        [],
+    )
+    .expect("Failed to create operation_journal_entry table");
+
+    // RSVP tokens used to be journaled along with the rest of the invitation.
+    conn.execute(
+        "UPDATE operation_journal_entry
+        SET before_json = json_remove(before_json, '$.rsvp_token'), after_json = json_remove(after_json, '$.rsvp_token')
+        WHERE entity_type = 'invite_soutenance'",
+        [],
+    )
+    .expect("Failed to remove RSVP tokens from the journal");
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS jury_composition_rule (
+            specialite_id INTEGER PRIMARY KEY,
+            min_size INTEGER NOT NULL,
+            max_size INTEGER,
+            presidents INTEGER NOT NULL,
+            min_rapporteurs INTEGER NOT NULL,
+            supervisor_can_preside INTEGER NOT NULL DEFAULT 0
+        )",
+        [],
+    )
+    .expect("Failed to create jury_composition_rule table");
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS jury_conflict (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            jury_id INTEGER NOT NULL,
+            kind TEXT NOT NULL,
+            etudiant_id INTEGER,
+            company TEXT,
+            other_jury_id INTEGER,
+            reason TEXT,
+            declared_at TEXT NOT NULL DEFAULT (datetime('now'))
+        )",
+        [],
+    )
+    .expect("Failed to create jury_conflict table");
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS jury_conflict_override (
+            jury_id INTEGER NOT NULL,
+            soutenance_id INTEGER NOT NULL,
+            justification TEXT NOT NULL,
+            conflicts TEXT NOT NULL,
+            user_email TEXT,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (jury_id, soutenance_id)
+        )",
+        [],
+    )
+    .expect("Failed to create jury_conflict_override table");
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS attendance (
+            soutenance_id INTEGER NOT NULL,
+            holder_kind TEXT NOT NULL,
+            holder_id INTEGER NOT NULL,
+            arrived_at TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (soutenance_id, holder_kind, holder_id)
+        )",
+        [],
+    )
+    .expect("Failed to create attendance table");
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS outbox (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            soutenance_id INTEGER,
+            template TEXT NOT NULL,
+            recipient_kind TEXT NOT NULL,
+            recipient_id INTEGER,
+            email TEXT NOT NULL,
+            subject TEXT NOT NULL,
+            body TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'queued',
+            attempts INTEGER NOT NULL DEFAULT 0,
+            next_attempt_at TEXT NOT NULL DEFAULT (datetime('now')),
+            last_error TEXT,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            sent_at TEXT
+        )",
+        [],
+    )
+    .expect("Failed to create outbox table");
+    conn.execute("CREATE INDEX IF NOT EXISTS outbox_due ON outbox (status, next_attempt_at)", [])
+        .expect("Failed to index outbox");
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS pending_notification (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            soutenance_id INTEGER NOT NULL,
+            recipient_kind TEXT NOT NULL,
+            recipient_id INTEGER NOT NULL,
+            field TEXT NOT NULL,
+            old_value TEXT,
+            new_value TEXT,
+            context TEXT NOT NULL,
+            due_at TEXT NOT NULL,
+            created_at TEXT NOT NULL DEFAULT (datetime('now'))
+        )",
+        [],
+    )
+    .expect("Failed to create pending_notification table");
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS notification_preference (
+            recipient_kind TEXT NOT NULL,
+            recipient_id INTEGER NOT NULL,
+            muted INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (recipient_kind, recipient_id)
+        )",
+        [],
+    )
+    .expect("Failed to create notification_preference table");
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS reminder (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            soutenance_id INTEGER NOT NULL,
+            recipient_kind TEXT NOT NULL,
+            recipient_id INTEGER NOT NULL,
+            offset_minutes INTEGER NOT NULL,
+            due_at TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'pending',
+            outbox_id INTEGER,
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            UNIQUE (soutenance_id, recipient_kind, recipient_id, offset_minutes)
+        )",
+        [],
+    )
+    .expect("Failed to create reminder table");
+
+    // At most one template per kind and language for each specialité, and one default.
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS template (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            kind TEXT NOT NULL,
+            language TEXT NOT NULL,
+            specialite_id INTEGER,
+            subject TEXT NOT NULL DEFAULT '',
+            body TEXT NOT NULL,
+            updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+        )",
+        [],
+    )
+    .expect("Failed to create template table");
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS template_scope ON template (kind, language, IFNULL(specialite_id, 0))",
+        [],
+    )
+    .expect("Failed to create template index");
+
+    // Rows created by the demo data generator, so they can be purged.
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS demo_row (
+            entity_type TEXT NOT NULL,
+            entity_id INTEGER NOT NULL,
+            PRIMARY KEY (entity_type, entity_id)
+        )",
+        [],
+    )
+    .expect("Failed to create demo_row table");
+
+    // Roles used to be free text and the frontend sent 'member' for everyone.
+    conn.execute(
+        "UPDATE jury_soutenance SET role = 'examinateur' WHERE role IS NULL OR role NOT IN
+            ('president', 'rapporteur', 'examinateur', 'encadrant_academique', 'encadrant_professionnel', 'invite_honneur')",
+        [],
+    )
+    .expect("Failed to migrate jury roles");
+
+    session::ensure_default_session(&conn).expect("Failed to initialise the active session");
+    search::ensure_search_index(&conn).expect("Failed to initialise the search index");
+    conn
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    let db_path = get_db_path();
+    let conn = open_database(&db_path);
+    mailer::spawn_worker(db_path.clone());
+
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_opener::init())
         .manage(AppState {
-            db: Mutex::new(conn),
+            db: Database::new(&db_path, conn),
+            current_user: Mutex::new(None),
+        })
+        .setup(|app| {
+            if let Err(e) = api::restart(app.handle()) {
+                eprintln!("API server: {}", e);
+            }
+            Ok(())
         })
         .invoke_handler(tauri::generate_handler![
-            login,
-            etudiant::create_student,
-            etudiant::get_student,
-            etudiant::get_students_by_department,
-            etudiant::update_student,
-            etudiant::delete_students,
-            etudiant::get_specialite_students,
-            invite::create_invite,
-            invite::get_invite,
-            invite::update_invite,
-            invite::delete_invite,
-            invite::get_invite_soutenances,
-            invite::get_all_invite,
-
-            invite_soutenance::create_invite_soutenance,
-            invite_soutenance::get_invite_soutenance,
-            invite_soutenance::update_invite_soutenance,
-            invite_soutenance::delete_invite_soutenance,
-
-            jury_soutenance::create_jury_soutenance,
-            jury_soutenance::get_jury_soutenance,
-            jury_soutenance::update_jury_soutenance,
-            jury_soutenance::delete_jury_soutenance,
-            jury::create_jury,
-            jury::get_jury,
-            jury::get_all_jury,
-            jury::update_jury,
-            jury::delete_jury,
-            jury::get_jury_soutenances,
-            pfe::create_pfe,
-            pfe::get_pfe,
-            pfe::update_pfe,
-            pfe::delete_pfe,
-            classroom::create_classroom,    
-            classroom::get_all_classrooms,
-            classroom::get_classroom,
-            classroom::update_classroom,
-            classroom::delete_classrooms,
-            classroom::get_classroom_soutenances,
-            soutenance::create_soutenance,
-            soutenance::get_soutenance,
-            soutenance::update_soutenance,
-            soutenance::delete_soutenance,
-            soutenance::get_soutenance_students,
-            soutenance::get_soutenance_jurys,
-            soutenance::get_soutenance_invites,
-            soutenance::get_specialite_soutenances,
-            specialite::create_specialite,
-            specialite::get_specialite,
-            specialite::update_specialite,
-            specialite::delete_specialite,
+            commands::user::login,
+            commands::user::logout,
+            backup_database,
+            commands::audit::get_audit_log,
+            commands::audit::export_audit_log_csv,
+            commands::journal::undo,
+            commands::journal::redo,
+            commands::journal::get_operation_history,
+            commands::archive::get_archive,
+            commands::archive::restore_archived,
+            commands::archive::purge_archive,
+            commands::archive::get_archive_retention_days,
+            commands::archive::set_archive_retention_days,
+            commands::search::global_search,
+            commands::demo::generate_demo_data,
+            commands::demo::get_demo_data,
+            commands::demo::purge_demo_data,
+            commands::composition::get_composition_rules,
+            commands::composition::set_composition_rules,
+            commands::composition::get_soutenance_composition,
+            commands::composition::get_composition_violations,
+            commands::workload::get_jury_workload,
+            commands::workload::suggest_jury_rebalancing,
+            commands::workload::apply_jury_swap,
+            commands::workload::get_jury_caps,
+            commands::workload::set_jury_caps,
+            commands::expertise::get_jury_profile,
+            commands::expertise::set_jury_profile,
+            commands::expertise::suggest_reviewers,
+            commands::conflict::get_jury_conflicts,
+            commands::conflict::declare_jury_conflict,
+            commands::conflict::delete_jury_conflict,
+            commands::conflict::get_assignment_conflicts,
+            commands::conflict::get_conflict_overrides,
+            commands::honoraria::get_jury_payment,
+            commands::honoraria::set_jury_payment,
+            commands::honoraria::get_honoraria_rates,
+            commands::honoraria::set_honoraria_rates,
+            commands::honoraria::compute_honoraria,
+            commands::honoraria::export_honoraria_csv,
+            commands::honoraria::export_honoraria_pdf,
+            commands::honoraria::export_payment_statement_csv,
+            commands::honoraria::export_payment_statement_pdf,
+            commands::etudiant::create_student,
+            commands::etudiant::get_student,
+            commands::etudiant::get_students_by_department,
+            commands::etudiant::update_student,
+            commands::etudiant::delete_students,
+            commands::etudiant::get_specialite_students,
+            commands::etudiant::set_student_grade,
+            commands::etudiant::set_student_supervisor,
+            commands::etudiant::import_students_csv,
+            commands::invite::create_invite,
+            commands::invite::get_invite,
+            commands::invite::update_invite,
+            commands::invite::delete_invite,
+            commands::invite::get_invite_soutenances,
+            commands::invite::get_all_invite,
+
+            commands::invite_soutenance::create_invite_soutenance,
+            commands::invite_soutenance::get_invite_soutenance,
+            commands::invite_soutenance::update_invite_soutenance,
+            commands::invite_soutenance::delete_invite_soutenance,
+            commands::invite_soutenance::get_soutenance_invitations,
+            commands::invite_soutenance::get_invitation_counts,
+            commands::invite_soutenance::respond_to_invitation,
+            commands::invite_soutenance::check_in_invitation,
+            commands::invite_soutenance::regenerate_rsvp_token,
+            commands::badge::get_soutenance_badges,
+            commands::badge::export_soutenance_badges,
+            commands::badge::check_in_badge,
+            commands::badge::get_attendance_list,
+            commands::badge::export_attendance_csv,
+            commands::badge::rotate_badge_key,
+            commands::outbox::get_smtp_settings,
+            commands::outbox::set_smtp_settings,
+            commands::outbox::send_test_email,
+            commands::outbox::preview_soutenance_notifications,
+            commands::outbox::enqueue_soutenance_notifications,
+            commands::outbox::get_outbox,
+            commands::outbox::cancel_outbox_message,
+            commands::outbox::cancel_soutenance_notifications,
+            commands::outbox::resend_outbox_message,
+            commands::outbox::flush_outbox,
+            commands::notification::get_pending_notifications,
+            commands::notification::flush_pending_notifications,
+            commands::notification::discard_pending_notifications,
+            commands::notification::get_notification_preference,
+            commands::notification::set_notification_preference,
+            commands::reminder::get_reminder_offsets,
+            commands::reminder::set_reminder_offsets,
+            commands::reminder::get_upcoming_reminders,
+            commands::reminder::cancel_reminder,
+            commands::reminder::cancel_soutenance_reminders,
+            commands::template::get_templates,
+            commands::template::get_effective_template,
+            commands::template::save_template,
+            commands::template::delete_template,
+            commands::template::validate_template,
+            commands::template::preview_template,
+            commands::template::get_template_variables,
+            commands::template::get_template_language,
+            commands::template::set_template_language,
+            commands::template::export_pv,
+            api::get_api_settings,
+            api::set_api_settings,
+            api::regenerate_api_token,
+
+            commands::jury_soutenance::get_jury_roles,
+            commands::jury_soutenance::create_jury_soutenance,
+            commands::jury_soutenance::get_jury_soutenance,
+            commands::jury_soutenance::update_jury_soutenance,
+            commands::jury_soutenance::delete_jury_soutenance,
+            commands::jury::create_jury,
+            commands::jury::get_jury,
+            commands::jury::get_all_jury,
+            commands::jury::update_jury,
+            commands::jury::delete_jury,
+            commands::jury::get_jury_soutenances,
+            commands::pfe::create_pfe,
+            commands::pfe::get_pfe,
+            commands::pfe::update_pfe,
+            commands::pfe::delete_pfe,
+            commands::classroom::create_classroom,    
+            commands::classroom::get_all_classrooms,
+            commands::classroom::get_classroom,
+            commands::classroom::update_classroom,
+            commands::classroom::delete_classrooms,
+            commands::classroom::get_classroom_soutenances,
+            commands::classroom::get_classroom_opening_hours,
+            commands::classroom::set_classroom_opening_hours,
+            commands::classroom::get_classroom_closures,
+            commands::classroom::add_classroom_closure,
+            commands::classroom::delete_classroom_closure,
+            commands::classroom::find_available_classrooms,
+            commands::occupancy::get_classroom_agenda,
+            commands::occupancy::get_classroom_utilisation,
+            commands::soutenance::create_soutenance,
+            commands::soutenance::create_soutenance_with_participants,
+            commands::soutenance::get_defence_duration,
+            commands::soutenance::set_defence_duration,
+            commands::soutenance::export_planning_csv,
+            commands::soutenance::get_soutenance,
+            commands::soutenance::update_soutenance,
+            commands::soutenance::delete_soutenance,
+            commands::soutenance::get_soutenance_students,
+            commands::soutenance::get_soutenance_jurys,
+            commands::soutenance::get_soutenance_invites,
+            commands::soutenance::get_specialite_soutenances,
+            commands::session::create_annee_universitaire,
+            commands::session::get_all_annees_universitaires,
+            commands::session::create_session,
+            commands::session::get_all_sessions,
+            commands::session::get_active_session,
+            commands::session::set_active_session,
+            commands::session::close_session,
+            commands::specialite::create_specialite,
+            commands::specialite::get_specialite,
+            commands::specialite::update_specialite,
+            commands::specialite::delete_specialite,
             
-            specialite::get_specialite_pfes,
-            user::create_user,
-            user::get_user,
-            user::update_user,
-            user::delete_user
+            commands::specialite::get_specialite_pfes,
+            commands::user::create_user,
+            commands::user::get_user,
+            commands::user::update_user,
+            commands::user::delete_user
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
 
-#[tauri::command]
-fn login(email: String, password: String, state: State<AppState>) -> Result<LoginResponse, String> {
-    let conn = state.db.lock().unwrap();
-    //let hashed_password = user::hash_password(&password);
-    let mut stmt = conn
-        .prepare("SELECT id FROM users WHERE email = ?1 AND password = ?2")
-        .map_err(|e| e.to_string())?;
-    let user_exists = stmt
-        .exists([&email, &password])
-        .map_err(|e| e.to_string())?;
-    if user_exists {
-        let token = Uuid::new_v4().to_string();
-        Ok(LoginResponse {
-            access_token: token,
-            message: "Login successful".to_string(),
-        })
-    } 
-    else {
-        //Err("Invalid credentials".to_string())
-        Ok(LoginResponse {
-            access_token: "".to_string(),
-            message: "Invalid credentials".to_string(),
-        })
+/// Writes a consistent copy of the database to `path`, which must not exist yet.
+pub fn backup_to(conn: &Connection, path: &Path) -> Result<(), String> {
+    if path.exists() {
+        return Err(format!("{} already exists", path.display()));
     }
+    conn.execute("VACUUM INTO ?1", [path.to_string_lossy()])
+        .map(|_| ())
+        .map_err(|e| format!("Backup failed: {}", e))
+}
+
+#[tauri::command]
+async fn backup_database(path: String, state: State<'_, AppState>) -> Result<(), String> {
+    // `VACUUM INTO` counts as a write, which the read connections refuse.
+    state.detached(move |conn| backup_to(conn, Path::new(&path))).await
 }
@@ -0,0 +1,190 @@
+use crate::models::{notification, outbox, reminder};
+use crate::models::settings::{get_setting, set_setting};
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use rusqlite::Connection;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+const HOST_KEY: &str = "smtp_host";
+const PORT_KEY: &str = "smtp_port";
+const SECURITY_KEY: &str = "smtp_security";
+const USERNAME_KEY: &str = "smtp_username";
+const PASSWORD_KEY: &str = "smtp_password";
+const FROM_KEY: &str = "smtp_from";
+/// How often the worker looks for messages that are due.
+const POLL_INTERVAL: Duration = Duration::from_secs(15);
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How the connection to the SMTP server is secured. `None` is meant for a local sink
+/// such as MailHog or `python -m aiosmtpd` while testing.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    None,
+    StartTls,
+    Tls,
+}
+
+impl SmtpSecurity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SmtpSecurity::None => "none",
+            SmtpSecurity::StartTls => "starttls",
+            SmtpSecurity::Tls => "tls",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<SmtpSecurity, String> {
+        match value {
+            "none" => Ok(SmtpSecurity::None),
+            "starttls" => Ok(SmtpSecurity::StartTls),
+            "tls" => Ok(SmtpSecurity::Tls),
+            other => Err(format!("Unknown SMTP security: {}", other)),
+        }
+    }
+
+    fn default_port(&self) -> u16 {
+        match self {
+            SmtpSecurity::None => 25,
+            SmtpSecurity::StartTls => 587,
+            SmtpSecurity::Tls => 465,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+}
+
+/// SMTP settings as shown to the user; the password never leaves the backend.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct SmtpSettings {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    /// On save, `None` keeps the stored password and an empty string removes it.
+    #[serde(default, skip_serializing)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub has_password: bool,
+    pub from: Option<String>,
+}
+
+pub fn load_settings(conn: &Connection) -> Result<SmtpSettings, String> {
+    Ok(SmtpSettings {
+        host: get_setting(conn, HOST_KEY)?,
+        port: get_setting(conn, PORT_KEY)?.and_then(|port| port.parse().ok()),
+        security: match get_setting(conn, SECURITY_KEY)? {
+            Some(security) => SmtpSecurity::parse(&security)?,
+            None => SmtpSecurity::StartTls,
+        },
+        username: get_setting(conn, USERNAME_KEY)?,
+        password: None,
+        has_password: get_setting(conn, PASSWORD_KEY)?.is_some(),
+        from: get_setting(conn, FROM_KEY)?,
+    })
+}
+
+/// Keys written by `save_settings`, except the password, for the audit log.
+pub const AUDITED_KEYS: [&str; 5] = [HOST_KEY, PORT_KEY, SECURITY_KEY, USERNAME_KEY, FROM_KEY];
+
+pub fn save_settings(conn: &Connection, settings: &SmtpSettings) -> Result<(), String> {
+    if let Some(from) = settings.from.as_deref().filter(|from| !from.trim().is_empty()) {
+        from.trim().parse::<Mailbox>().map_err(|e| format!("Invalid sender address {}: {}", from, e))?;
+    }
+    let trimmed = |value: &Option<String>| value.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(str::to_string);
+    set_setting(conn, HOST_KEY, trimmed(&settings.host).as_deref())?;
+    set_setting(conn, PORT_KEY, settings.port.map(|port| port.to_string()).as_deref())?;
+    set_setting(conn, SECURITY_KEY, Some(settings.security.as_str()))?;
+    set_setting(conn, USERNAME_KEY, trimmed(&settings.username).as_deref())?;
+    set_setting(conn, FROM_KEY, trimmed(&settings.from).as_deref())?;
+    if let Some(password) = &settings.password {
+        set_setting(conn, PASSWORD_KEY, Some(password.as_str()).filter(|p| !p.is_empty()))?;
+    }
+    Ok(())
+}
+
+/// The configuration to send with, or `None` while no server or sender is set up.
+pub fn load_config(conn: &Connection) -> Result<Option<SmtpConfig>, String> {
+    let settings = load_settings(conn)?;
+    let (Some(host), Some(from)) = (settings.host, settings.from) else {
+        return Ok(None);
+    };
+    Ok(Some(SmtpConfig {
+        port: settings.port.unwrap_or_else(|| settings.security.default_port()),
+        host,
+        security: settings.security,
+        username: settings.username,
+        password: get_setting(conn, PASSWORD_KEY)?,
+        from,
+    }))
+}
+
+pub fn transport(config: &SmtpConfig) -> Result<SmtpTransport, String> {
+    let builder = match config.security {
+        SmtpSecurity::None => SmtpTransport::builder_dangerous(&config.host),
+        SmtpSecurity::StartTls => SmtpTransport::starttls_relay(&config.host).map_err(|e| e.to_string())?,
+        SmtpSecurity::Tls => SmtpTransport::relay(&config.host).map_err(|e| e.to_string())?,
+    };
+    let mut builder = builder.port(config.port).timeout(Some(SEND_TIMEOUT));
+    if let Some(username) = &config.username {
+        builder = builder.credentials(Credentials::new(username.clone(), config.password.clone().unwrap_or_default()));
+    }
+    Ok(builder.build())
+}
+
+pub fn send(transport: &SmtpTransport, config: &SmtpConfig, to: &str, subject: &str, body: &str) -> Result<(), String> {
+    let message = Message::builder()
+        .from(config.from.parse::<Mailbox>().map_err(|e| format!("Invalid sender address: {}", e))?)
+        .to(to.parse::<Mailbox>().map_err(|e| format!("Invalid recipient address {}: {}", to, e))?)
+        .subject(subject)
+        .header(ContentType::TEXT_PLAIN)
+        .body(body.to_string())
+        .map_err(|e| format!("Failed to build message: {}", e))?;
+    transport.send(&message).map_err(|e| format!("SMTP error: {}", e))?;
+    Ok(())
+}
+
+/// Queues the batched change notifications once their window closes and the reminders
+/// that came due, and sends due outbox messages, in the background for as long as the
+/// app runs. The worker
+/// has its own connection so a slow SMTP server never holds the app's database lock.
+pub fn spawn_worker(db_path: PathBuf) {
+    std::thread::spawn(move || {
+        let conn = match Connection::open(&db_path) {
+            Ok(conn) => conn,
+            Err(e) => {
+                eprintln!("Outbox worker disabled: {}", e);
+                return;
+            }
+        };
+        let _ = conn.busy_timeout(Duration::from_secs(5));
+        if let Err(e) = outbox::requeue_interrupted(&conn) {
+            eprintln!("Outbox worker: {}", e);
+        }
+        loop {
+            if let Err(e) = notification::flush_pending(&conn, false) {
+                eprintln!("Outbox worker: {}", e);
+            }
+            if let Err(e) = reminder::process_due(&conn) {
+                eprintln!("Outbox worker: {}", e);
+            }
+            if let Err(e) = outbox::process_due(&conn) {
+                eprintln!("Outbox worker: {}", e);
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    });
+}
@@ -0,0 +1,205 @@
+use crate::models::audit;
+use crate::models::classroom;
+use crate::models::conflict;
+use crate::models::journal::Operation;
+use crate::models::notification;
+use crate::models::session::ensure_student_open;
+use crate::models::settings::{get_setting, set_setting};
+use crate::models::user::CurrentUser;
+use rusqlite::{Connection, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub const RETENTION_KEY: &str = "archive_retention_days";
+const DEFAULT_RETENTION_DAYS: i64 = 365;
+
+/// Tables supporting soft deletion, with the expression used to label their rows.
+const ARCHIVABLE: &[(&str, &str)] = &[
+    ("jury", "firstname || ' ' || lastname"),
+    ("invite", "firstname || ' ' || lastname"),
+    ("etudiant", "firstname || ' ' || lastname"),
+    ("classroom", "name"),
+];
+
+/// Assignment tables purged together with the row they point at.
+const DEPENDENTS: &[(&str, &str, &str)] = &[
+    ("jury", "jury_soutenance", "jury_id"),
+    ("invite", "invite_soutenance", "invite_id"),
+];
+
+/// Optional references cleared when the row they point at is purged.
+const REFERENCES: &[(&str, &str, &str)] = &[
+    ("classroom", "soutenance", "classroom_id"),
+    ("jury", "etudiant", "supervisor_id"),
+];
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct ArchivedEntry {
+    pub entity_type: String,
+    pub id: i32,
+    pub label: String,
+    pub deleted_at: String,
+}
+
+fn label_expression(entity_type: &str) -> Result<&'static str, String> {
+    ARCHIVABLE
+        .iter()
+        .find(|(table, _)| *table == entity_type)
+        .map(|(_, label)| *label)
+        .ok_or_else(|| format!("{} cannot be archived", entity_type))
+}
+
+fn retention_days(conn: &Connection) -> Result<i64, String> {
+    Ok(get_setting(conn, RETENTION_KEY)?
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_RETENTION_DAYS))
+}
+
+fn ids_where(conn: &Connection, query: &str, value: &dyn rusqlite::ToSql) -> Result<Vec<i32>, String> {
+    let mut stmt = conn.prepare(query).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([value], |row| row.get(0)).map_err(|e| e.to_string())?;
+    let result: Result<Vec<i32>, _> = rows.collect();
+    result.map_err(|e| e.to_string())
+}
+
+pub fn get_archive(conn: &Connection, entity_type: Option<String>) -> Result<Vec<ArchivedEntry>, String> {
+    let mut entries = Vec::new();
+    for (table, label) in ARCHIVABLE {
+        if entity_type.as_deref().is_some_and(|wanted| wanted != *table) {
+            continue;
+        }
+        let mut stmt = conn
+            .prepare(&format!(
+                "SELECT id, {}, deleted_at FROM {} WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC",
+                label, table
+            ))
+            .map_err(|e| format!("Failed to prepare archive query: {}", e))?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(ArchivedEntry {
+                    entity_type: table.to_string(),
+                    id: row.get(0)?,
+                    label: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                    deleted_at: row.get(2)?,
+                })
+            })
+            .map_err(|e| format!("Failed to query archive: {}", e))?;
+        for entry in rows {
+            entries.push(entry.map_err(|e| format!("Failed to collect archive: {}", e))?);
+        }
+    }
+    entries.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at));
+    Ok(entries)
+}
+
+pub fn restore_archived(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    entity_type: String,
+    ids: Vec<i32>,
+) -> Result<usize, String> {
+    label_expression(&entity_type)?;
+    let mut operation = Operation::new(&format!("restore_{}", entity_type));
+    let mut restored = 0;
+    for id in ids {
+        if entity_type == "etudiant" {
+            ensure_student_open(conn, id)?;
+        }
+        operation.track(conn, &entity_type, &[("id", id.into())])?;
+        restored += conn
+            .execute(
+                &format!("UPDATE {} SET deleted_at = NULL WHERE id = ?1 AND deleted_at IS NOT NULL", entity_type),
+                [id],
+            )
+            .map_err(|e| format!("Failed to restore {} {}: {}", entity_type, id, e))?;
+    }
+    operation.finish(conn, actor)?;
+    Ok(restored)
+}
+
+/// Permanently removes rows archived for longer than `older_than_days`, or than the
+/// configured retention period when omitted. Jury and invitee assignments, and room
+/// schedules, go with them.
+pub fn purge_archive(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    older_than_days: Option<i64>,
+) -> Result<usize, String> {
+    let days = match older_than_days {
+        Some(days) => days,
+        None => retention_days(conn)?,
+    };
+    let cutoff = format!("-{} days", days.max(0));
+
+    let mut purged = 0;
+    for (table, _) in ARCHIVABLE {
+        let ids = ids_where(
+            conn,
+            &format!(
+                "SELECT id FROM {} WHERE deleted_at IS NOT NULL AND deleted_at <= datetime('now', ?1)",
+                table
+            ),
+            &cutoff,
+        )?;
+        for id in ids {
+            for (owner, dependent, column) in DEPENDENTS {
+                if owner != table {
+                    continue;
+                }
+                let soutenance_ids = ids_where(
+                    conn,
+                    &format!("SELECT soutenance_id FROM {} WHERE {} = ?1", dependent, column),
+                    &id,
+                )?;
+                for soutenance_id in soutenance_ids {
+                    let key = [(*column, id.into()), ("soutenance_id", soutenance_id.into())];
+                    let before = audit::snapshot(conn, dependent, &key)?;
+                    conn.execute(
+                        &format!("DELETE FROM {} WHERE {} = ?1 AND soutenance_id = ?2", dependent, column),
+                        [id, soutenance_id],
+                    )
+                    .map_err(|e| format!("Failed to purge {}: {}", dependent, e))?;
+                    audit::record_change(conn, actor, dependent, &key, before)?;
+                }
+            }
+            for (owner, referrer, column) in REFERENCES {
+                if owner != table {
+                    continue;
+                }
+                let referrer_ids = ids_where(conn, &format!("SELECT id FROM {} WHERE {} = ?1", referrer, column), &id)?;
+                for referrer_id in referrer_ids {
+                    let before = audit::snapshot(conn, referrer, &[("id", referrer_id.into())])?;
+                    conn.execute(&format!("UPDATE {} SET {} = NULL WHERE id = ?1", referrer, column), [referrer_id])
+                        .map_err(|e| format!("Failed to clear {}.{}: {}", referrer, column, e))?;
+                    audit::record_change(conn, actor, referrer, &[("id", referrer_id.into())], before)?;
+                }
+            }
+            if *table == "classroom" {
+                classroom::purge_schedule(conn, actor, id)?;
+            }
+            conflict::purge_declarations(conn, actor, table, id)?;
+            notification::purge_preference(conn, actor, table, id)?;
+            let before = audit::snapshot(conn, table, &[("id", id.into())])?;
+            conn.execute(&format!("DELETE FROM {} WHERE id = ?1", table), [id])
+                .map_err(|e| format!("Failed to purge {} {}: {}", table, id, e))?;
+            audit::record_change(conn, actor, table, &[("id", id.into())], before)?;
+            purged += 1;
+        }
+    }
+    Ok(purged)
+}
+
+pub fn get_archive_retention_days(conn: &Connection) -> Result<i64, String> {
+    retention_days(conn)
+}
+
+pub fn set_archive_retention_days(conn: &Connection, actor: &Option<CurrentUser>, days: i64) -> Result<(), String> {
+    if days < 0 {
+        return Err("The retention period cannot be negative".to_string());
+    }
+    let key = [("key", RETENTION_KEY.to_string().into())];
+    let before = audit::snapshot(conn, "settings", &key)?;
+    set_setting(conn, RETENTION_KEY, Some(&days.to_string()))?;
+    audit::record_change(conn, actor, "settings", &key, before)?;
+    Ok(())
+}
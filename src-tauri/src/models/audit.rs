@@ -0,0 +1,223 @@
+use crate::export::write_csv;
+use crate::models::user::CurrentUser;
+use rusqlite::types::{Value as SqlValue, ValueRef};
+use rusqlite::{params, Connection, OptionalExtension, Result, ToSql};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Secrets never copied into the log or the undo journal.
+const REDACTED_COLUMNS: &[&str] = &["password", "rsvp_token"];
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub user_id: Option<i32>,
+    pub user_email: Option<String>,
+    pub timestamp: String,
+    pub action: String,
+    pub entity_type: String,
+    pub entity_id: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub changes: Value,
+}
+
+#[derive(Deserialize, JsonSchema, Default, Debug)]
+pub struct AuditFilter {
+    pub entity_type: Option<String>,
+    pub entity_id: Option<String>,
+    pub user_id: Option<i32>,
+    /// Inclusive lower bound, `YYYY-MM-DD` or `YYYY-MM-DD HH:MM:SS` (UTC).
+    pub from: Option<String>,
+    /// Inclusive upper bound; a bare date covers the whole day.
+    pub to: Option<String>,
+    pub limit: Option<i64>,
+}
+
+fn json_value(value: ValueRef) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => Value::from(i),
+        ValueRef::Real(f) => Value::from(f),
+        ValueRef::Text(t) => Value::String(String::from_utf8_lossy(t).into_owned()),
+        ValueRef::Blob(b) => Value::String(format!("<{} bytes>", b.len())),
+    }
+}
+
+fn entity_id(key: &[(&str, SqlValue)]) -> String {
+    key.iter()
+        .map(|(_, value)| match value {
+            SqlValue::Integer(i) => i.to_string(),
+            SqlValue::Real(f) => f.to_string(),
+            SqlValue::Text(t) => t.clone(),
+            SqlValue::Null => "null".to_string(),
+            SqlValue::Blob(_) => "blob".to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Reads the row identified by `key` as a JSON object, or `None` if it does not exist.
+pub fn snapshot(conn: &Connection, table: &str, key: &[(&str, SqlValue)]) -> Result<Option<Value>, String> {
+    let where_clause = key
+        .iter()
+        .enumerate()
+        .map(|(i, (column, _))| format!("{} = ?{}", column, i + 1))
+        .collect::<Vec<_>>()
+        .join(" AND ");
+    let mut stmt = conn
+        .prepare(&format!("SELECT * FROM {} WHERE {}", table, where_clause))
+        .map_err(|e| format!("Failed to prepare {} snapshot: {}", table, e))?;
+    let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+    let params: Vec<&dyn ToSql> = key.iter().map(|(_, value)| value as &dyn ToSql).collect();
+    stmt.query_row(&params[..], |row| {
+        let mut object = Map::new();
+        for (i, column) in columns.iter().enumerate() {
+            if !REDACTED_COLUMNS.contains(&column.as_str()) {
+                object.insert(column.clone(), json_value(row.get_ref(i)?));
+            }
+        }
+        Ok(Value::Object(object))
+    })
+    .optional()
+    .map_err(|e| format!("Failed to snapshot {}: {}", table, e))
+}
+
+fn diff(before: &Option<Value>, after: &Option<Value>) -> Value {
+    let empty = Map::new();
+    let before = before.as_ref().and_then(Value::as_object).unwrap_or(&empty);
+    let after = after.as_ref().and_then(Value::as_object).unwrap_or(&empty);
+    let mut changes = Map::new();
+    for key in before.keys().chain(after.keys().filter(|k| !before.contains_key(*k))) {
+        let old = before.get(key).cloned().unwrap_or(Value::Null);
+        let new = after.get(key).cloned().unwrap_or(Value::Null);
+        if old != new {
+            changes.insert(key.clone(), serde_json::json!({ "before": old, "after": new }));
+        }
+    }
+    Value::Object(changes)
+}
+
+/// Compares `before` with the current state of the row and appends the change to the log.
+/// Must run on the same connection or transaction as the mutation itself.
+pub fn record_change(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    table: &str,
+    key: &[(&str, SqlValue)],
+    before: Option<Value>,
+) -> Result<(), String> {
+    let after = snapshot(conn, table, key)?;
+    let action = match (&before, &after) {
+        (None, Some(_)) => "create",
+        (Some(_), None) => "delete",
+        (Some(old), Some(new)) if old != new => "update",
+        _ => return Ok(()),
+    };
+    let changes = diff(&before, &after);
+    conn.execute(
+        "INSERT INTO audit_log (user_id, user_email, action, entity_type, entity_id, before_json, after_json, changes_json)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            actor.as_ref().map(|u| u.id),
+            actor.as_ref().map(|u| u.email.clone()),
+            action,
+            table,
+            entity_id(key),
+            before.map(|v| v.to_string()),
+            after.map(|v| v.to_string()),
+            changes.to_string(),
+        ],
+    )
+    .map_err(|e| format!("Failed to write audit entry: {}", e))?;
+    Ok(())
+}
+
+fn parse_json(index: usize, text: Option<String>) -> Result<Option<Value>> {
+    text.map(|t| {
+        serde_json::from_str(&t).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(e))
+        })
+    })
+    .transpose()
+}
+
+fn query_entries(conn: &Connection, filter: &AuditFilter) -> Result<Vec<AuditEntry>, String> {
+    let to = filter.to.as_ref().map(|to| {
+        if to.len() == 10 {
+            format!("{} 23:59:59", to)
+        } else {
+            to.clone()
+        }
+    });
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, user_id, user_email, timestamp, action, entity_type, entity_id, before_json, after_json, changes_json
+            FROM audit_log
+            WHERE (?1 IS NULL OR entity_type = ?1)
+              AND (?2 IS NULL OR entity_id = ?2)
+              AND (?3 IS NULL OR user_id = ?3)
+              AND (?4 IS NULL OR timestamp >= ?4)
+              AND (?5 IS NULL OR timestamp <= ?5)
+            ORDER BY id DESC
+            LIMIT ?6",
+        )
+        .map_err(|e| format!("Failed to prepare audit query: {}", e))?;
+    let rows = stmt
+        .query_map(
+            params![
+                filter.entity_type,
+                filter.entity_id,
+                filter.user_id,
+                filter.from,
+                to,
+                filter.limit.unwrap_or(-1),
+            ],
+            |row| {
+                Ok(AuditEntry {
+                    id: row.get(0)?,
+                    user_id: row.get(1)?,
+                    user_email: row.get(2)?,
+                    timestamp: row.get(3)?,
+                    action: row.get(4)?,
+                    entity_type: row.get(5)?,
+                    entity_id: row.get(6)?,
+                    before: parse_json(7, row.get(7)?)?,
+                    after: parse_json(8, row.get(8)?)?,
+                    changes: parse_json(9, row.get(9)?)?.unwrap_or(Value::Null),
+                })
+            },
+        )
+        .map_err(|e| format!("Failed to query audit log: {}", e))?;
+    let result: Result<Vec<AuditEntry>, _> = rows.collect();
+    result.map_err(|e| format!("Failed to collect audit log: {}", e))
+}
+
+pub fn get_audit_log(conn: &Connection, filter: Option<AuditFilter>) -> Result<Vec<AuditEntry>, String> {
+    query_entries(conn, &filter.unwrap_or_default())
+}
+
+pub fn export_audit_log_csv(conn: &Connection, path: &str, filter: Option<AuditFilter>) -> Result<usize, String> {
+    let entries = query_entries(conn, &filter.unwrap_or_default())?;
+    let rows: Vec<Vec<String>> = entries
+        .into_iter()
+        .map(|entry| {
+            vec![
+                entry.id.to_string(),
+                entry.timestamp,
+                entry.user_id.map(|id| id.to_string()).unwrap_or_default(),
+                entry.user_email.unwrap_or_default(),
+                entry.action,
+                entry.entity_type,
+                entry.entity_id,
+                entry.changes.to_string(),
+            ]
+        })
+        .collect();
+    write_csv(
+        path,
+        &["id", "timestamp", "user_id", "user_email", "action", "entity_type", "entity_id", "changes"],
+        &rows,
+    )
+}
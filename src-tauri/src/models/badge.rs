@@ -0,0 +1,341 @@
+use crate::export::{escape_html, write_csv};
+use crate::models::audit;
+use crate::models::invite_soutenance::{ensure_defence_today, record_attendance};
+use crate::models::jury_soutenance::JuryRole;
+use crate::models::settings::{get_setting, set_setting};
+use crate::models::user::CurrentUser;
+use hmac::{Hmac, Mac};
+use qrcode::render::svg;
+use qrcode::QrCode;
+use rand::RngCore;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+pub const SIGNING_KEY: &str = "badge_signing_key";
+/// Bytes of the HMAC kept in a token, enough to rule out forgery while keeping the QR
+/// code small enough to scan from a printed badge.
+const SIGNATURE_BYTES: usize = 16;
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HolderKind {
+    Jury,
+    Invite,
+}
+
+impl HolderKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HolderKind::Jury => "jury",
+            HolderKind::Invite => "invite",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<HolderKind, String> {
+        match value {
+            "jury" => Ok(HolderKind::Jury),
+            "invite" => Ok(HolderKind::Invite),
+            other => Err(format!("Unknown badge holder: {}", other)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct Badge {
+    pub soutenance_id: i32,
+    pub holder_kind: HolderKind,
+    pub holder_id: i32,
+    pub name: String,
+    /// Jury role, or "Invité" for invitees.
+    pub role: String,
+    pub date: Option<String>,
+    pub hour: Option<String>,
+    pub classroom: Option<String>,
+    pub pfe: Option<String>,
+    pub token: String,
+    /// The token as a QR code, ready to inline in a page.
+    pub qr_svg: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct AttendanceEntry {
+    pub soutenance_id: i32,
+    pub holder_kind: HolderKind,
+    pub holder_id: i32,
+    pub name: String,
+    pub role: String,
+    pub arrived_at: Option<String>,
+}
+
+struct Holder {
+    kind: HolderKind,
+    id: i32,
+    name: String,
+    role: String,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(value.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// The key badges are signed with, created on first use. Rotating it voids every badge
+/// printed so far.
+fn signing_key(conn: &Connection) -> Result<Vec<u8>, String> {
+    if let Some(key) = get_setting(conn, SIGNING_KEY)?.and_then(|key| from_hex(&key)) {
+        return Ok(key);
+    }
+    let mut key = vec![0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut key);
+    set_setting(conn, SIGNING_KEY, Some(&to_hex(&key)))?;
+    Ok(key)
+}
+
+fn mac(key: &[u8], payload: &str) -> Result<Hmac<Sha256>, String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).map_err(|e| e.to_string())?;
+    mac.update(payload.as_bytes());
+    Ok(mac)
+}
+
+fn payload(soutenance_id: i32, kind: HolderKind, holder_id: i32) -> String {
+    format!("{}.{}.{}", soutenance_id, kind.as_str(), holder_id)
+}
+
+pub fn sign(conn: &Connection, soutenance_id: i32, kind: HolderKind, holder_id: i32) -> Result<String, String> {
+    let payload = payload(soutenance_id, kind, holder_id);
+    let signature = mac(&signing_key(conn)?, &payload)?.finalize().into_bytes();
+    Ok(format!("{}.{}", payload, to_hex(&signature[..SIGNATURE_BYTES])))
+}
+
+/// Checks a token's signature and returns the soutenance and holder it was issued for.
+pub fn verify(conn: &Connection, token: &str) -> Result<(i32, HolderKind, i32), String> {
+    let invalid = || "Invalid badge".to_string();
+    let parts: Vec<&str> = token.trim().split('.').collect();
+    let [soutenance_id, kind, holder_id, signature] = parts[..] else {
+        return Err(invalid());
+    };
+    let signature = from_hex(signature).filter(|s| s.len() == SIGNATURE_BYTES).ok_or_else(invalid)?;
+    let soutenance_id: i32 = soutenance_id.parse().map_err(|_| invalid())?;
+    let kind = HolderKind::parse(kind).map_err(|_| invalid())?;
+    let holder_id: i32 = holder_id.parse().map_err(|_| invalid())?;
+    mac(&signing_key(conn)?, &payload(soutenance_id, kind, holder_id))?
+        .verify_truncated_left(&signature)
+        .map_err(|_| invalid())?;
+    Ok((soutenance_id, kind, holder_id))
+}
+
+/// Jury members and invitees expected at a soutenance; invitees who declined are left out.
+fn holders(conn: &Connection, soutenance_id: i32) -> Result<Vec<Holder>, String> {
+    let mut holders = Vec::new();
+    let mut stmt = conn
+        .prepare(
+            "SELECT j.id, j.firstname || ' ' || j.lastname, js.role FROM jury_soutenance js
+            JOIN jury j ON j.id = js.jury_id WHERE js.soutenance_id = ?1 ORDER BY j.lastname, j.firstname",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([soutenance_id], |row| Ok((row.get(0)?, row.get(1)?, row.get::<_, String>(2)?)))
+        .map_err(|e| e.to_string())?;
+    for row in rows {
+        let (id, name, role) = row.map_err(|e| e.to_string())?;
+        holders.push(Holder {
+            kind: HolderKind::Jury,
+            id,
+            name,
+            role: JuryRole::parse(&role)?.label().to_string(),
+        });
+    }
+    let mut stmt = conn
+        .prepare(
+            "SELECT i.id, i.firstname || ' ' || i.lastname FROM invite_soutenance ins
+            JOIN invite i ON i.id = ins.invite_id
+            WHERE ins.soutenance_id = ?1 AND ins.status != 'declined' ORDER BY i.lastname, i.firstname",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([soutenance_id], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?;
+    for row in rows {
+        let (id, name) = row.map_err(|e| e.to_string())?;
+        holders.push(Holder {
+            kind: HolderKind::Invite,
+            id,
+            name,
+            role: "Invité".to_string(),
+        });
+    }
+    Ok(holders)
+}
+
+fn arrival(conn: &Connection, soutenance_id: i32, kind: HolderKind, holder_id: i32) -> Result<Option<String>, String> {
+    conn.query_row(
+        "SELECT arrived_at FROM attendance WHERE soutenance_id = ?1 AND holder_kind = ?2 AND holder_id = ?3",
+        params![soutenance_id, kind.as_str(), holder_id],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+fn qr_svg(token: &str) -> Result<String, String> {
+    let code = QrCode::new(token.as_bytes()).map_err(|e| format!("Failed to encode QR code: {}", e))?;
+    let image = code.render::<svg::Color>().min_dimensions(160, 160).build();
+    Ok(image.trim_start_matches(r#"<?xml version="1.0" standalone="yes"?>"#).to_string())
+}
+
+fn badges(conn: &Connection, soutenance_id: i32) -> Result<Vec<Badge>, String> {
+    let (date, hour, classroom, pfe): (Option<String>, Option<String>, Option<String>, Option<String>) = conn
+        .query_row(
+            "SELECT s.date, s.hour, c.name, s.pfe FROM soutenance s LEFT JOIN classroom c ON c.id = s.classroom_id
+            WHERE s.id = ?1",
+            [soutenance_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .map_err(|e| format!("Soutenance {} not found: {}", soutenance_id, e))?;
+    let mut badges = Vec::new();
+    for holder in holders(conn, soutenance_id)? {
+        let token = sign(conn, soutenance_id, holder.kind, holder.id)?;
+        badges.push(Badge {
+            soutenance_id,
+            holder_kind: holder.kind,
+            holder_id: holder.id,
+            name: holder.name,
+            role: holder.role,
+            date: date.clone(),
+            hour: hour.clone(),
+            classroom: classroom.clone(),
+            pfe: pfe.clone(),
+            qr_svg: qr_svg(&token)?,
+            token,
+        });
+    }
+    Ok(badges)
+}
+
+pub fn get_soutenance_badges(conn: &Connection, soutenance_id: i32) -> Result<Vec<Badge>, String> {
+    badges(conn, soutenance_id)
+}
+
+/// Writes the badges of a soutenance as a print-ready HTML page, several per A4 sheet.
+pub fn export_soutenance_badges(conn: &Connection, path: String, soutenance_id: i32) -> Result<usize, String> {
+    let badges = badges(conn, soutenance_id)?;
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html lang=\"fr\"><head><meta charset=\"utf-8\"><title>Badges</title><style>\
+        @page { size: A4; margin: 10mm; }\
+        body { font-family: sans-serif; display: flex; flex-wrap: wrap; gap: 6mm; }\
+        .badge { width: 90mm; height: 60mm; border: 1px dashed #999; padding: 4mm; box-sizing: border-box;\
+        display: flex; gap: 4mm; page-break-inside: avoid; }\
+        .badge svg { width: 32mm; height: 32mm; flex: none; }\
+        .name { font-size: 14pt; font-weight: bold; } .role { color: #555; } .info { font-size: 9pt; }\
+        </style></head><body>\n",
+    );
+    for badge in &badges {
+        html.push_str(&format!(
+            "<div class=\"badge\">{}<div><div class=\"name\">{}</div><div class=\"role\">{}</div>\
+            <div class=\"info\">{}</div><div class=\"info\">{} {} &middot; {}</div></div></div>\n",
+            badge.qr_svg,
+            escape_html(&badge.name),
+            escape_html(&badge.role),
+            escape_html(badge.pfe.as_deref().unwrap_or_default()),
+            escape_html(badge.date.as_deref().unwrap_or_default()),
+            escape_html(badge.hour.as_deref().unwrap_or_default()),
+            escape_html(badge.classroom.as_deref().unwrap_or_default()),
+        ));
+    }
+    html.push_str("</body></html>\n");
+    std::fs::write(&path, html).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    Ok(badges.len())
+}
+
+/// Scans a badge at the door of `soutenance_id`. The badge must be genuine, issued for
+/// this defence to someone still expected there, and not used before.
+pub fn check_in_badge(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    token: String,
+    soutenance_id: i32,
+) -> Result<AttendanceEntry, String> {
+    let (badge_soutenance, kind, holder_id) = verify(conn, &token)?;
+    if badge_soutenance != soutenance_id {
+        return Err(format!("This badge is for soutenance {}, not {}", badge_soutenance, soutenance_id));
+    }
+    ensure_defence_today(conn, soutenance_id)?;
+    let holder = holders(conn, soutenance_id)?
+        .into_iter()
+        .find(|holder| holder.kind == kind && holder.id == holder_id)
+        .ok_or_else(|| "The badge holder is no longer expected at this defence".to_string())?;
+    if let Some(arrived_at) = arrival(conn, soutenance_id, kind, holder_id)? {
+        return Err(format!("This badge was already used at {}", arrived_at));
+    }
+    let key = [
+        ("soutenance_id", soutenance_id.into()),
+        ("holder_kind", kind.as_str().to_string().into()),
+        ("holder_id", holder_id.into()),
+    ];
+    conn.execute(
+        "INSERT INTO attendance (soutenance_id, holder_kind, holder_id) VALUES (?1, ?2, ?3)",
+        params![soutenance_id, kind.as_str(), holder_id],
+    )
+    .map_err(|e| format!("Failed to record arrival: {}", e))?;
+    audit::record_change(conn, actor, "attendance", &key, None)?;
+    if kind == HolderKind::Invite {
+        record_attendance(conn, actor, holder_id, soutenance_id)?;
+    }
+    let arrived_at = arrival(conn, soutenance_id, kind, holder_id)?;
+    Ok(AttendanceEntry {
+        soutenance_id,
+        holder_kind: kind,
+        holder_id,
+        name: holder.name,
+        role: holder.role,
+        arrived_at,
+    })
+}
+
+fn attendance_list(conn: &Connection, soutenance_id: i32) -> Result<Vec<AttendanceEntry>, String> {
+    let mut entries = Vec::new();
+    for holder in holders(conn, soutenance_id)? {
+        entries.push(AttendanceEntry {
+            soutenance_id,
+            holder_kind: holder.kind,
+            holder_id: holder.id,
+            arrived_at: arrival(conn, soutenance_id, holder.kind, holder.id)?,
+            name: holder.name,
+            role: holder.role,
+        });
+    }
+    Ok(entries)
+}
+
+/// Everyone expected at a soutenance, with their arrival time once they checked in.
+pub fn get_attendance_list(conn: &Connection, soutenance_id: i32) -> Result<Vec<AttendanceEntry>, String> {
+    attendance_list(conn, soutenance_id)
+}
+
+pub fn export_attendance_csv(conn: &Connection, path: String, soutenance_id: i32) -> Result<usize, String> {
+    let entries = attendance_list(conn, soutenance_id)?;
+    let rows: Vec<Vec<String>> = entries
+        .into_iter()
+        .map(|entry| vec![entry.name, entry.role, entry.arrived_at.unwrap_or_default()])
+        .collect();
+    write_csv(&path, &["name", "role", "arrived_at"], &rows)
+}
+
+/// Voids every badge printed so far; new badges must be printed afterwards. The key is
+/// a secret, so unlike other settings its changes are not copied to the audit log.
+pub fn rotate_badge_key(conn: &Connection) -> Result<(), String> {
+    set_setting(conn, SIGNING_KEY, None)?;
+    signing_key(conn)?;
+    Ok(())
+}
@@ -1,48 +1,310 @@
-use crate::AppState;
-use rusqlite::Result;
+use crate::models::audit;
+use crate::models::journal::Operation;
+use crate::models::session::resolve_session;
+use crate::models::soutenance::{attendee_count, defence_duration};
+use crate::query::{ListQuery, Page, QueryOptions};
+use crate::models::user::CurrentUser;
+use rusqlite::{params, Connection, Result, Row};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tauri::State;
 
-#[derive(Serialize, Deserialize, Clone, Debug)] // Add Clone
+/// Columns read by `Salle::from_row`, in order.
+pub const SALLE_COLUMNS: &str = "id, name, deleted_at, capacity, building, floor, equipment";
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)] // Add Clone
 pub struct Salle {
     pub id: i32,
     pub name: String, // Added name field
+    pub deleted_at: Option<String>,
+    pub capacity: Option<i32>,
+    pub building: Option<String>,
+    pub floor: Option<String>,
+    /// Lowercase tags such as `projector`, `videoconference` or `accessibility`.
+    pub equipment: Vec<String>,
+}
+
+impl Salle {
+    pub fn from_row(row: &Row) -> rusqlite::Result<Salle> {
+        Ok(Salle {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            deleted_at: row.get(2)?,
+            capacity: row.get(3)?,
+            building: row.get(4)?,
+            floor: row.get(5)?,
+            equipment: parse_equipment(row.get(6)?),
+        })
+    }
+}
+
+#[derive(Deserialize, JsonSchema, Default)]
+pub struct ClassroomDetails {
+    pub capacity: Option<i32>,
+    pub building: Option<String>,
+    pub floor: Option<String>,
+    #[serde(default)]
+    pub equipment: Vec<String>,
+}
+
+/// Weekly opening hours; `weekday` follows SQLite's `%w` (0 = Sunday).
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct OpeningHours {
+    pub weekday: i32,
+    pub opens: String,
+    pub closes: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct Closure {
+    pub id: i32,
+    pub classroom_id: i32,
+    pub start_date: String,
+    pub end_date: String,
+    pub reason: Option<String>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct NewClosure {
+    pub classroom_id: i32,
+    pub start_date: String,
+    pub end_date: String,
+    pub reason: Option<String>,
+}
+
+/// What a defence needs from a room. Without `attendees`, the people already assigned to
+/// `soutenance_id` are counted.
+#[derive(Deserialize, JsonSchema)]
+pub struct RoomRequest {
+    pub date: String,
+    pub hour: String,
+    pub attendees: Option<i32>,
+    pub soutenance_id: Option<i32>,
+    #[serde(default)]
+    pub equipment: Vec<String>,
+}
+
+pub fn parse_equipment(json: Option<String>) -> Vec<String> {
+    json.and_then(|j| serde_json::from_str(&j).ok()).unwrap_or_default()
+}
+
+fn normalize_equipment(tags: &[String]) -> Vec<String> {
+    let mut tags: Vec<String> = tags
+        .iter()
+        .map(|tag| tag.trim().to_lowercase())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+/// Minutes since midnight for an `HH:MM` (or `HH:MM:SS`) time.
+pub fn parse_minutes(time: &str) -> Result<i32, String> {
+    let mut parts = time.trim().split(':');
+    let hours = parts.next().and_then(|h| h.parse::<i32>().ok());
+    let minutes = parts.next().and_then(|m| m.parse::<i32>().ok());
+    match (hours, minutes) {
+        (Some(h), Some(m)) if (0..24).contains(&h) && (0..60).contains(&m) => Ok(h * 60 + m),
+        _ => Err(format!("Invalid time {}, expected HH:MM", time)),
+    }
+}
+
+pub fn format_minutes(minutes: i32) -> String {
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// The `YYYY-MM-DD` part of a stored date, validated by SQLite.
+pub fn day_of(conn: &Connection, date: &str) -> Result<(String, i32), String> {
+    let day: String = date.chars().take(10).collect();
+    let weekday: Option<i32> = conn
+        .query_row("SELECT CAST(strftime('%w', ?1) AS INTEGER)", [&day], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    match weekday {
+        Some(weekday) => Ok((day, weekday)),
+        None => Err(format!("Invalid date {}, expected YYYY-MM-DD", date)),
+    }
+}
+
+pub fn find_classroom(conn: &Connection, id: i32) -> Result<Salle, String> {
+    conn.query_row(
+        &format!("SELECT {} FROM classroom WHERE id = ?1", SALLE_COLUMNS),
+        [id],
+        Salle::from_row,
+    )
+    .map_err(|e| format!("Classroom {} not found: {}", id, e))
+}
+
+pub fn opening_hours(conn: &Connection, classroom_id: i32) -> Result<Vec<OpeningHours>, String> {
+    let mut stmt = conn
+        .prepare("SELECT weekday, opens, closes FROM classroom_opening_hours WHERE classroom_id = ?1 ORDER BY weekday")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([classroom_id], |row| {
+            Ok(OpeningHours {
+                weekday: row.get(0)?,
+                opens: row.get(1)?,
+                closes: row.get(2)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    let result: Result<Vec<OpeningHours>, _> = rows.collect();
+    result.map_err(|e| e.to_string())
+}
+
+/// Why the classroom cannot host a defence starting at `date`/`hour`; empty when it is free.
+/// Rooms without opening hours are treated as always open.
+pub fn room_conflicts(
+    conn: &Connection,
+    classroom_id: i32,
+    date: &str,
+    hour: &str,
+    exclude_soutenance: Option<i32>,
+) -> Result<Vec<String>, String> {
+    let (day, weekday) = day_of(conn, date)?;
+    let duration = defence_duration(conn)?;
+    let start = parse_minutes(hour)?;
+    let end = start + duration;
+    let mut conflicts = Vec::new();
+
+    let mut stmt = conn
+        .prepare("SELECT reason FROM classroom_closure WHERE classroom_id = ?1 AND start_date <= ?2 AND end_date >= ?2")
+        .map_err(|e| e.to_string())?;
+    let reasons = stmt
+        .query_map(params![classroom_id, day], |row| row.get::<_, Option<String>>(0))
+        .map_err(|e| e.to_string())?;
+    for reason in reasons {
+        let reason = reason.map_err(|e| e.to_string())?;
+        conflicts.push(match reason {
+            Some(reason) => format!("The room is closed on {} ({})", day, reason),
+            None => format!("The room is closed on {}", day),
+        });
+    }
+
+    let hours = opening_hours(conn, classroom_id)?;
+    if !hours.is_empty() {
+        match hours.iter().find(|h| h.weekday == weekday) {
+            None => conflicts.push(format!("The room is not open on {}", day)),
+            Some(h) if start < parse_minutes(&h.opens)? || end > parse_minutes(&h.closes)? => conflicts.push(format!(
+                "{}-{} is outside the opening hours ({}-{})",
+                format_minutes(start),
+                format_minutes(end),
+                h.opens,
+                h.closes
+            )),
+            Some(_) => {}
+        }
+    }
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, hour FROM soutenance
+            WHERE classroom_id = ?1 AND substr(date, 1, 10) = ?2 AND hour IS NOT NULL AND (?3 IS NULL OR id != ?3)",
+        )
+        .map_err(|e| e.to_string())?;
+    let booked = stmt
+        .query_map(params![classroom_id, day, exclude_soutenance], |row| {
+            Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?))
+        })
+        .map_err(|e| e.to_string())?;
+    for booking in booked {
+        let (other, other_hour) = booking.map_err(|e| e.to_string())?;
+        let Ok(other_start) = parse_minutes(&other_hour) else {
+            continue;
+        };
+        if start < other_start + duration && other_start < end {
+            conflicts.push(format!("The room is already booked at {} by soutenance {}", other_hour, other));
+        }
+    }
+    Ok(conflicts)
 }
 
-#[tauri::command]
-pub fn create_classroom(name: String, state: State<AppState>) -> Result<i32, String> { // Added name parameter
-    let conn = state.db.lock().unwrap();
-    conn.execute("INSERT INTO classroom (name) VALUES (?1)", [name]) // Modified SQL to insert name
+/// Removes the opening hours and closures of a classroom that is being purged.
+pub fn purge_schedule(conn: &Connection, actor: &Option<crate::models::user::CurrentUser>, classroom_id: i32) -> Result<(), String> {
+    for hours in opening_hours(conn, classroom_id)? {
+        let key = [("classroom_id", classroom_id.into()), ("weekday", hours.weekday.into())];
+        let before = audit::snapshot(conn, "classroom_opening_hours", &key)?;
+        conn.execute(
+            "DELETE FROM classroom_opening_hours WHERE classroom_id = ?1 AND weekday = ?2",
+            [classroom_id, hours.weekday],
+        )
         .map_err(|e| e.to_string())?;
-    Ok(conn.last_insert_rowid() as i32)
+        audit::record_change(conn, actor, "classroom_opening_hours", &key, before)?;
+    }
+    for closure in closures(conn, classroom_id)? {
+        let key = [("id", closure.id.into())];
+        let before = audit::snapshot(conn, "classroom_closure", &key)?;
+        conn.execute("DELETE FROM classroom_closure WHERE id = ?1", [closure.id])
+            .map_err(|e| e.to_string())?;
+        audit::record_change(conn, actor, "classroom_closure", &key, before)?;
+    }
+    Ok(())
 }
 
-#[tauri::command]
-pub fn get_classroom(id: i32, state: State<AppState>) -> Result<Salle, String> {
-    let conn = state.db.lock().unwrap();
-    let salle = conn
-        .query_row("SELECT id, name FROM classroom WHERE id = ?1", [id], |row| { // Modified SQL to select name
-            Ok(Salle { 
+fn closures(conn: &Connection, classroom_id: i32) -> Result<Vec<Closure>, String> {
+    let mut stmt = conn
+        .prepare("SELECT id, classroom_id, start_date, end_date, reason FROM classroom_closure WHERE classroom_id = ?1 ORDER BY start_date")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([classroom_id], |row| {
+            Ok(Closure {
                 id: row.get(0)?,
-                name: row.get(1)? // Get name from row
+                classroom_id: row.get(1)?,
+                start_date: row.get(2)?,
+                end_date: row.get(3)?,
+                reason: row.get(4)?,
             })
         })
         .map_err(|e| e.to_string())?;
-    Ok(salle)
+    let result: Result<Vec<Closure>, _> = rows.collect();
+    result.map_err(|e| e.to_string())
+}
+
+pub fn create_classroom(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    name: String,
+    details: Option<ClassroomDetails>,
+) -> Result<i32, String> { // Added name parameter
+    let details = details.unwrap_or_default();
+    let equipment = serde_json::to_string(&normalize_equipment(&details.equipment)).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO classroom (name, capacity, building, floor, equipment) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![name, details.capacity, details.building, details.floor, equipment],
+    )
+    .map_err(|e| e.to_string())?;
+    let id = conn.last_insert_rowid() as i32;
+    audit::record_change(conn, actor, "classroom", &[("id", id.into())], None)?;
+    Ok(id)
 }
 
-#[tauri::command]
-pub fn update_classroom(id: i32, name: String, state: State<AppState>) -> Result<(), String> { // Added name parameter
-    let conn = state.db.lock().unwrap();
+pub fn get_classroom(conn: &Connection, id: i32) -> Result<Salle, String> {
+    find_classroom(conn, id)
+}
+
+/// Without `details`, only the name changes.
+pub fn update_classroom(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    id: i32,
+    name: String,
+    details: Option<ClassroomDetails>,
+) -> Result<(), String> { // Added name parameter
+    let before = audit::snapshot(conn, "classroom", &[("id", id.into())])?;
     conn.execute("UPDATE classroom SET name = ?1 WHERE id = ?2", [name, id.to_string()]) // Implemented update logic
         .map_err(|e| e.to_string())?;
+    if let Some(details) = details {
+        let equipment = serde_json::to_string(&normalize_equipment(&details.equipment)).map_err(|e| e.to_string())?;
+        conn.execute(
+            "UPDATE classroom SET capacity = ?1, building = ?2, floor = ?3, equipment = ?4 WHERE id = ?5",
+            params![details.capacity, details.building, details.floor, equipment, id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    audit::record_change(conn, actor, "classroom", &[("id", id.into())], before)?;
     Ok(())
 }
 
-#[tauri::command]
-pub fn delete_classrooms(ids: Vec<i32>, state: State<AppState>) -> Result<(), String> {
-    let conn = state.db.lock().map_err(|e| e.to_string())?;
-
+pub fn delete_classrooms(conn: &Connection, actor: &Option<CurrentUser>, ids: Vec<i32>) -> Result<(), String> {
     // If the input array is empty, return early with success
     if ids.is_empty() {
         return Ok(());
@@ -50,49 +312,165 @@ pub fn delete_classrooms(ids: Vec<i32>, state: State<AppState>) -> Result<(), St
 
     // Create a parameterized IN clause, e.g., "(?, ?, ?)"
     let placeholders = ids.iter().map(|_| "?").collect::<Vec<_>>().join(", ");
-    let query = format!("DELETE FROM classroom WHERE id IN ({})", placeholders);
+    // Archived rows stay referenced by past soutenances; see `archive` for restore and purge.
+    let query = format!(
+        "UPDATE classroom SET deleted_at = datetime('now') WHERE deleted_at IS NULL AND id IN ({})",
+        placeholders
+    );
 
     // Convert Vec<i32> to Vec<&dyn rusqlite::ToSql> for params
     let params: Vec<&dyn rusqlite::ToSql> = ids.iter().map(|id| id as &dyn rusqlite::ToSql).collect();
 
+    let mut operation = Operation::new("delete_classrooms");
+    for id in &ids {
+        operation.track(conn, "classroom", &[("id", (*id).into())])?;
+    }
+
     conn.execute(&query, &params[..])
         .map_err(|e| format!("BACKEND: Failed to delete classroom: {}", e.to_string()))?;
 
+    operation.finish(conn, actor)?;
+
     Ok(())
 }
 
 // New function to get all classrooms
-#[tauri::command]
-pub fn get_all_classrooms(state: State<AppState>) -> Result<Vec<Salle>, String> {
-    let conn = state.db.lock().unwrap();
-    let mut stmt = conn
-        .prepare("SELECT id, name FROM classroom")
-        .map_err(|e| e.to_string())?;
-    let salle_iter = stmt
-        .query_map([], |row| {
-            Ok(Salle {
-                id: row.get(0)?,
-                name: row.get(1)?,
-            })
-        })
-        .map_err(|e| e.to_string())?;
+pub fn get_all_classrooms(conn: &Connection, options: Option<QueryOptions>) -> Result<Page<Salle>, String> {
+    let options = options.unwrap_or_default();
+    options.ensure_supported("classroom", &[])?;
 
-    let mut salles = Vec::new();
-    for salle in salle_iter {
-        salles.push(salle.map_err(|e| e.to_string())?);
-    }
-    Ok(salles)
+    let mut query = ListQuery::new("FROM classroom");
+    query
+        .filter("deleted_at IS NULL", vec![])
+        .text(&options.text, &["name", "building"]);
+    query.fetch(
+        conn,
+        &format!("SELECT {}", SALLE_COLUMNS),
+        &options,
+        &[("name", "name"), ("capacity", "capacity"), ("building", "building, floor"), ("id", "id")],
+        Salle::from_row,
+    )
 }
 
-#[tauri::command]
-pub fn get_classroom_soutenances(salle_id: i32, state: State<AppState>) -> Result<Vec<i32>, String> {
-    let conn = state.db.lock().unwrap();
+pub fn get_classroom_soutenances(
+    conn: &Connection,
+    salle_id: i32,
+    session_id: Option<i32>,
+) -> Result<Vec<i32>, String> {
+    let session_id = resolve_session(conn, session_id)?;
     let mut stmt = conn
-        .prepare("SELECT id FROM soutenance WHERE salle_id = ?1")
+        .prepare("SELECT id FROM soutenance WHERE classroom_id = ?1 AND (?2 IS NULL OR session_id = ?2)")
         .map_err(|e| e.to_string())?;
     let rows = stmt
-        .query_map([salle_id], |row| row.get(0))
+        .query_map(rusqlite::params![salle_id, session_id], |row| row.get(0))
         .map_err(|e| e.to_string())?;
     let result: Result<Vec<i32>, _> = rows.collect();
     result.map_err(|e| e.to_string())
 }
+
+pub fn get_classroom_opening_hours(conn: &Connection, classroom_id: i32) -> Result<Vec<OpeningHours>, String> {
+    opening_hours(conn, classroom_id)
+}
+
+/// Replaces the weekly opening hours of a classroom; an empty list means always open.
+pub fn set_classroom_opening_hours(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    classroom_id: i32,
+    hours: Vec<OpeningHours>,
+) -> Result<(), String> {
+    for (i, h) in hours.iter().enumerate() {
+        if !(0..=6).contains(&h.weekday) {
+            return Err(format!("Invalid weekday {}, expected 0 (Sunday) to 6", h.weekday));
+        }
+        if parse_minutes(&h.opens)? >= parse_minutes(&h.closes)? {
+            return Err(format!("Opening time {} must be before closing time {}", h.opens, h.closes));
+        }
+        if hours[..i].iter().any(|other| other.weekday == h.weekday) {
+            return Err(format!("Weekday {} is listed twice", h.weekday));
+        }
+    }
+    find_classroom(conn, classroom_id)?;
+    let mut operation = Operation::new("set_classroom_opening_hours");
+    let current = opening_hours(conn, classroom_id)?;
+    for weekday in current.iter().map(|h| h.weekday).chain(hours.iter().map(|h| h.weekday)) {
+        operation.track(conn, "classroom_opening_hours", &[("classroom_id", classroom_id.into()), ("weekday", weekday.into())])?;
+    }
+    conn.execute("DELETE FROM classroom_opening_hours WHERE classroom_id = ?1", [classroom_id])
+        .map_err(|e| e.to_string())?;
+    for h in &hours {
+        conn.execute(
+            "INSERT INTO classroom_opening_hours (classroom_id, weekday, opens, closes) VALUES (?1, ?2, ?3, ?4)",
+            params![classroom_id, h.weekday, format_minutes(parse_minutes(&h.opens)?), format_minutes(parse_minutes(&h.closes)?)],
+        )
+        .map_err(|e| format!("Failed to save opening hours: {}", e))?;
+    }
+    operation.finish(conn, actor)?;
+    Ok(())
+}
+
+pub fn get_classroom_closures(conn: &Connection, classroom_id: i32) -> Result<Vec<Closure>, String> {
+    closures(conn, classroom_id)
+}
+
+pub fn add_classroom_closure(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    closure: NewClosure,
+) -> Result<i32, String> {
+    find_classroom(conn, closure.classroom_id)?;
+    let (start, _) = day_of(conn, &closure.start_date)?;
+    let (end, _) = day_of(conn, &closure.end_date)?;
+    if start > end {
+        return Err(format!("The closure ends ({}) before it starts ({})", end, start));
+    }
+    conn.execute(
+        "INSERT INTO classroom_closure (classroom_id, start_date, end_date, reason) VALUES (?1, ?2, ?3, ?4)",
+        params![closure.classroom_id, start, end, closure.reason],
+    )
+    .map_err(|e| format!("Failed to add closure: {}", e))?;
+    let id = conn.last_insert_rowid() as i32;
+    audit::record_change(conn, actor, "classroom_closure", &[("id", id.into())], None)?;
+    Ok(id)
+}
+
+pub fn delete_classroom_closure(conn: &Connection, actor: &Option<CurrentUser>, id: i32) -> Result<(), String> {
+    let mut operation = Operation::new("delete_classroom_closure");
+    operation.track(conn, "classroom_closure", &[("id", id.into())])?;
+    conn.execute("DELETE FROM classroom_closure WHERE id = ?1", [id])
+        .map_err(|e| format!("Failed to delete closure: {}", e))?;
+    operation.finish(conn, actor)?;
+    Ok(())
+}
+
+/// Rooms that can host a defence: big enough, equipped, open and free at that time.
+/// Rooms with an unknown capacity come after the ones known to fit, smallest first.
+pub fn find_available_classrooms(conn: &Connection, request: RoomRequest) -> Result<Vec<Salle>, String> {
+    let attendees = match (request.attendees, request.soutenance_id) {
+        (Some(attendees), _) => attendees,
+        (None, Some(soutenance_id)) => attendee_count(conn, soutenance_id)?,
+        (None, None) => 0,
+    };
+    let required = normalize_equipment(&request.equipment);
+
+    let mut stmt = conn
+        .prepare(&format!("SELECT {} FROM classroom WHERE deleted_at IS NULL", SALLE_COLUMNS))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([], Salle::from_row).map_err(|e| e.to_string())?;
+    let mut rooms = Vec::new();
+    for room in rows {
+        let room = room.map_err(|e| e.to_string())?;
+        if room.capacity.is_some_and(|capacity| capacity < attendees) {
+            continue;
+        }
+        if !required.iter().all(|tag| room.equipment.contains(tag)) {
+            continue;
+        }
+        if !room_conflicts(conn, room.id, &request.date, &request.hour, request.soutenance_id)?.is_empty() {
+            continue;
+        }
+        rooms.push(room);
+    }
+    rooms.sort_by_key(|room| (room.capacity.is_none(), room.capacity));
+    Ok(rooms)
+}
@@ -0,0 +1,235 @@
+use crate::models::audit;
+use crate::models::jury_soutenance::JuryRole;
+use crate::models::session::resolve_session;
+use crate::models::user::CurrentUser;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// How the jury of a soutenance must be made up. Specialités without their own rules use
+/// `CompositionRules::default_for`.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct CompositionRules {
+    pub specialite_id: i32,
+    /// Sitting members, guests of honour excluded.
+    pub min_size: i32,
+    pub max_size: Option<i32>,
+    /// Exact number of presidents.
+    pub presidents: i32,
+    pub min_rapporteurs: i32,
+    /// Whether a student's supervisor may preside over their defence.
+    pub supervisor_can_preside: bool,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct CompositionViolation {
+    pub soutenance_id: i32,
+    pub rule: String,
+    pub message: String,
+}
+
+impl CompositionRules {
+    pub fn default_for(specialite_id: i32) -> CompositionRules {
+        CompositionRules {
+            specialite_id,
+            min_size: 3,
+            max_size: Some(5),
+            presidents: 1,
+            min_rapporteurs: 1,
+            supervisor_can_preside: false,
+        }
+    }
+}
+
+pub fn rules_for(conn: &Connection, specialite_id: i32) -> Result<CompositionRules, String> {
+    let rules = conn
+        .query_row(
+            "SELECT specialite_id, min_size, max_size, presidents, min_rapporteurs, supervisor_can_preside
+            FROM jury_composition_rule WHERE specialite_id = ?1",
+            [specialite_id],
+            |row| {
+                Ok(CompositionRules {
+                    specialite_id: row.get(0)?,
+                    min_size: row.get(1)?,
+                    max_size: row.get(2)?,
+                    presidents: row.get(3)?,
+                    min_rapporteurs: row.get(4)?,
+                    supervisor_can_preside: row.get(5)?,
+                })
+            },
+        )
+        .optional()
+        .map_err(|e| format!("Failed to load composition rules: {}", e))?;
+    Ok(rules.unwrap_or_else(|| CompositionRules::default_for(specialite_id)))
+}
+
+/// Jury members of a soutenance with their role.
+pub fn jury_roles(conn: &Connection, soutenance_id: i32) -> Result<Vec<(i32, JuryRole)>, String> {
+    let mut stmt = conn
+        .prepare("SELECT jury_id, role FROM jury_soutenance WHERE soutenance_id = ?1")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([soutenance_id], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?)))
+        .map_err(|e| e.to_string())?;
+    let mut members = Vec::new();
+    for row in rows {
+        let (jury_id, role) = row.map_err(|e| e.to_string())?;
+        members.push((jury_id, JuryRole::parse(&role)?));
+    }
+    Ok(members)
+}
+
+/// Academic supervisors of the students defending in a soutenance.
+pub fn supervisors(conn: &Connection, soutenance_id: i32) -> Result<Vec<i32>, String> {
+    let mut stmt = conn
+        .prepare("SELECT DISTINCT supervisor_id FROM etudiant WHERE soutenance_id = ?1 AND supervisor_id IS NOT NULL")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([soutenance_id], |row| row.get(0)).map_err(|e| e.to_string())?;
+    let result: Result<Vec<i32>, _> = rows.collect();
+    result.map_err(|e| e.to_string())
+}
+
+/// Academic supervisors of the students defending a PFE, found through the soutenances
+/// that carry its title.
+pub fn pfe_supervisors(conn: &Connection, pfe_id: i32) -> Result<Vec<i32>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT DISTINCT e.supervisor_id FROM etudiant e
+            JOIN soutenance s ON s.id = e.soutenance_id
+            JOIN pfe p ON p.title = s.pfe
+            WHERE p.id = ?1 AND e.supervisor_id IS NOT NULL",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([pfe_id], |row| row.get(0)).map_err(|e| e.to_string())?;
+    let result: Result<Vec<i32>, _> = rows.collect();
+    result.map_err(|e| e.to_string())
+}
+
+/// Checks a soutenance against its specialité's rules. With `complete` unset, only the
+/// rules that adding more jury members could never fix are checked, so a jury can be
+/// built one member at a time.
+pub fn check_composition(conn: &Connection, soutenance_id: i32, complete: bool) -> Result<Vec<CompositionViolation>, String> {
+    let specialite_id: i32 = conn
+        .query_row("SELECT specialite_id FROM soutenance WHERE id = ?1", [soutenance_id], |row| row.get(0))
+        .map_err(|e| format!("Soutenance {} not found: {}", soutenance_id, e))?;
+    let rules = rules_for(conn, specialite_id)?;
+    let members = jury_roles(conn, soutenance_id)?;
+    let supervisors = supervisors(conn, soutenance_id)?;
+
+    let size = members.iter().filter(|(_, role)| role.is_member()).count() as i32;
+    let presidents: Vec<i32> = members
+        .iter()
+        .filter(|(_, role)| *role == JuryRole::President)
+        .map(|(jury_id, _)| *jury_id)
+        .collect();
+    let rapporteurs = members.iter().filter(|(_, role)| *role == JuryRole::Rapporteur).count() as i32;
+
+    let mut violations = Vec::new();
+    let mut violate = |rule: &str, message: String| {
+        violations.push(CompositionViolation {
+            soutenance_id,
+            rule: rule.to_string(),
+            message,
+        })
+    };
+    if let Some(max_size) = rules.max_size {
+        if size > max_size {
+            violate("max_size", format!("The jury has {} members, at most {} are allowed", size, max_size));
+        }
+    }
+    if presidents.len() as i32 > rules.presidents {
+        violate("presidents", format!("The jury has {} presidents, {} expected", presidents.len(), rules.presidents));
+    }
+    if !rules.supervisor_can_preside && presidents.iter().any(|jury_id| supervisors.contains(jury_id)) {
+        violate("supervisor_president", "A student's supervisor cannot preside over the jury".to_string());
+    }
+    if complete {
+        if size < rules.min_size {
+            violate("min_size", format!("The jury has {} members, at least {} are required", size, rules.min_size));
+        }
+        if (presidents.len() as i32) < rules.presidents {
+            violate("presidents", format!("The jury has {} presidents, {} expected", presidents.len(), rules.presidents));
+        }
+        if rapporteurs < rules.min_rapporteurs {
+            violate(
+                "min_rapporteurs",
+                format!("The jury has {} rapporteurs, at least {} are required", rapporteurs, rules.min_rapporteurs),
+            );
+        }
+    }
+    Ok(violations)
+}
+
+/// Refuses a jury change that breaks a rule no later addition could repair.
+pub fn ensure_attachable(conn: &Connection, soutenance_id: i32) -> Result<(), String> {
+    let violations = check_composition(conn, soutenance_id, false)?;
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations.into_iter().map(|v| v.message).collect::<Vec<_>>().join("; "))
+    }
+}
+
+pub fn get_composition_rules(conn: &Connection, specialite_id: i32) -> Result<CompositionRules, String> {
+    rules_for(conn, specialite_id)
+}
+
+pub fn set_composition_rules(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    rules: CompositionRules,
+) -> Result<(), String> {
+    if rules.min_size < 0 || rules.presidents < 0 || rules.min_rapporteurs < 0 {
+        return Err("Composition rules cannot be negative".to_string());
+    }
+    if rules.max_size.is_some_and(|max_size| max_size < rules.min_size) {
+        return Err("The maximum jury size is below the minimum".to_string());
+    }
+    let key = [("specialite_id", rules.specialite_id.into())];
+    let before = audit::snapshot(conn, "jury_composition_rule", &key)?;
+    conn.execute(
+        "INSERT OR REPLACE INTO jury_composition_rule
+            (specialite_id, min_size, max_size, presidents, min_rapporteurs, supervisor_can_preside)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            rules.specialite_id,
+            rules.min_size,
+            rules.max_size,
+            rules.presidents,
+            rules.min_rapporteurs,
+            rules.supervisor_can_preside
+        ],
+    )
+    .map_err(|e| format!("Failed to save composition rules: {}", e))?;
+    audit::record_change(conn, actor, "jury_composition_rule", &key, before)?;
+    Ok(())
+}
+
+pub fn get_soutenance_composition(conn: &Connection, soutenance_id: i32) -> Result<Vec<CompositionViolation>, String> {
+    check_composition(conn, soutenance_id, true)
+}
+
+/// Every rule broken by a soutenance of the session, optionally for one specialité.
+pub fn get_composition_violations(
+    conn: &Connection,
+    specialite_id: Option<i32>,
+    session_id: Option<i32>,
+) -> Result<Vec<CompositionViolation>, String> {
+    let session_id = resolve_session(conn, session_id)?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id FROM soutenance
+            WHERE (?1 IS NULL OR specialite_id = ?1) AND (?2 IS NULL OR session_id = ?2)
+            ORDER BY date, hour",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![specialite_id, session_id], |row| row.get::<_, i32>(0))
+        .map_err(|e| e.to_string())?;
+    let mut violations = Vec::new();
+    for soutenance_id in rows {
+        let soutenance_id = soutenance_id.map_err(|e| e.to_string())?;
+        violations.extend(check_composition(conn, soutenance_id, true)?);
+    }
+    Ok(violations)
+}
@@ -0,0 +1,358 @@
+use crate::models::audit;
+use crate::models::composition::jury_roles;
+use crate::models::journal::Operation;
+use crate::models::session::resolve_session;
+use crate::models::user::CurrentUser;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// What a jury member declared a conflict of interest with.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    /// A student, e.g. a relative or someone they supervised at their company.
+    Student,
+    /// A company hosting the project, matched against `pfe.company`.
+    Company,
+    /// Another jury member they should not sit with.
+    Jury,
+}
+
+impl ConflictKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConflictKind::Student => "student",
+            ConflictKind::Company => "company",
+            ConflictKind::Jury => "jury",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<ConflictKind, String> {
+        match value {
+            "student" => Ok(ConflictKind::Student),
+            "company" => Ok(ConflictKind::Company),
+            "jury" => Ok(ConflictKind::Jury),
+            _ => Err(format!("Unknown conflict kind: {}", value)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct Conflict {
+    pub id: i32,
+    pub jury_id: i32,
+    pub kind: ConflictKind,
+    pub etudiant_id: Option<i32>,
+    pub company: Option<String>,
+    pub other_jury_id: Option<i32>,
+    pub reason: Option<String>,
+    pub declared_at: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct NewConflict {
+    pub jury_id: i32,
+    pub kind: ConflictKind,
+    pub etudiant_id: Option<i32>,
+    pub company: Option<String>,
+    pub other_jury_id: Option<i32>,
+    pub reason: Option<String>,
+}
+
+/// A jury assignment that goes against a declared conflict.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct AssignmentConflict {
+    pub jury_id: i32,
+    pub soutenance_id: i32,
+    pub reasons: Vec<String>,
+    /// The justification given when the conflict was overridden, if it was.
+    pub justification: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct ConflictOverride {
+    pub jury_id: i32,
+    pub soutenance_id: i32,
+    pub justification: String,
+    pub conflicts: String,
+    pub user_email: Option<String>,
+    pub created_at: String,
+}
+
+const CONFLICT_COLUMNS: &str = "id, jury_id, kind, etudiant_id, company, other_jury_id, reason, declared_at";
+
+fn map_conflict(row: &rusqlite::Row) -> Result<Conflict> {
+    let kind: String = row.get(2)?;
+    Ok(Conflict {
+        id: row.get(0)?,
+        jury_id: row.get(1)?,
+        kind: ConflictKind::parse(&kind).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, e.into())
+        })?,
+        etudiant_id: row.get(3)?,
+        company: row.get(4)?,
+        other_jury_id: row.get(5)?,
+        reason: row.get(6)?,
+        declared_at: row.get(7)?,
+    })
+}
+
+fn load_conflicts(conn: &Connection, jury_id: Option<i32>) -> Result<Vec<Conflict>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM jury_conflict WHERE ?1 IS NULL OR jury_id = ?1 OR other_jury_id = ?1 ORDER BY id",
+            CONFLICT_COLUMNS
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([jury_id], map_conflict).map_err(|e| e.to_string())?;
+    let result: Result<Vec<_>, _> = rows.collect();
+    result.map_err(|e| e.to_string())
+}
+
+/// Why `jury_id` should not sit on `soutenance_id`, one line per declared conflict that
+/// applies. Conflicts between jury members count whichever of the two declared them.
+pub fn assignment_conflicts(conn: &Connection, jury_id: i32, soutenance_id: i32) -> Result<Vec<String>, String> {
+    let mut reasons = Vec::new();
+    for conflict in load_conflicts(conn, Some(jury_id))? {
+        // Student and company conflicts only bind the member who declared them.
+        if conflict.kind != ConflictKind::Jury && conflict.jury_id != jury_id {
+            continue;
+        }
+        let applies = match conflict.kind {
+            ConflictKind::Student => conn
+                .query_row(
+                    "SELECT firstname || ' ' || lastname FROM etudiant WHERE id = ?1 AND soutenance_id = ?2",
+                    params![conflict.etudiant_id, soutenance_id],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()
+                .map_err(|e| e.to_string())?
+                .map(|student| format!("conflict of interest with student {}", student)),
+            ConflictKind::Company => conn
+                .query_row(
+                    "SELECT p.company FROM soutenance s JOIN pfe p ON p.title = s.pfe
+                    WHERE s.id = ?1 AND lower(trim(p.company)) = lower(trim(?2))",
+                    params![soutenance_id, conflict.company],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()
+                .map_err(|e| e.to_string())?
+                .map(|company| format!("conflict of interest with company {}", company)),
+            ConflictKind::Jury => {
+                let other = if conflict.jury_id == jury_id { conflict.other_jury_id } else { Some(conflict.jury_id) };
+                conn.query_row(
+                    "SELECT j.firstname || ' ' || j.lastname FROM jury_soutenance js JOIN jury j ON j.id = js.jury_id
+                    WHERE js.soutenance_id = ?1 AND js.jury_id = ?2 AND js.jury_id != ?3",
+                    params![soutenance_id, other, jury_id],
+                    |row| row.get::<_, String>(0),
+                )
+                .optional()
+                .map_err(|e| e.to_string())?
+                .map(|member| format!("conflict of interest with jury member {}", member))
+            }
+        };
+        if let Some(reason) = applies {
+            reasons.push(reason);
+        }
+    }
+    Ok(reasons)
+}
+
+/// Refuses an assignment that goes against a declared conflict, unless a justification
+/// is given, in which case the override is recorded alongside the conflicts it waived.
+pub fn ensure_no_conflict(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    jury_id: i32,
+    soutenance_id: i32,
+    justification: Option<&str>,
+) -> Result<(), String> {
+    let reasons = assignment_conflicts(conn, jury_id, soutenance_id)?;
+    if reasons.is_empty() {
+        return Ok(());
+    }
+    let Some(justification) = justification.map(str::trim).filter(|j| !j.is_empty()) else {
+        return Err(format!(
+            "Jury {} has a declared {}; give a justification to assign them anyway",
+            jury_id,
+            reasons.join("; ")
+        ));
+    };
+    let key = [("jury_id", jury_id.into()), ("soutenance_id", soutenance_id.into())];
+    let before = audit::snapshot(conn, "jury_conflict_override", &key)?;
+    conn.execute(
+        "INSERT OR REPLACE INTO jury_conflict_override (jury_id, soutenance_id, justification, conflicts, user_email)
+        VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            jury_id,
+            soutenance_id,
+            justification,
+            reasons.join("; "),
+            actor.as_ref().map(|u| u.email.clone())
+        ],
+    )
+    .map_err(|e| format!("Failed to record the override: {}", e))?;
+    audit::record_change(conn, actor, "jury_conflict_override", &key, before)
+}
+
+/// Refuses a change to a soutenance's students or PFE that puts a seated member in a
+/// declared conflict, unless an override on record already waived it.
+pub fn ensure_seated_without_conflict(conn: &Connection, soutenance_id: i32) -> Result<(), String> {
+    for (jury_id, _) in jury_roles(conn, soutenance_id)? {
+        let waived: String = conn
+            .query_row(
+                "SELECT conflicts FROM jury_conflict_override WHERE jury_id = ?1 AND soutenance_id = ?2",
+                [jury_id, soutenance_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?
+            .unwrap_or_default();
+        let reasons: Vec<String> = assignment_conflicts(conn, jury_id, soutenance_id)?
+            .into_iter()
+            .filter(|reason| !waived.split("; ").any(|w| w == reason))
+            .collect();
+        if !reasons.is_empty() {
+            return Err(format!(
+                "Jury {} sits on soutenance {} and has a declared {}; reassign them first",
+                jury_id,
+                soutenance_id,
+                reasons.join("; ")
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub fn get_jury_conflicts(conn: &Connection, jury_id: Option<i32>) -> Result<Vec<Conflict>, String> {
+    load_conflicts(conn, jury_id)
+}
+
+pub fn declare_jury_conflict(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    conflict: NewConflict,
+) -> Result<i32, String> {
+    let target_missing = match conflict.kind {
+        ConflictKind::Student => conflict.etudiant_id.is_none(),
+        ConflictKind::Company => conflict.company.as_deref().is_none_or(|c| c.trim().is_empty()),
+        ConflictKind::Jury => conflict.other_jury_id.is_none(),
+    };
+    if target_missing {
+        return Err(format!("A {} conflict needs the {} it is declared with", conflict.kind.as_str(), conflict.kind.as_str()));
+    }
+    if conflict.kind == ConflictKind::Jury && conflict.other_jury_id == Some(conflict.jury_id) {
+        return Err("A jury member cannot declare a conflict with themselves".to_string());
+    }
+    conn.execute(
+        "INSERT INTO jury_conflict (jury_id, kind, etudiant_id, company, other_jury_id, reason) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            conflict.jury_id,
+            conflict.kind.as_str(),
+            conflict.etudiant_id.filter(|_| conflict.kind == ConflictKind::Student),
+            conflict.company.as_deref().map(str::trim).filter(|_| conflict.kind == ConflictKind::Company),
+            conflict.other_jury_id.filter(|_| conflict.kind == ConflictKind::Jury),
+            conflict.reason
+        ],
+    )
+    .map_err(|e| format!("Failed to declare conflict: {}", e))?;
+    let id = conn.last_insert_rowid() as i32;
+    audit::record_change(conn, actor, "jury_conflict", &[("id", id.into())], None)?;
+    Ok(id)
+}
+
+pub fn delete_jury_conflict(conn: &Connection, actor: &Option<CurrentUser>, id: i32) -> Result<(), String> {
+    let mut operation = Operation::new("delete_jury_conflict");
+    operation.track(conn, "jury_conflict", &[("id", id.into())])?;
+    conn.execute("DELETE FROM jury_conflict WHERE id = ?1", [id])
+        .map_err(|e| format!("Failed to delete conflict: {}", e))?;
+    operation.finish(conn, actor)?;
+    Ok(())
+}
+
+/// Current assignments of the session that go against a declared conflict, including the
+/// ones declared after the member was assigned.
+pub fn get_assignment_conflicts(conn: &Connection, session_id: Option<i32>) -> Result<Vec<AssignmentConflict>, String> {
+    session_conflicts(conn, session_id)
+}
+
+/// Jury assignments of a session that go against a declared conflict, overridden or not.
+pub fn session_conflicts(conn: &Connection, session_id: Option<i32>) -> Result<Vec<AssignmentConflict>, String> {
+    let session_id = resolve_session(conn, session_id)?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT js.jury_id, js.soutenance_id, o.justification
+            FROM jury_soutenance js
+            JOIN soutenance s ON s.id = js.soutenance_id
+            LEFT JOIN jury_conflict_override o ON o.jury_id = js.jury_id AND o.soutenance_id = js.soutenance_id
+            WHERE (?1 IS NULL OR s.session_id = ?1)
+                AND js.jury_id IN (SELECT jury_id FROM jury_conflict UNION SELECT other_jury_id FROM jury_conflict)
+            ORDER BY s.date, s.hour, js.jury_id",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([session_id], |row| Ok((row.get::<_, i32>(0)?, row.get::<_, i32>(1)?, row.get(2)?)))
+        .map_err(|e| e.to_string())?;
+    let mut flagged = Vec::new();
+    for row in rows {
+        let (jury_id, soutenance_id, justification) = row.map_err(|e| e.to_string())?;
+        let reasons = assignment_conflicts(conn, jury_id, soutenance_id)?;
+        if !reasons.is_empty() {
+            flagged.push(AssignmentConflict {
+                jury_id,
+                soutenance_id,
+                reasons,
+                justification,
+            });
+        }
+    }
+    Ok(flagged)
+}
+
+pub fn get_conflict_overrides(conn: &Connection, soutenance_id: Option<i32>) -> Result<Vec<ConflictOverride>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT jury_id, soutenance_id, justification, conflicts, user_email, created_at
+            FROM jury_conflict_override WHERE ?1 IS NULL OR soutenance_id = ?1 ORDER BY created_at DESC",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([soutenance_id], |row| {
+            Ok(ConflictOverride {
+                jury_id: row.get(0)?,
+                soutenance_id: row.get(1)?,
+                justification: row.get(2)?,
+                conflicts: row.get(3)?,
+                user_email: row.get(4)?,
+                created_at: row.get(5)?,
+            })
+        })
+        .map_err(|e| e.to_string())?;
+    let result: Result<Vec<_>, _> = rows.collect();
+    result.map_err(|e| e.to_string())
+}
+
+/// Drops the declarations naming a jury member or student that is being purged.
+pub fn purge_declarations(conn: &Connection, actor: &Option<CurrentUser>, table: &str, id: i32) -> Result<(), String> {
+    let condition = match table {
+        "jury" => "jury_id = ?1 OR other_jury_id = ?1",
+        "etudiant" => "etudiant_id = ?1",
+        _ => return Ok(()),
+    };
+    let mut stmt = conn
+        .prepare(&format!("SELECT id FROM jury_conflict WHERE {}", condition))
+        .map_err(|e| e.to_string())?;
+    let ids: Vec<i32> = stmt
+        .query_map([id], |row| row.get(0))
+        .and_then(|rows| rows.collect())
+        .map_err(|e| e.to_string())?;
+    for conflict_id in ids {
+        let key = [("id", conflict_id.into())];
+        let before = audit::snapshot(conn, "jury_conflict", &key)?;
+        conn.execute("DELETE FROM jury_conflict WHERE id = ?1", [conflict_id])
+            .map_err(|e| e.to_string())?;
+        audit::record_change(conn, actor, "jury_conflict", &key, before)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,535 @@
+//! Realistic fake data for training and load testing. Everything generated is recorded in
+//! `demo_row`, so it can be purged without touching real records.
+
+use crate::models::audit;
+use crate::models::classroom::{self, day_of, format_minutes, ClassroomDetails};
+use crate::models::composition::rules_for;
+use crate::models::conflict;
+use crate::models::etudiant::{self, NewStudent};
+use crate::models::expertise::{self, JuryGrade, JuryProfile};
+use crate::models::jury::{self, NewJury};
+use crate::models::jury_soutenance::{create_jury_soutenance, JuryRole};
+use crate::models::notification::{self, RecipientKind};
+use crate::models::pfe;
+use crate::models::session::{ensure_session_open, resolve_session};
+use crate::models::soutenance::{self, defence_duration};
+use crate::models::user::CurrentUser;
+use crate::models::workload::availability_problem;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rusqlite::types::Value as SqlValue;
+use rusqlite::Connection;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_STUDENTS_PER_SPECIALITE: usize = 10;
+const DEFAULT_CLASSROOMS: usize = 4;
+/// First defence of the day, and the time after which none starts.
+const DAY_START: i32 = 8 * 60 + 30;
+const DAY_END: i32 = 17 * 60;
+/// Minutes between two defences in the same room.
+const BREAK: i32 = 15;
+const EMAIL_DOMAIN: &str = "demo.example";
+
+const FIRSTNAMES: &[&str] = &[
+    "Mohamed", "Ahmed", "Youssef", "Amine", "Skander", "Oussema", "Hamza", "Mehdi", "Walid", "Karim", "Aziz",
+    "Fatma", "Mariem", "Amira", "Nour", "Salma", "Yasmine", "Ines", "Rania", "Sarra", "Chaima", "Emna", "Khadija",
+    "Lucas", "Camille", "Léa", "Hugo", "Manon", "Louis", "Chloé", "Julien", "Élodie",
+];
+const LASTNAMES: &[&str] = &[
+    "Ben Ali", "Trabelsi", "Gharbi", "Jebali", "Hamdi", "Bouazizi", "Mansouri", "Chaabane", "Ben Salah", "Karoui",
+    "Ayari", "Dridi", "Sassi", "Haddad", "Mejri", "Khelifi", "Zouari", "Masmoudi", "Ben Amor", "Laabidi",
+    "Martin", "Dubois", "Lefèvre", "Moreau", "Girard", "Rousseau",
+];
+const CITIES: &[&str] = &[
+    "Tunis", "Sfax", "Sousse", "Ariana", "Bizerte", "Nabeul", "Monastir", "Gabès", "Kairouan", "Ben Arous",
+];
+const COMPANIES: &[&str] = &[
+    "Sofrecom Tunisie", "Vermeg", "Telnet", "Poulina Group", "Sagemcom", "Leoni", "STEG", "Tunisie Télécom",
+    "Ooredoo", "BIAT", "Délice Danone", "Yazaki", "Draexlmaier", "Actia", "Focus Corporation", "Instadeep",
+];
+const TITLES: &[&str] = &[
+    "Conception et réalisation d'une solution de {topic}",
+    "Étude et mise en œuvre de {topic} chez {company}",
+    "Amélioration des processus de {topic} chez {company}",
+    "Développement d'un outil d'aide à la décision pour {topic}",
+];
+
+/// Department and project topics of the specialités whose name contains `pattern`; the
+/// last entry matches every other specialité.
+struct Domain {
+    pattern: &'static str,
+    department: &'static str,
+    topics: &'static [&'static str],
+}
+
+const DOMAINS: &[Domain] = &[
+    Domain {
+        pattern: "informatique",
+        department: "Informatique",
+        topics: &[
+            "apprentissage automatique", "développement web", "applications mobiles", "cybersécurité",
+            "cloud computing", "bases de données", "DevOps", "vision par ordinateur",
+        ],
+    },
+    Domain {
+        pattern: "industri",
+        department: "Génie Industriel",
+        topics: &[
+            "lean manufacturing", "gestion de la qualité", "logistique", "supply chain",
+            "planification de la production", "maintenance préventive", "ergonomie",
+        ],
+    },
+    Domain {
+        pattern: "civil",
+        department: "Génie Civil",
+        topics: &["béton armé", "structures métalliques", "géotechnique", "ouvrages d'art", "BIM", "hydraulique urbaine"],
+    },
+    Domain {
+        pattern: "procédé",
+        department: "Génie des Procédés",
+        topics: &["traitement des eaux", "génie chimique", "agroalimentaire", "énergies renouvelables", "contrôle des procédés"],
+    },
+    Domain {
+        pattern: "télécom",
+        department: "Télécommunications",
+        topics: &["réseaux 5G", "Internet des objets", "radiofréquences", "fibre optique", "systèmes embarqués"],
+    },
+    Domain {
+        pattern: "mécanique",
+        department: "Génie Mécanique",
+        topics: &["conception mécanique", "fabrication additive", "CAO", "mécanique des fluides", "robotique"],
+    },
+    Domain {
+        pattern: "",
+        department: "Sciences de l'ingénieur",
+        topics: &["gestion de projet", "analyse de données", "automatisation", "efficacité énergétique"],
+    },
+];
+
+fn domain_of(specialite_name: &str) -> &'static Domain {
+    let name = specialite_name.to_lowercase();
+    DOMAINS
+        .iter()
+        .find(|domain| name.contains(domain.pattern))
+        .expect("the last domain matches everything")
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct DemoOptions {
+    /// The same seed and options always produce the same data.
+    pub seed: u64,
+    pub students_per_specialite: Option<usize>,
+    /// Defaults to every specialité.
+    pub specialite_ids: Option<Vec<i32>>,
+    pub classrooms: Option<usize>,
+    /// Defaults to enough members to staff every room at once.
+    pub juries: Option<usize>,
+    /// First day of defences, `YYYY-MM-DD`; defaults to next Monday.
+    pub start_date: Option<String>,
+    /// Defaults to the active session.
+    pub session_id: Option<i32>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Default, PartialEq)]
+pub struct DemoSummary {
+    pub students: usize,
+    pub juries: usize,
+    pub classrooms: usize,
+    pub pfes: usize,
+    pub soutenances: usize,
+}
+
+fn track(conn: &Connection, entity_type: &str, id: i32) -> Result<(), String> {
+    conn.execute("INSERT INTO demo_row (entity_type, entity_id) VALUES (?1, ?2)", rusqlite::params![entity_type, id])
+        .map_err(|e| format!("Failed to flag demo data: {}", e))?;
+    Ok(())
+}
+
+fn tracked(conn: &Connection, entity_type: &str) -> Result<Vec<i32>, String> {
+    let mut stmt = conn
+        .prepare("SELECT entity_id FROM demo_row WHERE entity_type = ?1 ORDER BY entity_id")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([entity_type], |row| row.get(0)).map_err(|e| e.to_string())?;
+    let result: Result<Vec<i32>, _> = rows.collect();
+    result.map_err(|e| e.to_string())
+}
+
+/// Lowercase ASCII for email addresses: accents dropped, spaces and apostrophes removed.
+fn email_part(value: &str) -> String {
+    value
+        .chars()
+        .filter_map(|c| match c {
+            'à' | 'â' | 'ä' | 'À' | 'Â' => Some('a'),
+            'é' | 'è' | 'ê' | 'ë' | 'É' | 'È' => Some('e'),
+            'î' | 'ï' => Some('i'),
+            'ô' | 'ö' => Some('o'),
+            'ù' | 'û' | 'ü' => Some('u'),
+            'ç' => Some('c'),
+            c if c.is_ascii_alphanumeric() => Some(c.to_ascii_lowercase()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn person(rng: &mut StdRng) -> (String, String) {
+    (
+        FIRSTNAMES.choose(rng).expect("names").to_string(),
+        LASTNAMES.choose(rng).expect("names").to_string(),
+    )
+}
+
+/// Roughly bell-shaped around 14, with a few students below the passing grade.
+fn grade(rng: &mut StdRng) -> f64 {
+    let grade: f64 = 8.0 + rng.gen_range(0.0..6.0) + rng.gen_range(0.0..6.0);
+    ((grade * 4.0).round() / 4.0).min(20.0)
+}
+
+/// Weekdays from `start`, skipping Saturdays and Sundays.
+fn working_days(conn: &Connection, start: &str, count: usize) -> Result<Vec<String>, String> {
+    let mut days = Vec::new();
+    let mut offset = 0;
+    while days.len() < count {
+        let day: String = conn
+            .query_row("SELECT date(?1, '+' || ?2 || ' days')", rusqlite::params![start, offset], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        let (day, weekday) = day_of(conn, &day)?;
+        if weekday != 0 && weekday != 6 {
+            days.push(day);
+        }
+        offset += 1;
+    }
+    Ok(days)
+}
+
+/// Roles to seat on a defence of `specialite_id` besides the supervisor, following its
+/// composition rules.
+fn seat_roles(conn: &Connection, specialite_id: i32) -> Result<Vec<JuryRole>, String> {
+    let rules = rules_for(conn, specialite_id)?;
+    let mut roles = vec![JuryRole::President; rules.presidents.max(0) as usize];
+    roles.extend(vec![JuryRole::Rapporteur; rules.min_rapporteurs.max(1) as usize]);
+    while (roles.len() as i32) + 1 < rules.min_size {
+        roles.push(JuryRole::Examinateur);
+    }
+    Ok(roles)
+}
+
+/// Fills a session with students, juries with expertise, rooms, PFEs and a complete
+/// planning, then grades every student. Generated people are muted so nobody is emailed.
+pub fn generate_demo_data(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    options: DemoOptions,
+) -> Result<DemoSummary, String> {
+    let existing: i64 = conn
+        .query_row("SELECT COUNT(*) FROM demo_row", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if existing > 0 {
+        return Err("Demo data is already loaded; purge it first".to_string());
+    }
+    let session_id = resolve_session(conn, options.session_id)?;
+    ensure_session_open(conn, session_id)?;
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let mut summary = DemoSummary::default();
+
+    let specialites: Vec<(i32, String)> = {
+        let mut stmt = conn
+            .prepare("SELECT id, IFNULL(name, '') FROM specialite ORDER BY id")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).map_err(|e| e.to_string())?;
+        let all: Vec<(i32, String)> = rows.collect::<Result<_, _>>().map_err(|e| e.to_string())?;
+        match &options.specialite_ids {
+            Some(ids) => {
+                if let Some(missing) = ids.iter().find(|id| !all.iter().any(|(s, _)| s == *id)) {
+                    return Err(format!("Specialité {} not found", missing));
+                }
+                all.into_iter().filter(|(id, _)| ids.contains(id)).collect()
+            }
+            None => all,
+        }
+    };
+    let per_specialite = options.students_per_specialite.unwrap_or(DEFAULT_STUDENTS_PER_SPECIALITE);
+    let defences = specialites.len() * per_specialite;
+    let rooms = options.classrooms.unwrap_or(DEFAULT_CLASSROOMS).max(1);
+    let seats = specialites
+        .iter()
+        .map(|(id, _)| seat_roles(conn, *id).map(|roles| roles.len() + 1))
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .max()
+        .unwrap_or(0);
+    let jury_count = options.juries.unwrap_or((rooms * (seats + 2)).max(defences / 3));
+
+    let duration = defence_duration(conn)?;
+    let slots: Vec<String> = (0..)
+        .map(|i| DAY_START + i * (duration + BREAK))
+        .take_while(|start| start + duration <= DAY_END)
+        .map(format_minutes)
+        .collect();
+    if slots.is_empty() {
+        return Err(format!("Defences of {} minutes do not fit in a day", duration));
+    }
+    let start_date = match options.start_date {
+        Some(date) => day_of(conn, &date)?.0,
+        None => conn
+            .query_row("SELECT date('now', 'localtime', '+1 day', 'weekday 1')", [], |row| row.get(0))
+            .map_err(|e| e.to_string())?,
+    };
+    let days = working_days(conn, &start_date, defences.div_ceil(slots.len() * rooms))?;
+
+    let mut classroom_ids = Vec::new();
+    for i in 0..rooms {
+        let building = ["A", "B", "C"][i % 3];
+        let (name, capacity) = match i {
+            0 => ("Amphithéâtre A".to_string(), 150),
+            _ => (format!("Salle {}{}", building, 100 + i), *[30, 40, 60].choose(&mut rng).expect("capacities")),
+        };
+        let mut equipment = vec!["projector".to_string()];
+        if rng.gen_bool(0.4) {
+            equipment.push("videoconference".to_string());
+        }
+        let id = classroom::create_classroom(
+            conn,
+            actor,
+            name,
+            Some(ClassroomDetails {
+                capacity: Some(capacity),
+                building: Some(building.to_string()),
+                floor: Some((i / 3).to_string()),
+                equipment,
+            }),
+        )?;
+        track(conn, "classroom", id)?;
+        classroom_ids.push(id);
+        summary.classrooms += 1;
+    }
+
+    // Members are spread over the departments of the generated specialités.
+    let mut departments: Vec<&'static Domain> = Vec::new();
+    for (_, name) in &specialites {
+        let domain = domain_of(name);
+        if !departments.iter().any(|d| d.department == domain.department) {
+            departments.push(domain);
+        }
+    }
+    if departments.is_empty() {
+        departments.push(domain_of(""));
+    }
+    let mut juries: Vec<(i32, &'static str)> = Vec::new();
+    for i in 0..jury_count {
+        let (firstname, lastname) = person(&mut rng);
+        let domain = departments[i % departments.len()];
+        let id = jury::create_jury(
+            conn,
+            actor,
+            NewJury {
+                email: format!("{}.{}{}@{}", email_part(&firstname), email_part(&lastname), i + 1, EMAIL_DOMAIN),
+                firstname,
+                lastname,
+            },
+        )?;
+        let keywords = domain.topics.choose_multiple(&mut rng, 3).map(|t| t.to_string()).collect();
+        let grade = *[JuryGrade::MA, JuryGrade::MA, JuryGrade::MC, JuryGrade::Pr].choose(&mut rng).expect("grades");
+        expertise::set_jury_profile(
+            conn,
+            actor,
+            id,
+            JuryProfile {
+                keywords,
+                department: Some(domain.department.to_string()),
+                grade: Some(grade),
+                external: rng.gen_bool(0.2),
+            },
+        )?;
+        notification::set_notification_preference(conn, actor, RecipientKind::Jury, id, true)?;
+        track(conn, "jury", id)?;
+        juries.push((id, domain.department));
+        summary.juries += 1;
+    }
+
+    let mut slot = 0;
+    for (specialite_id, name) in &specialites {
+        let domain = domain_of(name);
+        let roles = seat_roles(conn, *specialite_id)?;
+        for _ in 0..per_specialite {
+            let (firstname, lastname) = person(&mut rng);
+            let student_id = etudiant::create_student(
+                conn,
+                actor,
+                &NewStudent {
+                    email: Some(format!(
+                        "{}.{}{}@etudiant.{}",
+                        email_part(&firstname),
+                        email_part(&lastname),
+                        summary.students + 1,
+                        EMAIL_DOMAIN
+                    )),
+                    firstname,
+                    lastname,
+                    address: CITIES.choose(&mut rng).expect("cities").to_string(),
+                    specialite_id: *specialite_id,
+                    soutenance_id: None,
+                    session_id,
+                },
+            )?;
+            notification::set_notification_preference(conn, actor, RecipientKind::Student, student_id, true)?;
+            track(conn, "etudiant", student_id)?;
+            summary.students += 1;
+
+            let topic = domain.topics.choose(&mut rng).expect("topics");
+            let company = COMPANIES.choose(&mut rng).expect("companies");
+            let title = TITLES
+                .choose(&mut rng)
+                .expect("titles")
+                .replace("{topic}", topic)
+                .replace("{company}", company);
+            let pfe_id = pfe::create_pfe(conn, actor, *specialite_id, Some(title.clone()), None, Some(company.to_string()))?;
+            track(conn, "pfe", pfe_id)?;
+            summary.pfes += 1;
+
+            let (day, hour) = (&days[slot / (slots.len() * rooms)], &slots[(slot / rooms) % slots.len()]);
+            let soutenance_id = soutenance::create_soutenance(
+                conn,
+                actor,
+                Some(day.clone()),
+                Some(hour.clone()),
+                *specialite_id,
+                Some(classroom_ids[slot % rooms]),
+                Some(title),
+                session_id,
+                None,
+            )?
+            .id;
+            track(conn, "soutenance", soutenance_id)?;
+            summary.soutenances += 1;
+            slot += 1;
+
+            // Members of the student's department first, the others as a fallback.
+            let mut candidates = juries.clone();
+            candidates.shuffle(&mut rng);
+            candidates.sort_by_key(|(_, department)| *department != domain.department);
+            let mut free = Vec::new();
+            for (jury_id, _) in candidates {
+                if availability_problem(conn, jury_id, soutenance_id)?.is_none() {
+                    free.push(jury_id);
+                }
+                if free.len() == roles.len() + 1 {
+                    break;
+                }
+            }
+            if free.len() < roles.len() + 1 {
+                return Err(format!(
+                    "Not enough jury members for the defence of {} at {}; generate more juries",
+                    day, hour
+                ));
+            }
+            let supervisor = free.remove(0);
+            etudiant::set_student_supervisor(conn, actor, student_id, Some(supervisor))?;
+            etudiant::assign_student(conn, actor, student_id, soutenance_id)?;
+            create_jury_soutenance(conn, actor, supervisor, soutenance_id, JuryRole::EncadrantAcademique.as_str().to_string(), None)?;
+            for (jury_id, role) in free.into_iter().zip(&roles) {
+                create_jury_soutenance(conn, actor, jury_id, soutenance_id, role.as_str().to_string(), None)?;
+            }
+            etudiant::set_student_grade(conn, actor, student_id, Some(grade(&mut rng)))?;
+            notification::discard_pending_notifications(conn, soutenance_id)?;
+        }
+    }
+    Ok(summary)
+}
+
+/// What is currently flagged as demo data.
+pub fn get_demo_data(conn: &Connection) -> Result<DemoSummary, String> {
+    Ok(DemoSummary {
+        students: tracked(conn, "etudiant")?.len(),
+        juries: tracked(conn, "jury")?.len(),
+        classrooms: tracked(conn, "classroom")?.len(),
+        pfes: tracked(conn, "pfe")?.len(),
+        soutenances: tracked(conn, "soutenance")?.len(),
+    })
+}
+
+fn delete_row(conn: &Connection, actor: &Option<CurrentUser>, table: &str, key: &[(&str, SqlValue)]) -> Result<(), String> {
+    let before = audit::snapshot(conn, table, key)?;
+    let condition: Vec<String> = key.iter().enumerate().map(|(i, (column, _))| format!("{} = ?{}", column, i + 1)).collect();
+    conn.execute(
+        &format!("DELETE FROM {} WHERE {}", table, condition.join(" AND ")),
+        rusqlite::params_from_iter(key.iter().map(|(_, value)| value)),
+    )
+    .map_err(|e| format!("Failed to purge {}: {}", table, e))?;
+    audit::record_change(conn, actor, table, key, before)
+}
+
+fn pairs(conn: &Connection, query: &str, id: i32) -> Result<Vec<(i32, i32)>, String> {
+    let mut stmt = conn.prepare(query).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([id], |row| Ok((row.get(0)?, row.get(1)?))).map_err(|e| e.to_string())?;
+    let result: Result<Vec<(i32, i32)>, _> = rows.collect();
+    result.map_err(|e| e.to_string())
+}
+
+/// Real students pointing at demo rows are detached; demo students go anyway.
+const REAL_STUDENT: &str = "id NOT IN (SELECT entity_id FROM demo_row WHERE entity_type = 'etudiant')";
+
+/// Permanently removes every row flagged as demo data, with its assignments, reminders
+/// and queued emails. Returns the number of rows removed.
+pub fn purge_demo_data(conn: &Connection, actor: &Option<CurrentUser>) -> Result<usize, String> {
+    let mut purged = 0;
+    for id in tracked(conn, "soutenance")? {
+        for (jury_id, soutenance_id) in pairs(conn, "SELECT jury_id, soutenance_id FROM jury_soutenance WHERE soutenance_id = ?1", id)? {
+            delete_row(conn, actor, "jury_soutenance", &[("jury_id", jury_id.into()), ("soutenance_id", soutenance_id.into())])?;
+        }
+        for (invite_id, soutenance_id) in pairs(conn, "SELECT invite_id, soutenance_id FROM invite_soutenance WHERE soutenance_id = ?1", id)? {
+            delete_row(conn, actor, "invite_soutenance", &[("invite_id", invite_id.into()), ("soutenance_id", soutenance_id.into())])?;
+        }
+        for (student_id, _) in pairs(conn, &format!("SELECT id, soutenance_id FROM etudiant WHERE soutenance_id = ?1 AND {}", REAL_STUDENT), id)? {
+            let before = audit::snapshot(conn, "etudiant", &[("id", student_id.into())])?;
+            conn.execute("UPDATE etudiant SET soutenance_id = NULL WHERE id = ?1", [student_id])
+                .map_err(|e| e.to_string())?;
+            audit::record_change(conn, actor, "etudiant", &[("id", student_id.into())], before)?;
+        }
+        for table in ["attendance", "reminder", "pending_notification", "outbox", "jury_conflict_override"] {
+            conn.execute(&format!("DELETE FROM {} WHERE soutenance_id = ?1", table), [id])
+                .map_err(|e| format!("Failed to purge {}: {}", table, e))?;
+        }
+        delete_row(conn, actor, "soutenance", &[("id", id.into())])?;
+        purged += 1;
+    }
+    for id in tracked(conn, "jury")? {
+        for (jury_id, soutenance_id) in pairs(conn, "SELECT jury_id, soutenance_id FROM jury_soutenance WHERE jury_id = ?1", id)? {
+            delete_row(conn, actor, "jury_soutenance", &[("jury_id", jury_id.into()), ("soutenance_id", soutenance_id.into())])?;
+        }
+        for (student_id, _) in pairs(conn, &format!("SELECT id, supervisor_id FROM etudiant WHERE supervisor_id = ?1 AND {}", REAL_STUDENT), id)? {
+            let before = audit::snapshot(conn, "etudiant", &[("id", student_id.into())])?;
+            conn.execute("UPDATE etudiant SET supervisor_id = NULL WHERE id = ?1", [student_id])
+                .map_err(|e| e.to_string())?;
+            audit::record_change(conn, actor, "etudiant", &[("id", student_id.into())], before)?;
+        }
+        conflict::purge_declarations(conn, actor, "jury", id)?;
+        notification::purge_preference(conn, actor, "jury", id)?;
+        delete_row(conn, actor, "jury", &[("id", id.into())])?;
+        purged += 1;
+    }
+    for id in tracked(conn, "etudiant")? {
+        conflict::purge_declarations(conn, actor, "etudiant", id)?;
+        notification::purge_preference(conn, actor, "etudiant", id)?;
+        delete_row(conn, actor, "etudiant", &[("id", id.into())])?;
+        purged += 1;
+    }
+    for id in tracked(conn, "pfe")? {
+        delete_row(conn, actor, "pfe", &[("id", id.into())])?;
+        purged += 1;
+    }
+    for id in tracked(conn, "classroom")? {
+        for (soutenance_id, _) in pairs(conn, "SELECT id, classroom_id FROM soutenance WHERE classroom_id = ?1", id)? {
+            let before = audit::snapshot(conn, "soutenance", &[("id", soutenance_id.into())])?;
+            conn.execute("UPDATE soutenance SET classroom_id = NULL WHERE id = ?1", [soutenance_id])
+                .map_err(|e| e.to_string())?;
+            audit::record_change(conn, actor, "soutenance", &[("id", soutenance_id.into())], before)?;
+        }
+        classroom::purge_schedule(conn, actor, id)?;
+        delete_row(conn, actor, "classroom", &[("id", id.into())])?;
+        purged += 1;
+    }
+    conn.execute("DELETE FROM demo_row", []).map_err(|e| e.to_string())?;
+    Ok(purged)
+}
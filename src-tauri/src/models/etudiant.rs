@@ -1,9 +1,17 @@
-use crate::AppState;
-use rusqlite::Result;
+use crate::export::read_csv;
+use crate::models::audit;
+use crate::models::composition::ensure_attachable;
+use crate::models::conflict::ensure_seated_without_conflict;
+use crate::models::journal::Operation;
+use crate::models::notification::{self, RecipientKind};
+use crate::models::session::{ensure_session_open, ensure_student_open, resolve_session};
+use crate::models::user::CurrentUser;
+use crate::query::{ListQuery, Page, QueryOptions};
+use rusqlite::{Connection, Result};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tauri::State;
 
-#[derive(Serialize, Deserialize, Clone, Debug)] // Added Clone and Debug
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)] // Added Clone and Debug
 pub struct Etudiant {
     pub id: i32,
     pub firstname: String,
@@ -11,32 +19,112 @@ pub struct Etudiant {
     pub address: String,
     pub specialite_id: i32,
     pub soutenance_id: Option<i32>,
+    pub session_id: Option<i32>,
+    pub grade: Option<f64>,
+    pub deleted_at: Option<String>,
+    /// Jury member supervising the project.
+    pub supervisor_id: Option<i32>,
+    /// Where notifications about the defence are sent.
+    #[serde(default)]
+    pub email: Option<String>,
 }
 
-#[tauri::command]
-pub fn create_student(
-    firstname: String,
-    lastname: String,
-    address: String,
-    specialite_id: i32,
-    soutenance_id: Option<i32>,
-    state: State<AppState>,
-) -> Result<i32, String> {
-    let conn = state.db.lock().unwrap();
+/// Fields of a student being created.
+#[derive(Clone, Debug)]
+pub struct NewStudent {
+    pub firstname: String,
+    pub lastname: String,
+    pub address: String,
+    pub specialite_id: i32,
+    pub soutenance_id: Option<i32>,
+    pub session_id: Option<i32>,
+    pub email: Option<String>,
+}
+
+/// Inserts a student, records it in the audit log and notifies them if they already have a
+/// soutenance.
+pub fn create_student(conn: &Connection, actor: &Option<CurrentUser>, student: &NewStudent) -> Result<i32, String> {
+    let session_id = resolve_session(conn, student.session_id)?;
+    ensure_session_open(conn, session_id)?;
     conn.execute(
-        "INSERT INTO etudiant (firstname, lastname, address, specialite_id, soutenance_id) VALUES (?1, ?2, ?3, ?4, ?5)",
-        rusqlite::params![firstname, lastname, address, specialite_id, soutenance_id],
+        "INSERT INTO etudiant (firstname, lastname, address, specialite_id, soutenance_id, session_id, email) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        rusqlite::params![
+            student.firstname,
+            student.lastname,
+            student.address,
+            student.specialite_id,
+            student.soutenance_id,
+            session_id,
+            student.email
+        ],
     )
     .map_err(|e| e.to_string())?;
-    Ok(conn.last_insert_rowid() as i32)
+    let id = conn.last_insert_rowid() as i32;
+    audit::record_change(conn, actor, "etudiant", &[("id", id.into())], None)?;
+    if let Some(soutenance_id) = student.soutenance_id {
+        ensure_seated_without_conflict(conn, soutenance_id)?;
+        notification::assigned(conn, soutenance_id, (RecipientKind::Student, id), None)?;
+    }
+    Ok(id)
 }
 
-#[tauri::command]
-pub fn get_student(id: i32, state: State<AppState>) -> Result<Etudiant, String> {
-    let conn = state.db.lock().unwrap();
+/// Reads students from a CSV file with a header row. `firstname`, `lastname` and
+/// `specialite_id` are required; `address`, `email` and `soutenance_id` are optional.
+pub fn read_students_csv(path: &str, session_id: Option<i32>) -> Result<Vec<NewStudent>, String> {
+    let mut rows = read_csv(path)?.into_iter();
+    let headers: Vec<String> = rows
+        .next()
+        .ok_or_else(|| format!("{} is empty", path))?
+        .iter()
+        .map(|h| h.trim().to_lowercase())
+        .collect();
+    let column = |name: &str| headers.iter().position(|h| h == name);
+    let required = |name: &str| column(name).ok_or_else(|| format!("Missing column '{}' in {}", name, path));
+    let (firstname, lastname, specialite) = (required("firstname")?, required("lastname")?, required("specialite_id")?);
+    let (address, email, soutenance) = (column("address"), column("email"), column("soutenance_id"));
+
+    let mut students = Vec::new();
+    for (index, row) in rows.enumerate() {
+        let line = index + 2; // the header is row 1
+        if row.iter().all(|cell| cell.trim().is_empty()) {
+            continue;
+        }
+        let cell = |i: Option<usize>| {
+            i.and_then(|i| row.get(i)).map(|v| v.trim().to_string()).filter(|v| !v.is_empty())
+        };
+        let text = |i: usize, name: &str| cell(Some(i)).ok_or_else(|| format!("Row {}: '{}' is empty", line, name));
+        let number = |value: String, name: &str| {
+            value
+                .parse::<i32>()
+                .map_err(|_| format!("Row {}: '{}' is not a valid {}", line, value, name))
+        };
+        students.push(NewStudent {
+            firstname: text(firstname, "firstname")?,
+            lastname: text(lastname, "lastname")?,
+            address: cell(address).unwrap_or_default(),
+            specialite_id: number(text(specialite, "specialite_id")?, "specialite_id")?,
+            soutenance_id: cell(soutenance).map(|v| number(v, "soutenance_id")).transpose()?,
+            session_id,
+            email: cell(email),
+        });
+    }
+    Ok(students)
+}
+
+/// Creates every student of the file; run in one transaction, a bad line leaves nothing behind.
+pub fn import_students_csv(conn: &Connection, actor: &Option<CurrentUser>, path: &str, session_id: Option<i32>) -> Result<usize, String> {
+    let students = read_students_csv(path, session_id)?;
+    for (index, student) in students.iter().enumerate() {
+        create_student(conn, actor, student)
+            .map_err(|e| format!("Student {} ({} {}): {}", index + 1, student.firstname, student.lastname, e))?;
+    }
+    Ok(students.len())
+}
+
+pub fn get_student(conn: &Connection, id: i32) -> Result<Etudiant, String> {
     let etudiant = conn
         .query_row(
-            "SELECT id, firstname, lastname, address, specialite_id, soutenance_id FROM etudiant WHERE id = ?1",
+            "SELECT id, firstname, lastname, address, specialite_id, soutenance_id, session_id, grade, deleted_at, supervisor_id, email FROM etudiant WHERE id = ?1",
             [id],
             |row| {
                 Ok(Etudiant {
@@ -46,6 +134,11 @@ pub fn get_student(id: i32, state: State<AppState>) -> Result<Etudiant, String>
                     address: row.get(3)?,
                     specialite_id: row.get(4)?,
                     soutenance_id: row.get(5)?,
+                    session_id: row.get(6)?,
+                    grade: row.get(7)?,
+                    deleted_at: row.get(8)?,
+                    supervisor_id: row.get(9)?,
+                    email: row.get(10)?,
                 })
             },
         )
@@ -54,60 +147,100 @@ pub fn get_student(id: i32, state: State<AppState>) -> Result<Etudiant, String>
 }
 
 
-#[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub fn update_student(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
     id: i32,
     firstname: String,
     lastname: String,
     address: String,
     specialite_id: i32,
     soutenance_id: i32,
-    state: State<AppState>,
-) -> Result<Etudiant, String> { // Changed return type here
-    {
-        let conn = state.db.lock().unwrap();
-        // conn.execute(
-        //     "UPDATE etudiant SET firstname = ?1, lastname = ?2, address = ?3, soutenance_id = ?4 WHERE id = ?5",
-        //     rusqlite::params![firstname, lastname, address, soutenance_id, id],
-        // )
-        conn.execute(
-            "UPDATE etudiant SET firstname = ?1, lastname = ?2, address = ?3, specialite_id = ?4, soutenance_id = ?5 WHERE id = ?6",
-            rusqlite::params![firstname, lastname, address, specialite_id, soutenance_id, id],
-        )
+    email: Option<String>,
+) -> Result<Etudiant, String> {
+    ensure_student_open(conn, id)?;
+    let before = audit::snapshot(conn, "etudiant", &[("id", id.into())])?;
+    let previous: Option<i32> = conn
+        .query_row("SELECT soutenance_id FROM etudiant WHERE id = ?1", [id], |row| row.get(0))
         .map_err(|e| e.to_string())?;
-        // conn is dropped here as it goes out of scope
+    // conn.execute(
+    //     "UPDATE etudiant SET firstname = ?1, lastname = ?2, address = ?3, soutenance_id = ?4 WHERE id = ?5",
+    //     rusqlite::params![firstname, lastname, address, soutenance_id, id],
+    // )
+    conn.execute(
+        "UPDATE etudiant SET firstname = ?1, lastname = ?2, address = ?3, specialite_id = ?4, soutenance_id = ?5, email = ?6 WHERE id = ?7",
+        rusqlite::params![firstname, lastname, address, specialite_id, soutenance_id, email, id],
+    )
+    .map_err(|e| e.to_string())?;
+    audit::record_change(conn, actor, "etudiant", &[("id", id.into())], before)?;
+    if previous != Some(soutenance_id) {
+        ensure_attachable(conn, soutenance_id)?;
+        ensure_seated_without_conflict(conn, soutenance_id)?;
+        if let Some(previous) = previous {
+            notification::unassigned(conn, previous, (RecipientKind::Student, id))?;
+        }
+        notification::assigned(conn, soutenance_id, (RecipientKind::Student, id), None)?;
     }
-    
-    get_student(id, state)
+    get_student(conn, id)
 }
 
-#[tauri::command]
-pub fn delete_students(ids: Vec<i32>, state: State<AppState>) -> Result<(), String> {
-    let mut conn = state.db.lock().unwrap(); // Add `mut` here
-    
-    // Start a transaction to ensure all deletions succeed or fail together
+/// Moves a student to a soutenance and notifies them.
+pub fn assign_student(conn: &Connection, actor: &Option<CurrentUser>, id: i32, soutenance_id: i32) -> Result<(), String> {
+    ensure_student_open(conn, id)?;
+    let before = audit::snapshot(conn, "etudiant", &[("id", id.into())])?;
+    let previous: Option<i32> = conn
+        .query_row("SELECT soutenance_id FROM etudiant WHERE id = ?1", [id], |row| row.get(0))
+        .map_err(|_| format!("Student {} not found", id))?;
+    conn.execute("UPDATE etudiant SET soutenance_id = ?1 WHERE id = ?2", [soutenance_id, id])
+        .map_err(|e| e.to_string())?;
+    audit::record_change(conn, actor, "etudiant", &[("id", id.into())], before)?;
+    if previous != Some(soutenance_id) {
+        ensure_attachable(conn, soutenance_id)?;
+        ensure_seated_without_conflict(conn, soutenance_id)?;
+        if let Some(previous) = previous {
+            notification::unassigned(conn, previous, (RecipientKind::Student, id))?;
+        }
+        notification::assigned(conn, soutenance_id, (RecipientKind::Student, id), None)?;
+    }
+    Ok(())
+}
 
-    let tx = conn.transaction().map_err(|e| e.to_string())?;
-    
+pub fn delete_students(conn: &Connection, actor: &Option<CurrentUser>, ids: Vec<i32>) -> Result<(), String> {
+    let mut operation = Operation::new("delete_students");
     for id in ids {
-        tx.execute("DELETE FROM etudiant WHERE id = ?1", [id])
-            .map_err(|e| e.to_string())?;
+        ensure_student_open(conn, id)?;
+        operation.track(conn, "etudiant", &[("id", id.into())])?;
+        // Archived students keep their soutenance; see `archive` for restore and purge.
+        conn.execute(
+            "UPDATE etudiant SET deleted_at = datetime('now') WHERE id = ?1 AND deleted_at IS NULL",
+            [id],
+        )
+        .map_err(|e| e.to_string())?;
     }
-    
-    // Commit the transaction
-    tx.commit().map_err(|e| e.to_string())?;
-    
+    operation.finish(conn, actor)?;
     Ok(())
 }
 
-#[tauri::command]
-pub fn get_students_by_department(department_id: i32, state: State<AppState>) -> Result<Vec<Etudiant>, String> {
-    let conn = state.db.lock().unwrap();
-    let mut stmt = conn
-        .prepare("SELECT id, firstname, lastname, address, specialite_id, soutenance_id FROM etudiant WHERE specialite_id = ?1")
-        .map_err(|e| e.to_string())?;
-    let etudiant_iter = stmt
-        .query_map([department_id], |row| {
+/// Lists the live students of a specialité, optionally restricted to one session.
+fn list_students(
+    conn: &Connection,
+    specialite_id: i32,
+    session_id: Option<i32>,
+    options: &QueryOptions,
+) -> Result<Page<Etudiant>, String> {
+    options.ensure_supported("etudiant", &[])?;
+    let mut query = ListQuery::new("FROM etudiant");
+    query
+        .filter("specialite_id = ? AND deleted_at IS NULL", vec![specialite_id.into()])
+        .filter_opt("session_id = ?", session_id)
+        .text(&options.text, &["firstname", "lastname", "address"]);
+    query.fetch(
+        conn,
+        "SELECT id, firstname, lastname, address, specialite_id, soutenance_id, session_id, grade, deleted_at, supervisor_id, email",
+        options,
+        &[("lastname", "lastname, firstname"), ("firstname", "firstname"), ("grade", "grade"), ("id", "id")],
+        |row| {
             Ok(Etudiant {
                 id: row.get(0)?,
                 firstname: row.get(1)?,
@@ -115,39 +248,78 @@ pub fn get_students_by_department(department_id: i32, state: State<AppState>) ->
                 address: row.get(3)?,
                 specialite_id: row.get(4)?,
                 soutenance_id: row.get(5)?,
+                session_id: row.get(6)?,
+                grade: row.get(7)?,
+                deleted_at: row.get(8)?,
+                supervisor_id: row.get(9)?,
+                email: row.get(10)?,
             })
-        })
-        .map_err(|e| e.to_string())?;
+        },
+    )
+}
 
-    let mut students = Vec::new();
-    for etudiant in etudiant_iter {
-        students.push(etudiant.map_err(|e| e.to_string())?);
-    }
-    Ok(students)
+pub fn get_students_by_department(
+    conn: &Connection,
+    department_id: i32,
+    session_id: Option<i32>,
+    options: Option<QueryOptions>,
+) -> Result<Page<Etudiant>, String> {
+    let session_id = resolve_session(conn, session_id)?;
+    list_students(conn, department_id, session_id, &options.unwrap_or_default())
 }
 
 
-#[tauri::command]
 pub fn get_specialite_students(
+    conn: &Connection,
     specialite_id: i32,
-    state: State<AppState>,
-) -> Result<Vec<Etudiant>, String> {
-    let conn = state.db.lock().unwrap();
-    let mut stmt = conn
-        .prepare("SELECT id, firstname, lastname, address, specialite_id, soutenance_id FROM etudiant WHERE specialite_id = ?1")
-        .map_err(|e| e.to_string())?;
-    let rows = stmt
-        .query_map([specialite_id], |row| {
-            Ok(Etudiant {
-                id: row.get(0)?,
-                firstname: row.get(1)?,
-                lastname: row.get(2)?,
-                address: row.get(3)?,
-                specialite_id: row.get(4)?,
-                soutenance_id: row.get(5)?,
-            })
-        })
+    session_id: Option<i32>,
+    options: Option<QueryOptions>,
+) -> Result<Page<Etudiant>, String> {
+    let session_id = resolve_session(conn, session_id)?;
+    list_students(conn, specialite_id, session_id, &options.unwrap_or_default())
+}
+
+pub fn set_student_grade(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    id: i32,
+    grade: Option<f64>,
+) -> Result<Etudiant, String> {
+    if let Some(value) = grade {
+        if !(0.0..=20.0).contains(&value) {
+            return Err(format!("Grade must be between 0 and 20, got {}", value));
+        }
+    }
+    ensure_student_open(conn, id)?;
+    let before = audit::snapshot(conn, "etudiant", &[("id", id.into())])?;
+    conn.execute(
+        "UPDATE etudiant SET grade = ?1 WHERE id = ?2",
+        rusqlite::params![grade, id],
+    )
+    .map_err(|e| e.to_string())?;
+    audit::record_change(conn, actor, "etudiant", &[("id", id.into())], before)?;
+    get_student(conn, id)
+}
+
+pub fn set_student_supervisor(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    id: i32,
+    supervisor_id: Option<i32>,
+) -> Result<Etudiant, String> {
+    ensure_student_open(conn, id)?;
+    let before = audit::snapshot(conn, "etudiant", &[("id", id.into())])?;
+    conn.execute(
+        "UPDATE etudiant SET supervisor_id = ?1 WHERE id = ?2",
+        rusqlite::params![supervisor_id, id],
+    )
+    .map_err(|e| e.to_string())?;
+    let soutenance_id: Option<i32> = conn
+        .query_row("SELECT soutenance_id FROM etudiant WHERE id = ?1", [id], |row| row.get(0))
         .map_err(|e| e.to_string())?;
-    let result: Result<Vec<Etudiant>, _> = rows.collect();
-    result.map_err(|e| e.to_string())
+    if let Some(soutenance_id) = soutenance_id {
+        ensure_attachable(conn, soutenance_id)?;
+    }
+    audit::record_change(conn, actor, "etudiant", &[("id", id.into())], before)?;
+    get_student(conn, id)
 }
@@ -0,0 +1,280 @@
+use crate::models::audit;
+use crate::models::composition::{pfe_supervisors, supervisors};
+use crate::models::conflict::assignment_conflicts;
+use crate::models::session::resolve_session;
+use crate::models::workload::{availability_problem, session_loads};
+use crate::models::user::CurrentUser;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+const DEFAULT_LIMIT: usize = 10;
+/// Score lost per defence already sat on in the session.
+const LOAD_PENALTY: f64 = 0.1;
+const DEPARTMENT_BONUS: f64 = 0.5;
+const STOP_WORDS: &[&str] = &[
+    "les", "des", "une", "pour", "par", "dans", "sur", "avec", "aux", "the", "and", "for", "with", "from", "into",
+];
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JuryGrade {
+    /// Maître assistant.
+    MA,
+    /// Maître de conférences.
+    MC,
+    /// Professeur.
+    Pr,
+}
+
+impl JuryGrade {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JuryGrade::MA => "MA",
+            JuryGrade::MC => "MC",
+            JuryGrade::Pr => "Pr",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<JuryGrade, String> {
+        match value.trim().to_lowercase().as_str() {
+            "ma" => Ok(JuryGrade::MA),
+            "mc" => Ok(JuryGrade::MC),
+            "pr" => Ok(JuryGrade::Pr),
+            _ => Err(format!("Unknown grade: {} (expected MA, MC or Pr)", value)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Default)]
+pub struct JuryProfile {
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    pub department: Option<String>,
+    pub grade: Option<JuryGrade>,
+    #[serde(default)]
+    pub external: bool,
+}
+
+/// The project to find reviewers for: a stored PFE, free text, or both. The supervisors
+/// of the students defending the PFE or soutenance are left out, and with `soutenance_id`
+/// availability is checked against its date and time.
+#[derive(Deserialize, JsonSchema)]
+pub struct ReviewerRequest {
+    pub pfe_id: Option<i32>,
+    pub title: Option<String>,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    pub specialite_id: Option<i32>,
+    pub soutenance_id: Option<i32>,
+    /// Jury members to leave out, such as a supervisor not recorded on the students.
+    #[serde(default)]
+    pub exclude: Vec<i32>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct ReviewerCandidate {
+    pub jury_id: i32,
+    pub firstname: String,
+    pub lastname: String,
+    #[serde(flatten)]
+    pub profile: JuryProfile,
+    pub matched_keywords: Vec<String>,
+    pub topic_score: f64,
+    pub load: i64,
+    /// Why the member cannot take the defence, if a soutenance was given.
+    pub unavailable: Option<String>,
+    pub score: f64,
+}
+
+/// Lowercase without accents, so "Télécom" and "telecom" compare equal.
+fn fold(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'à' | 'â' | 'ä' => 'a',
+            'é' | 'è' | 'ê' | 'ë' => 'e',
+            'î' | 'ï' => 'i',
+            'ô' | 'ö' => 'o',
+            'ù' | 'û' | 'ü' => 'u',
+            'ç' => 'c',
+            c => c,
+        })
+        .collect()
+}
+
+fn words(text: &str) -> Vec<String> {
+    fold(text)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 3 && !STOP_WORDS.contains(word))
+        .map(str::to_string)
+        .collect()
+}
+
+fn normalize_keywords(keywords: &[String]) -> Vec<String> {
+    let set: BTreeSet<String> = keywords
+        .iter()
+        .map(|keyword| keyword.trim().to_lowercase())
+        .filter(|keyword| !keyword.is_empty())
+        .collect();
+    set.into_iter().collect()
+}
+
+fn profile_from(keywords: Option<String>, department: Option<String>, grade: Option<String>, external: bool) -> JuryProfile {
+    JuryProfile {
+        keywords: keywords.and_then(|k| serde_json::from_str(&k).ok()).unwrap_or_default(),
+        department,
+        grade: grade.and_then(|g| JuryGrade::parse(&g).ok()),
+        external,
+    }
+}
+
+pub fn find_profile(conn: &Connection, jury_id: i32) -> Result<JuryProfile, String> {
+    conn.query_row(
+        "SELECT keywords, department, grade, external FROM jury WHERE id = ?1",
+        [jury_id],
+        |row| Ok(profile_from(row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+    )
+    .map_err(|e| format!("Jury {} not found: {}", jury_id, e))
+}
+
+pub fn get_jury_profile(conn: &Connection, jury_id: i32) -> Result<JuryProfile, String> {
+    find_profile(conn, jury_id)
+}
+
+pub fn set_jury_profile(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    jury_id: i32,
+    profile: JuryProfile,
+) -> Result<JuryProfile, String> {
+    let keywords = serde_json::to_string(&normalize_keywords(&profile.keywords)).map_err(|e| e.to_string())?;
+    let key = [("id", jury_id.into())];
+    let before = audit::snapshot(conn, "jury", &key)?;
+    conn.execute(
+        "UPDATE jury SET keywords = ?1, department = ?2, grade = ?3, external = ?4 WHERE id = ?5",
+        params![
+            keywords,
+            profile.department.as_deref().map(str::trim).filter(|d| !d.is_empty()),
+            profile.grade.map(|g| g.as_str()),
+            profile.external,
+            jury_id
+        ],
+    )
+    .map_err(|e| format!("Failed to save jury profile: {}", e))?;
+    audit::record_change(conn, actor, "jury", &key, before)?;
+    let saved = find_profile(conn, jury_id)?;
+    Ok(saved)
+}
+
+/// Ranks jury members for reviewing a project: members free to sit first, then by how
+/// many of their keywords the project mentions, a bonus for the matching department,
+/// and a penalty for the defences they already sit on this session.
+pub fn suggest_reviewers(conn: &Connection, request: ReviewerRequest) -> Result<Vec<ReviewerCandidate>, String> {
+    let mut text = request.title.clone().unwrap_or_default();
+    let mut specialite_id = request.specialite_id;
+    let mut excluded = request.exclude.clone();
+    if let Some(pfe_id) = request.pfe_id {
+        let (pfe_specialite, title, resume): (Option<i32>, Option<String>, Option<String>) = conn
+            .query_row("SELECT specialite_id, title, resume FROM pfe WHERE id = ?1", [pfe_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .map_err(|e| format!("PFE {} not found: {}", pfe_id, e))?;
+        specialite_id = specialite_id.or(pfe_specialite);
+        text = format!("{} {} {}", text, title.unwrap_or_default(), resume.unwrap_or_default());
+        excluded.extend(pfe_supervisors(conn, pfe_id)?);
+    }
+    let mut session_id = None;
+    if let Some(soutenance_id) = request.soutenance_id {
+        let (soutenance_specialite, pfe, soutenance_session): (i32, Option<String>, Option<i32>) = conn
+            .query_row("SELECT specialite_id, pfe, session_id FROM soutenance WHERE id = ?1", [soutenance_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .map_err(|e| format!("Soutenance {} not found: {}", soutenance_id, e))?;
+        specialite_id = specialite_id.or(Some(soutenance_specialite));
+        text = format!("{} {}", text, pfe.unwrap_or_default());
+        session_id = soutenance_session;
+        excluded.extend(supervisors(conn, soutenance_id)?);
+    }
+    let session_id = resolve_session(conn, session_id)?;
+    let project_words: BTreeSet<String> = words(&format!("{} {}", text, request.keywords.join(" "))).into_iter().collect();
+    let specialite = match specialite_id {
+        Some(id) => conn
+            .query_row("SELECT name FROM specialite WHERE id = ?1", [id], |row| row.get::<_, Option<String>>(0))
+            .optional()
+            .map_err(|e| e.to_string())?
+            .flatten()
+            .map(|name| fold(&name)),
+        None => None,
+    };
+    let loads = session_loads(conn, session_id)?;
+
+    let mut stmt = conn
+        .prepare("SELECT id, firstname, lastname, keywords, department, grade, external FROM jury WHERE deleted_at IS NULL")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i32>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                profile_from(row.get(3)?, row.get(4)?, row.get(5)?, row.get(6)?),
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+    let mut candidates = Vec::new();
+    for row in rows {
+        let (jury_id, firstname, lastname, profile) = row.map_err(|e| e.to_string())?;
+        if excluded.contains(&jury_id) {
+            continue;
+        }
+        // A keyword matches when every word of it appears in the project.
+        let matched_keywords: Vec<String> = profile
+            .keywords
+            .iter()
+            .filter(|keyword| {
+                let keyword_words = words(keyword);
+                !keyword_words.is_empty() && keyword_words.iter().all(|word| project_words.contains(word))
+            })
+            .cloned()
+            .collect();
+        let topic_score = if profile.keywords.is_empty() {
+            0.0
+        } else {
+            matched_keywords.len() as f64 / (profile.keywords.len() as f64).sqrt()
+        };
+        let department_bonus = match (&profile.department, &specialite) {
+            (Some(department), Some(specialite)) if specialite.contains(&fold(department)) => DEPARTMENT_BONUS,
+            _ => 0.0,
+        };
+        let load = loads.get(&jury_id).copied().unwrap_or(0);
+        let unavailable = match request.soutenance_id {
+            Some(soutenance_id) => match assignment_conflicts(conn, jury_id, soutenance_id)?.pop() {
+                Some(conflict) => Some(conflict),
+                None => availability_problem(conn, jury_id, soutenance_id)?,
+            },
+            None => None,
+        };
+        candidates.push(ReviewerCandidate {
+            jury_id,
+            firstname,
+            lastname,
+            profile,
+            matched_keywords,
+            topic_score,
+            load,
+            unavailable,
+            score: topic_score + department_bonus - LOAD_PENALTY * load as f64,
+        });
+    }
+    candidates.sort_by(|a, b| {
+        a.unavailable
+            .is_some()
+            .cmp(&b.unavailable.is_some())
+            .then(b.score.total_cmp(&a.score))
+            .then(a.load.cmp(&b.load))
+    });
+    candidates.truncate(request.limit.unwrap_or(DEFAULT_LIMIT));
+    Ok(candidates)
+}
@@ -0,0 +1,362 @@
+use crate::export::{write_csv, write_pdf};
+use crate::models::audit;
+use crate::models::jury_soutenance::JuryRole;
+use crate::models::session::{find_session, resolve_session};
+use crate::models::settings::{get_setting, set_setting};
+use crate::models::user::CurrentUser;
+use rusqlite::{params, Connection, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub const RATE_KEY: &str = "honorarium_rate";
+pub const TRAVEL_RATE_KEY: &str = "travel_allowance_rate";
+pub const CURRENCY_KEY: &str = "currency";
+const DEFAULT_CURRENCY: &str = "TND";
+
+/// How a jury member is paid. `None` rates fall back to the defaults for external
+/// members; internal members are only paid when they have rates of their own.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Default)]
+pub struct PaymentDetails {
+    pub affiliation: Option<String>,
+    /// Bank account (RIB/IBAN) or other reference the finance office pays to.
+    pub payment_reference: Option<String>,
+    /// Paid per defence sat on.
+    pub rate: Option<f64>,
+    /// Paid per day the member travels in for defences.
+    pub travel_rate: Option<f64>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct HonorariaRates {
+    pub rate: f64,
+    pub travel_rate: f64,
+    pub currency: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct HonorariumLine {
+    pub soutenance_id: i32,
+    pub date: Option<String>,
+    pub hour: Option<String>,
+    pub pfe: Option<String>,
+    pub role: JuryRole,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct Honorarium {
+    pub jury_id: i32,
+    pub firstname: String,
+    pub lastname: String,
+    pub email: String,
+    pub external: bool,
+    #[serde(flatten)]
+    pub payment: PaymentDetails,
+    pub defences: Vec<HonorariumLine>,
+    pub travel_days: i64,
+    pub fees: f64,
+    pub travel: f64,
+    pub total: f64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct HonorariaSummary {
+    pub session_id: Option<i32>,
+    pub currency: String,
+    pub members: Vec<Honorarium>,
+    pub total_fees: f64,
+    pub total_travel: f64,
+    pub total: f64,
+}
+
+fn rates(conn: &Connection) -> Result<HonorariaRates, String> {
+    Ok(HonorariaRates {
+        rate: get_setting(conn, RATE_KEY)?.and_then(|v| v.parse().ok()).unwrap_or(0.0),
+        travel_rate: get_setting(conn, TRAVEL_RATE_KEY)?.and_then(|v| v.parse().ok()).unwrap_or(0.0),
+        currency: get_setting(conn, CURRENCY_KEY)?.unwrap_or_else(|| DEFAULT_CURRENCY.to_string()),
+    })
+}
+
+fn round(amount: f64) -> f64 {
+    (amount * 1000.0).round() / 1000.0
+}
+
+/// Fees and travel allowances of every member sitting in the session, or only of
+/// `jury_id`. Guests of honour do not sit and are not paid.
+fn compute(conn: &Connection, session_id: Option<i32>, jury_id: Option<i32>) -> Result<HonorariaSummary, String> {
+    let defaults = rates(conn)?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT j.id, j.firstname, j.lastname, j.email, j.external, j.affiliation, j.payment_reference,
+                j.honorarium_rate, j.travel_rate, s.id, s.date, s.hour, s.pfe, js.role
+            FROM jury_soutenance js
+            JOIN jury j ON j.id = js.jury_id
+            JOIN soutenance s ON s.id = js.soutenance_id
+            WHERE (?1 IS NULL OR s.session_id = ?1) AND (?2 IS NULL OR j.id = ?2)
+            ORDER BY j.lastname, j.firstname, j.id, s.date, s.hour",
+        )
+        .map_err(|e| e.to_string())?;
+    let mut rows = stmt.query(params![session_id, jury_id]).map_err(|e| e.to_string())?;
+    let mut members: Vec<Honorarium> = Vec::new();
+    while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+        let role: String = row.get(13).map_err(|e| e.to_string())?;
+        let role = JuryRole::parse(&role)?;
+        if !role.is_member() {
+            continue;
+        }
+        let id: i32 = row.get(0).map_err(|e| e.to_string())?;
+        if members.last().is_none_or(|member| member.jury_id != id) {
+            members.push(Honorarium {
+                jury_id: id,
+                firstname: row.get(1).map_err(|e| e.to_string())?,
+                lastname: row.get(2).map_err(|e| e.to_string())?,
+                email: row.get(3).map_err(|e| e.to_string())?,
+                external: row.get(4).map_err(|e| e.to_string())?,
+                payment: PaymentDetails {
+                    affiliation: row.get(5).map_err(|e| e.to_string())?,
+                    payment_reference: row.get(6).map_err(|e| e.to_string())?,
+                    rate: row.get(7).map_err(|e| e.to_string())?,
+                    travel_rate: row.get(8).map_err(|e| e.to_string())?,
+                },
+                defences: Vec::new(),
+                travel_days: 0,
+                fees: 0.0,
+                travel: 0.0,
+                total: 0.0,
+            });
+        }
+        let member = members.last_mut().expect("member pushed above");
+        member.defences.push(HonorariumLine {
+            soutenance_id: row.get(9).map_err(|e| e.to_string())?,
+            date: row.get(10).map_err(|e| e.to_string())?,
+            hour: row.get(11).map_err(|e| e.to_string())?,
+            pfe: row.get(12).map_err(|e| e.to_string())?,
+            role,
+        });
+    }
+
+    for member in &mut members {
+        let fallback = |rate: f64| if member.external { rate } else { 0.0 };
+        let rate = member.payment.rate.unwrap_or_else(|| fallback(defaults.rate));
+        let travel_rate = member.payment.travel_rate.unwrap_or_else(|| fallback(defaults.travel_rate));
+        let mut days: Vec<&str> = member.defences.iter().filter_map(|d| d.date.as_deref()).collect();
+        days.sort_unstable();
+        days.dedup();
+        member.travel_days = days.len() as i64;
+        member.fees = round(rate * member.defences.len() as f64);
+        member.travel = round(travel_rate * member.travel_days as f64);
+        member.total = round(member.fees + member.travel);
+    }
+    Ok(HonorariaSummary {
+        session_id,
+        currency: defaults.currency,
+        total_fees: round(members.iter().map(|m| m.fees).sum()),
+        total_travel: round(members.iter().map(|m| m.travel).sum()),
+        total: round(members.iter().map(|m| m.total).sum()),
+        members,
+    })
+}
+
+fn session_label(conn: &Connection, session_id: Option<i32>) -> Result<String, String> {
+    match session_id {
+        Some(id) => {
+            let session = find_session(conn, id)?;
+            Ok(format!("session {} {}", session.kind.as_str(), session.annee_label))
+        }
+        None => Ok("toutes sessions".to_string()),
+    }
+}
+
+fn member_honorarium(summary: HonorariaSummary, jury_id: i32) -> Result<(Honorarium, String), String> {
+    let currency = summary.currency;
+    summary
+        .members
+        .into_iter()
+        .next()
+        .map(|member| (member, currency))
+        .ok_or_else(|| format!("Jury {} does not sit on any defence in this session", jury_id))
+}
+
+pub fn get_jury_payment(conn: &Connection, jury_id: i32) -> Result<PaymentDetails, String> {
+    conn.query_row(
+        "SELECT affiliation, payment_reference, honorarium_rate, travel_rate FROM jury WHERE id = ?1",
+        [jury_id],
+        |row| {
+            Ok(PaymentDetails {
+                affiliation: row.get(0)?,
+                payment_reference: row.get(1)?,
+                rate: row.get(2)?,
+                travel_rate: row.get(3)?,
+            })
+        },
+    )
+    .map_err(|e| format!("Jury {} not found: {}", jury_id, e))
+}
+
+pub fn set_jury_payment(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    jury_id: i32,
+    payment: PaymentDetails,
+) -> Result<(), String> {
+    if payment.rate.is_some_and(|rate| rate < 0.0) || payment.travel_rate.is_some_and(|rate| rate < 0.0) {
+        return Err("Rates cannot be negative".to_string());
+    }
+    let key = [("id", jury_id.into())];
+    let before = audit::snapshot(conn, "jury", &key)?;
+    conn.execute(
+        "UPDATE jury SET affiliation = ?1, payment_reference = ?2, honorarium_rate = ?3, travel_rate = ?4 WHERE id = ?5",
+        params![payment.affiliation, payment.payment_reference, payment.rate, payment.travel_rate, jury_id],
+    )
+    .map_err(|e| format!("Failed to save payment details: {}", e))?;
+    audit::record_change(conn, actor, "jury", &key, before)?;
+    Ok(())
+}
+
+pub fn get_honoraria_rates(conn: &Connection) -> Result<HonorariaRates, String> {
+    rates(conn)
+}
+
+pub fn set_honoraria_rates(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    rates: HonorariaRates,
+) -> Result<(), String> {
+    if rates.rate < 0.0 || rates.travel_rate < 0.0 {
+        return Err("Rates cannot be negative".to_string());
+    }
+    let currency = rates.currency.trim().to_uppercase();
+    for (name, value) in [
+        (RATE_KEY, rates.rate.to_string()),
+        (TRAVEL_RATE_KEY, rates.travel_rate.to_string()),
+        (CURRENCY_KEY, currency),
+    ] {
+        let key = [("key", name.to_string().into())];
+        let before = audit::snapshot(conn, "settings", &key)?;
+        set_setting(conn, name, Some(value.as_str()).filter(|v| !v.is_empty()))?;
+        audit::record_change(conn, actor, "settings", &key, before)?;
+    }
+    Ok(())
+}
+
+pub fn compute_honoraria(conn: &Connection, session_id: Option<i32>) -> Result<HonorariaSummary, String> {
+    let session_id = resolve_session(conn, session_id)?;
+    compute(conn, session_id, None)
+}
+
+/// Global summary for the finance office, one line per paid member.
+pub fn export_honoraria_csv(conn: &Connection, path: String, session_id: Option<i32>) -> Result<usize, String> {
+    let session_id = resolve_session(conn, session_id)?;
+    let summary = compute(conn, session_id, None)?;
+    let rows: Vec<Vec<String>> = summary
+        .members
+        .iter()
+        .filter(|member| member.total > 0.0)
+        .map(|member| {
+            vec![
+                member.jury_id.to_string(),
+                member.lastname.clone(),
+                member.firstname.clone(),
+                member.payment.affiliation.clone().unwrap_or_default(),
+                member.payment.payment_reference.clone().unwrap_or_default(),
+                member.defences.len().to_string(),
+                member.travel_days.to_string(),
+                format!("{:.3}", member.fees),
+                format!("{:.3}", member.travel),
+                format!("{:.3}", member.total),
+                summary.currency.clone(),
+            ]
+        })
+        .collect();
+    write_csv(
+        &path,
+        &["jury_id", "lastname", "firstname", "affiliation", "payment_reference", "defences", "travel_days", "fees", "travel", "total", "currency"],
+        &rows,
+    )
+}
+
+pub fn export_honoraria_pdf(conn: &Connection, path: String, session_id: Option<i32>) -> Result<(), String> {
+    let session_id = resolve_session(conn, session_id)?;
+    let (summary, label) = (compute(conn, session_id, None)?, session_label(conn, session_id)?);
+    let mut lines = vec![
+        format!("{:<30} {:>4} {:>5} {:>12} {:>12} {:>12}", "Membre", "Sout", "Jours", "Honoraires", "Déplacement", "Total"),
+        "-".repeat(80),
+    ];
+    for member in summary.members.iter().filter(|member| member.total > 0.0) {
+        lines.push(format!(
+            "{:<30} {:>4} {:>5} {:>12.3} {:>12.3} {:>12.3}",
+            format!("{} {}", member.lastname, member.firstname).chars().take(30).collect::<String>(),
+            member.defences.len(),
+            member.travel_days,
+            member.fees,
+            member.travel,
+            member.total
+        ));
+    }
+    lines.push("-".repeat(80));
+    lines.push(format!(
+        "{:<41} {:>12.3} {:>12.3} {:>12.3} {}",
+        "Total", summary.total_fees, summary.total_travel, summary.total, summary.currency
+    ));
+    write_pdf(&path, &format!("Honoraires des jurys - {}", label), &lines)
+}
+
+/// Payment statement of one member, one line per defence with the fee it earns. Travel
+/// allowances and totals are per member, so only the PDF statement carries them.
+pub fn export_payment_statement_csv(
+    conn: &Connection,
+    path: String,
+    jury_id: i32,
+    session_id: Option<i32>,
+) -> Result<usize, String> {
+    let session_id = resolve_session(conn, session_id)?;
+    let (member, currency) = member_honorarium(compute(conn, session_id, Some(jury_id))?, jury_id)?;
+    let fee = round(member.fees / member.defences.len() as f64);
+    let rows: Vec<Vec<String>> = member
+        .defences
+        .iter()
+        .map(|defence| {
+            vec![
+                defence.date.clone().unwrap_or_default(),
+                defence.hour.clone().unwrap_or_default(),
+                defence.pfe.clone().unwrap_or_default(),
+                defence.role.label().to_string(),
+                format!("{:.3}", fee),
+                currency.clone(),
+            ]
+        })
+        .collect();
+    write_csv(&path, &["date", "hour", "pfe", "role", "fee", "currency"], &rows)
+}
+
+pub fn export_payment_statement_pdf(
+    conn: &Connection,
+    path: String,
+    jury_id: i32,
+    session_id: Option<i32>,
+) -> Result<(), String> {
+    let session_id = resolve_session(conn, session_id)?;
+    let (member, currency) = member_honorarium(compute(conn, session_id, Some(jury_id))?, jury_id)?;
+    let label = session_label(conn, session_id)?;
+    let mut lines = vec![
+        format!("{} {} <{}>", member.firstname, member.lastname, member.email),
+        format!("Établissement : {}", member.payment.affiliation.clone().unwrap_or_default()),
+        format!("Référence de paiement : {}", member.payment.payment_reference.clone().unwrap_or_default()),
+        String::new(),
+        format!("{:<12} {:<6} {:<24} {}", "Date", "Heure", "Rôle", "Projet"),
+        "-".repeat(80),
+    ];
+    for defence in &member.defences {
+        lines.push(format!(
+            "{:<12} {:<6} {:<24} {}",
+            defence.date.clone().unwrap_or_default(),
+            defence.hour.clone().unwrap_or_default(),
+            defence.role.label(),
+            defence.pfe.clone().unwrap_or_default().chars().take(36).collect::<String>()
+        ));
+    }
+    lines.push("-".repeat(80));
+    lines.push(format!("Honoraires ({} soutenances) : {:.3} {}", member.defences.len(), member.fees, currency));
+    lines.push(format!("Déplacement ({} jours) : {:.3} {}", member.travel_days, member.travel, currency));
+    lines.push(format!("Total : {:.3} {}", member.total, currency));
+    write_pdf(&path, &format!("État de paiement - {}", label), &lines)
+}
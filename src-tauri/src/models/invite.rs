@@ -1,67 +1,73 @@
-use crate::AppState;
-use rusqlite::{params, Result};
+use crate::models::audit;
+use crate::models::journal::Operation;
+use crate::models::session::resolve_session;
+use crate::query::{ListQuery, Page, QueryOptions};
+use crate::models::user::CurrentUser;
+use rusqlite::{Connection, params, Result};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tauri::State;
 //use rand::random;
 
 
-#[derive(Serialize, Deserialize, Clone, Debug)] // Add Clone
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)] // Add Clone
 pub struct Invitee {
     pub id: i32,
     pub firstname: String,
     pub lastname: String,
     pub email: String,
+    pub deleted_at: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 pub struct NewInvitee {
     pub firstname: String,
     pub lastname: String,
     pub email: String,
 }
 
-#[tauri::command]
-pub fn create_invite(state: State<AppState>, invite: NewInvitee) -> Result<i32, String> {
-    let conn = state.db.lock().unwrap();
-    let id = rand::random::<i32>();
-
+pub fn create_invite(conn: &Connection, actor: &Option<CurrentUser>, invite: NewInvitee) -> Result<i32, String> {
     conn.execute(
         "INSERT INTO invite (firstname, lastname, email) VALUES (?1, ?2, ?3)",
         (&invite.firstname, &invite.lastname, &invite.email),
     )
     .map_err(|e| format!("BACKEND: Failed to create invite: {}", e.to_string()))?;
 
+    let id = conn.last_insert_rowid() as i32;
+    audit::record_change(conn, actor, "invite", &[("id", id.into())], None)?;
+
     Ok(id)
 }
 
-#[tauri::command]
-pub fn get_all_invite(state: State<AppState>) -> Result<Vec<Invitee>, String> {
-    let conn = state.db.lock().unwrap();
-    let mut stmt = conn
-        .prepare("SELECT id, firstname, lastname, email FROM invite")
-        .map_err(|e| e.to_string())?;
-    
-    let invite_iter = stmt.query_map([], |row| {
-        Ok(Invitee {
-            id: row.get(0)?,
-            firstname: row.get(1)?,
-            lastname: row.get(2)?,
-            email: row.get(3)?,
-        })
-    })
-    .map_err(|e: rusqlite::Error| e.to_string())?;
-
-    let result: Result<Vec<Invitee>, _> = invite_iter.collect();
-    result.map_err(|e: rusqlite::Error| e.to_string())
+pub fn get_all_invite(conn: &Connection, options: Option<QueryOptions>) -> Result<Page<Invitee>, String> {
+    let options = options.unwrap_or_default();
+    options.ensure_supported("invite", &[])?;
+
+    let mut query = ListQuery::new("FROM invite");
+    query
+        .filter("deleted_at IS NULL", vec![])
+        .text(&options.text, &["firstname", "lastname", "email"]);
+    query.fetch(
+        conn,
+        "SELECT id, firstname, lastname, email, deleted_at",
+        &options,
+        &[("lastname", "lastname, firstname"), ("firstname", "firstname"), ("email", "email"), ("id", "id")],
+        |row| {
+            Ok(Invitee {
+                id: row.get(0)?,
+                firstname: row.get(1)?,
+                lastname: row.get(2)?,
+                email: row.get(3)?,
+                deleted_at: row.get(4)?,
+            })
+        },
+    )
 }
 
 
-#[tauri::command]
-pub fn get_invite(id: i32, state: State<AppState>) -> Result<Invitee, String> {
-    let conn = state.db.lock().unwrap();
+pub fn get_invite(conn: &Connection, id: i32) -> Result<Invitee, String> {
     let invite = conn
         .query_row(
-            "SELECT id, firstname, lastname, mail FROM invite WHERE id = ?1",
+            "SELECT id, firstname, lastname, email, deleted_at FROM invite WHERE id = ?1",
             [id],
             |row| {
                 Ok(Invitee {
@@ -69,6 +75,7 @@ pub fn get_invite(id: i32, state: State<AppState>) -> Result<Invitee, String> {
                     firstname: row.get(1)?,
                     lastname: row.get(2)?,
                     email: row.get(3)?,
+                    deleted_at: row.get(4)?,
                 })
             },
         )
@@ -76,14 +83,12 @@ pub fn get_invite(id: i32, state: State<AppState>) -> Result<Invitee, String> {
     Ok(invite)
 }
 
-#[tauri::command]
 pub fn update_invite(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
     id: i32,
     invite: NewInvitee,
-    state: State<AppState>,
 ) -> Result<String, String> {
-    let conn = state.db.lock().map_err(|e| e.to_string())?;
-    
     // Check if email already exists for a different invite
     let existing_invite = conn
         .query_row(
@@ -98,6 +103,7 @@ pub fn update_invite(
         Err(e) => return Err(format!("Database error: {}", e.to_string())),
     }
     
+    let before = audit::snapshot(conn, "invite", &[("id", id.into())])?;
     conn.execute(
         "UPDATE invite SET firstname = ?1, lastname = ?2, email = ?3 WHERE id = ?4",
         params![
@@ -108,13 +114,11 @@ pub fn update_invite(
         ],
     )
     .map_err(|e| format!("BACKEND: Failed to update invite: {}", e.to_string()))?;
+    audit::record_change(conn, actor, "invite", &[("id", id.into())], before)?;
     Ok("Invitee updated successfully".to_string())
 }
 
-#[tauri::command]
-pub fn delete_invite(ids: Vec<i32>, state: State<AppState>) -> Result<(), String> {
-    let conn = state.db.lock().map_err(|e| e.to_string())?;
-
+pub fn delete_invite(conn: &Connection, actor: &Option<CurrentUser>, ids: Vec<i32>) -> Result<(), String> {
     // If the input array is empty, return early with success
     if ids.is_empty() {
         return Ok(());
@@ -122,29 +126,40 @@ pub fn delete_invite(ids: Vec<i32>, state: State<AppState>) -> Result<(), String
 
     // Create a parameterized IN clause, e.g., "(?, ?, ?)"
     let placeholders = ids.iter().map(|_| "?").collect::<Vec<_>>().join(", ");
-    let query = format!("DELETE FROM invite WHERE id IN ({})", placeholders);
+    // Archived rows stay referenced by past soutenances; see `archive` for restore and purge.
+    let query = format!(
+        "UPDATE invite SET deleted_at = datetime('now') WHERE deleted_at IS NULL AND id IN ({})",
+        placeholders
+    );
 
     // Convert Vec<i32> to Vec<&dyn rusqlite::ToSql> for params
     let params: Vec<&dyn rusqlite::ToSql> = ids.iter().map(|id| id as &dyn rusqlite::ToSql).collect();
 
+    let mut operation = Operation::new("delete_invite");
+    for id in &ids {
+        operation.track(conn, "invite", &[("id", (*id).into())])?;
+    }
+
     conn.execute(&query, &params[..])
         .map_err(|e| format!("BACKEND: Failed to delete invite: {}", e.to_string()))?;
 
+    operation.finish(conn, actor)?;
+
     Ok(())
 }
 
-#[tauri::command]
-pub fn get_invite_soutenances(
-    invite_id: i32,
-    state: State<AppState>,
-) -> Result<Vec<(i32, String)>, String> {
-    let conn = state.db.lock().unwrap();
+pub fn get_invite_soutenances(conn: &Connection, invite_id: i32, session_id: Option<i32>) -> Result<Vec<i32>, String> {
+    let session_id = resolve_session(conn, session_id)?;
     let mut stmt = conn
-        .prepare("SELECT soutenance_id, role FROM invite_soutenance WHERE invite_id = ?1")
+        .prepare(
+            "SELECT ins.soutenance_id FROM invite_soutenance ins
+            JOIN soutenance s ON s.id = ins.soutenance_id
+            WHERE ins.invite_id = ?1 AND (?2 IS NULL OR s.session_id = ?2)",
+        )
         .map_err(|e| e.to_string())?;
     let rows = stmt
-        .query_map([invite_id], |row| Ok((row.get(0)?, row.get(1)?)))
+        .query_map(params![invite_id, session_id], |row| row.get(0))
         .map_err(|e| e.to_string())?;
-    let result: Result<Vec<(i32, String)>, _> = rows.collect();
+    let result: Result<Vec<i32>, _> = rows.collect();
     result.map_err(|e| e.to_string())
 }
@@ -1,73 +1,321 @@
-use crate::AppState;
-use rusqlite::Result;
+use crate::models::audit;
+use crate::models::journal::Operation;
+use crate::models::notification::{self, RecipientKind};
+use crate::models::session::ensure_soutenance_open;
+use crate::models::user::CurrentUser;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tauri::State;
+use uuid::Uuid;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum InvitationStatus {
+    Pending,
+    Accepted,
+    Declined,
+    Attended,
+}
+
+impl InvitationStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InvitationStatus::Pending => "pending",
+            InvitationStatus::Accepted => "accepted",
+            InvitationStatus::Declined => "declined",
+            InvitationStatus::Attended => "attended",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<InvitationStatus, String> {
+        match value {
+            "pending" => Ok(InvitationStatus::Pending),
+            "accepted" => Ok(InvitationStatus::Accepted),
+            "declined" => Ok(InvitationStatus::Declined),
+            "attended" => Ok(InvitationStatus::Attended),
+            other => Err(format!("Unknown invitation status: {}", other)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct InviteSoutenance {
     pub invite_id: i32,
     pub soutenance_id: i32,
+    pub status: InvitationStatus,
+    /// Secret sent to the invitee to answer the invitation and check in.
+    pub rsvp_token: String,
+    pub responded_at: Option<String>,
+    pub checked_in_at: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Default)]
+pub struct InvitationCounts {
+    pub soutenance_id: i32,
+    pub pending: i32,
+    pub accepted: i32,
+    pub declined: i32,
+    pub attended: i32,
+    pub total: i32,
+}
+
+const INVITATION_SELECT: &str = "SELECT invite_id, soutenance_id, status, rsvp_token, responded_at, checked_in_at
+    FROM invite_soutenance";
+
+fn map_invitation(row: &rusqlite::Row) -> Result<InviteSoutenance> {
+    let status: String = row.get(2)?;
+    Ok(InviteSoutenance {
+        invite_id: row.get(0)?,
+        soutenance_id: row.get(1)?,
+        status: InvitationStatus::parse(&status).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, e.into())
+        })?,
+        rsvp_token: row.get(3)?,
+        responded_at: row.get(4)?,
+        checked_in_at: row.get(5)?,
+    })
+}
+
+/// A fresh RSVP token: 122 random bits, so tokens cannot be guessed from one another.
+pub fn new_rsvp_token() -> String {
+    Uuid::new_v4().simple().to_string()
+}
+
+/// Gives a token to invitations created before tokens existed.
+pub fn ensure_rsvp_tokens(conn: &Connection) -> Result<(), String> {
+    let mut stmt = conn
+        .prepare("SELECT invite_id, soutenance_id FROM invite_soutenance WHERE rsvp_token IS NULL")
+        .map_err(|e| e.to_string())?;
+    let missing: Vec<(i32, i32)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .and_then(|rows| rows.collect())
+        .map_err(|e| e.to_string())?;
+    for (invite_id, soutenance_id) in missing {
+        conn.execute(
+            "UPDATE invite_soutenance SET rsvp_token = ?1 WHERE invite_id = ?2 AND soutenance_id = ?3",
+            params![new_rsvp_token(), invite_id, soutenance_id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+pub fn find_by_token(conn: &Connection, token: &str) -> Result<InviteSoutenance, String> {
+    conn.query_row(&format!("{} WHERE rsvp_token = ?1", INVITATION_SELECT), [token.trim()], map_invitation)
+        .optional()
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Unknown invitation token".to_string())
+}
+
+fn set_status(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    invitation: &InviteSoutenance,
+    status: InvitationStatus,
+    column: &str,
+) -> Result<InviteSoutenance, String> {
+    let key = [("invite_id", invitation.invite_id.into()), ("soutenance_id", invitation.soutenance_id.into())];
+    let before = audit::snapshot(conn, "invite_soutenance", &key)?;
+    conn.execute(
+        &format!(
+            "UPDATE invite_soutenance SET status = ?1, {} = datetime('now') WHERE invite_id = ?2 AND soutenance_id = ?3",
+            column
+        ),
+        params![status.as_str(), invitation.invite_id, invitation.soutenance_id],
+    )
+    .map_err(|e| format!("Failed to update invitation: {}", e))?;
+    audit::record_change(conn, actor, "invite_soutenance", &key, before)?;
+    find_by_token(conn, &invitation.rsvp_token)
 }
 
-#[tauri::command]
 pub fn create_invite_soutenance(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
     invite_id: i32,
     soutenance_id: i32,
-    state: State<AppState>,
 ) -> Result<(), String> {
-    let conn = state.db.lock().unwrap();
+    ensure_soutenance_open(conn, soutenance_id)?;
+    let key = [("invite_id", invite_id.into()), ("soutenance_id", soutenance_id.into())];
+    let before = audit::snapshot(conn, "invite_soutenance", &key)?;
     conn.execute(
-        "INSERT INTO invite_soutenance (invite_id, soutenance_id) VALUES (?1, ?2)",
-        rusqlite::params![invite_id, soutenance_id], // Use params! and as_str()
+        "INSERT INTO invite_soutenance (invite_id, soutenance_id, status, rsvp_token) VALUES (?1, ?2, 'pending', ?3)",
+        rusqlite::params![invite_id, soutenance_id, new_rsvp_token()], // Use params! and as_str()
     )
     .map_err(|e| e.to_string())?;
+    audit::record_change(conn, actor, "invite_soutenance", &key, before)?;
+    notification::assigned(conn, soutenance_id, (RecipientKind::Invite, invite_id), None)?;
     Ok(())
 }
 
-#[tauri::command]
 pub fn get_invite_soutenance(
+    conn: &Connection,
     invite_id: i32,
     soutenance_id: i32,
-    state: State<AppState>,
 ) -> Result<InviteSoutenance, String> {
-    let conn = state.db.lock().unwrap();
     let pivot = conn.query_row(
-        "SELECT invite_id, soutenance_id FROM invite_soutenance WHERE invite_id = ?1 AND soutenance_id = ?2",
+        &format!("{} WHERE invite_id = ?1 AND soutenance_id = ?2", INVITATION_SELECT),
         [invite_id, soutenance_id],
-        |row| Ok(InviteSoutenance {
-            invite_id: row.get(0)?,
-            soutenance_id: row.get(1)?,
-        }),
+        map_invitation,
     ).map_err(|e| e.to_string())?;
     Ok(pivot)
 }
 
-#[tauri::command]
 pub fn update_invite_soutenance(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
     invite_id: i32,
     soutenance_id: i32,
-    state: State<AppState>,
 ) -> Result<(), String> {
-    let conn = state.db.lock().unwrap();
+    ensure_soutenance_open(conn, soutenance_id)?;
+    let key = [("invite_id", invite_id.into()), ("soutenance_id", soutenance_id.into())];
+    let before = audit::snapshot(conn, "invite_soutenance", &key)?;
     conn.execute(
         "UPDATE invite_soutenance SET invite_id = ?1, soutenance_id = ?2 WHERE invite_id = ?1 AND soutenance_id = ?2",
         rusqlite::params![invite_id, soutenance_id],
     )
     .map_err(|e| e.to_string())?;
+    audit::record_change(conn, actor, "invite_soutenance", &key, before)?;
     Ok(())
 }
 
-#[tauri::command]
 pub fn delete_invite_soutenance(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
     invite_id: i32,
     soutenance_id: i32,
-    state: State<AppState>,
 ) -> Result<(), String> {
-    let conn = state.db.lock().unwrap();
+    ensure_soutenance_open(conn, soutenance_id)?;
+    let key = [("invite_id", invite_id.into()), ("soutenance_id", soutenance_id.into())];
+    let mut operation = Operation::new("delete_invite_soutenance");
+    operation.track(conn, "invite_soutenance", &key)?;
     conn.execute(
         "DELETE FROM invite_soutenance WHERE invite_id = ?1 AND soutenance_id = ?2",
         [invite_id, soutenance_id],
     )
     .map_err(|e| e.to_string())?;
+    notification::unassigned(conn, soutenance_id, (RecipientKind::Invite, invite_id))?;
+    operation.finish(conn, actor)?;
+    Ok(())
+}
+
+pub fn get_soutenance_invitations(conn: &Connection, soutenance_id: i32) -> Result<Vec<InviteSoutenance>, String> {
+    let mut stmt = conn
+        .prepare(&format!("{} WHERE soutenance_id = ?1 ORDER BY invite_id", INVITATION_SELECT))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([soutenance_id], map_invitation).map_err(|e| e.to_string())?;
+    let result: Result<Vec<_>, _> = rows.collect();
+    result.map_err(|e| e.to_string())
+}
+
+pub fn get_invitation_counts(conn: &Connection, soutenance_id: i32) -> Result<InvitationCounts, String> {
+    let mut stmt = conn
+        .prepare("SELECT status, COUNT(*) FROM invite_soutenance WHERE soutenance_id = ?1 GROUP BY status")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([soutenance_id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i32>(1)?)))
+        .map_err(|e| e.to_string())?;
+    let mut counts = InvitationCounts {
+        soutenance_id,
+        ..Default::default()
+    };
+    for row in rows {
+        let (status, count) = row.map_err(|e| e.to_string())?;
+        match InvitationStatus::parse(&status)? {
+            InvitationStatus::Pending => counts.pending = count,
+            InvitationStatus::Accepted => counts.accepted = count,
+            InvitationStatus::Declined => counts.declined = count,
+            InvitationStatus::Attended => counts.attended = count,
+        }
+        counts.total += count;
+    }
+    Ok(counts)
+}
+
+/// Records the invitee's answer. They may change their mind until they have checked in.
+pub fn respond_to_invitation(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    token: String,
+    accepted: bool,
+) -> Result<InviteSoutenance, String> {
+    let invitation = find_by_token(conn, &token)?;
+    ensure_soutenance_open(conn, invitation.soutenance_id)?;
+    if invitation.status == InvitationStatus::Attended {
+        return Err("The invitee has already attended this defence".to_string());
+    }
+    let status = if accepted { InvitationStatus::Accepted } else { InvitationStatus::Declined };
+    let updated = set_status(conn, actor, &invitation, status, "responded_at")?;
+    Ok(updated)
+}
+
+/// Refuses check-in outside the day of the defence.
+pub fn ensure_defence_today(conn: &Connection, soutenance_id: i32) -> Result<(), String> {
+    let (date, today): (Option<String>, String) = conn
+        .query_row(
+            "SELECT substr(date, 1, 10), date('now', 'localtime') FROM soutenance WHERE id = ?1",
+            [soutenance_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| format!("Soutenance {} not found: {}", soutenance_id, e))?;
+    if date.as_deref() != Some(today.as_str()) {
+        return Err(format!(
+            "This defence takes place on {}, check-in is only open on that day",
+            date.unwrap_or_else(|| "an unscheduled date".to_string())
+        ));
+    }
     Ok(())
 }
+
+/// Marks an invitation as attended, for check-ins that do not go through the RSVP token.
+pub fn record_attendance(conn: &Connection, actor: &Option<CurrentUser>, invite_id: i32, soutenance_id: i32) -> Result<(), String> {
+    let invitation = conn
+        .query_row(
+            &format!("{} WHERE invite_id = ?1 AND soutenance_id = ?2", INVITATION_SELECT),
+            [invite_id, soutenance_id],
+            map_invitation,
+        )
+        .map_err(|e| format!("Invite {} is not invited to soutenance {}: {}", invite_id, soutenance_id, e))?;
+    if invitation.checked_in_at.is_none() {
+        set_status(conn, actor, &invitation, InvitationStatus::Attended, "checked_in_at")?;
+    }
+    Ok(())
+}
+
+/// Marks the invitee as present. Only works on the day of the defence, whatever they
+/// answered beforehand, and only once per invitation.
+pub fn check_in_invitation(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    token: String,
+) -> Result<InviteSoutenance, String> {
+    let invitation = find_by_token(conn, &token)?;
+    if let Some(checked_in_at) = &invitation.checked_in_at {
+        return Err(format!("Already checked in at {}", checked_in_at));
+    }
+    ensure_defence_today(conn, invitation.soutenance_id)?;
+    let updated = set_status(conn, actor, &invitation, InvitationStatus::Attended, "checked_in_at")?;
+    Ok(updated)
+}
+
+/// Replaces a leaked token; the old one stops working.
+pub fn regenerate_rsvp_token(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    invite_id: i32,
+    soutenance_id: i32,
+) -> Result<String, String> {
+    let key = [("invite_id", invite_id.into()), ("soutenance_id", soutenance_id.into())];
+    let before = audit::snapshot(conn, "invite_soutenance", &key)?;
+    let token = new_rsvp_token();
+    let updated = conn
+        .execute(
+            "UPDATE invite_soutenance SET rsvp_token = ?1 WHERE invite_id = ?2 AND soutenance_id = ?3",
+            params![token, invite_id, soutenance_id],
+        )
+        .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err(format!("Invite {} is not invited to soutenance {}", invite_id, soutenance_id));
+    }
+    audit::record_change(conn, actor, "invite_soutenance", &key, before)?;
+    Ok(token)
+}
@@ -0,0 +1,420 @@
+use crate::models::audit;
+use crate::models::invite_soutenance::ensure_rsvp_tokens;
+use crate::models::session::{ensure_session_open, ensure_soutenance_open};
+use crate::models::settings::get_setting;
+use crate::models::user::CurrentUser;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, Connection, OptionalExtension, Result, ToSql};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+pub const UNDO_DEPTH_KEY: &str = "undo_depth";
+const DEFAULT_UNDO_DEPTH: i64 = 20;
+
+/// Columns that point at another table; a row is only restored if its targets still exist.
+const REFERENCES: &[(&str, &str, &str)] = &[
+    ("jury_soutenance", "jury_id", "jury"),
+    ("jury_soutenance", "soutenance_id", "soutenance"),
+    ("invite_soutenance", "invite_id", "invite"),
+    ("invite_soutenance", "soutenance_id", "soutenance"),
+    ("etudiant", "soutenance_id", "soutenance"),
+    ("etudiant", "supervisor_id", "jury"),
+    ("classroom_opening_hours", "classroom_id", "classroom"),
+    ("classroom_closure", "classroom_id", "classroom"),
+    ("jury_conflict", "jury_id", "jury"),
+    ("jury_conflict", "other_jury_id", "jury"),
+    ("jury_conflict", "etudiant_id", "etudiant"),
+];
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct OperationSummary {
+    pub id: i64,
+    pub label: String,
+    pub created_at: String,
+    pub undone: bool,
+    pub rows: i64,
+}
+
+struct TrackedRow {
+    table: String,
+    key: Vec<(String, SqlValue)>,
+    before: Option<Value>,
+}
+
+/// Collects the rows touched by one destructive command so it can be undone as a whole.
+/// Call `track` before changing a row, then `finish` in the same transaction afterwards.
+pub struct Operation {
+    label: String,
+    rows: Vec<TrackedRow>,
+}
+
+impl Operation {
+    pub fn new(label: &str) -> Operation {
+        Operation {
+            label: label.to_string(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn track(&mut self, conn: &Connection, table: &str, key: &[(&str, SqlValue)]) -> Result<(), String> {
+        let before = audit::snapshot(conn, table, key)?;
+        self.rows.push(TrackedRow {
+            table: table.to_string(),
+            key: key.iter().map(|(c, v)| (c.to_string(), v.clone())).collect(),
+            before,
+        });
+        Ok(())
+    }
+
+    /// Writes the audit entries and the journal record for every tracked row.
+    pub fn finish(self, conn: &Connection, actor: &Option<CurrentUser>) -> Result<(), String> {
+        let mut entries = Vec::new();
+        for row in self.rows {
+            let key: Vec<(&str, SqlValue)> = row.key.iter().map(|(c, v)| (c.as_str(), v.clone())).collect();
+            audit::record_change(conn, actor, &row.table, &key, row.before.clone())?;
+            let after = audit::snapshot(conn, &row.table, &key)?;
+            if row.before != after {
+                entries.push((row.table, key_json(&row.key), row.before, after));
+            }
+        }
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let token = session_token(actor);
+        // A new operation invalidates whatever could still be redone.
+        conn.execute(
+            "DELETE FROM operation_journal_entry WHERE operation_id IN
+                (SELECT id FROM operation_journal WHERE session_token = ?1 AND undone = 1)",
+            [&token],
+        )
+        .map_err(|e| format!("Failed to clear redo history: {}", e))?;
+        conn.execute("DELETE FROM operation_journal WHERE session_token = ?1 AND undone = 1", [&token])
+            .map_err(|e| format!("Failed to clear redo history: {}", e))?;
+
+        conn.execute(
+            "INSERT INTO operation_journal (session_token, user_id, label) VALUES (?1, ?2, ?3)",
+            params![token, actor.as_ref().map(|u| u.id), self.label],
+        )
+        .map_err(|e| format!("Failed to journal operation: {}", e))?;
+        let operation_id = conn.last_insert_rowid();
+        for (seq, (table, key, before, after)) in entries.into_iter().enumerate() {
+            conn.execute(
+                "INSERT INTO operation_journal_entry (operation_id, seq, entity_type, key_json, before_json, after_json)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    operation_id,
+                    seq as i64,
+                    table,
+                    key.to_string(),
+                    before.map(|v| v.to_string()),
+                    after.map(|v| v.to_string()),
+                ],
+            )
+            .map_err(|e| format!("Failed to journal operation: {}", e))?;
+        }
+        prune(conn, &token)
+    }
+}
+
+fn session_token(actor: &Option<CurrentUser>) -> String {
+    actor.as_ref().map(|u| u.token.clone()).unwrap_or_default()
+}
+
+fn undo_depth(conn: &Connection) -> Result<i64, String> {
+    Ok(get_setting(conn, UNDO_DEPTH_KEY)?
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_UNDO_DEPTH))
+}
+
+/// Keeps only the most recent operations of a session.
+fn prune(conn: &Connection, token: &str) -> Result<(), String> {
+    let depth = undo_depth(conn)?;
+    conn.execute(
+        "DELETE FROM operation_journal_entry WHERE operation_id IN (
+            SELECT id FROM operation_journal WHERE session_token = ?1
+            ORDER BY id DESC LIMIT -1 OFFSET ?2)",
+        params![token, depth],
+    )
+    .map_err(|e| format!("Failed to prune journal: {}", e))?;
+    conn.execute(
+        "DELETE FROM operation_journal WHERE id IN (
+            SELECT id FROM operation_journal WHERE session_token = ?1
+            ORDER BY id DESC LIMIT -1 OFFSET ?2)",
+        params![token, depth],
+    )
+    .map_err(|e| format!("Failed to prune journal: {}", e))?;
+    Ok(())
+}
+
+fn key_json(key: &[(String, SqlValue)]) -> Value {
+    let mut object = Map::new();
+    for (column, value) in key {
+        object.insert(column.clone(), sql_to_json(value));
+    }
+    Value::Object(object)
+}
+
+fn sql_to_json(value: &SqlValue) -> Value {
+    match value {
+        SqlValue::Null => Value::Null,
+        SqlValue::Integer(i) => Value::from(*i),
+        SqlValue::Real(f) => Value::from(*f),
+        SqlValue::Text(t) => Value::String(t.clone()),
+        SqlValue::Blob(_) => Value::Null,
+    }
+}
+
+fn json_to_sql(value: &Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(*b as i64),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => SqlValue::Text(s.clone()),
+        other => SqlValue::Text(other.to_string()),
+    }
+}
+
+fn columns_of(value: &Value) -> Vec<(String, SqlValue)> {
+    value
+        .as_object()
+        .map(|object| object.iter().map(|(k, v)| (k.clone(), json_to_sql(v))).collect())
+        .unwrap_or_default()
+}
+
+fn describe(table: &str, key: &Value) -> String {
+    format!("{} {}", table, key)
+}
+
+fn ensure_references(conn: &Connection, table: &str, row: &Value) -> Result<(), String> {
+    for (owner, column, target) in REFERENCES {
+        if *owner != table {
+            continue;
+        }
+        let Some(id) = row.get(*column).and_then(Value::as_i64) else {
+            continue;
+        };
+        let exists = conn
+            .prepare(&format!("SELECT 1 FROM {} WHERE id = ?1", target))
+            .and_then(|mut stmt| stmt.exists([id]))
+            .map_err(|e| e.to_string())?;
+        if !exists {
+            return Err(format!("{} {} no longer exists", target, id));
+        }
+    }
+    Ok(())
+}
+
+fn ensure_writable(conn: &Connection, table: &str, row: &Value) -> Result<(), String> {
+    match table {
+        "soutenance" | "etudiant" => {
+            ensure_session_open(conn, row.get("session_id").and_then(Value::as_i64).map(|id| id as i32))
+        }
+        "jury_soutenance" | "invite_soutenance" => match row.get("soutenance_id").and_then(Value::as_i64) {
+            Some(id) => ensure_soutenance_open(conn, id as i32),
+            None => Ok(()),
+        },
+        _ => Ok(()),
+    }
+}
+
+/// Moves one row from `expected` to `target`, refusing if someone changed it in between.
+fn apply_row(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    table: &str,
+    key: &Value,
+    expected: &Option<Value>,
+    target: &Option<Value>,
+) -> Result<(), String> {
+    let key_columns = columns_of(key);
+    let key_ref: Vec<(&str, SqlValue)> = key_columns.iter().map(|(c, v)| (c.as_str(), v.clone())).collect();
+    let current = audit::snapshot(conn, table, &key_ref)?;
+    if &current != expected {
+        return Err(format!("{} has changed since this operation", describe(table, key)));
+    }
+    for row in current.iter().chain(target.iter()) {
+        ensure_writable(conn, table, row)?;
+    }
+
+    let where_clause = key_columns
+        .iter()
+        .enumerate()
+        .map(|(i, (c, _))| format!("{} = ?{}", c, i + 1))
+        .collect::<Vec<_>>()
+        .join(" AND ");
+    let key_params: Vec<&dyn ToSql> = key_columns.iter().map(|(_, v)| v as &dyn ToSql).collect();
+
+    match target {
+        None => {
+            conn.execute(&format!("DELETE FROM {} WHERE {}", table, where_clause), &key_params[..])
+                .map_err(|e| format!("Cannot remove {}: {}", describe(table, key), e))?;
+        }
+        Some(row) => {
+            ensure_references(conn, table, row)
+                .map_err(|e| format!("Cannot restore {}: {}", describe(table, key), e))?;
+            let columns = columns_of(row);
+            let values: Vec<&dyn ToSql> = columns.iter().map(|(_, v)| v as &dyn ToSql).collect();
+            let sql = if current.is_none() {
+                format!(
+                    "INSERT INTO {} ({}) VALUES ({})",
+                    table,
+                    columns.iter().map(|(c, _)| c.as_str()).collect::<Vec<_>>().join(", "),
+                    (1..=columns.len()).map(|i| format!("?{}", i)).collect::<Vec<_>>().join(", ")
+                )
+            } else {
+                let assignments = columns
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (c, _))| format!("{} = ?{}", c, i + 1))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let where_clause = key_columns
+                    .iter()
+                    .enumerate()
+                    .map(|(i, (c, _))| format!("{} = ?{}", c, columns.len() + i + 1))
+                    .collect::<Vec<_>>()
+                    .join(" AND ");
+                format!("UPDATE {} SET {} WHERE {}", table, assignments, where_clause)
+            };
+            let mut all_params = values;
+            if current.is_some() {
+                all_params.extend(key_params.iter());
+            }
+            conn.execute(&sql, &all_params[..])
+                .map_err(|e| format!("Cannot restore {}: {}", describe(table, key), e))?;
+            // The token is not journaled, so a restored invitation is issued a new one.
+            if table == "invite_soutenance" && current.is_none() {
+                ensure_rsvp_tokens(conn)?;
+            }
+        }
+    }
+
+    audit::record_change(conn, actor, table, &key_ref, current)
+}
+
+struct JournalEntry {
+    table: String,
+    key: Value,
+    before: Option<Value>,
+    after: Option<Value>,
+}
+
+fn parse_json(text: Option<String>) -> Result<Option<Value>, String> {
+    text.map(|t| serde_json::from_str(&t).map_err(|e| format!("Corrupted journal entry: {}", e)))
+        .transpose()
+}
+
+fn load_entries(conn: &Connection, operation_id: i64) -> Result<Vec<JournalEntry>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT entity_type, key_json, before_json, after_json FROM operation_journal_entry
+            WHERE operation_id = ?1 ORDER BY seq",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([operation_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+    let mut entries = Vec::new();
+    for row in rows {
+        let (table, key, before, after) = row.map_err(|e| e.to_string())?;
+        entries.push(JournalEntry {
+            table,
+            key: serde_json::from_str(&key).map_err(|e| format!("Corrupted journal entry: {}", e))?,
+            before: parse_json(before)?,
+            after: parse_json(after)?,
+        });
+    }
+    Ok(entries)
+}
+
+fn find_operation(conn: &Connection, token: &str, undone: bool) -> Result<Option<OperationSummary>, String> {
+    let order = if undone { "ASC" } else { "DESC" };
+    conn.query_row(
+        &format!(
+            "SELECT o.id, o.label, o.created_at, o.undone,
+                (SELECT COUNT(*) FROM operation_journal_entry e WHERE e.operation_id = o.id)
+            FROM operation_journal o WHERE o.session_token = ?1 AND o.undone = ?2
+            ORDER BY o.id {} LIMIT 1",
+            order
+        ),
+        params![token, undone],
+        map_summary,
+    )
+    .optional()
+    .map_err(|e| format!("Failed to read journal: {}", e))
+}
+
+fn map_summary(row: &rusqlite::Row) -> Result<OperationSummary> {
+    Ok(OperationSummary {
+        id: row.get(0)?,
+        label: row.get(1)?,
+        created_at: row.get(2)?,
+        undone: row.get(3)?,
+        rows: row.get(4)?,
+    })
+}
+
+fn replay(conn: &Connection, actor: &Option<CurrentUser>, undo: bool) -> Result<Option<OperationSummary>, String> {
+    let token = session_token(actor);
+
+    // Undo walks back from the latest operation, redo forward from the oldest undone one.
+    let Some(mut operation) = find_operation(conn, &token, !undo)? else {
+        return Ok(None);
+    };
+    let mut entries = load_entries(conn, operation.id)?;
+    if undo {
+        entries.reverse();
+    }
+    for entry in &entries {
+        let (expected, target) = if undo {
+            (&entry.after, &entry.before)
+        } else {
+            (&entry.before, &entry.after)
+        };
+        apply_row(conn, actor, &entry.table, &entry.key, expected, target).map_err(|e| {
+            format!("Cannot {} \"{}\": {}", if undo { "undo" } else { "redo" }, operation.label, e)
+        })?;
+    }
+
+    conn.execute("UPDATE operation_journal SET undone = ?1 WHERE id = ?2", params![undo, operation.id])
+        .map_err(|e| format!("Failed to update journal: {}", e))?;
+    operation.undone = undo;
+    Ok(Some(operation))
+}
+
+/// Reverts the latest operation of the current user session; `None` when there is nothing to undo.
+pub fn undo(conn: &Connection, actor: &Option<CurrentUser>) -> Result<Option<OperationSummary>, String> {
+    replay(conn, actor, true)
+}
+
+pub fn redo(conn: &Connection, actor: &Option<CurrentUser>) -> Result<Option<OperationSummary>, String> {
+    replay(conn, actor, false)
+}
+
+pub fn get_operation_history(conn: &Connection, actor: &Option<CurrentUser>) -> Result<Vec<OperationSummary>, String> {
+    let token = session_token(actor);
+    let mut stmt = conn
+        .prepare(
+            "SELECT o.id, o.label, o.created_at, o.undone,
+                (SELECT COUNT(*) FROM operation_journal_entry e WHERE e.operation_id = o.id)
+            FROM operation_journal o WHERE o.session_token = ?1 ORDER BY o.id DESC",
+        )
+        .map_err(|e| format!("Failed to prepare journal query: {}", e))?;
+    let rows = stmt
+        .query_map([token], map_summary)
+        .map_err(|e| format!("Failed to query journal: {}", e))?;
+    let result: Result<Vec<OperationSummary>, _> = rows.collect();
+    result.map_err(|e| format!("Failed to collect journal: {}", e))
+}
@@ -1,67 +1,73 @@
-use crate::AppState;
-use rusqlite::{params, Result};
+use crate::models::audit;
+use crate::models::journal::Operation;
+use crate::models::session::resolve_session;
+use crate::query::{ListQuery, Page, QueryOptions};
+use crate::models::user::CurrentUser;
+use rusqlite::{Connection, params, Result};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tauri::State;
 //use rand::random;
 
 
-#[derive(Serialize, Deserialize, Clone, Debug)] // Added Clone and Debug
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)] // Added Clone and Debug
 pub struct Jury {
     pub id: i32,
     pub firstname: String,
     pub lastname: String,
     pub email: String,
+    pub deleted_at: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 pub struct NewJury {
     pub firstname: String,
     pub lastname: String,
     pub email: String,
 }
 
-#[tauri::command]
-pub fn create_jury(state: State<AppState>, jury: NewJury) -> Result<i32, String> {
-    let conn = state.db.lock().unwrap();
-    let id = rand::random::<i32>();
-
+pub fn create_jury(conn: &Connection, actor: &Option<CurrentUser>, jury: NewJury) -> Result<i32, String> {
     conn.execute(
         "INSERT INTO jury (firstname, lastname, email) VALUES (?1, ?2, ?3)",
         (&jury.firstname, &jury.lastname, &jury.email),
     )
     .map_err(|e| format!("BACKEND: Failed to create jury: {}", e.to_string()))?;
 
+    let id = conn.last_insert_rowid() as i32;
+    audit::record_change(conn, actor, "jury", &[("id", id.into())], None)?;
+
     Ok(id)
 }
 
-#[tauri::command]
-pub fn get_all_jury(state: State<AppState>) -> Result<Vec<Jury>, String> {
-    let conn = state.db.lock().unwrap();
-    let mut stmt = conn
-        .prepare("SELECT id, firstname, lastname, email FROM jury")
-        .map_err(|e| e.to_string())?;
-    
-    let jury_iter = stmt.query_map([], |row| {
-        Ok(Jury {
-            id: row.get(0)?,
-            firstname: row.get(1)?,
-            lastname: row.get(2)?,
-            email: row.get(3)?,
-        })
-    })
-    .map_err(|e: rusqlite::Error| e.to_string())?;
-
-    let result: Result<Vec<Jury>, _> = jury_iter.collect();
-    result.map_err(|e: rusqlite::Error| e.to_string())
+pub fn get_all_jury(conn: &Connection, options: Option<QueryOptions>) -> Result<Page<Jury>, String> {
+    let options = options.unwrap_or_default();
+    options.ensure_supported("jury", &[])?;
+
+    let mut query = ListQuery::new("FROM jury");
+    query
+        .filter("deleted_at IS NULL", vec![])
+        .text(&options.text, &["firstname", "lastname", "email"]);
+    query.fetch(
+        conn,
+        "SELECT id, firstname, lastname, email, deleted_at",
+        &options,
+        &[("lastname", "lastname, firstname"), ("firstname", "firstname"), ("email", "email"), ("id", "id")],
+        |row| {
+            Ok(Jury {
+                id: row.get(0)?,
+                firstname: row.get(1)?,
+                lastname: row.get(2)?,
+                email: row.get(3)?,
+                deleted_at: row.get(4)?,
+            })
+        },
+    )
 }
 
 
-#[tauri::command]
-pub fn get_jury(id: i32, state: State<AppState>) -> Result<Jury, String> {
-    let conn = state.db.lock().unwrap();
+pub fn get_jury(conn: &Connection, id: i32) -> Result<Jury, String> {
     let jury = conn
         .query_row(
-            "SELECT id, firstname, lastname, mail FROM jury WHERE id = ?1",
+            "SELECT id, firstname, lastname, email, deleted_at FROM jury WHERE id = ?1",
             [id],
             |row| {
                 Ok(Jury {
@@ -69,6 +75,7 @@ pub fn get_jury(id: i32, state: State<AppState>) -> Result<Jury, String> {
                     firstname: row.get(1)?,
                     lastname: row.get(2)?,
                     email: row.get(3)?,
+                    deleted_at: row.get(4)?,
                 })
             },
         )
@@ -76,14 +83,7 @@ pub fn get_jury(id: i32, state: State<AppState>) -> Result<Jury, String> {
     Ok(jury)
 }
 
-#[tauri::command]
-pub fn update_jury(
-    id: i32,
-    jury: NewJury,
-    state: State<AppState>,
-) -> Result<String, String> {
-    let conn = state.db.lock().map_err(|e| e.to_string())?;
-    
+pub fn update_jury(conn: &Connection, actor: &Option<CurrentUser>, id: i32, jury: NewJury) -> Result<String, String> {
     // Check if email already exists for a different jury
     let existing_jury = conn
         .query_row(
@@ -98,6 +98,7 @@ pub fn update_jury(
         Err(e) => return Err(format!("Database error: {}", e.to_string())),
     }
     
+    let before = audit::snapshot(conn, "jury", &[("id", id.into())])?;
     conn.execute(
         "UPDATE jury SET firstname = ?1, lastname = ?2, email = ?3 WHERE id = ?4",
         params![
@@ -108,13 +109,11 @@ pub fn update_jury(
         ],
     )
     .map_err(|e| format!("BACKEND: Failed to update jury: {}", e.to_string()))?;
+    audit::record_change(conn, actor, "jury", &[("id", id.into())], before)?;
     Ok("Jury updated successfully".to_string())
 }
 
-#[tauri::command]
-pub fn delete_jury(ids: Vec<i32>, state: State<AppState>) -> Result<(), String> {
-    let conn = state.db.lock().map_err(|e| e.to_string())?;
-
+pub fn delete_jury(conn: &Connection, actor: &Option<CurrentUser>, ids: Vec<i32>) -> Result<(), String> {
     // If the input array is empty, return early with success
     if ids.is_empty() {
         return Ok(());
@@ -122,28 +121,43 @@ pub fn delete_jury(ids: Vec<i32>, state: State<AppState>) -> Result<(), String>
 
     // Create a parameterized IN clause, e.g., "(?, ?, ?)"
     let placeholders = ids.iter().map(|_| "?").collect::<Vec<_>>().join(", ");
-    let query = format!("DELETE FROM jury WHERE id IN ({})", placeholders);
+    // Archived rows stay referenced by past soutenances; see `archive` for restore and purge.
+    let query = format!(
+        "UPDATE jury SET deleted_at = datetime('now') WHERE deleted_at IS NULL AND id IN ({})",
+        placeholders
+    );
 
     // Convert Vec<i32> to Vec<&dyn rusqlite::ToSql> for params
     let params: Vec<&dyn rusqlite::ToSql> = ids.iter().map(|id| id as &dyn rusqlite::ToSql).collect();
 
+    let mut operation = Operation::new("delete_jury");
+    for id in &ids {
+        operation.track(conn, "jury", &[("id", (*id).into())])?;
+    }
+
     conn.execute(&query, &params[..])
         .map_err(|e| format!("BACKEND: Failed to delete jury: {}", e.to_string()))?;
 
+    operation.finish(conn, actor)?;
+
     Ok(())
 }
 
-#[tauri::command]
 pub fn get_jury_soutenances(
+    conn: &Connection,
     jury_id: i32,
-    state: State<AppState>,
+    session_id: Option<i32>,
 ) -> Result<Vec<(i32, String)>, String> {
-    let conn = state.db.lock().unwrap();
+    let session_id = resolve_session(conn, session_id)?;
     let mut stmt = conn
-        .prepare("SELECT soutenance_id, role FROM jury_soutenance WHERE jury_id = ?1")
+        .prepare(
+            "SELECT js.soutenance_id, js.role FROM jury_soutenance js
+            JOIN soutenance s ON s.id = js.soutenance_id
+            WHERE js.jury_id = ?1 AND (?2 IS NULL OR s.session_id = ?2)",
+        )
         .map_err(|e| e.to_string())?;
     let rows = stmt
-        .query_map([jury_id], |row| Ok((row.get(0)?, row.get(1)?)))
+        .query_map(params![jury_id, session_id], |row| Ok((row.get(0)?, row.get(1)?)))
         .map_err(|e| e.to_string())?;
     let result: Result<Vec<(i32, String)>, _> = rows.collect();
     result.map_err(|e| e.to_string())
@@ -1,77 +1,205 @@
-use crate::AppState;
-use rusqlite::Result;
+use crate::models::audit;
+use crate::models::composition::ensure_attachable;
+use crate::models::conflict::ensure_no_conflict;
+use crate::models::journal::Operation;
+use crate::models::notification::{self, RecipientKind};
+use crate::models::session::ensure_soutenance_open;
+use crate::models::workload::ensure_within_caps;
+use crate::models::user::CurrentUser;
+use rusqlite::{Connection, OptionalExtension, Result};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tauri::State;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JuryRole {
+    President,
+    Rapporteur,
+    Examinateur,
+    EncadrantAcademique,
+    EncadrantProfessionnel,
+    InviteHonneur,
+}
+
+impl JuryRole {
+    pub const ALL: [JuryRole; 6] = [
+        JuryRole::President,
+        JuryRole::Rapporteur,
+        JuryRole::Examinateur,
+        JuryRole::EncadrantAcademique,
+        JuryRole::EncadrantProfessionnel,
+        JuryRole::InviteHonneur,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JuryRole::President => "president",
+            JuryRole::Rapporteur => "rapporteur",
+            JuryRole::Examinateur => "examinateur",
+            JuryRole::EncadrantAcademique => "encadrant_academique",
+            JuryRole::EncadrantProfessionnel => "encadrant_professionnel",
+            JuryRole::InviteHonneur => "invite_honneur",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            JuryRole::President => "Président",
+            JuryRole::Rapporteur => "Rapporteur",
+            JuryRole::Examinateur => "Examinateur",
+            JuryRole::EncadrantAcademique => "Encadrant académique",
+            JuryRole::EncadrantProfessionnel => "Encadrant professionnel",
+            JuryRole::InviteHonneur => "Invité d'honneur",
+        }
+    }
+
+    /// Accepts the stored values and their French labels. `member`, which older versions
+    /// sent for everyone, means examinateur.
+    pub fn parse(value: &str) -> Result<JuryRole, String> {
+        let normalized = value.trim().to_lowercase().replace(['é', 'è'], "e").replace([' ', '\''], "_");
+        match normalized.as_str() {
+            "president" => Ok(JuryRole::President),
+            "rapporteur" => Ok(JuryRole::Rapporteur),
+            "examinateur" | "member" => Ok(JuryRole::Examinateur),
+            "encadrant_academique" => Ok(JuryRole::EncadrantAcademique),
+            "encadrant_professionnel" => Ok(JuryRole::EncadrantProfessionnel),
+            "invite_honneur" | "invite_d_honneur" => Ok(JuryRole::InviteHonneur),
+            _ => Err(format!(
+                "Unknown jury role: {} (expected one of {})",
+                value,
+                JuryRole::ALL.map(|role| role.as_str()).join(", ")
+            )),
+        }
+    }
+
+    /// Guests of honour attend without sitting on the jury.
+    pub fn is_member(&self) -> bool {
+        *self != JuryRole::InviteHonneur
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct JuryRoleOption {
+    pub value: JuryRole,
+    pub label: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct JurySoutenance {
     pub jury_id: i32,
     pub soutenance_id: i32,
-    pub role: String,
+    pub role: JuryRole,
+}
+
+/// The role of a jury member on a soutenance, `None` when they are not on it.
+pub fn jury_role(conn: &Connection, soutenance_id: i32, jury_id: i32) -> Result<Option<JuryRole>, String> {
+    conn.query_row(
+        "SELECT role FROM jury_soutenance WHERE jury_id = ?1 AND soutenance_id = ?2",
+        [jury_id, soutenance_id],
+        |row| row.get::<_, String>(0),
+    )
+    .optional()
+    .map_err(|e| e.to_string())?
+    .map(|role| JuryRole::parse(&role))
+    .transpose()
 }
 
-#[tauri::command]
+pub fn get_jury_roles() -> Vec<JuryRoleOption> {
+    JuryRole::ALL
+        .iter()
+        .map(|role| JuryRoleOption {
+            value: *role,
+            label: role.label().to_string(),
+        })
+        .collect()
+}
+
+/// Attaches a jury member to a soutenance. An assignment that goes against a declared
+/// conflict of interest is refused unless `justification` explains why it is needed.
 pub fn create_jury_soutenance(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
     jury_id: i32,
     soutenance_id: i32,
     role: String,
-    state: State<AppState>,
+    justification: Option<String>,
 ) -> Result<(), String> {
-    let conn = state.db.lock().unwrap();
+    let role = JuryRole::parse(&role)?;
+    ensure_soutenance_open(conn, soutenance_id)?;
+    let key = [("jury_id", jury_id.into()), ("soutenance_id", soutenance_id.into())];
+    let before = audit::snapshot(conn, "jury_soutenance", &key)?;
     conn.execute(
         "INSERT INTO jury_soutenance (jury_id, soutenance_id, role) VALUES (?1, ?2, ?3)",
         rusqlite::params![jury_id, soutenance_id, role.as_str()], // Use params! and as_str()
     )
     .map_err(|e| e.to_string())?;
+    ensure_attachable(conn, soutenance_id)?;
+    ensure_within_caps(conn, jury_id, soutenance_id)?;
+    ensure_no_conflict(conn, actor, jury_id, soutenance_id, justification.as_deref())?;
+    audit::record_change(conn, actor, "jury_soutenance", &key, before)?;
+    notification::assigned(conn, soutenance_id, (RecipientKind::Jury, jury_id), Some(role.as_str()))?;
     Ok(())
 }
 
-#[tauri::command]
-pub fn get_jury_soutenance(
-    jury_id: i32,
-    soutenance_id: i32,
-    state: State<AppState>,
-) -> Result<JurySoutenance, String> {
-    let conn = state.db.lock().unwrap();
+pub fn get_jury_soutenance(conn: &Connection, jury_id: i32, soutenance_id: i32) -> Result<JurySoutenance, String> {
     let pivot = conn.query_row(
         "SELECT jury_id, soutenance_id, role FROM jury_soutenance WHERE jury_id = ?1 AND soutenance_id = ?2",
         [jury_id, soutenance_id],
-        |row| Ok(JurySoutenance {
-            jury_id: row.get(0)?,
-            soutenance_id: row.get(1)?,
-            role: row.get(2)?,
-        }),
+        |row| Ok((row.get(0)?, row.get(1)?, row.get::<_, String>(2)?)),
     ).map_err(|e| e.to_string())?;
-    Ok(pivot)
+    Ok(JurySoutenance {
+        jury_id: pivot.0,
+        soutenance_id: pivot.1,
+        role: JuryRole::parse(&pivot.2)?,
+    })
 }
 
-#[tauri::command]
 pub fn update_jury_soutenance(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
     jury_id: i32,
     soutenance_id: i32,
     role: String,
-    state: State<AppState>,
 ) -> Result<(), String> {
-    let conn = state.db.lock().unwrap();
+    let role = JuryRole::parse(&role)?;
+    ensure_soutenance_open(conn, soutenance_id)?;
+    let key = [("jury_id", jury_id.into()), ("soutenance_id", soutenance_id.into())];
+    let before = audit::snapshot(conn, "jury_soutenance", &key)?;
+    let previous: String = conn
+        .query_row(
+            "SELECT role FROM jury_soutenance WHERE jury_id = ?1 AND soutenance_id = ?2",
+            [jury_id, soutenance_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
     conn.execute(
         "UPDATE jury_soutenance SET role = ?1 WHERE jury_id = ?2 AND soutenance_id = ?3",
-        rusqlite::params![&role, &jury_id.to_string(), &soutenance_id.to_string()],
+        rusqlite::params![role.as_str(), &jury_id.to_string(), &soutenance_id.to_string()],
     )
     .map_err(|e| e.to_string())?;
+    ensure_attachable(conn, soutenance_id)?;
+    ensure_within_caps(conn, jury_id, soutenance_id)?;
+    audit::record_change(conn, actor, "jury_soutenance", &key, before)?;
+    notification::role_changed(conn, soutenance_id, jury_id, JuryRole::parse(&previous)?, role)?;
     Ok(())
 }
 
-#[tauri::command]
 pub fn delete_jury_soutenance(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
     jury_id: i32,
     soutenance_id: i32,
-    state: State<AppState>,
 ) -> Result<(), String> {
-    let conn = state.db.lock().unwrap();
+    ensure_soutenance_open(conn, soutenance_id)?;
+    let key = [("jury_id", jury_id.into()), ("soutenance_id", soutenance_id.into())];
+    let mut operation = Operation::new("delete_jury_soutenance");
+    operation.track(conn, "jury_soutenance", &key)?;
     conn.execute(
         "DELETE FROM jury_soutenance WHERE jury_id = ?1 AND soutenance_id = ?2",
         [jury_id, soutenance_id],
     )
     .map_err(|e| e.to_string())?;
+    notification::unassigned(conn, soutenance_id, (RecipientKind::Jury, jury_id))?;
+    operation.finish(conn, actor)?;
     Ok(())
 }
@@ -1,10 +1,28 @@
+pub mod archive;
+pub mod audit;
+pub mod badge;
+pub mod composition;
+pub mod conflict;
+pub mod demo;
 pub mod etudiant;
+pub mod expertise;
+pub mod honoraria;
 pub mod invite;
+pub mod journal;
 pub mod jury;
 pub mod jury_soutenance;
 pub mod invite_soutenance;
 pub mod pfe;
 pub mod classroom;
+pub mod notification;
+pub mod occupancy;
+pub mod outbox;
+pub mod reminder;
+pub mod search;
 pub mod soutenance;
+pub mod session;
+pub mod settings;
 pub mod specialite;
+pub mod template;
 pub mod user;
+pub mod workload;
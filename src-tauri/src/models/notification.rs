@@ -0,0 +1,522 @@
+use crate::models::audit;
+use crate::models::jury_soutenance::{jury_role, JuryRole};
+use crate::models::outbox::{self, NotificationPreview};
+use crate::models::settings::get_setting;
+use crate::models::template::{self, ChangeKind, Language, Rendered, TemplateKind};
+use crate::models::user::CurrentUser;
+use rusqlite::{params, Connection, OptionalExtension, Result, Transaction, TransactionBehavior};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+pub const BATCH_WINDOW_KEY: &str = "notification_batch_seconds";
+/// Changes are held this long after the last edit of a soutenance, so editing several
+/// fields in a row sends one message.
+const DEFAULT_BATCH_WINDOW: i64 = 120;
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum RecipientKind {
+    Student,
+    Jury,
+    Invite,
+}
+
+impl RecipientKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RecipientKind::Student => "student",
+            RecipientKind::Jury => "jury",
+            RecipientKind::Invite => "invite",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<RecipientKind, String> {
+        match value {
+            "student" => Ok(RecipientKind::Student),
+            "jury" => Ok(RecipientKind::Jury),
+            "invite" => Ok(RecipientKind::Invite),
+            other => Err(format!("Unknown recipient kind: {}", other)),
+        }
+    }
+
+    fn table(&self) -> &'static str {
+        match self {
+            RecipientKind::Student => "etudiant",
+            RecipientKind::Jury => "jury",
+            RecipientKind::Invite => "invite",
+        }
+    }
+}
+
+/// What the people attending a soutenance are told about it. Stored with each change,
+/// since a cancelled soutenance is gone by the time its message is written.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+pub struct Schedule {
+    pub date: Option<String>,
+    pub hour: Option<String>,
+    pub classroom: Option<String>,
+    pub pfe: Option<String>,
+    #[serde(default)]
+    pub specialite_id: Option<i32>,
+}
+
+impl Schedule {
+    fn fields(&self) -> [(&'static str, &Option<String>); 4] {
+        [("date", &self.date), ("hour", &self.hour), ("classroom", &self.classroom), ("pfe", &self.pfe)]
+    }
+}
+
+/// Who a message is addressed to.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct Contact {
+    pub id: i32,
+    pub firstname: String,
+    pub lastname: String,
+    pub email: String,
+}
+
+/// A change waiting for the batch window to close.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct PendingNotification {
+    pub id: i32,
+    pub soutenance_id: i32,
+    pub recipient_kind: RecipientKind,
+    pub recipient_id: i32,
+    pub field: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub context: String,
+    pub due_at: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct NotificationPreference {
+    pub recipient_kind: RecipientKind,
+    pub recipient_id: i32,
+    /// No message is sent to muted recipients; their changes are dropped.
+    pub muted: bool,
+}
+
+const PENDING_SELECT: &str = "SELECT id, soutenance_id, recipient_kind, recipient_id, field, old_value, new_value, context,
+    due_at FROM pending_notification";
+
+fn map_pending(row: &rusqlite::Row) -> Result<PendingNotification> {
+    let kind: String = row.get(2)?;
+    Ok(PendingNotification {
+        id: row.get(0)?,
+        soutenance_id: row.get(1)?,
+        recipient_kind: RecipientKind::parse(&kind).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, e.into())
+        })?,
+        recipient_id: row.get(3)?,
+        field: row.get(4)?,
+        old_value: row.get(5)?,
+        new_value: row.get(6)?,
+        context: row.get(7)?,
+        due_at: row.get(8)?,
+    })
+}
+
+pub fn find_schedule(conn: &Connection, soutenance_id: i32) -> Result<Schedule, String> {
+    conn.query_row(
+        "SELECT s.date, s.hour, c.name, s.pfe, s.specialite_id FROM soutenance s
+        LEFT JOIN classroom c ON c.id = s.classroom_id WHERE s.id = ?1",
+        [soutenance_id],
+        |row| {
+            Ok(Schedule {
+                date: row.get(0)?,
+                hour: row.get(1)?,
+                classroom: row.get(2)?,
+                pfe: row.get(3)?,
+                specialite_id: row.get(4)?,
+            })
+        },
+    )
+    .map_err(|e| format!("Soutenance {} not found: {}", soutenance_id, e))
+}
+
+/// Students, jury members and invitees who have not declined.
+fn participants(conn: &Connection, soutenance_id: i32) -> Result<Vec<(RecipientKind, i32)>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT 'student', id FROM etudiant WHERE soutenance_id = ?1 AND deleted_at IS NULL
+            UNION ALL SELECT 'jury', jury_id FROM jury_soutenance WHERE soutenance_id = ?1
+            UNION ALL SELECT 'invite', invite_id FROM invite_soutenance WHERE soutenance_id = ?1 AND status != 'declined'",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows: Vec<(String, i32)> = stmt
+        .query_map([soutenance_id], |row| Ok((row.get(0)?, row.get(1)?)))
+        .and_then(|rows| rows.collect())
+        .map_err(|e| e.to_string())?;
+    rows.into_iter()
+        .map(|(kind, id)| Ok((RecipientKind::parse(&kind)?, id)))
+        .collect()
+}
+
+fn batch_window(conn: &Connection) -> Result<i64, String> {
+    Ok(get_setting(conn, BATCH_WINDOW_KEY)?
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_BATCH_WINDOW))
+}
+
+struct Change<'a> {
+    field: &'a str,
+    old: Option<&'a str>,
+    new: Option<&'a str>,
+}
+
+fn snapshot(schedule: &Schedule) -> Result<String, String> {
+    serde_json::to_string(schedule).map_err(|e| e.to_string())
+}
+
+/// Stores a change and pushes back the batch of its soutenance.
+fn record(
+    conn: &Connection,
+    soutenance_id: i32,
+    recipient: (RecipientKind, i32),
+    change: &Change,
+    context: &str,
+) -> Result<(), String> {
+    let delay = format!("+{} seconds", batch_window(conn)?.max(0));
+    conn.execute(
+        "INSERT INTO pending_notification (soutenance_id, recipient_kind, recipient_id, field, old_value, new_value,
+        context, due_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, datetime('now', ?8))",
+        params![soutenance_id, recipient.0.as_str(), recipient.1, change.field, change.old, change.new, context, delay],
+    )
+    .map_err(|e| format!("Failed to record change: {}", e))?;
+    conn.execute(
+        "UPDATE pending_notification SET due_at = datetime('now', ?2) WHERE soutenance_id = ?1",
+        params![soutenance_id, delay],
+    )
+    .map_err(|e| format!("Failed to record change: {}", e))?;
+    Ok(())
+}
+
+/// Records, for everyone attending, each field that differs from `before`.
+pub fn schedule_changed(conn: &Connection, soutenance_id: i32, before: &Schedule) -> Result<(), String> {
+    let after = find_schedule(conn, soutenance_id)?;
+    if after == *before {
+        return Ok(());
+    }
+    let context = snapshot(&after)?;
+    let recipients = participants(conn, soutenance_id)?;
+    for ((field, old), (_, new)) in before.fields().into_iter().zip(after.fields()) {
+        if old == new {
+            continue;
+        }
+        let change = Change { field, old: old.as_deref(), new: new.as_deref() };
+        for recipient in &recipients {
+            record(conn, soutenance_id, *recipient, &change, &context)?;
+        }
+    }
+    Ok(())
+}
+
+/// Tells everyone attending that the soutenance is cancelled. Call before the
+/// assignments are removed.
+pub fn cancelled(conn: &Connection, soutenance_id: i32) -> Result<(), String> {
+    let context = snapshot(&find_schedule(conn, soutenance_id)?)?;
+    let change = Change { field: "cancelled", old: None, new: Some("cancelled") };
+    for recipient in participants(conn, soutenance_id)? {
+        record(conn, soutenance_id, recipient, &change, &context)?;
+    }
+    Ok(())
+}
+
+pub fn assigned(
+    conn: &Connection,
+    soutenance_id: i32,
+    recipient: (RecipientKind, i32),
+    role: Option<&str>,
+) -> Result<(), String> {
+    let context = snapshot(&find_schedule(conn, soutenance_id)?)?;
+    record(conn, soutenance_id, recipient, &Change { field: "assignment", old: None, new: Some("assigned") }, &context)?;
+    if let Some(role) = role {
+        record(conn, soutenance_id, recipient, &Change { field: "role", old: None, new: Some(role) }, &context)?;
+    }
+    Ok(())
+}
+
+pub fn unassigned(conn: &Connection, soutenance_id: i32, recipient: (RecipientKind, i32)) -> Result<(), String> {
+    let context = snapshot(&find_schedule(conn, soutenance_id)?)?;
+    record(conn, soutenance_id, recipient, &Change { field: "assignment", old: Some("assigned"), new: None }, &context)
+}
+
+pub fn role_changed(conn: &Connection, soutenance_id: i32, jury_id: i32, old: JuryRole, new: JuryRole) -> Result<(), String> {
+    if old == new {
+        return Ok(());
+    }
+    let context = snapshot(&find_schedule(conn, soutenance_id)?)?;
+    let change = Change { field: "role", old: Some(old.as_str()), new: Some(new.as_str()) };
+    record(conn, soutenance_id, (RecipientKind::Jury, jury_id), &change, &context)
+}
+
+/// What a recipient's accumulated changes amount to.
+struct Summary {
+    kind: ChangeKind,
+    /// Fields whose value differs between before the batch and after it.
+    changes: Vec<(&'static str, Option<String>, Option<String>)>,
+    role: Option<String>,
+}
+
+/// Folds the changes a recipient accumulated, keeping for each field the first old
+/// value and the last new one. `None` when they cancel out.
+fn summarize(changes: &[PendingNotification]) -> Option<Summary> {
+    let first_value = |field: &str| changes.iter().find(|change| change.field == field).map(|change| &change.old_value);
+    let last_value = |field: &str| changes.iter().rev().find(|change| change.field == field).map(|change| &change.new_value);
+    let joined = matches!(first_value("assignment"), Some(None));
+    let role = last_value("role").cloned().flatten();
+
+    if changes.iter().any(|change| change.field == "cancelled") {
+        // Someone added during the batch never heard of the soutenance in the first place.
+        if joined {
+            return None;
+        }
+        return Some(Summary { kind: ChangeKind::Cancelled, changes: Vec::new(), role });
+    }
+    if let (Some(was), Some(now)) = (first_value("assignment"), last_value("assignment")) {
+        match (was, now) {
+            (None, None) => return None,
+            (Some(_), None) => return Some(Summary { kind: ChangeKind::Removed, changes: Vec::new(), role }),
+            (None, Some(_)) => return Some(Summary { kind: ChangeKind::Added, changes: Vec::new(), role }),
+            (Some(_), Some(_)) => {}
+        }
+    }
+
+    let mut lines = Vec::new();
+    for field in ["date", "hour", "classroom", "pfe", "role"] {
+        let (Some(was), Some(now)) = (first_value(field), last_value(field)) else {
+            continue;
+        };
+        if was != now {
+            lines.push((field, was.clone(), now.clone()));
+        }
+    }
+    if lines.is_empty() {
+        return None;
+    }
+    Some(Summary { kind: ChangeKind::Modified, changes: lines, role })
+}
+
+/// Role codes are shown in the recipient's language; anything else as recorded.
+fn display_role(value: Option<String>, language: Language) -> Option<String> {
+    value.map(|value| match JuryRole::parse(&value) {
+        Ok(role) => template::role_label(role, language).to_string(),
+        Err(_) => value,
+    })
+}
+
+/// Renders the change template for one recipient's batch.
+fn change_message(
+    conn: &Connection,
+    batch: &[PendingNotification],
+    summary: Summary,
+    contact: &Contact,
+) -> Result<Option<Rendered>, String> {
+    let first = &batch[0];
+    let schedule = match serde_json::from_str::<Schedule>(&batch[batch.len() - 1].context) {
+        Ok(schedule) => schedule,
+        // Changes recorded before schedules were stored with them.
+        Err(_) => match find_schedule(conn, first.soutenance_id) {
+            Ok(schedule) => schedule,
+            Err(_) => return Ok(None),
+        },
+    };
+    let language = template::language(conn)?;
+    let role = match summary.role {
+        Some(role) => Some(role),
+        None if first.recipient_kind == RecipientKind::Jury => {
+            jury_role(conn, first.soutenance_id, first.recipient_id)?.map(|role| role.as_str().to_string())
+        }
+        None => None,
+    };
+    let changes = summary
+        .changes
+        .into_iter()
+        .map(|(field, old, new)| {
+            let (old, new) = if field == "role" {
+                (display_role(old, language), display_role(new, language))
+            } else {
+                (old, new)
+            };
+            json!({ "field": field, "label": template::field_label(field, language), "old": old, "new": new })
+        })
+        .collect();
+    let specialite = match schedule.specialite_id {
+        Some(id) => template::specialite_name(conn, id)?,
+        None => None,
+    };
+    let context = template::with_recipient(
+        template::change_context(first.soutenance_id, &schedule, specialite, summary.kind, changes),
+        serde_json::to_value(contact).map_err(|e| e.to_string())?,
+        display_role(role, language).as_deref(),
+        None,
+    );
+    template::render(conn, TemplateKind::SoutenanceChange, schedule.specialite_id, &context).map(Some)
+}
+
+pub fn is_muted(conn: &Connection, recipient: (RecipientKind, i32)) -> Result<bool, String> {
+    let muted = conn
+        .query_row(
+            "SELECT muted FROM notification_preference WHERE recipient_kind = ?1 AND recipient_id = ?2",
+            params![recipient.0.as_str(), recipient.1],
+            |row| row.get::<_, bool>(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    Ok(muted.unwrap_or(false))
+}
+
+/// A live recipient who has an email address.
+pub fn contact(conn: &Connection, recipient: (RecipientKind, i32)) -> Result<Option<Contact>, String> {
+    let contact: Option<(String, String, Option<String>)> = conn
+        .query_row(
+            &format!("SELECT firstname, lastname, email FROM {} WHERE id = ?1 AND deleted_at IS NULL", recipient.0.table()),
+            [recipient.1],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    Ok(contact.and_then(|(firstname, lastname, email)| {
+        Some(Contact {
+            id: recipient.1,
+            firstname,
+            lastname,
+            email: email.filter(|email| !email.trim().is_empty())?,
+        })
+    }))
+}
+
+/// Turns the batches whose window has closed (or every batch, with `all`) into outbox
+/// messages, one per recipient. Returns the number of messages queued.
+pub fn flush_pending(conn: &Connection, all: bool) -> Result<usize, String> {
+    // Immediate, so the worker waits for the app's writes instead of failing on upgrade.
+    let tx = Transaction::new_unchecked(conn, TransactionBehavior::Immediate).map_err(|e| e.to_string())?;
+    let mut stmt = tx
+        .prepare(&format!(
+            "{} WHERE soutenance_id IN (SELECT soutenance_id FROM pending_notification
+                WHERE ?1 OR due_at <= datetime('now'))
+            ORDER BY soutenance_id, recipient_kind, recipient_id, id",
+            PENDING_SELECT
+        ))
+        .map_err(|e| e.to_string())?;
+    let pending: Vec<PendingNotification> = stmt
+        .query_map([all], map_pending)
+        .and_then(|rows| rows.collect())
+        .map_err(|e| e.to_string())?;
+    drop(stmt);
+
+    let mut queued = 0;
+    for batch in pending.chunk_by(|a, b| {
+        (a.soutenance_id, a.recipient_kind, a.recipient_id) == (b.soutenance_id, b.recipient_kind, b.recipient_id)
+    }) {
+        let first = &batch[0];
+        let recipient = (first.recipient_kind, first.recipient_id);
+        for change in batch {
+            tx.execute("DELETE FROM pending_notification WHERE id = ?1", [change.id])
+                .map_err(|e| e.to_string())?;
+        }
+        if is_muted(&tx, recipient)? {
+            continue;
+        }
+        let (Some(summary), Some(contact)) = (summarize(batch), contact(&tx, recipient)?) else {
+            continue;
+        };
+        let Some(message) = change_message(&tx, batch, summary, &contact)? else {
+            continue;
+        };
+        outbox::queue(
+            &tx,
+            &None,
+            &NotificationPreview {
+                soutenance_id: first.soutenance_id,
+                template: TemplateKind::SoutenanceChange.as_str().to_string(),
+                recipient_kind: recipient.0.as_str().to_string(),
+                recipient_id: recipient.1,
+                email: contact.email,
+                subject: message.subject,
+                body: message.body,
+            },
+        )?;
+        queued += 1;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(queued)
+}
+
+/// Drops the preference of a recipient being purged.
+pub fn purge_preference(conn: &Connection, actor: &Option<CurrentUser>, table: &str, id: i32) -> Result<(), String> {
+    let kind = match table {
+        "etudiant" => RecipientKind::Student,
+        "jury" => RecipientKind::Jury,
+        "invite" => RecipientKind::Invite,
+        _ => return Ok(()),
+    };
+    let key = [("recipient_kind", kind.as_str().to_string().into()), ("recipient_id", id.into())];
+    let Some(before) = audit::snapshot(conn, "notification_preference", &key)? else {
+        return Ok(());
+    };
+    conn.execute(
+        "DELETE FROM notification_preference WHERE recipient_kind = ?1 AND recipient_id = ?2",
+        params![kind.as_str(), id],
+    )
+    .map_err(|e| e.to_string())?;
+    audit::record_change(conn, actor, "notification_preference", &key, Some(before))
+}
+
+pub fn get_pending_notifications(
+    conn: &Connection,
+    soutenance_id: Option<i32>,
+) -> Result<Vec<PendingNotification>, String> {
+    let mut stmt = conn
+        .prepare(&format!("{} WHERE ?1 IS NULL OR soutenance_id = ?1 ORDER BY id", PENDING_SELECT))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([soutenance_id], map_pending).map_err(|e| e.to_string())?;
+    let result: Result<Vec<_>, _> = rows.collect();
+    result.map_err(|e| e.to_string())
+}
+
+/// Queues the pending changes now instead of waiting for their batch window.
+pub fn flush_pending_notifications(conn: &Connection) -> Result<usize, String> {
+    flush_pending(conn, true)
+}
+
+/// Forgets the pending changes of a soutenance, e.g. while it is still a draft.
+pub fn discard_pending_notifications(conn: &Connection, soutenance_id: i32) -> Result<usize, String> {
+    conn.execute("DELETE FROM pending_notification WHERE soutenance_id = ?1", [soutenance_id])
+        .map_err(|e| format!("Failed to discard changes: {}", e))
+}
+
+pub fn get_notification_preference(
+    conn: &Connection,
+    recipient_kind: RecipientKind,
+    recipient_id: i32,
+) -> Result<NotificationPreference, String> {
+    Ok(NotificationPreference {
+        recipient_kind,
+        recipient_id,
+        muted: is_muted(conn, (recipient_kind, recipient_id))?,
+    })
+}
+
+pub fn set_notification_preference(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    recipient_kind: RecipientKind,
+    recipient_id: i32,
+    muted: bool,
+) -> Result<(), String> {
+    conn.query_row(&format!("SELECT 1 FROM {} WHERE id = ?1", recipient_kind.table()), [recipient_id], |_| Ok(()))
+        .map_err(|_| format!("{} {} not found", recipient_kind.table(), recipient_id))?;
+    let key = [("recipient_kind", recipient_kind.as_str().to_string().into()), ("recipient_id", recipient_id.into())];
+    let before = audit::snapshot(conn, "notification_preference", &key)?;
+    conn.execute(
+        "INSERT INTO notification_preference (recipient_kind, recipient_id, muted) VALUES (?1, ?2, ?3)
+        ON CONFLICT (recipient_kind, recipient_id) DO UPDATE SET muted = excluded.muted",
+        params![recipient_kind.as_str(), recipient_id, muted],
+    )
+    .map_err(|e| format!("Failed to save preference: {}", e))?;
+    audit::record_change(conn, actor, "notification_preference", &key, before)?;
+    Ok(())
+}
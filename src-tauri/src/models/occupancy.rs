@@ -0,0 +1,334 @@
+use crate::models::classroom::{find_classroom, format_minutes, opening_hours, parse_minutes, Salle, SALLE_COLUMNS};
+use crate::models::session::resolve_session;
+use crate::models::soutenance::defence_duration;
+use rusqlite::{params, Connection, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Rooms without configured opening hours are counted as open during this window.
+const NOMINAL_OPENS: &str = "08:00";
+const NOMINAL_CLOSES: &str = "18:00";
+const MAX_RANGE_DAYS: i64 = 366;
+
+/// A defence, or a free gap when `soutenance_id` is `None`.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct AgendaBlock {
+    pub start: String,
+    pub end: String,
+    pub soutenance_id: Option<i32>,
+    pub pfe: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct AgendaDay {
+    pub date: String,
+    pub opens: Option<String>,
+    pub closes: Option<String>,
+    /// Set when the room is closed for the whole day.
+    pub closed_reason: Option<String>,
+    pub blocks: Vec<AgendaBlock>,
+    pub open_minutes: i64,
+    /// Booked time within the opening hours; none on a closed day.
+    pub occupied_minutes: i64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct RoomAgenda {
+    pub classroom: Salle,
+    pub days: Vec<AgendaDay>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Default)]
+pub struct Utilisation {
+    pub open_minutes: i64,
+    pub occupied_minutes: i64,
+    /// Occupied over open time; 0 when the room was never open.
+    pub rate: f64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct RoomUtilisation {
+    pub classroom_id: i32,
+    pub name: String,
+    pub defences: i64,
+    #[serde(flatten)]
+    pub usage: Utilisation,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct DayUtilisation {
+    pub date: String,
+    pub rooms_used: i64,
+    /// Most defences running at the same moment, i.e. how many rooms that day really needs.
+    pub peak_rooms: i64,
+    #[serde(flatten)]
+    pub usage: Utilisation,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct UtilisationReport {
+    pub rooms: Vec<RoomUtilisation>,
+    pub days: Vec<DayUtilisation>,
+    #[serde(flatten)]
+    pub total: Utilisation,
+}
+
+struct Booking {
+    start: i32,
+    end: i32,
+    soutenance_id: i32,
+    pfe: Option<String>,
+}
+
+impl Utilisation {
+    fn add(&mut self, open: i64, occupied: i64) {
+        self.open_minutes += open;
+        self.occupied_minutes += occupied;
+        self.rate = if self.open_minutes > 0 {
+            self.occupied_minutes as f64 / self.open_minutes as f64
+        } else {
+            0.0
+        };
+    }
+}
+
+/// Every day from `date_from` to `date_to` inclusive, with its weekday (0 = Sunday).
+fn days_between(conn: &Connection, date_from: &str, date_to: &str) -> Result<Vec<(String, i32)>, String> {
+    let span: Option<i64> = conn
+        .query_row("SELECT CAST(julianday(?2) - julianday(?1) AS INTEGER)", [date_from, date_to], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    match span {
+        None => return Err(format!("Invalid date range {} to {}, expected YYYY-MM-DD", date_from, date_to)),
+        Some(span) if span < 0 => return Err(format!("The range ends ({}) before it starts ({})", date_to, date_from)),
+        Some(span) if span >= MAX_RANGE_DAYS => return Err(format!("The range cannot exceed {} days", MAX_RANGE_DAYS)),
+        Some(_) => {}
+    }
+    let mut stmt = conn
+        .prepare(
+            "WITH RECURSIVE days(d) AS (
+                SELECT date(?1) UNION ALL SELECT date(d, '+1 day') FROM days WHERE d < date(?2)
+            )
+            SELECT d, CAST(strftime('%w', d) AS INTEGER) FROM days",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([date_from, date_to], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?;
+    let result: Result<Vec<(String, i32)>, _> = rows.collect();
+    result.map_err(|e| e.to_string())
+}
+
+fn bookings(
+    conn: &Connection,
+    classroom_id: i32,
+    day: &str,
+    session_id: Option<i32>,
+    duration: i32,
+) -> Result<Vec<Booking>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, hour, pfe FROM soutenance
+            WHERE classroom_id = ?1 AND substr(date, 1, 10) = ?2 AND hour IS NOT NULL AND (?3 IS NULL OR session_id = ?3)",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![classroom_id, day, session_id], |row| {
+            Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<String>>(2)?))
+        })
+        .map_err(|e| e.to_string())?;
+    let mut bookings = Vec::new();
+    for row in rows {
+        let (soutenance_id, hour, pfe) = row.map_err(|e| e.to_string())?;
+        // Defences without a usable time cannot be placed on the agenda.
+        if let Ok(start) = parse_minutes(&hour) {
+            bookings.push(Booking { start, end: start + duration, soutenance_id, pfe });
+        }
+    }
+    bookings.sort_by_key(|booking| booking.start);
+    Ok(bookings)
+}
+
+/// Minutes between `opens` and `closes` covered by at least one of the sorted `bookings`.
+fn occupied_minutes(bookings: &[Booking], (opens, closes): (i32, i32)) -> i64 {
+    let mut total = 0;
+    let mut covered_until = opens;
+    for booking in bookings {
+        let (start, end) = (booking.start.max(covered_until), booking.end.min(closes));
+        if end > start {
+            total += (end - start) as i64;
+        }
+        covered_until = covered_until.max(booking.end);
+    }
+    total
+}
+
+fn room_day(
+    conn: &Connection,
+    classroom_id: i32,
+    day: &str,
+    weekday: i32,
+    session_id: Option<i32>,
+    duration: i32,
+) -> Result<(AgendaDay, Vec<Booking>), String> {
+    let closure: Option<Option<String>> = conn
+        .query_row(
+            "SELECT reason FROM classroom_closure WHERE classroom_id = ?1 AND start_date <= ?2 AND end_date >= ?2",
+            params![classroom_id, day],
+            |row| row.get(0),
+        )
+        .map(Some)
+        .or_else(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => Ok(None),
+            e => Err(e.to_string()),
+        })?;
+
+    let hours = opening_hours(conn, classroom_id)?;
+    let (window, closed_reason) = match closure {
+        Some(reason) => (None, Some(reason.unwrap_or_else(|| "Closed".to_string()))),
+        None if hours.is_empty() => (Some((NOMINAL_OPENS.to_string(), NOMINAL_CLOSES.to_string())), None),
+        None => match hours.into_iter().find(|h| h.weekday == weekday) {
+            Some(h) => (Some((h.opens, h.closes)), None),
+            None => (None, Some("Not open on this weekday".to_string())),
+        },
+    };
+
+    let bookings = bookings(conn, classroom_id, day, session_id, duration)?;
+    let mut blocks = Vec::new();
+    let bounds = match &window {
+        Some((opens, closes)) => Some((parse_minutes(opens)?, parse_minutes(closes)?)),
+        None => None,
+    };
+    let mut cursor = bounds.map(|(opens, _)| opens);
+    for booking in &bookings {
+        if let Some(free_from) = cursor {
+            if free_from < booking.start {
+                blocks.push(AgendaBlock {
+                    start: format_minutes(free_from),
+                    end: format_minutes(booking.start),
+                    soutenance_id: None,
+                    pfe: None,
+                });
+            }
+            cursor = Some(free_from.max(booking.end));
+        }
+        blocks.push(AgendaBlock {
+            start: format_minutes(booking.start),
+            end: format_minutes(booking.end),
+            soutenance_id: Some(booking.soutenance_id),
+            pfe: booking.pfe.clone(),
+        });
+    }
+    if let (Some(free_from), Some((_, closes))) = (cursor, bounds) {
+        if free_from < closes {
+            blocks.push(AgendaBlock {
+                start: format_minutes(free_from),
+                end: format_minutes(closes),
+                soutenance_id: None,
+                pfe: None,
+            });
+        }
+    }
+
+    let agenda = AgendaDay {
+        date: day.to_string(),
+        open_minutes: bounds.map(|(opens, closes)| (closes - opens) as i64).unwrap_or(0),
+        occupied_minutes: bounds.map(|bounds| occupied_minutes(&bookings, bounds)).unwrap_or(0),
+        opens: window.as_ref().map(|(opens, _)| opens.clone()),
+        closes: window.map(|(_, closes)| closes),
+        closed_reason,
+        blocks,
+    };
+    Ok((agenda, bookings))
+}
+
+/// Day-by-day occupancy of a classroom: defences in time order, with the free gaps
+/// between them inside the opening hours.
+pub fn get_classroom_agenda(
+    conn: &Connection,
+    classroom_id: i32,
+    date_from: String,
+    date_to: String,
+    session_id: Option<i32>,
+) -> Result<RoomAgenda, String> {
+    let session_id = resolve_session(conn, session_id)?;
+    let classroom = find_classroom(conn, classroom_id)?;
+    let duration = defence_duration(conn)?;
+    let mut days = Vec::new();
+    for (day, weekday) in days_between(conn, &date_from, &date_to)? {
+        days.push(room_day(conn, classroom_id, &day, weekday, session_id, duration)?.0);
+    }
+    Ok(RoomAgenda { classroom, days })
+}
+
+/// Occupied versus open time per room, per day and overall. Archived rooms are left out
+/// unless asked for by id.
+pub fn get_classroom_utilisation(
+    conn: &Connection,
+    date_from: String,
+    date_to: String,
+    classroom_ids: Option<Vec<i32>>,
+    session_id: Option<i32>,
+) -> Result<UtilisationReport, String> {
+    let session_id = resolve_session(conn, session_id)?;
+    let duration = defence_duration(conn)?;
+    let rooms: Vec<Salle> = match classroom_ids {
+        Some(ids) => ids.into_iter().map(|id| find_classroom(conn, id)).collect::<Result<_, _>>()?,
+        None => {
+            let mut stmt = conn
+                .prepare(&format!("SELECT {} FROM classroom WHERE deleted_at IS NULL ORDER BY name", SALLE_COLUMNS))
+                .map_err(|e| e.to_string())?;
+            let rows = stmt.query_map([], Salle::from_row).map_err(|e| e.to_string())?;
+            let result: Result<Vec<Salle>, _> = rows.collect();
+            result.map_err(|e| e.to_string())?
+        }
+    };
+
+    let days = days_between(conn, &date_from, &date_to)?;
+    let mut report = UtilisationReport {
+        rooms: rooms
+            .iter()
+            .map(|room| RoomUtilisation {
+                classroom_id: room.id,
+                name: room.name.clone(),
+                defences: 0,
+                usage: Utilisation::default(),
+            })
+            .collect(),
+        days: Vec::new(),
+        total: Utilisation::default(),
+    };
+    for (day, weekday) in days {
+        let mut usage = Utilisation::default();
+        let mut rooms_used = 0;
+        // +1 when a defence starts, -1 when it ends; ends sort first so back-to-back
+        // defences do not count as overlapping.
+        let mut events = Vec::new();
+        for (room, room_usage) in rooms.iter().zip(report.rooms.iter_mut()) {
+            let (agenda, bookings) = room_day(conn, room.id, &day, weekday, session_id, duration)?;
+            usage.add(agenda.open_minutes, agenda.occupied_minutes);
+            room_usage.usage.add(agenda.open_minutes, agenda.occupied_minutes);
+            room_usage.defences += bookings.len() as i64;
+            if !bookings.is_empty() {
+                rooms_used += 1;
+            }
+            for booking in &bookings {
+                events.push((booking.start, 1));
+                events.push((booking.end, -1));
+            }
+        }
+        events.sort();
+        let (mut busy, mut peak_rooms) = (0, 0);
+        for (_, change) in events {
+            busy += change;
+            peak_rooms = peak_rooms.max(busy);
+        }
+        report.total.add(usage.open_minutes, usage.occupied_minutes);
+        report.days.push(DayUtilisation {
+            date: day,
+            rooms_used,
+            peak_rooms,
+            usage,
+        });
+    }
+    Ok(report)
+}
@@ -0,0 +1,414 @@
+use crate::mailer::{self, SmtpConfig, SmtpSettings};
+use crate::models::audit;
+use crate::models::settings::get_setting;
+use crate::models::soutenance::Soutenance;
+use crate::models::template::{self, TemplateKind};
+use crate::models::user::CurrentUser;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub const MAX_ATTEMPTS_KEY: &str = "outbox_max_attempts";
+const DEFAULT_MAX_ATTEMPTS: i64 = 5;
+/// Delay before the first retry; it doubles after each failed attempt.
+const BACKOFF_BASE_SECS: i64 = 60;
+const BACKOFF_MAX_SECS: i64 = 6 * 3600;
+/// Messages sent per pass of the worker.
+const BATCH_SIZE: i64 = 20;
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutboxStatus {
+    Queued,
+    Sending,
+    Sent,
+    Failed,
+    Cancelled,
+}
+
+impl OutboxStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutboxStatus::Queued => "queued",
+            OutboxStatus::Sending => "sending",
+            OutboxStatus::Sent => "sent",
+            OutboxStatus::Failed => "failed",
+            OutboxStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<OutboxStatus, String> {
+        match value {
+            "queued" => Ok(OutboxStatus::Queued),
+            "sending" => Ok(OutboxStatus::Sending),
+            "sent" => Ok(OutboxStatus::Sent),
+            "failed" => Ok(OutboxStatus::Failed),
+            "cancelled" => Ok(OutboxStatus::Cancelled),
+            other => Err(format!("Unknown outbox status: {}", other)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct OutboxMessage {
+    pub id: i32,
+    pub soutenance_id: Option<i32>,
+    pub template: String,
+    pub recipient_kind: String,
+    pub recipient_id: Option<i32>,
+    pub email: String,
+    pub subject: String,
+    pub body: String,
+    pub status: OutboxStatus,
+    pub attempts: i64,
+    pub next_attempt_at: Option<String>,
+    pub last_error: Option<String>,
+    pub created_at: String,
+    pub sent_at: Option<String>,
+}
+
+/// A message as it would be queued, before anything is written.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct NotificationPreview {
+    pub soutenance_id: i32,
+    pub template: String,
+    pub recipient_kind: String,
+    pub recipient_id: i32,
+    pub email: String,
+    pub subject: String,
+    pub body: String,
+}
+
+const OUTBOX_SELECT: &str = "SELECT id, soutenance_id, template, recipient_kind, recipient_id, email, subject, body, status,
+    attempts, next_attempt_at, last_error, created_at, sent_at FROM outbox";
+
+fn map_message(row: &rusqlite::Row) -> Result<OutboxMessage> {
+    let status: String = row.get(8)?;
+    Ok(OutboxMessage {
+        id: row.get(0)?,
+        soutenance_id: row.get(1)?,
+        template: row.get(2)?,
+        recipient_kind: row.get(3)?,
+        recipient_id: row.get(4)?,
+        email: row.get(5)?,
+        subject: row.get(6)?,
+        body: row.get(7)?,
+        status: OutboxStatus::parse(&status).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(8, rusqlite::types::Type::Text, e.into())
+        })?,
+        attempts: row.get(9)?,
+        next_attempt_at: row.get(10)?,
+        last_error: row.get(11)?,
+        created_at: row.get(12)?,
+        sent_at: row.get(13)?,
+    })
+}
+
+fn find_message(conn: &Connection, id: i32) -> Result<OutboxMessage, String> {
+    conn.query_row(&format!("{} WHERE id = ?1", OUTBOX_SELECT), [id], map_message)
+        .map_err(|e| format!("Outbox message {} not found: {}", id, e))
+}
+
+/// The convocations and invitations a soutenance would send right now: one per jury
+/// member and one per invitee who has not declined.
+fn notifications(conn: &Connection, soutenance_id: i32) -> Result<Vec<NotificationPreview>, String> {
+    let language = template::language(conn)?;
+    let context = template::soutenance_context(conn, soutenance_id, language)?;
+    let soutenance: Soutenance = serde_json::from_value(context["soutenance"].clone()).map_err(|e| e.to_string())?;
+    let specialite_id = Some(soutenance.specialite_id);
+
+    let mut previews = Vec::new();
+    for jury in &soutenance.juries {
+        let recipient = serde_json::to_value(&jury.jury).map_err(|e| e.to_string())?;
+        let role = template::role_label(jury.role, language);
+        let context = template::with_recipient(context.clone(), recipient, Some(role), None);
+        let rendered = template::render(conn, TemplateKind::JuryConvocation, specialite_id, &context)?;
+        previews.push(NotificationPreview {
+            soutenance_id,
+            template: TemplateKind::JuryConvocation.as_str().to_string(),
+            recipient_kind: "jury".to_string(),
+            recipient_id: jury.jury.id,
+            email: jury.jury.email.clone(),
+            subject: rendered.subject,
+            body: rendered.body,
+        });
+    }
+    let mut invitees = soutenance.invitees;
+    invitees.sort_by(|a, b| (&a.lastname, &a.firstname).cmp(&(&b.lastname, &b.firstname)));
+    for invitee in invitees {
+        let token: Option<String> = conn
+            .query_row(
+                "SELECT rsvp_token FROM invite_soutenance WHERE invite_id = ?1 AND soutenance_id = ?2 AND status != 'declined'",
+                [invitee.id, soutenance_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        let Some(token) = token else {
+            continue;
+        };
+        let recipient = serde_json::to_value(&invitee).map_err(|e| e.to_string())?;
+        let context = template::with_recipient(context.clone(), recipient, None, Some(&token));
+        let rendered = template::render(conn, TemplateKind::Invitation, specialite_id, &context)?;
+        previews.push(NotificationPreview {
+            soutenance_id,
+            template: TemplateKind::Invitation.as_str().to_string(),
+            recipient_kind: "invite".to_string(),
+            recipient_id: invitee.id,
+            email: invitee.email,
+            subject: rendered.subject,
+            body: rendered.body,
+        });
+    }
+    Ok(previews)
+}
+
+fn max_attempts(conn: &Connection) -> Result<i64, String> {
+    Ok(get_setting(conn, MAX_ATTEMPTS_KEY)?
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_ATTEMPTS))
+}
+
+/// Seconds to wait after the given number of failed attempts.
+fn backoff(attempts: i64) -> i64 {
+    let exponent = attempts.clamp(1, 20) - 1;
+    (BACKOFF_BASE_SECS << exponent).min(BACKOFF_MAX_SECS)
+}
+
+/// Puts back in the queue messages a previous run stopped in the middle of sending.
+pub fn requeue_interrupted(conn: &Connection) -> Result<usize, String> {
+    conn.execute("UPDATE outbox SET status = 'queued' WHERE status = 'sending'", [])
+        .map_err(|e| format!("Failed to requeue messages: {}", e))
+}
+
+/// Sends the messages that are due, retrying failures later with exponential backoff
+/// until they run out of attempts. Returns how many were sent. Does nothing until SMTP
+/// is configured.
+pub fn process_due(conn: &Connection) -> Result<usize, String> {
+    let Some(config) = mailer::load_config(conn)? else {
+        return Ok(0);
+    };
+    let max_attempts = max_attempts(conn)?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "{} WHERE status = 'queued' AND next_attempt_at <= datetime('now') ORDER BY next_attempt_at, id LIMIT ?1",
+            OUTBOX_SELECT
+        ))
+        .map_err(|e| e.to_string())?;
+    let due: Vec<OutboxMessage> = stmt
+        .query_map([BATCH_SIZE], map_message)
+        .and_then(|rows| rows.collect())
+        .map_err(|e| e.to_string())?;
+    if due.is_empty() {
+        return Ok(0);
+    }
+    let transport = mailer::transport(&config)?;
+    let mut sent = 0;
+    for message in due {
+        // Claim the message first, so a cancel in between wins.
+        let claimed = conn
+            .execute(
+                "UPDATE outbox SET status = 'sending', attempts = attempts + 1 WHERE id = ?1 AND status = 'queued'",
+                [message.id],
+            )
+            .map_err(|e| e.to_string())?;
+        if claimed == 0 {
+            continue;
+        }
+        let attempts = message.attempts + 1;
+        match mailer::send(&transport, &config, &message.email, &message.subject, &message.body) {
+            Ok(()) => {
+                conn.execute(
+                    "UPDATE outbox SET status = 'sent', sent_at = datetime('now'), last_error = NULL WHERE id = ?1",
+                    [message.id],
+                )
+                .map_err(|e| e.to_string())?;
+                sent += 1;
+            }
+            Err(error) if attempts >= max_attempts => {
+                conn.execute(
+                    "UPDATE outbox SET status = 'failed', last_error = ?1 WHERE id = ?2",
+                    params![error, message.id],
+                )
+                .map_err(|e| e.to_string())?;
+            }
+            Err(error) => {
+                conn.execute(
+                    "UPDATE outbox SET status = 'queued', last_error = ?1, next_attempt_at = datetime('now', ?2) WHERE id = ?3",
+                    params![error, format!("+{} seconds", backoff(attempts)), message.id],
+                )
+                .map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    Ok(sent)
+}
+
+pub fn queue(conn: &Connection, actor: &Option<CurrentUser>, preview: &NotificationPreview) -> Result<i32, String> {
+    conn.execute(
+        "INSERT INTO outbox (soutenance_id, template, recipient_kind, recipient_id, email, subject, body)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            preview.soutenance_id,
+            preview.template,
+            preview.recipient_kind,
+            preview.recipient_id,
+            preview.email,
+            preview.subject,
+            preview.body
+        ],
+    )
+    .map_err(|e| format!("Failed to queue message: {}", e))?;
+    let id = conn.last_insert_rowid() as i32;
+    audit::record_change(conn, actor, "outbox", &[("id", id.into())], None)?;
+    Ok(id)
+}
+
+fn set_status(conn: &Connection, actor: &Option<CurrentUser>, id: i32, sql: &str) -> Result<usize, String> {
+    let key = [("id", id.into())];
+    let before = audit::snapshot(conn, "outbox", &key)?;
+    let changed = conn.execute(sql, [id]).map_err(|e| e.to_string())?;
+    audit::record_change(conn, actor, "outbox", &key, before)?;
+    Ok(changed)
+}
+
+pub fn get_smtp_settings(conn: &Connection) -> Result<SmtpSettings, String> {
+    mailer::load_settings(conn)
+}
+
+/// Saves the SMTP settings. The password is kept out of the audit log.
+pub fn set_smtp_settings(conn: &Connection, actor: &Option<CurrentUser>, settings: SmtpSettings) -> Result<(), String> {
+    let keys: Vec<_> = mailer::AUDITED_KEYS.iter().map(|name| [("key", name.to_string().into())]).collect();
+    let before = keys
+        .iter()
+        .map(|key| audit::snapshot(conn, "settings", key))
+        .collect::<Result<Vec<_>, _>>()?;
+    mailer::save_settings(conn, &settings)?;
+    for (key, before) in keys.iter().zip(before) {
+        audit::record_change(conn, actor, "settings", key, before)?;
+    }
+    Ok(())
+}
+
+/// The SMTP configuration, or an error while it is incomplete.
+pub fn smtp_config(conn: &Connection) -> Result<SmtpConfig, String> {
+    mailer::load_config(conn)?.ok_or_else(|| "SMTP is not configured: set a host and a sender".to_string())
+}
+
+/// Sends one message straight away, bypassing the outbox, to check the settings.
+pub fn send_test_email(config: &SmtpConfig, to: &str) -> Result<(), String> {
+    let transport = mailer::transport(config)?;
+    mailer::send(
+        &transport,
+        config,
+        to,
+        "Test de configuration SMTP",
+        "Ce message confirme que l'envoi d'e-mails fonctionne.",
+    )
+}
+
+pub fn preview_soutenance_notifications(
+    conn: &Connection,
+    soutenance_id: i32,
+) -> Result<Vec<NotificationPreview>, String> {
+    notifications(conn, soutenance_id)
+}
+
+/// Queues the notifications of a soutenance, skipping recipients who already have the
+/// same message waiting or sent. Returns the number of messages queued.
+pub fn enqueue_soutenance_notifications(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    soutenance_id: i32,
+) -> Result<usize, String> {
+    let mut queued = 0;
+    for preview in notifications(conn, soutenance_id)? {
+        let already = conn
+            .query_row(
+                "SELECT 1 FROM outbox WHERE soutenance_id = ?1 AND template = ?2 AND recipient_kind = ?3
+                AND recipient_id = ?4 AND status IN ('queued', 'sending', 'sent')",
+                params![soutenance_id, preview.template, preview.recipient_kind, preview.recipient_id],
+                |_| Ok(()),
+            )
+            .optional()
+            .map_err(|e| e.to_string())?;
+        if already.is_none() {
+            queue(conn, actor, &preview)?;
+            queued += 1;
+        }
+    }
+    Ok(queued)
+}
+
+pub fn get_outbox(
+    conn: &Connection,
+    soutenance_id: Option<i32>,
+    status: Option<OutboxStatus>,
+) -> Result<Vec<OutboxMessage>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "{} WHERE (?1 IS NULL OR soutenance_id = ?1) AND (?2 IS NULL OR status = ?2) ORDER BY id DESC",
+            OUTBOX_SELECT
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params![soutenance_id, status.map(|s| s.as_str())], map_message)
+        .map_err(|e| e.to_string())?;
+    let result: Result<Vec<_>, _> = rows.collect();
+    result.map_err(|e| e.to_string())
+}
+
+/// Stops a message that has not been sent yet.
+pub fn cancel_outbox_message(conn: &Connection, actor: &Option<CurrentUser>, id: i32) -> Result<(), String> {
+    let message = find_message(conn, id)?;
+    if message.status != OutboxStatus::Queued {
+        return Err(format!("Only queued messages can be cancelled, this one is {}", message.status.as_str()));
+    }
+    set_status(conn, actor, id, "UPDATE outbox SET status = 'cancelled' WHERE id = ?1 AND status = 'queued'")?;
+    Ok(())
+}
+
+/// Cancels every queued message of a soutenance, e.g. when it is postponed.
+pub fn cancel_soutenance_notifications(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    soutenance_id: i32,
+) -> Result<usize, String> {
+    let mut stmt = conn
+        .prepare("SELECT id FROM outbox WHERE soutenance_id = ?1 AND status = 'queued'")
+        .map_err(|e| e.to_string())?;
+    let ids: Vec<i32> = stmt
+        .query_map([soutenance_id], |row| row.get(0))
+        .and_then(|rows| rows.collect())
+        .map_err(|e| e.to_string())?;
+    drop(stmt);
+    let mut cancelled = 0;
+    for id in ids {
+        cancelled += set_status(conn, actor, id, "UPDATE outbox SET status = 'cancelled' WHERE id = ?1 AND status = 'queued'")?;
+    }
+    Ok(cancelled)
+}
+
+/// Queues a fresh copy of a message, whatever became of the original. Returns the id of
+/// the copy.
+pub fn resend_outbox_message(conn: &Connection, actor: &Option<CurrentUser>, id: i32) -> Result<i32, String> {
+    let message = find_message(conn, id)?;
+    if matches!(message.status, OutboxStatus::Queued | OutboxStatus::Sending) {
+        return Err("This message has not been sent yet".to_string());
+    }
+    conn.execute(
+        "INSERT INTO outbox (soutenance_id, template, recipient_kind, recipient_id, email, subject, body)
+        SELECT soutenance_id, template, recipient_kind, recipient_id, email, subject, body FROM outbox WHERE id = ?1",
+        [id],
+    )
+    .map_err(|e| format!("Failed to queue message: {}", e))?;
+    let copy = conn.last_insert_rowid() as i32;
+    audit::record_change(conn, actor, "outbox", &[("id", copy.into())], None)?;
+    Ok(copy)
+}
+
+/// Sends the due messages now instead of waiting for the worker. Meant for a connection
+/// of its own, so other commands keep running while the SMTP server answers.
+pub fn flush_outbox(conn: &Connection) -> Result<usize, String> {
+    process_due(conn)
+}
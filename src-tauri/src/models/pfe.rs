@@ -1,36 +1,48 @@
-use crate::AppState;
-use rusqlite::Result;
+use crate::models::audit;
+use crate::models::user::CurrentUser;
+use rusqlite::{Connection, Result};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tauri::State;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct Pfe {
     pub id: i32,
     pub specialite_id: i32,
+    pub title: Option<String>,
+    pub resume: Option<String>,
+    pub company: Option<String>,
 }
 
-#[tauri::command]
-pub fn create_pfe(specialite_id: i32, state: State<AppState>) -> Result<i32, String> {
-    let conn = state.db.lock().unwrap();
+pub fn create_pfe(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    specialite_id: i32,
+    title: Option<String>,
+    resume: Option<String>,
+    company: Option<String>,
+) -> Result<i32, String> {
     conn.execute(
-        "INSERT INTO pfe (specialite_id) VALUES (?1)",
-        [specialite_id],
+        "INSERT INTO pfe (specialite_id, title, resume, company) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![specialite_id, title, resume, company],
     )
     .map_err(|e| e.to_string())?;
-    Ok(conn.last_insert_rowid() as i32)
+    let id = conn.last_insert_rowid() as i32;
+    audit::record_change(conn, actor, "pfe", &[("id", id.into())], None)?;
+    Ok(id)
 }
 
-#[tauri::command]
-pub fn get_pfe(id: i32, state: State<AppState>) -> Result<Pfe, String> {
-    let conn = state.db.lock().unwrap();
+pub fn get_pfe(conn: &Connection, id: i32) -> Result<Pfe, String> {
     let pfe = conn
         .query_row(
-            "SELECT id, specialite_id FROM pfe WHERE id = ?1",
+            "SELECT id, specialite_id, title, resume, company FROM pfe WHERE id = ?1",
             [id],
             |row| {
                 Ok(Pfe {
                     id: row.get(0)?,
                     specialite_id: row.get(1)?,
+                    title: row.get(2)?,
+                    resume: row.get(3)?,
+                    company: row.get(4)?,
                 })
             },
         )
@@ -38,21 +50,29 @@ pub fn get_pfe(id: i32, state: State<AppState>) -> Result<Pfe, String> {
     Ok(pfe)
 }
 
-#[tauri::command]
-pub fn update_pfe(id: i32, specialite_id: i32, state: State<AppState>) -> Result<(), String> {
-    let conn = state.db.lock().unwrap();
+pub fn update_pfe(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    id: i32,
+    specialite_id: i32,
+    title: Option<String>,
+    resume: Option<String>,
+    company: Option<String>,
+) -> Result<(), String> {
+    let before = audit::snapshot(conn, "pfe", &[("id", id.into())])?;
     conn.execute(
-        "UPDATE pfe SET specialite_id = ?1 WHERE id = ?2",
-        [specialite_id, id],
+        "UPDATE pfe SET specialite_id = ?1, title = ?2, resume = ?3, company = ?4 WHERE id = ?5",
+        rusqlite::params![specialite_id, title, resume, company, id],
     )
     .map_err(|e| e.to_string())?;
+    audit::record_change(conn, actor, "pfe", &[("id", id.into())], before)?;
     Ok(())
 }
 
-#[tauri::command]
-pub fn delete_pfe(id: i32, state: State<AppState>) -> Result<(), String> {
-    let conn = state.db.lock().unwrap();
+pub fn delete_pfe(conn: &Connection, actor: &Option<CurrentUser>, id: i32) -> Result<(), String> {
+    let before = audit::snapshot(conn, "pfe", &[("id", id.into())])?;
     conn.execute("DELETE FROM pfe WHERE id = ?1", [id])
         .map_err(|e| e.to_string())?;
+    audit::record_change(conn, actor, "pfe", &[("id", id.into())], before)?;
     Ok(())
 }
@@ -0,0 +1,309 @@
+use crate::models::audit;
+use crate::models::jury_soutenance::jury_role;
+use crate::models::notification::{self, Contact, RecipientKind};
+use crate::models::outbox::{self, NotificationPreview};
+use crate::models::settings::{get_setting, set_setting};
+use crate::models::template::{self, Rendered, TemplateKind};
+use crate::models::user::CurrentUser;
+use rusqlite::{params, Connection, Result, Transaction, TransactionBehavior};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub const OFFSETS_KEY: &str = "reminder_offsets_minutes";
+/// 7 days, 1 day and 2 hours before the defence.
+const DEFAULT_OFFSETS: [i64; 3] = [7 * 24 * 60, 24 * 60, 120];
+
+/// Local start time of a soutenance, NULL until both its date and hour are set.
+const START: &str = "datetime(s.date || ' ' || s.hour)";
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReminderStatus {
+    Pending,
+    Queued,
+    Skipped,
+    Cancelled,
+}
+
+impl ReminderStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReminderStatus::Pending => "pending",
+            ReminderStatus::Queued => "queued",
+            ReminderStatus::Skipped => "skipped",
+            ReminderStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<ReminderStatus, String> {
+        match value {
+            "pending" => Ok(ReminderStatus::Pending),
+            "queued" => Ok(ReminderStatus::Queued),
+            "skipped" => Ok(ReminderStatus::Skipped),
+            "cancelled" => Ok(ReminderStatus::Cancelled),
+            other => Err(format!("Unknown reminder status: {}", other)),
+        }
+    }
+}
+
+/// One reminder for one person, `offset_minutes` before the defence. `due_at` is in
+/// local time, like the soutenance date and hour.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct Reminder {
+    pub id: i32,
+    pub soutenance_id: i32,
+    pub recipient_kind: RecipientKind,
+    pub recipient_id: i32,
+    pub recipient_name: Option<String>,
+    pub offset_minutes: i64,
+    pub due_at: String,
+    pub status: ReminderStatus,
+    /// The message queued when the reminder came due.
+    pub outbox_id: Option<i32>,
+}
+
+const REMINDER_SELECT: &str = "SELECT r.id, r.soutenance_id, r.recipient_kind, r.recipient_id,
+    CASE r.recipient_kind
+        WHEN 'jury' THEN (SELECT firstname || ' ' || lastname FROM jury WHERE id = r.recipient_id)
+        ELSE (SELECT firstname || ' ' || lastname FROM etudiant WHERE id = r.recipient_id)
+    END,
+    r.offset_minutes, r.due_at, r.status, r.outbox_id FROM reminder r";
+
+fn map_reminder(row: &rusqlite::Row) -> Result<Reminder> {
+    let kind: String = row.get(2)?;
+    let status: String = row.get(7)?;
+    Ok(Reminder {
+        id: row.get(0)?,
+        soutenance_id: row.get(1)?,
+        recipient_kind: RecipientKind::parse(&kind).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, e.into())
+        })?,
+        recipient_id: row.get(3)?,
+        recipient_name: row.get(4)?,
+        offset_minutes: row.get(5)?,
+        due_at: row.get(6)?,
+        status: ReminderStatus::parse(&status).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(7, rusqlite::types::Type::Text, e.into())
+        })?,
+        outbox_id: row.get(8)?,
+    })
+}
+
+fn find_reminder(conn: &Connection, id: i32) -> Result<Reminder, String> {
+    conn.query_row(&format!("{} WHERE r.id = ?1", REMINDER_SELECT), [id], map_reminder)
+        .map_err(|e| format!("Reminder {} not found: {}", id, e))
+}
+
+/// How long before a defence reminders go out, in minutes, largest first.
+pub fn offsets(conn: &Connection) -> Result<Vec<i64>, String> {
+    Ok(get_setting(conn, OFFSETS_KEY)?
+        .and_then(|v| serde_json::from_str(&v).ok())
+        .unwrap_or_else(|| DEFAULT_OFFSETS.to_vec()))
+}
+
+/// Brings the stored reminders in line with the current schedule: creates the missing
+/// ones, follows defences that moved and drops those nobody needs any more. A reminder
+/// keeps its row once sent, which is what prevents a second send; it only becomes
+/// pending again if its defence moves to a later due time.
+pub fn sync(conn: &Connection) -> Result<(), String> {
+    let offsets = serde_json::to_string(&offsets(conn)?).map_err(|e| e.to_string())?;
+    conn.execute(
+        &format!(
+            "DELETE FROM reminder WHERE status = 'pending' AND (
+                offset_minutes NOT IN (SELECT value FROM json_each(?1))
+                OR NOT EXISTS (SELECT 1 FROM soutenance s WHERE s.id = reminder.soutenance_id AND {} IS NOT NULL)
+                OR (recipient_kind = 'jury' AND NOT EXISTS (SELECT 1 FROM jury_soutenance
+                    WHERE jury_id = reminder.recipient_id AND soutenance_id = reminder.soutenance_id))
+                OR (recipient_kind = 'student' AND NOT EXISTS (SELECT 1 FROM etudiant
+                    WHERE id = reminder.recipient_id AND soutenance_id = reminder.soutenance_id AND deleted_at IS NULL)))",
+            START
+        ),
+        [&offsets],
+    )
+    .map_err(|e| format!("Failed to drop stale reminders: {}", e))?;
+
+    let due = format!(
+        "(SELECT datetime({}, '-' || reminder.offset_minutes || ' minutes') FROM soutenance s WHERE s.id = reminder.soutenance_id)",
+        START
+    );
+    conn.execute(&format!("UPDATE reminder SET due_at = {0} WHERE status = 'pending' AND due_at != {0}", due), [])
+        .map_err(|e| format!("Failed to reschedule reminders: {}", e))?;
+    conn.execute(
+        &format!(
+            "UPDATE reminder SET due_at = {0}, status = 'pending', outbox_id = NULL
+            WHERE status IN ('queued', 'skipped') AND {0} > due_at AND {0} > datetime('now', 'localtime')",
+            due
+        ),
+        [],
+    )
+    .map_err(|e| format!("Failed to reschedule reminders: {}", e))?;
+
+    conn.execute(
+        &format!(
+            "INSERT OR IGNORE INTO reminder (soutenance_id, recipient_kind, recipient_id, offset_minutes, due_at)
+            SELECT a.soutenance_id, a.kind, a.id, o.value, datetime({0}, '-' || o.value || ' minutes')
+            FROM (SELECT soutenance_id, 'jury' AS kind, jury_id AS id FROM jury_soutenance
+                UNION ALL SELECT soutenance_id, 'student', id FROM etudiant
+                WHERE soutenance_id IS NOT NULL AND deleted_at IS NULL) a
+            JOIN soutenance s ON s.id = a.soutenance_id
+            JOIN json_each(?1) o
+            WHERE datetime({0}, '-' || o.value || ' minutes') > datetime('now', 'localtime')",
+            START
+        ),
+        [&offsets],
+    )
+    .map_err(|e| format!("Failed to schedule reminders: {}", e))?;
+    Ok(())
+}
+
+fn message(conn: &Connection, reminder: &Reminder, contact: &Contact) -> Result<Rendered, String> {
+    let language = template::language(conn)?;
+    let context = template::soutenance_context(conn, reminder.soutenance_id, language)?;
+    let role = match reminder.recipient_kind {
+        RecipientKind::Jury => jury_role(conn, reminder.soutenance_id, reminder.recipient_id)?,
+        _ => None,
+    };
+    let specialite_id = context["soutenance"]["specialite_id"].as_i64().map(|id| id as i32);
+    let context = template::with_recipient(
+        context,
+        serde_json::to_value(contact).map_err(|e| e.to_string())?,
+        role.map(|role| template::role_label(role, language)),
+        None,
+    );
+    template::render(conn, TemplateKind::Reminder, specialite_id, &context)
+}
+
+/// Queues the reminders that came due, once each. Reminders for muted recipients, for
+/// people without an email address or for defences that already started are skipped.
+/// Returns the number of messages queued.
+pub fn process_due(conn: &Connection) -> Result<usize, String> {
+    // Immediate, so the worker waits for the app's writes instead of failing on upgrade.
+    let tx = Transaction::new_unchecked(conn, TransactionBehavior::Immediate).map_err(|e| e.to_string())?;
+    sync(&tx)?;
+    let mut stmt = tx
+        .prepare(&format!(
+            "{} WHERE r.status = 'pending' AND r.due_at <= datetime('now', 'localtime') ORDER BY r.due_at, r.id",
+            REMINDER_SELECT
+        ))
+        .map_err(|e| e.to_string())?;
+    let due: Vec<Reminder> = stmt
+        .query_map([], map_reminder)
+        .and_then(|rows| rows.collect())
+        .map_err(|e| e.to_string())?;
+    drop(stmt);
+
+    let mut queued = 0;
+    for reminder in due {
+        let recipient = (reminder.recipient_kind, reminder.recipient_id);
+        let started: bool = tx
+            .query_row(
+                &format!("SELECT {} <= datetime('now', 'localtime') FROM soutenance s WHERE s.id = ?1", START),
+                [reminder.soutenance_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| e.to_string())?;
+        let contact = notification::contact(&tx, recipient)?;
+        let (Some(contact), false, false) = (contact, started, notification::is_muted(&tx, recipient)?) else {
+            tx.execute("UPDATE reminder SET status = 'skipped' WHERE id = ?1", [reminder.id])
+                .map_err(|e| e.to_string())?;
+            continue;
+        };
+        let message = message(&tx, &reminder, &contact)?;
+        let outbox_id = outbox::queue(
+            &tx,
+            &None,
+            &NotificationPreview {
+                soutenance_id: reminder.soutenance_id,
+                template: TemplateKind::Reminder.as_str().to_string(),
+                recipient_kind: reminder.recipient_kind.as_str().to_string(),
+                recipient_id: reminder.recipient_id,
+                email: contact.email,
+                subject: message.subject,
+                body: message.body,
+            },
+        )?;
+        tx.execute(
+            "UPDATE reminder SET status = 'queued', outbox_id = ?1 WHERE id = ?2",
+            params![outbox_id, reminder.id],
+        )
+        .map_err(|e| e.to_string())?;
+        queued += 1;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(queued)
+}
+
+fn cancel(conn: &Connection, actor: &Option<CurrentUser>, id: i32) -> Result<(), String> {
+    let key = [("id", id.into())];
+    let before = audit::snapshot(conn, "reminder", &key)?;
+    conn.execute("UPDATE reminder SET status = 'cancelled' WHERE id = ?1 AND status = 'pending'", [id])
+        .map_err(|e| format!("Failed to cancel reminder: {}", e))?;
+    audit::record_change(conn, actor, "reminder", &key, before)
+}
+
+pub fn get_reminder_offsets(conn: &Connection) -> Result<Vec<i64>, String> {
+    offsets(conn)
+}
+
+/// Sets how many minutes before each defence reminders go out. Pending reminders for
+/// offsets no longer listed are dropped; those already sent are kept.
+pub fn set_reminder_offsets(conn: &Connection, actor: &Option<CurrentUser>, offsets: Vec<i64>) -> Result<(), String> {
+    if offsets.iter().any(|offset| *offset <= 0) {
+        return Err("Reminders must go out at least one minute before the defence".to_string());
+    }
+    let mut offsets = offsets;
+    offsets.sort_unstable_by(|a, b| b.cmp(a));
+    offsets.dedup();
+    let key = [("key", OFFSETS_KEY.to_string().into())];
+    let before = audit::snapshot(conn, "settings", &key)?;
+    let value = serde_json::to_string(&offsets).map_err(|e| e.to_string())?;
+    set_setting(conn, OFFSETS_KEY, Some(&value))?;
+    audit::record_change(conn, actor, "settings", &key, before)?;
+    sync(conn)?;
+    Ok(())
+}
+
+/// Reminders still to go out, soonest first.
+pub fn get_upcoming_reminders(conn: &Connection, soutenance_id: Option<i32>) -> Result<Vec<Reminder>, String> {
+    sync(conn)?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "{} WHERE r.status = 'pending' AND (?1 IS NULL OR r.soutenance_id = ?1) ORDER BY r.due_at, r.id",
+            REMINDER_SELECT
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([soutenance_id], map_reminder).map_err(|e| e.to_string())?;
+    let result: Result<Vec<_>, _> = rows.collect();
+    result.map_err(|e| e.to_string())
+}
+
+/// Cancels one reminder for good; it is not recreated by later scheduling.
+pub fn cancel_reminder(conn: &Connection, actor: &Option<CurrentUser>, id: i32) -> Result<(), String> {
+    let reminder = find_reminder(conn, id)?;
+    if reminder.status != ReminderStatus::Pending {
+        return Err(format!("Only pending reminders can be cancelled, this one is {}", reminder.status.as_str()));
+    }
+    cancel(conn, actor, id)?;
+    Ok(())
+}
+
+/// Cancels every pending reminder of a soutenance. Returns how many were cancelled.
+pub fn cancel_soutenance_reminders(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    soutenance_id: i32,
+) -> Result<usize, String> {
+    sync(conn)?;
+    let mut stmt = conn
+        .prepare("SELECT id FROM reminder WHERE soutenance_id = ?1 AND status = 'pending'")
+        .map_err(|e| e.to_string())?;
+    let ids: Vec<i32> = stmt
+        .query_map([soutenance_id], |row| row.get(0))
+        .and_then(|rows| rows.collect())
+        .map_err(|e| e.to_string())?;
+    drop(stmt);
+    for id in &ids {
+        cancel(conn, actor, *id)?;
+    }
+    Ok(ids.len())
+}
@@ -0,0 +1,151 @@
+use rusqlite::{params, Connection, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_LIMIT: i64 = 50;
+
+/// What gets indexed for each table: (table, kind, title, body, condition). Expressions use
+/// `{r}` for the row, which becomes `new` inside the triggers and the table itself on rebuild.
+const INDEXED: &[(&str, &str, &str, &str, &str)] = &[
+    (
+        "etudiant",
+        "etudiant",
+        "{r}.firstname || ' ' || {r}.lastname",
+        "''",
+        "{r}.deleted_at IS NULL",
+    ),
+    (
+        "jury",
+        "jury",
+        "{r}.firstname || ' ' || {r}.lastname",
+        "{r}.email",
+        "{r}.deleted_at IS NULL",
+    ),
+    (
+        "invite",
+        "invite",
+        "{r}.firstname || ' ' || {r}.lastname",
+        "{r}.email",
+        "{r}.deleted_at IS NULL",
+    ),
+    (
+        "pfe",
+        "pfe",
+        "COALESCE({r}.title, '')",
+        "COALESCE({r}.resume, '') || ' ' || COALESCE({r}.company, '')",
+        "1",
+    ),
+    ("classroom", "classroom", "COALESCE({r}.name, '')", "''", "{r}.deleted_at IS NULL"),
+    ("soutenance", "soutenance", "COALESCE({r}.pfe, '')", "''", "{r}.pfe IS NOT NULL"),
+];
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct SearchResult {
+    pub kind: String,
+    pub id: i32,
+    pub title: String,
+    /// Matching excerpt with the matched terms wrapped in `<mark>`.
+    pub snippet: String,
+    /// Lower is better (BM25).
+    pub rank: f64,
+}
+
+fn expr(template: &str, row: &str) -> String {
+    template.replace("{r}", row)
+}
+
+/// Creates the full-text index and its triggers, then fills it from the current tables.
+/// Triggers are recreated on every start so changes to `INDEXED` reach existing databases.
+pub fn ensure_search_index(conn: &Connection) -> Result<(), String> {
+    // `remove_diacritics 2` makes "Mastere" match "Mastére".
+    conn.execute(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS search_index USING fts5(
+            kind UNINDEXED,
+            entity_id UNINDEXED,
+            title,
+            body,
+            tokenize = 'unicode61 remove_diacritics 2'
+        )",
+        [],
+    )
+    .map_err(|e| format!("Failed to create search index: {}", e))?;
+
+    let mut sql = String::from("DELETE FROM search_index;");
+    for (table, kind, title, body, condition) in INDEXED {
+        let insert_new = format!(
+            "INSERT INTO search_index (kind, entity_id, title, body) SELECT '{kind}', new.id, {}, {} WHERE {};",
+            expr(title, "new"),
+            expr(body, "new"),
+            expr(condition, "new"),
+        );
+        let delete_old = format!("DELETE FROM search_index WHERE kind = '{kind}' AND entity_id = old.id;");
+        sql.push_str(&format!(
+            "DROP TRIGGER IF EXISTS search_{table}_insert;
+            CREATE TRIGGER search_{table}_insert AFTER INSERT ON {table} BEGIN {insert_new} END;
+            DROP TRIGGER IF EXISTS search_{table}_update;
+            CREATE TRIGGER search_{table}_update AFTER UPDATE ON {table} BEGIN {delete_old} {insert_new} END;
+            DROP TRIGGER IF EXISTS search_{table}_delete;
+            CREATE TRIGGER search_{table}_delete AFTER DELETE ON {table} BEGIN {delete_old} END;
+            INSERT INTO search_index (kind, entity_id, title, body)
+                SELECT '{kind}', {table}.id, {}, {} FROM {table} WHERE {};",
+            expr(title, table),
+            expr(body, table),
+            expr(condition, table),
+        ));
+    }
+    conn.execute_batch(&sql)
+        .map_err(|e| format!("Failed to build search index: {}", e))
+}
+
+/// Turns free text into an FTS5 query: every word must match, as a prefix, and any
+/// FTS5 syntax typed by the user is treated literally.
+fn match_expression(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split(|c: char| c.is_whitespace() || c == '"')
+        .filter(|term| !term.is_empty())
+        .map(|term| format!("\"{}\"*", term))
+        .collect();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+pub fn global_search(
+    conn: &Connection,
+    query: String,
+    kinds: Option<Vec<String>>,
+    limit: Option<i64>,
+) -> Result<Vec<SearchResult>, String> {
+    let Some(expression) = match_expression(&query) else {
+        return Ok(Vec::new());
+    };
+    let kinds = kinds.map(|k| serde_json::to_string(&k).unwrap_or_default());
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT kind, entity_id, title,
+                snippet(search_index, -1, '<mark>', '</mark>', '…', 12),
+                bm25(search_index, 10.0, 1.0)
+            FROM search_index
+            WHERE search_index MATCH ?1
+              AND (?2 IS NULL OR kind IN (SELECT value FROM json_each(?2)))
+            ORDER BY bm25(search_index, 10.0, 1.0)
+            LIMIT ?3",
+        )
+        .map_err(|e| format!("Failed to prepare search: {}", e))?;
+    let rows = stmt
+        .query_map(params![expression, kinds, limit.unwrap_or(DEFAULT_LIMIT)], |row| {
+            Ok(SearchResult {
+                kind: row.get(0)?,
+                id: row.get(1)?,
+                title: row.get(2)?,
+                snippet: row.get(3)?,
+                rank: row.get(4)?,
+            })
+        })
+        .map_err(|e| format!("Failed to search: {}", e))?;
+    let result: Result<Vec<SearchResult>, _> = rows.collect();
+    result.map_err(|e| format!("Failed to collect search results: {}", e))
+}
@@ -0,0 +1,354 @@
+use crate::models::audit;
+use crate::models::settings::{get_setting, set_setting, ACTIVE_SESSION_KEY};
+use crate::models::user::CurrentUser;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Students below this grade (out of 20) are carried over when a session is closed.
+pub const PASSING_GRADE: f64 = 10.0;
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionKind {
+    Principale,
+    Controle,
+}
+
+impl SessionKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SessionKind::Principale => "principale",
+            SessionKind::Controle => "controle",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<SessionKind, String> {
+        match value {
+            "principale" => Ok(SessionKind::Principale),
+            "controle" | "rattrapage" => Ok(SessionKind::Controle),
+            other => Err(format!("Unknown session kind: {}", other)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct AnneeUniversitaire {
+    pub id: i32,
+    pub label: String,
+    pub start_date: Option<String>,
+    pub end_date: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct Session {
+    pub id: i32,
+    pub annee_id: i32,
+    pub annee_label: String,
+    pub kind: SessionKind,
+    pub closed: bool,
+    pub closed_at: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct SessionClosing {
+    pub closed_session_id: i32,
+    pub next_session_id: i32,
+    pub rolled_students: usize,
+}
+
+const SESSION_SELECT: &str = "SELECT s.id, s.annee_id, a.label, s.kind, s.closed, s.closed_at
+    FROM session s JOIN annee_universitaire a ON a.id = s.annee_id";
+
+fn map_session(row: &rusqlite::Row) -> Result<Session> {
+    let kind: String = row.get(3)?;
+    Ok(Session {
+        id: row.get(0)?,
+        annee_id: row.get(1)?,
+        annee_label: row.get(2)?,
+        kind: SessionKind::parse(&kind).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, e.into())
+        })?,
+        closed: row.get(4)?,
+        closed_at: row.get(5)?,
+    })
+}
+
+pub fn find_session(conn: &Connection, id: i32) -> Result<Session, String> {
+    conn.query_row(&format!("{} WHERE s.id = ?1", SESSION_SELECT), [id], map_session)
+        .map_err(|e| format!("Failed to fetch session {}: {}", id, e))
+}
+
+pub fn active_session_id(conn: &Connection) -> Result<Option<i32>, String> {
+    match get_setting(conn, ACTIVE_SESSION_KEY)? {
+        Some(value) => value
+            .parse::<i32>()
+            .map(Some)
+            .map_err(|e| format!("Invalid active session setting: {}", e)),
+        None => Ok(None),
+    }
+}
+
+/// Falls back to the active session when the caller did not ask for a specific one.
+pub fn resolve_session(conn: &Connection, session_id: Option<i32>) -> Result<Option<i32>, String> {
+    match session_id {
+        Some(id) => Ok(Some(id)),
+        None => active_session_id(conn),
+    }
+}
+
+pub fn ensure_session_open(conn: &Connection, session_id: Option<i32>) -> Result<(), String> {
+    let Some(id) = session_id else {
+        return Ok(());
+    };
+    let closed: bool = conn
+        .query_row("SELECT closed FROM session WHERE id = ?1", [id], |row| row.get(0))
+        .map_err(|e| format!("Failed to fetch session {}: {}", id, e))?;
+    if closed {
+        return Err(format!("Session {} is closed and can no longer be modified", id));
+    }
+    Ok(())
+}
+
+pub fn ensure_soutenance_open(conn: &Connection, soutenance_id: i32) -> Result<(), String> {
+    let session_id: Option<i32> = conn
+        .query_row("SELECT session_id FROM soutenance WHERE id = ?1", [soutenance_id], |row| row.get(0))
+        .optional()
+        .map_err(|e| format!("Failed to fetch soutenance {}: {}", soutenance_id, e))?
+        .flatten();
+    ensure_session_open(conn, session_id)
+}
+
+pub fn ensure_student_open(conn: &Connection, student_id: i32) -> Result<(), String> {
+    let session_id: Option<i32> = conn
+        .query_row("SELECT session_id FROM etudiant WHERE id = ?1", [student_id], |row| row.get(0))
+        .optional()
+        .map_err(|e| format!("Failed to fetch student {}: {}", student_id, e))?
+        .flatten();
+    ensure_session_open(conn, session_id)
+}
+
+fn current_year_label(conn: &Connection) -> Result<String, String> {
+    conn.query_row(
+        "SELECT CASE WHEN CAST(strftime('%m', 'now') AS INTEGER) >= 9
+            THEN strftime('%Y', 'now') || '-' || (CAST(strftime('%Y', 'now') AS INTEGER) + 1)
+            ELSE (CAST(strftime('%Y', 'now') AS INTEGER) - 1) || '-' || strftime('%Y', 'now')
+        END",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|e| format!("Failed to compute academic year: {}", e))
+}
+
+fn next_year_label(label: &str) -> Result<String, String> {
+    let (start, end) = label
+        .split_once('-')
+        .ok_or_else(|| format!("Cannot derive the next academic year from {}", label))?;
+    let start: i32 = start.trim().parse().map_err(|_| format!("Invalid academic year: {}", label))?;
+    let end: i32 = end.trim().parse().map_err(|_| format!("Invalid academic year: {}", label))?;
+    Ok(format!("{}-{}", start + 1, end + 1))
+}
+
+fn find_or_create_year(conn: &Connection, label: &str) -> Result<i32, String> {
+    conn.execute(
+        "INSERT INTO annee_universitaire (label) SELECT ?1
+        WHERE NOT EXISTS (SELECT 1 FROM annee_universitaire WHERE label = ?1)",
+        [label],
+    )
+    .map_err(|e| format!("Failed to create academic year: {}", e))?;
+    conn.query_row("SELECT id FROM annee_universitaire WHERE label = ?1", [label], |row| row.get(0))
+        .map_err(|e| format!("Failed to fetch academic year: {}", e))
+}
+
+fn find_or_create_session(conn: &Connection, annee_id: i32, kind: SessionKind) -> Result<i32, String> {
+    conn.execute(
+        "INSERT INTO session (annee_id, kind) SELECT ?1, ?2
+        WHERE NOT EXISTS (SELECT 1 FROM session WHERE annee_id = ?1 AND kind = ?2)",
+        params![annee_id, kind.as_str()],
+    )
+    .map_err(|e| format!("Failed to create session: {}", e))?;
+    conn.query_row(
+        "SELECT id FROM session WHERE annee_id = ?1 AND kind = ?2",
+        params![annee_id, kind.as_str()],
+        |row| row.get(0),
+    )
+    .map_err(|e| format!("Failed to fetch session: {}", e))
+}
+
+/// Creates the current academic year and its main session on first launch, and attaches
+/// every soutenance and student recorded before sessions existed to the active session.
+pub fn ensure_default_session(conn: &Connection) -> Result<(), String> {
+    if active_session_id(conn)?.is_none() {
+        let label = current_year_label(conn)?;
+        let annee_id = find_or_create_year(conn, &label)?;
+        let session_id = find_or_create_session(conn, annee_id, SessionKind::Principale)?;
+        set_setting(conn, ACTIVE_SESSION_KEY, Some(&session_id.to_string()))?;
+    }
+    let active = active_session_id(conn)?;
+    conn.execute("UPDATE soutenance SET session_id = ?1 WHERE session_id IS NULL", [active])
+        .map_err(|e| format!("Failed to attach soutenances to session: {}", e))?;
+    conn.execute("UPDATE etudiant SET session_id = ?1 WHERE session_id IS NULL", [active])
+        .map_err(|e| format!("Failed to attach students to session: {}", e))?;
+    Ok(())
+}
+
+pub fn create_annee_universitaire(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    label: String,
+    start_date: Option<String>,
+    end_date: Option<String>,
+) -> Result<i32, String> {
+    conn.execute(
+        "INSERT INTO annee_universitaire (label, start_date, end_date) VALUES (?1, ?2, ?3)",
+        params![label, start_date, end_date],
+    )
+    .map_err(|e| format!("Failed to create academic year: {}", e))?;
+    let id = conn.last_insert_rowid() as i32;
+    audit::record_change(conn, actor, "annee_universitaire", &[("id", id.into())], None)?;
+    Ok(id)
+}
+
+pub fn get_all_annees_universitaires(conn: &Connection) -> Result<Vec<AnneeUniversitaire>, String> {
+    let mut stmt = conn
+        .prepare("SELECT id, label, start_date, end_date FROM annee_universitaire ORDER BY label DESC")
+        .map_err(|e| format!("Failed to prepare academic year query: {}", e))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(AnneeUniversitaire {
+                id: row.get(0)?,
+                label: row.get(1)?,
+                start_date: row.get(2)?,
+                end_date: row.get(3)?,
+            })
+        })
+        .map_err(|e| format!("Failed to query academic years: {}", e))?;
+    let result: Result<Vec<AnneeUniversitaire>, _> = rows.collect();
+    result.map_err(|e| format!("Failed to collect academic years: {}", e))
+}
+
+pub fn create_session(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    annee_id: i32,
+    kind: String,
+) -> Result<i32, String> {
+    let kind = SessionKind::parse(&kind)?;
+    conn.execute(
+        "INSERT INTO session (annee_id, kind) VALUES (?1, ?2)",
+        params![annee_id, kind.as_str()],
+    )
+    .map_err(|e| format!("Failed to create session: {}", e))?;
+    let id = conn.last_insert_rowid() as i32;
+    audit::record_change(conn, actor, "session", &[("id", id.into())], None)?;
+    Ok(id)
+}
+
+pub fn get_all_sessions(conn: &Connection, annee_id: Option<i32>) -> Result<Vec<Session>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "{} WHERE ?1 IS NULL OR s.annee_id = ?1 ORDER BY a.label DESC, s.kind DESC",
+            SESSION_SELECT
+        ))
+        .map_err(|e| format!("Failed to prepare session query: {}", e))?;
+    let rows = stmt
+        .query_map([annee_id], map_session)
+        .map_err(|e| format!("Failed to query sessions: {}", e))?;
+    let result: Result<Vec<Session>, _> = rows.collect();
+    result.map_err(|e| format!("Failed to collect sessions: {}", e))
+}
+
+pub fn get_active_session(conn: &Connection) -> Result<Option<Session>, String> {
+    match active_session_id(conn)? {
+        Some(id) => find_session(conn, id).map(Some),
+        None => Ok(None),
+    }
+}
+
+pub fn set_active_session(conn: &Connection, actor: &Option<CurrentUser>, session_id: i32) -> Result<Session, String> {
+    let session = find_session(conn, session_id)?;
+    let key = [("key", ACTIVE_SESSION_KEY.to_string().into())];
+    let before = audit::snapshot(conn, "settings", &key)?;
+    set_setting(conn, ACTIVE_SESSION_KEY, Some(&session_id.to_string()))?;
+    audit::record_change(conn, actor, "settings", &key, before)?;
+    Ok(session)
+}
+
+/// Freezes a session and re-enrols every student who failed (or never defended) in the
+/// following session: the contrôle session of the same year after the principale, the
+/// principale of the next year after the contrôle.
+pub fn close_session(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    session_id: i32,
+    next_session_id: Option<i32>,
+) -> Result<SessionClosing, String> {
+    let session = find_session(conn, session_id)?;
+    if session.closed {
+        return Err(format!("Session {} is already closed", session_id));
+    }
+
+    let next_session_id = match next_session_id {
+        Some(id) => {
+            ensure_session_open(conn, Some(id))?;
+            id
+        }
+        None => match session.kind {
+            SessionKind::Principale => {
+                find_or_create_session(conn, session.annee_id, SessionKind::Controle)?
+            }
+            SessionKind::Controle => {
+                let annee_id = find_or_create_year(conn, &next_year_label(&session.annee_label)?)?;
+                find_or_create_session(conn, annee_id, SessionKind::Principale)?
+            }
+        },
+    };
+    if next_session_id == session_id {
+        return Err("A session cannot be rolled over into itself".to_string());
+    }
+
+    let last_student_id: i32 = conn
+        .query_row("SELECT COALESCE(MAX(id), 0) FROM etudiant", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    let rolled_students = conn
+        .execute(
+            "INSERT INTO etudiant (firstname, lastname, address, specialite_id, email, supervisor_id, session_id)
+            SELECT firstname, lastname, address, specialite_id, email, supervisor_id, ?1 FROM etudiant
+            WHERE session_id = ?2 AND deleted_at IS NULL AND (grade IS NULL OR grade < ?3)",
+            params![next_session_id, session_id, PASSING_GRADE],
+        )
+        .map_err(|e| format!("Failed to roll students over: {}", e))?;
+    let rolled_ids: Vec<i32> = {
+        let mut stmt = conn
+            .prepare("SELECT id FROM etudiant WHERE id > ?1")
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([last_student_id], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<_, _>>().map_err(|e| e.to_string())?
+    };
+    for id in rolled_ids {
+        audit::record_change(conn, actor, "etudiant", &[("id", id.into())], None)?;
+    }
+
+    let before = audit::snapshot(conn, "session", &[("id", session_id.into())])?;
+    conn.execute(
+        "UPDATE session SET closed = 1, closed_at = datetime('now') WHERE id = ?1",
+        [session_id],
+    )
+    .map_err(|e| format!("Failed to close session: {}", e))?;
+    audit::record_change(conn, actor, "session", &[("id", session_id.into())], before)?;
+
+    if active_session_id(conn)? == Some(session_id) {
+        let key = [("key", ACTIVE_SESSION_KEY.to_string().into())];
+        let before = audit::snapshot(conn, "settings", &key)?;
+        set_setting(conn, ACTIVE_SESSION_KEY, Some(&next_session_id.to_string()))?;
+        audit::record_change(conn, actor, "settings", &key, before)?;
+    }
+
+    Ok(SessionClosing {
+        closed_session_id: session_id,
+        next_session_id,
+        rolled_students,
+    })
+}
@@ -0,0 +1,20 @@
+use rusqlite::{params, Connection, OptionalExtension};
+
+pub const ACTIVE_SESSION_KEY: &str = "active_session_id";
+
+pub fn get_setting(conn: &Connection, key: &str) -> Result<Option<String>, String> {
+    conn.query_row("SELECT value FROM settings WHERE key = ?1", [key], |row| row.get(0))
+        .optional()
+        .map(Option::flatten)
+        .map_err(|e| format!("Failed to read setting {}: {}", key, e))
+}
+
+pub fn set_setting(conn: &Connection, key: &str, value: Option<&str>) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO settings (key, value) VALUES (?1, ?2)
+        ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![key, value],
+    )
+    .map_err(|e| format!("Failed to write setting {}: {}", key, e))?;
+    Ok(())
+}
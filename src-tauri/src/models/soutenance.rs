@@ -1,63 +1,141 @@
-use crate::AppState;
+use crate::export::write_csv;
+use crate::models::template::specialite_name;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tauri::State;
 use crate::models::{classroom::Salle, invite::Invitee, jury::Jury, etudiant::Etudiant};
-use rusqlite::{Result, Row, ffi};
+use crate::models::jury_soutenance::{create_jury_soutenance, JuryRole};
+use crate::models::invite_soutenance::create_invite_soutenance;
+use crate::models::etudiant::assign_student;
+use crate::models::composition::{check_composition, jury_roles};
+use crate::models::conflict::ensure_seated_without_conflict;
+use crate::models::classroom::{find_classroom, parse_equipment, room_conflicts};
+use crate::models::settings::{get_setting, set_setting};
+use crate::models::audit;
+use crate::models::journal::Operation;
+use crate::models::notification;
+use crate::models::session::{ensure_session_open, ensure_soutenance_open, resolve_session};
+use crate::models::workload::ensure_within_caps;
+use crate::query::{ListQuery, Page, QueryOptions};
+use crate::models::user::CurrentUser;
+use rusqlite::{Connection, Result, Row, ffi};
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+pub const DEFENCE_DURATION_KEY: &str = "defence_duration_minutes";
+const DEFAULT_DEFENCE_DURATION: i32 = 60;
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
 pub struct JurySoutenanceDetails {
     #[serde(flatten)]
     pub jury: Jury,
-    pub role: String,
+    pub role: JuryRole,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
 pub struct Soutenance {
     pub id: i32,
     pub date: Option<String>,
     pub hour: Option<String>,
     pub specialite_id: i32,
     pub pfe: Option<String>,
+    pub session_id: Option<i32>,
     pub classroom: Option<Salle>,
     pub juries: Vec<JurySoutenanceDetails>,
     pub invitees: Vec<Invitee>,
     pub students: Vec<Etudiant>,
+    /// Scheduling problems noticed when the soutenance was saved, such as an undersized room.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// A jury member to seat when a soutenance is created with its participants.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct JuryAssignment {
+    pub jury_id: i32,
+    pub role: JuryRole,
+    /// Required when the member has a declared conflict with the defence.
+    #[serde(default)]
+    pub justification: Option<String>,
+}
+
+/// Everything needed to create a soutenance in one go.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct SoutenancePlan {
+    pub date: Option<String>,
+    pub hour: Option<String>,
+    pub specialite_id: i32,
+    pub classroom_id: Option<i32>,
+    pub pfe: Option<String>,
+    pub session_id: Option<i32>,
+    pub expected_attendees: Option<i32>,
+    #[serde(default)]
+    pub juries: Vec<JuryAssignment>,
+    #[serde(default)]
+    pub invitee_ids: Vec<i32>,
+    #[serde(default)]
+    pub student_ids: Vec<i32>,
+}
+
+/// How long a defence occupies its room, used for overlaps and occupancy.
+pub fn defence_duration(conn: &Connection) -> Result<i32, String> {
+    Ok(get_setting(conn, DEFENCE_DURATION_KEY)?
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_DEFENCE_DURATION))
+}
+
+/// Students, juries and invitees attending a soutenance.
+pub fn attendee_count(conn: &Connection, soutenance_id: i32) -> Result<i32, String> {
+    conn.query_row(
+        "SELECT (SELECT COUNT(*) FROM etudiant WHERE soutenance_id = ?1 AND deleted_at IS NULL)
+            + (SELECT COUNT(*) FROM jury_soutenance WHERE soutenance_id = ?1)
+            + (SELECT COUNT(*) FROM invite_soutenance WHERE soutenance_id = ?1 AND status != 'declined')",
+        [soutenance_id],
+        |row| row.get(0),
+    )
+    .map_err(|e| format!("Failed to count attendees: {}", e))
 }
 
-#[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub fn create_soutenance(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
     date: Option<String>,
     hour: Option<String>,
     specialite_id: i32,
-    classroom_id: Option<i32>, // Changed to Option<i32> to handle NULL
+    classroom_id: Option<i32>,
+    // Changed to Option<i32> to handle NULL
     pfe: Option<String>,
-    state: State<AppState>,
+    session_id: Option<i32>,
+    expected_attendees: Option<i32>,
 ) -> Result<Soutenance, String> {
-    let conn = state.db.lock().map_err(|e| format!("Failed to lock database: {}", e))?;
+    let session_id = resolve_session(conn, session_id)?;
+    ensure_session_open(conn, session_id)?;
 
     conn.execute(
-        "INSERT INTO soutenance (date, hour, specialite_id, classroom_id, pfe) VALUES (?1, ?2, ?3, ?4, ?5)",
-        rusqlite::params![date, hour, specialite_id, classroom_id, pfe],
+        "INSERT INTO soutenance (date, hour, specialite_id, classroom_id, pfe, session_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        rusqlite::params![date, hour, specialite_id, classroom_id, pfe, session_id],
     )
     .map_err(|e| format!("Failed to insert soutenance: {}", e))?;
 
     let id = conn.last_insert_rowid() as i32;
+    audit::record_change(conn, actor, "soutenance", &[("id", id.into())], None)?;
 
     // Fetch the classroom object
-    let classroom = if let Some(cid) = classroom_id {
-        conn.query_row(
-            "SELECT id, name FROM classroom WHERE id = ?1",
-            [cid],
-            |row| Ok(Salle {
-                id: row.get(0)?,
-                name: row.get(1)?,
-            }),
-        )
-        .map_err(|e| format!("Failed to fetch classroom: {}", e))
-        .ok() // Convert error to None if classroom not found
-    } else {
-        None
-    };
+    let classroom = classroom_id.and_then(|cid| find_classroom(conn, cid).ok()); // None if classroom not found
+
+    // The soutenance is saved anyway; the caller decides what to do with the warnings.
+    let mut warnings = Vec::new();
+    if let Some(room) = &classroom {
+        if let (Some(capacity), Some(expected)) = (room.capacity, expected_attendees) {
+            if expected > capacity {
+                warnings.push(format!("{} holds {} people but {} are expected", room.name, capacity, expected));
+            }
+        }
+        if let (Some(date), Some(hour)) = (&date, &hour) {
+            match room_conflicts(conn, room.id, date, hour, Some(id)) {
+                Ok(conflicts) => warnings.extend(conflicts),
+                Err(e) => warnings.push(e),
+            }
+        }
+    }
 
     Ok(Soutenance {
         id,
@@ -65,17 +143,68 @@ pub fn create_soutenance(
         hour,
         specialite_id,
         pfe,
+        session_id,
         classroom,
         juries: Vec::new(), // Initially empty
         invitees: Vec::new(),
         students: Vec::new(),
+        warnings,
     })
 }
 
-#[tauri::command]
-pub fn get_soutenance(id: i32, state: State<AppState>) -> Result<Soutenance, String> {
-    let conn = state.db.lock().map_err(|e| format!("Failed to lock database: {}", e))?;
+/// Creates a soutenance with its jury, invitees and students. Run in one transaction, it
+/// leaves nothing behind when one of them cannot be added. Students come first, so the
+/// jury is checked against their supervisors and conflicts like a seat added later would.
+/// An incomplete jury or a room too small for everyone is reported among the warnings.
+pub fn create_soutenance_with_participants(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    plan: SoutenancePlan,
+) -> Result<Soutenance, String> {
+    let created = create_soutenance(
+        conn,
+        actor,
+        plan.date,
+        plan.hour,
+        plan.specialite_id,
+        plan.classroom_id,
+        plan.pfe,
+        plan.session_id,
+        None,
+    )?;
+    for student_id in plan.student_ids {
+        assign_student(conn, actor, student_id, created.id).map_err(|e| format!("Student {}: {}", student_id, e))?;
+    }
+    for jury in plan.juries {
+        create_jury_soutenance(conn, actor, jury.jury_id, created.id, jury.role.as_str().to_string(), jury.justification)
+            .map_err(|e| format!("Jury {}: {}", jury.jury_id, e))?;
+    }
+    for invite_id in plan.invitee_ids {
+        create_invite_soutenance(conn, actor, invite_id, created.id).map_err(|e| format!("Invitee {}: {}", invite_id, e))?;
+    }
+    let mut soutenance = find_soutenance(conn, created.id)?;
+    soutenance.warnings = created.warnings;
+    if let Some(room) = &soutenance.classroom {
+        let attending = (soutenance.juries.len() + soutenance.invitees.len() + soutenance.students.len()) as i32;
+        let expected = plan.expected_attendees.unwrap_or(0).max(attending);
+        if let Some(capacity) = room.capacity.filter(|capacity| expected > *capacity) {
+            soutenance
+                .warnings
+                .push(format!("{} holds {} people but {} are expected", room.name, capacity, expected));
+        }
+    }
+    soutenance
+        .warnings
+        .extend(check_composition(conn, created.id, true)?.into_iter().map(|violation| violation.message));
+    Ok(soutenance)
+}
 
+pub fn get_soutenance(conn: &Connection, id: i32) -> Result<Soutenance, String> {
+    find_soutenance(conn, id)
+}
+
+/// A soutenance with its room, jury, invitees and students.
+pub fn find_soutenance(conn: &Connection, id: i32) -> Result<Soutenance, String> {
     let query = r#"
         SELECT 
             s.id, s.date, s.hour, s.specialite_id, s.pfe,
@@ -87,6 +216,7 @@ pub fn get_soutenance(id: i32, state: State<AppState>) -> Result<Soutenance, Str
                         'firstname', j.firstname,
                         'lastname', j.lastname,
                         'email', j.email,
+                        'deleted_at', j.deleted_at,
                         'role', js.role
                     )
                 )
@@ -100,7 +230,8 @@ pub fn get_soutenance(id: i32, state: State<AppState>) -> Result<Soutenance, Str
                         'id', i.id,
                         'firstname', i.firstname,
                         'lastname', i.lastname,
-                        'email', i.email
+                        'email', i.email,
+                        'deleted_at', i.deleted_at
                     )
                 )
                 FROM invite i
@@ -115,12 +246,20 @@ pub fn get_soutenance(id: i32, state: State<AppState>) -> Result<Soutenance, Str
                         'lastname', e.lastname,
                         'address', e.address,
                         'specialite_id', e.specialite_id,
-                        'soutenance_id', e.soutenance_id
+                        'soutenance_id', e.soutenance_id,
+                        'session_id', e.session_id,
+                        'grade', e.grade,
+                        'deleted_at', e.deleted_at,
+                        'supervisor_id', e.supervisor_id,
+                        'email', e.email
                     )
                 )
                 FROM etudiant e
                 WHERE e.soutenance_id = s.id
-            ), '[]') AS students
+            ), '[]') AS students,
+            s.session_id,
+            c.deleted_at AS classroom_deleted_at,
+            c.capacity, c.building, c.floor, c.equipment
         FROM soutenance s
         LEFT JOIN classroom c ON s.classroom_id = c.id
         WHERE s.id = ?1
@@ -138,6 +277,11 @@ pub fn get_soutenance(id: i32, state: State<AppState>) -> Result<Soutenance, Str
                 Some(cid) => Some(Salle {
                     id: cid,
                     name: row.get(6)?,
+                    deleted_at: row.get(11)?,
+                    capacity: row.get(12)?,
+                    building: row.get(13)?,
+                    floor: row.get(14)?,
+                    equipment: parse_equipment(row.get(15)?),
                 }),
                 None => None,
             };
@@ -172,16 +316,20 @@ pub fn get_soutenance(id: i32, state: State<AppState>) -> Result<Soutenance, Str
                     )
                 })?;
 
+            let session_id: Option<i32> = row.get(10)?;
+
             Ok(Soutenance {
                 id,
                 date,
                 hour,
                 specialite_id,
                 pfe,
+                session_id,
                 classroom,
                 juries,
                 invitees,
                 students,
+                warnings: Vec::new(),
             })
         })
         .map_err(|e| format!("Failed to fetch soutenance: {}", e))?;
@@ -189,39 +337,78 @@ pub fn get_soutenance(id: i32, state: State<AppState>) -> Result<Soutenance, Str
     Ok(soutenance)
 }
 
-#[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub fn update_soutenance(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
     id: i32,
     date: Option<String>,
     hour: Option<String>,
     specialite_id: i32,
-    classroom_id: Option<i32>, // Changed to Option<i32>
+    classroom_id: Option<i32>,
+    // Changed to Option<i32>
     pfe: Option<String>,
-    state: State<AppState>,
 ) -> Result<(), String> {
-    let conn = state.db.lock().map_err(|e| format!("Failed to lock database: {}", e))?;
+    ensure_soutenance_open(conn, id)?;
+    let before = audit::snapshot(conn, "soutenance", &[("id", id.into())])?;
+    let schedule = notification::find_schedule(conn, id)?;
     conn.execute(
         "UPDATE soutenance SET date = ?1, hour = ?2, specialite_id = ?3, classroom_id = ?4, pfe = ?5 WHERE id = ?6",
         rusqlite::params![date, hour, specialite_id, classroom_id, pfe, id],
     )
     .map_err(|e| format!("Failed to update soutenance: {}", e))?;
+    ensure_seated_without_conflict(conn, id)?;
+    for (jury_id, _) in jury_roles(conn, id)? {
+        ensure_within_caps(conn, jury_id, id)?;
+    }
+    audit::record_change(conn, actor, "soutenance", &[("id", id.into())], before)?;
+    notification::schedule_changed(conn, id, &schedule)?;
     Ok(())
 }
 
-#[tauri::command]
-pub fn delete_soutenance(id: i32, state: State<AppState>) -> Result<(), String> {
-    let conn = state.db.lock().map_err(|e| format!("Failed to lock database: {}", e))?;
+pub fn delete_soutenance(conn: &Connection, actor: &Option<CurrentUser>, id: i32) -> Result<(), String> {
+    ensure_soutenance_open(conn, id)?;
+    let mut operation = Operation::new("delete_soutenance");
+    notification::cancelled(conn, id)?;
+
+    // Detach the students and drop the jury/invitee assignments with the soutenance,
+    // so an undo can put all of them back.
+    let student_ids = ids_where(conn, "SELECT id FROM etudiant WHERE soutenance_id = ?1", id)?;
+    for student_id in student_ids {
+        operation.track(conn, "etudiant", &[("id", student_id.into())])?;
+    }
+    conn.execute("UPDATE etudiant SET soutenance_id = NULL WHERE soutenance_id = ?1", [id])
+        .map_err(|e| format!("Failed to detach students: {}", e))?;
+
+    let jury_ids = ids_where(conn, "SELECT jury_id FROM jury_soutenance WHERE soutenance_id = ?1", id)?;
+    for jury_id in jury_ids {
+        operation.track(conn, "jury_soutenance", &[("jury_id", jury_id.into()), ("soutenance_id", id.into())])?;
+    }
+    conn.execute("DELETE FROM jury_soutenance WHERE soutenance_id = ?1", [id])
+        .map_err(|e| format!("Failed to delete jury assignments: {}", e))?;
+
+    let invite_ids = ids_where(conn, "SELECT invite_id FROM invite_soutenance WHERE soutenance_id = ?1", id)?;
+    for invite_id in invite_ids {
+        operation.track(conn, "invite_soutenance", &[("invite_id", invite_id.into()), ("soutenance_id", id.into())])?;
+    }
+    conn.execute("DELETE FROM invite_soutenance WHERE soutenance_id = ?1", [id])
+        .map_err(|e| format!("Failed to delete invitations: {}", e))?;
+
+    operation.track(conn, "soutenance", &[("id", id.into())])?;
     conn.execute("DELETE FROM soutenance WHERE id = ?1", [id])
         .map_err(|e| format!("Failed to delete soutenance: {}", e))?;
+    operation.finish(conn, actor)?;
     Ok(())
 }
 
-#[tauri::command]
-pub fn get_soutenance_students(
-    soutenance_id: i32,
-    state: State<AppState>,
-) -> Result<Vec<i32>, String> {
-    let conn = state.db.lock().map_err(|e| format!("Failed to lock database: {}", e))?;
+fn ids_where(conn: &rusqlite::Connection, query: &str, id: i32) -> Result<Vec<i32>, String> {
+    let mut stmt = conn.prepare(query).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([id], |row| row.get(0)).map_err(|e| e.to_string())?;
+    let result: Result<Vec<i32>, _> = rows.collect();
+    result.map_err(|e| e.to_string())
+}
+
+pub fn get_soutenance_students(conn: &Connection, soutenance_id: i32) -> Result<Vec<i32>, String> {
     let mut stmt = conn
         .prepare("SELECT id FROM etudiant WHERE soutenance_id = ?1")
         .map_err(|e| format!("Failed to prepare student query: {}", e))?;
@@ -232,12 +419,7 @@ pub fn get_soutenance_students(
     result.map_err(|e| format!("Failed to collect students: {}", e))
 }
 
-#[tauri::command]
-pub fn get_soutenance_jurys(
-    soutenance_id: i32,
-    state: State<AppState>,
-) -> Result<Vec<(i32, String)>, String> {
-    let conn = state.db.lock().map_err(|e| format!("Failed to lock database: {}", e))?;
+pub fn get_soutenance_jurys(conn: &Connection, soutenance_id: i32) -> Result<Vec<(i32, String)>, String> {
     let mut stmt = conn
         .prepare("SELECT jury_id, role FROM jury_soutenance WHERE soutenance_id = ?1")
         .map_err(|e| format!("Failed to prepare jury query: {}", e))?;
@@ -248,12 +430,7 @@ pub fn get_soutenance_jurys(
     result.map_err(|e| format!("Failed to collect juries: {}", e))
 }
 
-#[tauri::command]
-pub fn get_soutenance_invites(
-    soutenance_id: i32,
-    state: State<AppState>,
-) -> Result<Vec<i32>, String> {
-    let conn = state.db.lock().map_err(|e| format!("Failed to lock database: {}", e))?;
+pub fn get_soutenance_invites(conn: &Connection, soutenance_id: i32) -> Result<Vec<i32>, String> {
     let mut stmt = conn
         .prepare("SELECT invite_id FROM invite_soutenance WHERE soutenance_id = ?1")
         .map_err(|e| format!("Failed to prepare invite query: {}", e))?;
@@ -264,14 +441,17 @@ pub fn get_soutenance_invites(
     result.map_err(|e| format!("Failed to collect invitees: {}", e))
 }
 
-#[tauri::command]
 pub fn get_specialite_soutenances(
+    conn: &Connection,
     specialite_id: i32,
-    state: State<AppState>,
-) -> Result<Vec<Soutenance>, String> {
-    let conn = state.db.lock().map_err(|e| format!("Failed to lock database: {}", e))?;
+    session_id: Option<i32>,
+    options: Option<QueryOptions>,
+) -> Result<Page<Soutenance>, String> {
+    let options = options.unwrap_or_default();
+    options.ensure_supported("soutenance", &["date_from", "date_to", "classroom_id", "jury_id"])?;
+    let session_id = resolve_session(conn, session_id)?;
 
-    let query = r#"
+    let select = r#"
         SELECT 
             s.id, s.date, s.hour, s.specialite_id, s.pfe,
             c.id AS classroom_id, c.name AS classroom_name,
@@ -282,6 +462,7 @@ pub fn get_specialite_soutenances(
                         'firstname', j.firstname,
                         'lastname', j.lastname,
                         'email', j.email,
+                        'deleted_at', j.deleted_at,
                         'role', js.role
                     )
                 )
@@ -295,7 +476,8 @@ pub fn get_specialite_soutenances(
                         'id', i.id,
                         'firstname', i.firstname,
                         'lastname', i.lastname,
-                        'email', i.email
+                        'email', i.email,
+                        'deleted_at', i.deleted_at
                     )
                 )
                 FROM invite i
@@ -310,81 +492,180 @@ pub fn get_specialite_soutenances(
                         'lastname', e.lastname,
                         'address', e.address,
                         'specialite_id', e.specialite_id,
-                        'soutenance_id', e.soutenance_id
+                        'soutenance_id', e.soutenance_id,
+                        'session_id', e.session_id,
+                        'grade', e.grade,
+                        'deleted_at', e.deleted_at,
+                        'supervisor_id', e.supervisor_id,
+                        'email', e.email
                     )
                 )
                 FROM etudiant e
                 WHERE e.soutenance_id = s.id
-            ), '[]') AS students
-        FROM soutenance s
-        LEFT JOIN classroom c ON s.classroom_id = c.id
-        WHERE s.specialite_id = ?1
+            ), '[]') AS students,
+            s.session_id,
+            c.deleted_at AS classroom_deleted_at,
+            c.capacity, c.building, c.floor, c.equipment
     "#;
 
-    let mut stmt = conn
-        .prepare(query)
-        .map_err(|e| format!("Failed to prepare soutenance query: {}", e))?;
+    let mut query = ListQuery::new("FROM soutenance s LEFT JOIN classroom c ON s.classroom_id = c.id");
+    query
+        .filter("s.specialite_id = ?", vec![specialite_id.into()])
+        .filter_opt("s.session_id = ?", session_id)
+        .filter_opt("substr(s.date, 1, 10) >= ?", options.date_from.clone())
+        .filter_opt("substr(s.date, 1, 10) <= ?", options.date_to.clone())
+        .filter_opt("s.classroom_id = ?", options.classroom_id)
+        .filter_opt(
+            "EXISTS (SELECT 1 FROM jury_soutenance js WHERE js.soutenance_id = s.id AND js.jury_id = ?)",
+            options.jury_id,
+        )
+        .text(
+            &options.text,
+            &[
+                "s.pfe",
+                "c.name",
+                "(SELECT group_concat(e.firstname || ' ' || e.lastname, ' ') FROM etudiant e WHERE e.soutenance_id = s.id)",
+            ],
+        );
 
-    let rows = stmt
-        .query_map([specialite_id], |row| {
-            let id: i32 = row.get(0)?;
-            let date: Option<String> = row.get(1)?;
-            let hour: Option<String> = row.get(2)?;
-            let specialite_id: i32 = row.get(3)?;
-            let pfe: Option<String> = row.get(4)?;
+    let sorts = [
+        ("date", "s.date, s.hour"),
+        ("hour", "s.hour"),
+        ("pfe", "s.pfe"),
+        ("classroom", "c.name"),
+        ("id", "s.id"),
+    ];
+    query.fetch(conn, select, &options, &sorts, |row| {
+        let id: i32 = row.get(0)?;
+        let date: Option<String> = row.get(1)?;
+        let hour: Option<String> = row.get(2)?;
+        let specialite_id: i32 = row.get(3)?;
+        let pfe: Option<String> = row.get(4)?;
 
-            let classroom = match row.get::<_, Option<i32>>(5)? {
-                Some(cid) => Some(Salle {
-                    id: cid,
-                    name: row.get(6)?,
-                }),
-                None => None,
-            };
+        let classroom = match row.get::<_, Option<i32>>(5)? {
+            Some(cid) => Some(Salle {
+                id: cid,
+                name: row.get(6)?,
+                deleted_at: row.get(11)?,
+                capacity: row.get(12)?,
+                building: row.get(13)?,
+                floor: row.get(14)?,
+                equipment: parse_equipment(row.get(15)?),
+            }),
+            None => None,
+        };
 
-            let juries_json: String = row.get(7)?;
-            let juries: Vec<JurySoutenanceDetails> = serde_json::from_str(&juries_json)
-                .map_err(|e| {
-                    let err_msg = format!("Failed to parse juries JSON: {}", e);
-                    rusqlite::Error::SqliteFailure(
-                        ffi::Error::new(ffi::SQLITE_MISUSE),
-                        Some(err_msg),
-                    )
-                })?;
+        let juries_json: String = row.get(7)?;
+        let juries: Vec<JurySoutenanceDetails> = serde_json::from_str(&juries_json)
+            .map_err(|e| {
+                let err_msg = format!("Failed to parse juries JSON: {}", e);
+                rusqlite::Error::SqliteFailure(
+                    ffi::Error::new(ffi::SQLITE_MISUSE),
+                    Some(err_msg),
+                )
+            })?;
 
-            let invitees_json: String = row.get(8)?;
-            let invitees: Vec<Invitee> = serde_json::from_str(&invitees_json)
-                .map_err(|e| {
-                    let err_msg = format!("Failed to parse invitees JSON: {}", e);
-                    rusqlite::Error::SqliteFailure(
-                        ffi::Error::new(ffi::SQLITE_MISUSE),
-                        Some(err_msg),
-                    )
-                })?;
+        let invitees_json: String = row.get(8)?;
+        let invitees: Vec<Invitee> = serde_json::from_str(&invitees_json)
+            .map_err(|e| {
+                let err_msg = format!("Failed to parse invitees JSON: {}", e);
+                rusqlite::Error::SqliteFailure(
+                    ffi::Error::new(ffi::SQLITE_MISUSE),
+                    Some(err_msg),
+                )
+            })?;
 
-            let students_json: String = row.get(9)?;
-            let students: Vec<Etudiant> = serde_json::from_str(&students_json)
-                .map_err(|e| {
-                    let err_msg = format!("Failed to parse students JSON: {}", e);
-                    rusqlite::Error::SqliteFailure(
-                        ffi::Error::new(ffi::SQLITE_MISUSE),
-                        Some(err_msg),
-                    )
-                })?;
+        let students_json: String = row.get(9)?;
+        let students: Vec<Etudiant> = serde_json::from_str(&students_json)
+            .map_err(|e| {
+                let err_msg = format!("Failed to parse students JSON: {}", e);
+                rusqlite::Error::SqliteFailure(
+                    ffi::Error::new(ffi::SQLITE_MISUSE),
+                    Some(err_msg),
+                )
+            })?;
 
-            Ok(Soutenance {
-                id,
-                date,
-                hour,
-                specialite_id,
-                pfe,
-                classroom,
-                juries,
-                invitees,
-                students,
-            })
+        let session_id: Option<i32> = row.get(10)?;
+
+        Ok(Soutenance {
+            id,
+            date,
+            hour,
+            specialite_id,
+            pfe,
+            session_id,
+            classroom,
+            juries,
+            invitees,
+            students,
+            warnings: Vec::new(),
         })
-        .map_err(|e| format!("Failed to query soutenances: {}", e))?;
+    })
+}
 
-    let result: Result<Vec<Soutenance>, _> = rows.collect();
-    result.map_err(|e| format!("Failed to collect soutenances: {}", e))
+/// Soutenances of a session in chronological order, unscheduled ones last.
+pub fn session_soutenance_ids(conn: &Connection, session_id: Option<i32>) -> Result<Vec<i32>, String> {
+    let session_id = resolve_session(conn, session_id)?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id FROM soutenance WHERE ?1 IS NULL OR session_id = ?1
+            ORDER BY date IS NULL, date, hour, id",
+        )
+        .map_err(|e| e.to_string())?;
+    let ids = stmt
+        .query_map([session_id], |row| row.get(0))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<i32>>>()
+        .map_err(|e| e.to_string())?;
+    Ok(ids)
+}
+
+/// Writes the planning of a session as CSV, one line per soutenance.
+pub fn write_planning_csv(conn: &Connection, path: &str, session_id: Option<i32>) -> Result<usize, String> {
+    let mut rows = Vec::new();
+    for id in session_soutenance_ids(conn, session_id)? {
+        let soutenance = find_soutenance(conn, id)?;
+        let names = |people: Vec<String>| people.join("; ");
+        rows.push(vec![
+            soutenance.id.to_string(),
+            soutenance.date.unwrap_or_default(),
+            soutenance.hour.unwrap_or_default(),
+            soutenance.classroom.map(|c| c.name).unwrap_or_default(),
+            specialite_name(conn, soutenance.specialite_id)?.unwrap_or_default(),
+            soutenance.pfe.unwrap_or_default(),
+            names(soutenance.students.iter().map(|e| format!("{} {}", e.firstname, e.lastname)).collect()),
+            names(
+                soutenance
+                    .juries
+                    .iter()
+                    .map(|j| format!("{} {} ({})", j.jury.firstname, j.jury.lastname, j.role.label()))
+                    .collect(),
+            ),
+            names(soutenance.invitees.iter().map(|i| format!("{} {}", i.firstname, i.lastname)).collect()),
+        ]);
+    }
+    write_csv(
+        path,
+        &["id", "date", "hour", "classroom", "specialite", "pfe", "students", "jury", "invitees"],
+        &rows,
+    )
+}
+
+pub fn export_planning_csv(conn: &Connection, path: String, session_id: Option<i32>) -> Result<usize, String> {
+    write_planning_csv(conn, &path, session_id)
+}
+
+pub fn get_defence_duration(conn: &Connection) -> Result<i32, String> {
+    defence_duration(conn)
+}
+
+pub fn set_defence_duration(conn: &Connection, actor: &Option<CurrentUser>, minutes: i32) -> Result<(), String> {
+    if minutes <= 0 {
+        return Err("A defence must last at least one minute".to_string());
+    }
+    let key = [("key", DEFENCE_DURATION_KEY.to_string().into())];
+    let before = audit::snapshot(conn, "settings", &key)?;
+    set_setting(conn, DEFENCE_DURATION_KEY, Some(&minutes.to_string()))?;
+    audit::record_change(conn, actor, "settings", &key, before)?;
+    Ok(())
 }
@@ -1,27 +1,26 @@
-use crate::AppState;
-use rusqlite::Result;
+use crate::models::audit;
+use crate::models::user::CurrentUser;
+use rusqlite::{Connection, Result};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tauri::State;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct Specialite {
     pub id: i32,
     pub name: String
 }
 
-#[tauri::command]
-pub fn create_specialite(state: State<AppState>) -> Result<i32, String> {
-    let conn = state.db.lock().unwrap();
+pub fn create_specialite(conn: &Connection, actor: &Option<CurrentUser>) -> Result<i32, String> {
     conn.execute("INSERT INTO specialite DEFAULT VALUES", [])
         .map_err(|e| e.to_string())?;
-    Ok(conn.last_insert_rowid() as i32)
+    let id = conn.last_insert_rowid() as i32;
+    audit::record_change(conn, actor, "specialite", &[("id", id.into())], None)?;
+    Ok(id)
 }
 
-#[tauri::command]
-pub fn get_specialite(id: i32, state: State<AppState>) -> Result<Specialite, String> {
-    let conn = state.db.lock().unwrap();
+pub fn get_specialite(conn: &Connection, id: i32) -> Result<Specialite, String> {
     let specialite = conn
-        .query_row("SELECT id, name FROM specialite WHERE id = ?1", [id], |row| {
+        .query_row("SELECT id, IFNULL(name, '') FROM specialite WHERE id = ?1", [id], |row| {
             Ok(Specialite {
                 id: row.get(0)?,
                 name: row.get(1)?,
@@ -31,23 +30,16 @@ pub fn get_specialite(id: i32, state: State<AppState>) -> Result<Specialite, Str
     Ok(specialite)
 }
 
-#[tauri::command]
-pub fn update_specialite(_id: i32, _state: State<AppState>) -> Result<(), String> {
-    Ok(())
-}
-
-#[tauri::command]
-pub fn delete_specialite(id: i32, state: State<AppState>) -> Result<(), String> {
-    let conn = state.db.lock().unwrap();
+pub fn delete_specialite(conn: &Connection, actor: &Option<CurrentUser>, id: i32) -> Result<(), String> {
+    let before = audit::snapshot(conn, "specialite", &[("id", id.into())])?;
     conn.execute("DELETE FROM specialite WHERE id = ?1", [id])
         .map_err(|e| e.to_string())?;
+    audit::record_change(conn, actor, "specialite", &[("id", id.into())], before)?;
     Ok(())
 }
 
 
-#[tauri::command]
-pub fn get_specialite_pfes(specialite_id: i32, state: State<AppState>) -> Result<Vec<i32>, String> {
-    let conn = state.db.lock().unwrap();
+pub fn get_specialite_pfes(conn: &Connection, specialite_id: i32) -> Result<Vec<i32>, String> {
     let mut stmt = conn
         .prepare("SELECT id FROM pfe WHERE specialite_id = ?1")
         .map_err(|e| e.to_string())?;
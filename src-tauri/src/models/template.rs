@@ -0,0 +1,884 @@
+use crate::export::escape_html;
+use crate::models::audit;
+use crate::models::classroom::Salle;
+use crate::models::etudiant::Etudiant;
+use crate::models::invite::Invitee;
+use crate::models::jury::Jury;
+use crate::models::jury_soutenance::JuryRole;
+use crate::models::notification::{Contact, Schedule};
+use crate::models::settings::{get_setting, set_setting};
+use crate::models::soutenance::{find_soutenance, JurySoutenanceDetails, Soutenance};
+use crate::templating::{self, Template};
+use crate::models::user::CurrentUser;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+pub const LANGUAGE_KEY: &str = "template_language";
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    Fr,
+    En,
+    Ar,
+}
+
+impl Language {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Language::Fr => "fr",
+            Language::En => "en",
+            Language::Ar => "ar",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Language, String> {
+        match value {
+            "fr" => Ok(Language::Fr),
+            "en" => Ok(Language::En),
+            "ar" => Ok(Language::Ar),
+            other => Err(format!("Unknown language: {}", other)),
+        }
+    }
+}
+
+/// What a template is used for; each kind has its own variables.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateKind {
+    JuryConvocation,
+    Invitation,
+    Reminder,
+    SoutenanceChange,
+    /// The procès-verbal of a defence; a document, so it has no subject.
+    Pv,
+}
+
+impl TemplateKind {
+    pub const ALL: [TemplateKind; 5] = [
+        TemplateKind::JuryConvocation,
+        TemplateKind::Invitation,
+        TemplateKind::Reminder,
+        TemplateKind::SoutenanceChange,
+        TemplateKind::Pv,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TemplateKind::JuryConvocation => "jury_convocation",
+            TemplateKind::Invitation => "invitation",
+            TemplateKind::Reminder => "reminder",
+            TemplateKind::SoutenanceChange => "soutenance_change",
+            TemplateKind::Pv => "pv",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<TemplateKind, String> {
+        TemplateKind::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == value)
+            .ok_or_else(|| format!("Unknown template: {}", value))
+    }
+
+    fn is_document(&self) -> bool {
+        *self == TemplateKind::Pv
+    }
+}
+
+/// A template saved in the database, either the default for a language or an override
+/// for one specialité.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct StoredTemplate {
+    pub id: i32,
+    pub kind: TemplateKind,
+    pub language: Language,
+    pub specialite_id: Option<i32>,
+    pub subject: String,
+    pub body: String,
+    pub updated_at: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct TemplateInput {
+    pub kind: TemplateKind,
+    pub language: Language,
+    pub specialite_id: Option<i32>,
+    #[serde(default)]
+    pub subject: String,
+    pub body: String,
+}
+
+/// The template actually used for a kind, language and specialité, and where it came from.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct EffectiveTemplate {
+    pub kind: TemplateKind,
+    pub language: Language,
+    /// The stored template in use, `None` for the built-in one.
+    pub id: Option<i32>,
+    pub specialite_id: Option<i32>,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct Rendered {
+    pub subject: String,
+    pub body: String,
+}
+
+const TEMPLATE_SELECT: &str = "SELECT id, kind, language, specialite_id, subject, body, updated_at FROM template";
+
+fn map_template(row: &rusqlite::Row) -> Result<StoredTemplate> {
+    let kind: String = row.get(1)?;
+    let language: String = row.get(2)?;
+    Ok(StoredTemplate {
+        id: row.get(0)?,
+        kind: TemplateKind::parse(&kind).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, e.into())
+        })?,
+        language: Language::parse(&language).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, e.into())
+        })?,
+        specialite_id: row.get(3)?,
+        subject: row.get(4)?,
+        body: row.get(5)?,
+        updated_at: row.get(6)?,
+    })
+}
+
+pub fn role_label(role: JuryRole, language: Language) -> &'static str {
+    match (language, role) {
+        (Language::Fr, role) => role.label(),
+        (Language::En, JuryRole::President) => "Chair",
+        (Language::En, JuryRole::Rapporteur) => "Reviewer",
+        (Language::En, JuryRole::Examinateur) => "Examiner",
+        (Language::En, JuryRole::EncadrantAcademique) => "Academic supervisor",
+        (Language::En, JuryRole::EncadrantProfessionnel) => "Industry supervisor",
+        (Language::En, JuryRole::InviteHonneur) => "Guest of honour",
+        (Language::Ar, JuryRole::President) => "رئيس",
+        (Language::Ar, JuryRole::Rapporteur) => "مقرر",
+        (Language::Ar, JuryRole::Examinateur) => "ممتحن",
+        (Language::Ar, JuryRole::EncadrantAcademique) => "مؤطر أكاديمي",
+        (Language::Ar, JuryRole::EncadrantProfessionnel) => "مؤطر مهني",
+        (Language::Ar, JuryRole::InviteHonneur) => "ضيف شرف",
+    }
+}
+
+/// Name of a soutenance field in change notifications.
+pub fn field_label(field: &str, language: Language) -> &str {
+    match (language, field) {
+        (Language::Fr, "date") | (Language::En, "date") => "Date",
+        (Language::Fr, "hour") => "Heure",
+        (Language::Fr, "classroom") => "Salle",
+        (Language::Fr, "pfe") => "Sujet",
+        (Language::Fr, "role") => "Rôle",
+        (Language::En, "hour") => "Time",
+        (Language::En, "classroom") => "Room",
+        (Language::En, "pfe") => "Topic",
+        (Language::En, "role") => "Role",
+        (Language::Ar, "date") => "التاريخ",
+        (Language::Ar, "hour") => "الساعة",
+        (Language::Ar, "classroom") => "القاعة",
+        (Language::Ar, "pfe") => "الموضوع",
+        (Language::Ar, "role") => "الدور",
+        (_, other) => other,
+    }
+}
+
+pub fn language(conn: &Connection) -> Result<Language, String> {
+    match get_setting(conn, LANGUAGE_KEY)? {
+        Some(language) => Language::parse(&language),
+        None => Ok(Language::Fr),
+    }
+}
+
+fn builtin(kind: TemplateKind, language: Language) -> (&'static str, &'static str) {
+    match (kind, language) {
+        (TemplateKind::JuryConvocation, Language::Fr) => (
+            "Convocation : soutenance du {{ soutenance.date }} à {{ soutenance.hour }}",
+            "Bonjour {{ recipient.firstname }} {{ recipient.lastname }},
+
+Vous êtes convoqué(e) en tant que {{ role | lower }} à la soutenance « {{ soutenance.pfe }} »
+le {{ soutenance.date }} à {{ soutenance.hour }}, salle {% if salle %}{{ salle.name }}{% else %}à définir{% endif %}.
+
+Composition du jury :
+{% for jury in soutenance.juries %}
+- {{ jury.firstname }} {{ jury.lastname }} ({{ jury.role_label }})
+{% endfor %}
+
+Cordialement,
+Le service des soutenances",
+        ),
+        (TemplateKind::JuryConvocation, Language::En) => (
+            "Notice: defence on {{ soutenance.date }} at {{ soutenance.hour }}",
+            "Dear {{ recipient.firstname }} {{ recipient.lastname }},
+
+You are invited to sit as {{ role | lower }} on the defence \"{{ soutenance.pfe }}\"
+on {{ soutenance.date }} at {{ soutenance.hour }}, room {% if salle %}{{ salle.name }}{% else %}to be announced{% endif %}.
+
+Jury:
+{% for jury in soutenance.juries %}
+- {{ jury.firstname }} {{ jury.lastname }} ({{ jury.role_label }})
+{% endfor %}
+
+Kind regards,
+The defences office",
+        ),
+        (TemplateKind::JuryConvocation, Language::Ar) => (
+            "استدعاء: مناقشة يوم {{ soutenance.date }} على الساعة {{ soutenance.hour }}",
+            "السيد(ة) {{ recipient.firstname }} {{ recipient.lastname }}،
+
+يشرفنا دعوتكم بصفتكم {{ role }} في مناقشة مشروع «{{ soutenance.pfe }}»
+يوم {{ soutenance.date }} على الساعة {{ soutenance.hour }}، القاعة {% if salle %}{{ salle.name }}{% else %}تحدد لاحقا{% endif %}.
+
+تركيبة اللجنة:
+{% for jury in soutenance.juries %}
+- {{ jury.firstname }} {{ jury.lastname }} ({{ jury.role_label }})
+{% endfor %}
+
+مع التحية،
+مصلحة المناقشات",
+        ),
+        (TemplateKind::Invitation, Language::Fr) => (
+            "Invitation : soutenance du {{ soutenance.date }} à {{ soutenance.hour }}",
+            "Bonjour {{ recipient.firstname }} {{ recipient.lastname }},
+
+Vous êtes invité(e) à la soutenance « {{ soutenance.pfe }} »
+le {{ soutenance.date }} à {{ soutenance.hour }}, salle {% if salle %}{{ salle.name }}{% else %}à définir{% endif %}.
+
+Merci de confirmer votre présence avec le code suivant : {{ rsvp_token }}
+
+Cordialement,
+Le service des soutenances",
+        ),
+        (TemplateKind::Invitation, Language::En) => (
+            "Invitation: defence on {{ soutenance.date }} at {{ soutenance.hour }}",
+            "Dear {{ recipient.firstname }} {{ recipient.lastname }},
+
+You are invited to the defence \"{{ soutenance.pfe }}\"
+on {{ soutenance.date }} at {{ soutenance.hour }}, room {% if salle %}{{ salle.name }}{% else %}to be announced{% endif %}.
+
+Please confirm your attendance with the following code: {{ rsvp_token }}
+
+Kind regards,
+The defences office",
+        ),
+        (TemplateKind::Invitation, Language::Ar) => (
+            "دعوة: مناقشة يوم {{ soutenance.date }} على الساعة {{ soutenance.hour }}",
+            "السيد(ة) {{ recipient.firstname }} {{ recipient.lastname }}،
+
+يسعدنا دعوتكم لحضور مناقشة مشروع «{{ soutenance.pfe }}»
+يوم {{ soutenance.date }} على الساعة {{ soutenance.hour }}، القاعة {% if salle %}{{ salle.name }}{% else %}تحدد لاحقا{% endif %}.
+
+يرجى تأكيد حضوركم باستعمال الرمز التالي: {{ rsvp_token }}
+
+مع التحية،
+مصلحة المناقشات",
+        ),
+        (TemplateKind::Reminder, Language::Fr) => (
+            "Rappel : soutenance le {{ soutenance.date }} à {{ soutenance.hour }}",
+            "Bonjour {{ recipient.firstname }} {{ recipient.lastname }},
+
+Pour rappel, vous participez{% if role %} en tant que {{ role | lower }}{% endif %} à la soutenance « {{ soutenance.pfe }} »,
+le {{ soutenance.date }} à {{ soutenance.hour }}, salle {% if salle %}{{ salle.name }}{% else %}à définir{% endif %}.
+
+Cordialement,
+Le service des soutenances",
+        ),
+        (TemplateKind::Reminder, Language::En) => (
+            "Reminder: defence on {{ soutenance.date }} at {{ soutenance.hour }}",
+            "Dear {{ recipient.firstname }} {{ recipient.lastname }},
+
+This is a reminder that you take part{% if role %} as {{ role | lower }}{% endif %} in the defence \"{{ soutenance.pfe }}\",
+on {{ soutenance.date }} at {{ soutenance.hour }}, room {% if salle %}{{ salle.name }}{% else %}to be announced{% endif %}.
+
+Kind regards,
+The defences office",
+        ),
+        (TemplateKind::Reminder, Language::Ar) => (
+            "تذكير: مناقشة يوم {{ soutenance.date }} على الساعة {{ soutenance.hour }}",
+            "السيد(ة) {{ recipient.firstname }} {{ recipient.lastname }}،
+
+نذكركم بمشاركتكم{% if role %} بصفتكم {{ role }}{% endif %} في مناقشة مشروع «{{ soutenance.pfe }}»،
+يوم {{ soutenance.date }} على الساعة {{ soutenance.hour }}، القاعة {% if salle %}{{ salle.name }}{% else %}تحدد لاحقا{% endif %}.
+
+مع التحية،
+مصلحة المناقشات",
+        ),
+        (TemplateKind::SoutenanceChange, Language::Fr) => (
+            "{% if cancelled %}Soutenance annulée{% elif added %}Nouvelle soutenance{% elif removed %}Retrait d'une soutenance{% else %}Modification de votre soutenance{% endif %}",
+            "Bonjour {{ recipient.firstname }} {{ recipient.lastname }},
+
+{% if cancelled %}
+La soutenance « {{ soutenance.pfe }} » du {{ soutenance.date }} à {{ soutenance.hour }} est annulée.
+{% elif added %}
+Vous avez été ajouté(e){% if role %} en tant que {{ role | lower }}{% endif %} à la soutenance « {{ soutenance.pfe }} »,
+le {{ soutenance.date }} à {{ soutenance.hour }}, salle {% if salle %}{{ salle.name }}{% else %}à définir{% endif %}.
+{% elif removed %}
+Vous ne participez plus à la soutenance « {{ soutenance.pfe }} » du {{ soutenance.date }} à {{ soutenance.hour }}.
+{% else %}
+Votre soutenance « {{ soutenance.pfe }} » a été modifiée :
+{% for change in changes %}
+- {{ change.label }} : {% if change.old %}{{ change.old }}{% else %}non défini{% endif %} → {% if change.new %}{{ change.new }}{% else %}non défini{% endif %}
+{% endfor %}
+
+Elle aura lieu le {{ soutenance.date }} à {{ soutenance.hour }}, salle {% if salle %}{{ salle.name }}{% else %}à définir{% endif %}.
+{% endif %}
+
+Cordialement,
+Le service des soutenances",
+        ),
+        (TemplateKind::SoutenanceChange, Language::En) => (
+            "{% if cancelled %}Defence cancelled{% elif added %}New defence{% elif removed %}Removed from a defence{% else %}Your defence has changed{% endif %}",
+            "Dear {{ recipient.firstname }} {{ recipient.lastname }},
+
+{% if cancelled %}
+The defence \"{{ soutenance.pfe }}\" on {{ soutenance.date }} at {{ soutenance.hour }} is cancelled.
+{% elif added %}
+You have been added{% if role %} as {{ role | lower }}{% endif %} to the defence \"{{ soutenance.pfe }}\",
+on {{ soutenance.date }} at {{ soutenance.hour }}, room {% if salle %}{{ salle.name }}{% else %}to be announced{% endif %}.
+{% elif removed %}
+You no longer take part in the defence \"{{ soutenance.pfe }}\" on {{ soutenance.date }} at {{ soutenance.hour }}.
+{% else %}
+Your defence \"{{ soutenance.pfe }}\" has changed:
+{% for change in changes %}
+- {{ change.label }}: {% if change.old %}{{ change.old }}{% else %}not set{% endif %} → {% if change.new %}{{ change.new }}{% else %}not set{% endif %}
+{% endfor %}
+
+It will take place on {{ soutenance.date }} at {{ soutenance.hour }}, room {% if salle %}{{ salle.name }}{% else %}to be announced{% endif %}.
+{% endif %}
+
+Kind regards,
+The defences office",
+        ),
+        (TemplateKind::SoutenanceChange, Language::Ar) => (
+            "{% if cancelled %}إلغاء مناقشة{% elif added %}مناقشة جديدة{% elif removed %}سحب من مناقشة{% else %}تعديل مناقشتكم{% endif %}",
+            "السيد(ة) {{ recipient.firstname }} {{ recipient.lastname }}،
+
+{% if cancelled %}
+تم إلغاء مناقشة مشروع «{{ soutenance.pfe }}» المقررة يوم {{ soutenance.date }} على الساعة {{ soutenance.hour }}.
+{% elif added %}
+تمت إضافتكم{% if role %} بصفتكم {{ role }}{% endif %} إلى مناقشة مشروع «{{ soutenance.pfe }}»،
+يوم {{ soutenance.date }} على الساعة {{ soutenance.hour }}، القاعة {% if salle %}{{ salle.name }}{% else %}تحدد لاحقا{% endif %}.
+{% elif removed %}
+لم تعودوا مشاركين في مناقشة مشروع «{{ soutenance.pfe }}» المقررة يوم {{ soutenance.date }} على الساعة {{ soutenance.hour }}.
+{% else %}
+تم تعديل مناقشة مشروع «{{ soutenance.pfe }}»:
+{% for change in changes %}
+- {{ change.label }}: {% if change.old %}{{ change.old }}{% else %}غير محدد{% endif %} ← {% if change.new %}{{ change.new }}{% else %}غير محدد{% endif %}
+{% endfor %}
+
+ستجرى المناقشة يوم {{ soutenance.date }} على الساعة {{ soutenance.hour }}، القاعة {% if salle %}{{ salle.name }}{% else %}تحدد لاحقا{% endif %}.
+{% endif %}
+
+مع التحية،
+مصلحة المناقشات",
+        ),
+        (TemplateKind::Pv, Language::Fr) => (
+            "",
+            "PROCÈS-VERBAL DE SOUTENANCE
+
+Spécialité : {{ specialite.name }}
+Sujet : {{ soutenance.pfe }}
+Date : {{ soutenance.date }} à {{ soutenance.hour }}
+Salle : {% if salle %}{{ salle.name }}{% else %}-{% endif %}
+
+Étudiant(e)s :
+{% for student in soutenance.students %}
+- {{ student.lastname | upper }} {{ student.firstname }}{% if student.grade %} : {{ student.grade }}/20{% endif %}
+{% endfor %}
+
+Membres du jury :
+{% for jury in soutenance.juries %}
+- {{ jury.role_label }} : {{ jury.firstname }} {{ jury.lastname }}
+{% endfor %}
+
+Décision du jury : ..............................................
+
+Signatures :
+{% for jury in soutenance.juries %}
+{{ jury.firstname }} {{ jury.lastname }} : ____________________
+{% endfor %}",
+        ),
+        (TemplateKind::Pv, Language::En) => (
+            "",
+            "DEFENCE REPORT
+
+Programme: {{ specialite.name }}
+Topic: {{ soutenance.pfe }}
+Date: {{ soutenance.date }} at {{ soutenance.hour }}
+Room: {% if salle %}{{ salle.name }}{% else %}-{% endif %}
+
+Students:
+{% for student in soutenance.students %}
+- {{ student.lastname | upper }} {{ student.firstname }}{% if student.grade %}: {{ student.grade }}/20{% endif %}
+{% endfor %}
+
+Jury:
+{% for jury in soutenance.juries %}
+- {{ jury.role_label }}: {{ jury.firstname }} {{ jury.lastname }}
+{% endfor %}
+
+Decision of the jury: ..............................................
+
+Signatures:
+{% for jury in soutenance.juries %}
+{{ jury.firstname }} {{ jury.lastname }}: ____________________
+{% endfor %}",
+        ),
+        (TemplateKind::Pv, Language::Ar) => (
+            "",
+            "محضر مناقشة
+
+الاختصاص: {{ specialite.name }}
+الموضوع: {{ soutenance.pfe }}
+التاريخ: {{ soutenance.date }} على الساعة {{ soutenance.hour }}
+القاعة: {% if salle %}{{ salle.name }}{% else %}-{% endif %}
+
+الطلبة:
+{% for student in soutenance.students %}
+- {{ student.lastname }} {{ student.firstname }}{% if student.grade %}: {{ student.grade }}/20{% endif %}
+{% endfor %}
+
+أعضاء اللجنة:
+{% for jury in soutenance.juries %}
+- {{ jury.role_label }}: {{ jury.firstname }} {{ jury.lastname }}
+{% endfor %}
+
+قرار اللجنة: ..............................................
+
+الإمضاءات:
+{% for jury in soutenance.juries %}
+{{ jury.firstname }} {{ jury.lastname }}: ____________________
+{% endfor %}",
+        ),
+    }
+}
+
+/// Adds the translated role of each jury member as `role_label`.
+fn label_roles(value: &mut Value, language: Language) {
+    let Some(juries) = value.get_mut("juries").and_then(Value::as_array_mut) else {
+        return;
+    };
+    for jury in juries {
+        let label = jury
+            .get("role")
+            .and_then(Value::as_str)
+            .and_then(|role| JuryRole::parse(role).ok())
+            .map(|role| role_label(role, language));
+        if let Some(object) = jury.as_object_mut() {
+            object.insert("role_label".to_string(), json!(label));
+        }
+    }
+}
+
+fn base_context(soutenance: &Soutenance, specialite: Option<String>, language: Language) -> Result<Value, String> {
+    let mut value = serde_json::to_value(soutenance).map_err(|e| e.to_string())?;
+    label_roles(&mut value, language);
+    Ok(json!({
+        "salle": soutenance.classroom,
+        "specialite": { "id": soutenance.specialite_id, "name": specialite },
+        "soutenance": value,
+    }))
+}
+
+/// Variables about a soutenance: `soutenance` (with its `juries`, `students` and
+/// `invitees`), `salle` and `specialite`.
+pub fn soutenance_context(conn: &Connection, soutenance_id: i32, language: Language) -> Result<Value, String> {
+    let mut soutenance = find_soutenance(conn, soutenance_id)?;
+    soutenance.juries.retain(|jury| jury.jury.deleted_at.is_none());
+    soutenance.juries.sort_by(|a, b| (&a.jury.lastname, &a.jury.firstname).cmp(&(&b.jury.lastname, &b.jury.firstname)));
+    soutenance.invitees.retain(|invitee| invitee.deleted_at.is_none());
+    soutenance.students.retain(|student| student.deleted_at.is_none());
+    let specialite = specialite_name(conn, soutenance.specialite_id)?;
+    base_context(&soutenance, specialite, language)
+}
+
+pub fn specialite_name(conn: &Connection, id: i32) -> Result<Option<String>, String> {
+    Ok(conn
+        .query_row("SELECT name FROM specialite WHERE id = ?1", [id], |row| row.get(0))
+        .optional()
+        .map_err(|e| e.to_string())?
+        .flatten())
+}
+
+/// Adds who the message is for; every message kind has these variables.
+pub fn with_recipient(mut context: Value, recipient: Value, role: Option<&str>, rsvp_token: Option<&str>) -> Value {
+    if let Some(object) = context.as_object_mut() {
+        object.insert("recipient".to_string(), recipient);
+        object.insert("role".to_string(), json!(role));
+        object.insert("rsvp_token".to_string(), json!(rsvp_token));
+    }
+    context
+}
+
+fn sample_jury(id: i32, firstname: &str, lastname: &str, role: JuryRole) -> JurySoutenanceDetails {
+    JurySoutenanceDetails {
+        jury: Jury {
+            id,
+            firstname: firstname.to_string(),
+            lastname: lastname.to_string(),
+            email: format!("{}.{}@example.com", firstname.to_lowercase(), lastname.to_lowercase()),
+            deleted_at: None,
+        },
+        role,
+    }
+}
+
+/// Made-up data with every variable of `kind` set, used for previews and validation.
+pub fn sample_context(kind: TemplateKind, language: Language) -> Value {
+    let salle = Salle {
+        id: 1,
+        name: "A12".to_string(),
+        deleted_at: None,
+        capacity: Some(40),
+        building: Some("Bloc A".to_string()),
+        floor: Some("1".to_string()),
+        equipment: vec!["projector".to_string()],
+    };
+    let president = sample_jury(1, "Amel", "Ben Salah", JuryRole::President);
+    let soutenance = Soutenance {
+        id: 1,
+        date: Some("2030-06-15".to_string()),
+        hour: Some("09:00".to_string()),
+        specialite_id: 1,
+        pfe: Some("Optimisation d'une ligne d'assemblage".to_string()),
+        session_id: Some(1),
+        classroom: Some(salle),
+        juries: vec![
+            president.clone(),
+            sample_jury(2, "Karim", "Trabelsi", JuryRole::Rapporteur),
+            sample_jury(3, "Sonia", "Gharbi", JuryRole::Examinateur),
+        ],
+        invitees: vec![Invitee {
+            id: 1,
+            firstname: "Hedi".to_string(),
+            lastname: "Mansour".to_string(),
+            email: "hedi.mansour@example.com".to_string(),
+            deleted_at: None,
+        }],
+        students: vec![Etudiant {
+            id: 1,
+            firstname: "Yasmine".to_string(),
+            lastname: "Jlassi".to_string(),
+            address: "Tunis".to_string(),
+            specialite_id: 1,
+            soutenance_id: Some(1),
+            session_id: Some(1),
+            grade: Some(16.5),
+            deleted_at: None,
+            supervisor_id: Some(3),
+            email: Some("yasmine.jlassi@example.com".to_string()),
+        }],
+        warnings: Vec::new(),
+    };
+    let specialite = "Licence Génie Industriel".to_string();
+    let context = base_context(&soutenance, Some(specialite.clone()), language).expect("sample data serializes");
+    let contact = |id: i32, firstname: &str, lastname: &str, email: &str| {
+        json!(Contact { id, firstname: firstname.to_string(), lastname: lastname.to_string(), email: email.to_string() })
+    };
+    let jury = contact(1, "Amel", "Ben Salah", &president.jury.email);
+    let role = role_label(JuryRole::President, language);
+    match kind {
+        TemplateKind::JuryConvocation | TemplateKind::Reminder => with_recipient(context, jury, Some(role), None),
+        TemplateKind::Invitation => with_recipient(
+            context,
+            contact(1, "Hedi", "Mansour", "hedi.mansour@example.com"),
+            None,
+            Some("3f2b9c0e5d8a4b7c9e1f2a3b4c5d6e7f"),
+        ),
+        TemplateKind::SoutenanceChange => {
+            let schedule = Schedule {
+                date: soutenance.date.clone(),
+                hour: soutenance.hour.clone(),
+                classroom: Some("A12".to_string()),
+                pfe: soutenance.pfe.clone(),
+                specialite_id: Some(soutenance.specialite_id),
+            };
+            let changes = vec![
+                json!({ "field": "date", "label": field_label("date", language), "old": "2030-06-14", "new": "2030-06-15" }),
+                json!({ "field": "classroom", "label": field_label("classroom", language), "old": null, "new": "A12" }),
+            ];
+            with_recipient(
+                change_context(soutenance.id, &schedule, Some(specialite), ChangeKind::Modified, changes),
+                jury,
+                Some(role),
+                None,
+            )
+        }
+        TemplateKind::Pv => context,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    Cancelled,
+    Added,
+    Removed,
+    Modified,
+}
+
+/// Variables of a change notification. The soutenance may be gone by then, so only what
+/// was recorded with the change is there, plus the `cancelled`, `added` and `removed`
+/// flags and the `changes` list of `field`, `label`, `old` and `new`.
+pub fn change_context(
+    soutenance_id: i32,
+    schedule: &Schedule,
+    specialite: Option<String>,
+    kind: ChangeKind,
+    changes: Vec<Value>,
+) -> Value {
+    json!({
+        "soutenance": {
+            "id": soutenance_id,
+            "date": schedule.date,
+            "hour": schedule.hour,
+            "pfe": schedule.pfe,
+            "specialite_id": schedule.specialite_id,
+        },
+        "salle": schedule.classroom.as_ref().map(|name| json!({ "name": name })),
+        "specialite": { "id": schedule.specialite_id, "name": specialite },
+        "cancelled": kind == ChangeKind::Cancelled,
+        "added": kind == ChangeKind::Added,
+        "removed": kind == ChangeKind::Removed,
+        "changes": changes,
+    })
+}
+
+/// The stored template for the specialité, else the stored default, else the built-in one.
+pub fn resolve(
+    conn: &Connection,
+    kind: TemplateKind,
+    language: Language,
+    specialite_id: Option<i32>,
+) -> Result<EffectiveTemplate, String> {
+    let stored = conn
+        .query_row(
+            &format!(
+                "{} WHERE kind = ?1 AND language = ?2 AND (specialite_id = ?3 OR specialite_id IS NULL)
+                ORDER BY specialite_id IS NULL LIMIT 1",
+                TEMPLATE_SELECT
+            ),
+            params![kind.as_str(), language.as_str(), specialite_id],
+            map_template,
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    Ok(match stored {
+        Some(stored) => EffectiveTemplate {
+            kind,
+            language,
+            id: Some(stored.id),
+            specialite_id: stored.specialite_id,
+            subject: stored.subject,
+            body: stored.body,
+        },
+        None => {
+            let (subject, body) = builtin(kind, language);
+            EffectiveTemplate {
+                kind,
+                language,
+                id: None,
+                specialite_id: None,
+                subject: subject.to_string(),
+                body: body.to_string(),
+            }
+        }
+    })
+}
+
+fn render_text(source: &str, context: &Value, part: &str) -> Result<String, String> {
+    Template::parse(source)
+        .and_then(|template| template.render(context))
+        .map_err(|e| format!("Template {}: {}", part, e))
+}
+
+/// Renders the template in effect for `kind` with `context`, in the configured language.
+pub fn render(
+    conn: &Connection,
+    kind: TemplateKind,
+    specialite_id: Option<i32>,
+    context: &Value,
+) -> Result<Rendered, String> {
+    let template = resolve(conn, kind, language(conn)?, specialite_id)?;
+    Ok(Rendered {
+        subject: render_text(&template.subject, context, "subject")?.trim().to_string(),
+        body: render_text(&template.body, context, "body")?,
+    })
+}
+
+/// Syntax errors and unknown variables, each prefixed with the part it was found in.
+fn problems(kind: TemplateKind, language: Language, subject: &str, body: &str) -> Vec<String> {
+    let sample = sample_context(kind, language);
+    let mut problems = Vec::new();
+    for (part, source) in [("subject", subject), ("body", body)] {
+        match Template::parse(source) {
+            Ok(template) => {
+                problems.extend(template.unknown_variables(&sample).into_iter().map(|p| format!("{}: {}", part, p)))
+            }
+            Err(e) => problems.push(format!("{}: {}", part, e)),
+        }
+    }
+    problems
+}
+
+fn find_template(conn: &Connection, id: i32) -> Result<StoredTemplate, String> {
+    conn.query_row(&format!("{} WHERE id = ?1", TEMPLATE_SELECT), [id], map_template)
+        .map_err(|e| format!("Template {} not found: {}", id, e))
+}
+
+pub fn get_templates(conn: &Connection, kind: Option<TemplateKind>) -> Result<Vec<StoredTemplate>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "{} WHERE ?1 IS NULL OR kind = ?1 ORDER BY kind, language, specialite_id",
+            TEMPLATE_SELECT
+        ))
+        .map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([kind.map(|k| k.as_str())], map_template).map_err(|e| e.to_string())?;
+    let result: Result<Vec<_>, _> = rows.collect();
+    result.map_err(|e| e.to_string())
+}
+
+pub fn get_effective_template(
+    conn: &Connection,
+    kind: TemplateKind,
+    language: Language,
+    specialite_id: Option<i32>,
+) -> Result<EffectiveTemplate, String> {
+    resolve(conn, kind, language, specialite_id)
+}
+
+/// Saves a template, replacing the one stored for the same kind, language and
+/// specialité. Templates with syntax errors or unknown variables are refused.
+pub fn save_template(conn: &Connection, actor: &Option<CurrentUser>, template: TemplateInput) -> Result<i32, String> {
+    let subject = if template.kind.is_document() { "" } else { template.subject.trim() };
+    if !template.kind.is_document() && subject.is_empty() {
+        return Err("An email template needs a subject".to_string());
+    }
+    let problems = problems(template.kind, template.language, subject, &template.body);
+    if !problems.is_empty() {
+        return Err(format!("Invalid template: {}", problems.join("; ")));
+    }
+    let existing: Option<i32> = conn
+        .query_row(
+            "SELECT id FROM template WHERE kind = ?1 AND language = ?2 AND specialite_id IS ?3",
+            params![template.kind.as_str(), template.language.as_str(), template.specialite_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| e.to_string())?;
+    let id = match existing {
+        Some(id) => {
+            let before = audit::snapshot(conn, "template", &[("id", id.into())])?;
+            conn.execute(
+                "UPDATE template SET subject = ?1, body = ?2, updated_at = datetime('now') WHERE id = ?3",
+                params![subject, template.body, id],
+            )
+            .map_err(|e| format!("Failed to save template: {}", e))?;
+            audit::record_change(conn, actor, "template", &[("id", id.into())], before)?;
+            id
+        }
+        None => {
+            conn.execute(
+                "INSERT INTO template (kind, language, specialite_id, subject, body) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![template.kind.as_str(), template.language.as_str(), template.specialite_id, subject, template.body],
+            )
+            .map_err(|e| format!("Failed to save template: {}", e))?;
+            let id = conn.last_insert_rowid() as i32;
+            audit::record_change(conn, actor, "template", &[("id", id.into())], None)?;
+            id
+        }
+    };
+    Ok(id)
+}
+
+/// Removes a stored template; the next one in line (the default, then the built-in
+/// wording) takes over.
+pub fn delete_template(conn: &Connection, actor: &Option<CurrentUser>, id: i32) -> Result<(), String> {
+    find_template(conn, id)?;
+    let before = audit::snapshot(conn, "template", &[("id", id.into())])?;
+    conn.execute("DELETE FROM template WHERE id = ?1", [id])
+        .map_err(|e| format!("Failed to delete template: {}", e))?;
+    audit::record_change(conn, actor, "template", &[("id", id.into())], before)?;
+    Ok(())
+}
+
+/// Lists what is wrong with a draft; empty when it can be saved.
+pub fn validate_template(kind: TemplateKind, language: Language, subject: String, body: String) -> Vec<String> {
+    problems(kind, language, &subject, &body)
+}
+
+/// Renders a draft, or the template in effect when no draft is given, with sample data.
+pub fn preview_template(
+    conn: &Connection,
+    kind: TemplateKind,
+    language: Language,
+    specialite_id: Option<i32>,
+    subject: Option<String>,
+    body: Option<String>,
+) -> Result<Rendered, String> {
+    let effective = resolve(conn, kind, language, specialite_id)?;
+    let sample = sample_context(kind, language);
+    Ok(Rendered {
+        subject: render_text(subject.as_deref().unwrap_or(&effective.subject), &sample, "subject")?.trim().to_string(),
+        body: render_text(body.as_deref().unwrap_or(&effective.body), &sample, "body")?,
+    })
+}
+
+/// The variables a template of this kind can use.
+pub fn get_template_variables(kind: TemplateKind) -> Vec<String> {
+    templating::variables(&sample_context(kind, Language::Fr))
+}
+
+pub fn get_template_language(conn: &Connection) -> Result<Language, String> {
+    language(conn)
+}
+
+/// Sets the language emails are written in.
+pub fn set_template_language(conn: &Connection, actor: &Option<CurrentUser>, language: Language) -> Result<(), String> {
+    let key = [("key", LANGUAGE_KEY.to_string().into())];
+    let before = audit::snapshot(conn, "settings", &key)?;
+    set_setting(conn, LANGUAGE_KEY, Some(language.as_str()))?;
+    audit::record_change(conn, actor, "settings", &key, before)?;
+    Ok(())
+}
+
+/// Writes the procès-verbal of a soutenance as a print-ready HTML page, right to left
+/// in Arabic. Uses the configured language unless one is given.
+pub fn export_pv(
+    conn: &Connection,
+    path: String,
+    soutenance_id: i32,
+    language: Option<Language>,
+) -> Result<(), String> {
+    let (template, context) = pv_source(conn, soutenance_id, language)?;
+    write_pv(&path, &template, &context)
+}
+
+/// Resolves the PV template of a soutenance together with its context.
+pub fn pv_source(conn: &Connection, soutenance_id: i32, language: Option<Language>) -> Result<(EffectiveTemplate, Value), String> {
+    let language = match language {
+        Some(language) => language,
+        None => self::language(conn)?,
+    };
+    let context = soutenance_context(conn, soutenance_id, language)?;
+    let specialite_id = context["soutenance"]["specialite_id"].as_i64().map(|id| id as i32);
+    Ok((resolve(conn, TemplateKind::Pv, language, specialite_id)?, context))
+}
+
+pub fn write_pv(path: &str, template: &EffectiveTemplate, context: &Value) -> Result<(), String> {
+    let text = render_text(&template.body, context, "body")?;
+    let direction = if template.language == Language::Ar { "rtl" } else { "ltr" };
+    let html = format!(
+        "<!DOCTYPE html>\n<html lang=\"{}\" dir=\"{}\"><head><meta charset=\"utf-8\"><title>PV</title><style>\
+        @page {{ size: A4; margin: 20mm; }}\
+        body {{ font-family: serif; white-space: pre-wrap; line-height: 1.5; }}\
+        </style></head><body>{}</body></html>\n",
+        template.language.as_str(),
+        direction,
+        escape_html(&text)
+    );
+    std::fs::write(path, html).map_err(|e| format!("Failed to write {}: {}", path, e))
+}
@@ -1,10 +1,11 @@
-use crate::AppState;
-use rusqlite::Result;
+use crate::models::audit;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use tauri::State;
+use uuid::Uuid;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, JsonSchema)]
 pub struct User {
     pub id: i32,
     pub name: String,
@@ -14,29 +15,44 @@ pub struct User {
     pub password: String,
 }
 
-#[tauri::command]
-pub fn create_user(
-    name: String,
-    email: String,
-    password: String,
-    state: State<AppState>,
-) -> Result<i32, String> {
-    let conn = state.db.lock().unwrap();
+/// The user who logged in through `login`; mutations are attributed to them in the audit log.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct CurrentUser {
+    pub id: i32,
+    pub username: String,
+    pub email: String,
+    /// Identifies the login session; undo history is kept per session.
+    #[serde(skip)]
+    pub token: String,
+}
+
+/// Who REST API requests act as, whoever is signed in to the app: their changes are
+/// audited under this name and their undo history is kept apart.
+pub fn api_user() -> CurrentUser {
+    CurrentUser {
+        id: 0,
+        username: "API".to_string(),
+        email: "api".to_string(),
+        token: "api".to_string(),
+    }
+}
+
+pub fn create_user(conn: &Connection, actor: &Option<CurrentUser>, name: String, email: String, password: String) -> Result<i32, String> {
     let hashed_password = hash_password(&password);
     conn.execute(
-        "INSERT INTO users (name, email, password) VALUES (?1, ?2, ?3)",
+        "INSERT INTO users (username, email, password) VALUES (?1, ?2, ?3)",
         [&name, &email, &hashed_password],
     )
     .map_err(|e| e.to_string())?;
-    Ok(conn.last_insert_rowid() as i32)
+    let id = conn.last_insert_rowid() as i32;
+    audit::record_change(conn, actor, "users", &[("id", id.into())], None)?;
+    Ok(id)
 }
 
-#[tauri::command]
-pub fn get_user(id: i32, state: State<AppState>) -> Result<User, String> {
-    let conn = state.db.lock().unwrap();
+pub fn get_user(conn: &Connection, id: i32) -> Result<User, String> {
     let user = conn
         .query_row(
-            "SELECT id, name, email, password FROM users WHERE id = ?1",
+            "SELECT id, username, email, password FROM users WHERE id = ?1",
             [id],
             |row| {
                 Ok(User {
@@ -51,29 +67,65 @@ pub fn get_user(id: i32, state: State<AppState>) -> Result<User, String> {
     Ok(user)
 }
 
-#[tauri::command]
 pub fn update_user(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
     id: i32,
     name: String,
     email: String,
     password: String,
-    state: State<AppState>,
 ) -> Result<(), String> {
-    let conn = state.db.lock().unwrap();
     let hashed_password = hash_password(&password);
+    let before = audit::snapshot(conn, "users", &[("id", id.into())])?;
     conn.execute(
-        "UPDATE users SET name = ?1, email = ?2, password = ?3 WHERE id = ?4",
+        "UPDATE users SET username = ?1, email = ?2, password = ?3 WHERE id = ?4",
         [&name, &email, &hashed_password, &id.to_string()],
     )
     .map_err(|e| e.to_string())?;
+    audit::record_change(conn, actor, "users", &[("id", id.into())], before)?;
     Ok(())
 }
 
-#[tauri::command]
-pub fn delete_user(id: i32, state: State<AppState>) -> Result<(), String> {
-    let conn = state.db.lock().unwrap();
+pub fn delete_user(conn: &Connection, actor: &Option<CurrentUser>, id: i32) -> Result<(), String> {
+    let before = audit::snapshot(conn, "users", &[("id", id.into())])?;
     conn.execute("DELETE FROM users WHERE id = ?1", [id])
         .map_err(|e| e.to_string())?;
+    audit::record_change(conn, actor, "users", &[("id", id.into())], before)?;
+    Ok(())
+}
+
+/// The user with these credentials, with a fresh session token.
+pub fn authenticate(conn: &Connection, email: &str, password: &str) -> Result<Option<CurrentUser>, String> {
+    conn.query_row(
+        "SELECT id, username, email FROM users WHERE email = ?1 AND password = ?2",
+        [email, &hash_password(password)],
+        |row| {
+            Ok(CurrentUser {
+                id: row.get(0)?,
+                username: row.get(1)?,
+                email: row.get(2)?,
+                token: Uuid::new_v4().to_string(),
+            })
+        },
+    )
+    .optional()
+    .map_err(|e| e.to_string())
+}
+
+/// Replaces passwords still stored in clear, like the seeded admin's, with their hash.
+pub fn hash_clear_passwords(conn: &Connection) -> Result<(), String> {
+    let mut stmt = conn
+        .prepare("SELECT id, password FROM users WHERE length(password) != 64 OR password GLOB '*[^0-9a-f]*'")
+        .map_err(|e| e.to_string())?;
+    let clear: Vec<(i32, String)> = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    for (id, password) in clear {
+        conn.execute("UPDATE users SET password = ?1 WHERE id = ?2", params![hash_password(&password), id])
+            .map_err(|e| e.to_string())?;
+    }
     Ok(())
 }
 
@@ -0,0 +1,405 @@
+use crate::models::audit;
+use crate::models::classroom::parse_minutes;
+use crate::models::composition::{ensure_attachable, rules_for, supervisors};
+use crate::models::conflict::{assignment_conflicts, ensure_no_conflict};
+use crate::models::journal::Operation;
+use crate::models::jury_soutenance::{jury_role, JuryRole};
+use crate::models::notification::{self, RecipientKind};
+use crate::models::session::{ensure_soutenance_open, resolve_session};
+use crate::models::settings::{get_setting, set_setting};
+use crate::models::soutenance::defence_duration;
+use crate::models::user::CurrentUser;
+use rusqlite::{params, Connection, Result};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+pub const MAX_PER_DAY_KEY: &str = "jury_max_per_day";
+pub const MAX_PER_SESSION_KEY: &str = "jury_max_per_session";
+const MAX_SUGGESTIONS: usize = 50;
+
+/// Limits on how many defences a jury member sits on. A jury's own caps take precedence
+/// over the defaults; `None` means unlimited.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, Default)]
+pub struct JuryCaps {
+    pub max_per_day: Option<i64>,
+    pub max_per_session: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct JuryWorkload {
+    pub jury_id: i32,
+    pub firstname: String,
+    pub lastname: String,
+    /// Defences sat on, guests of honour excluded.
+    pub total: i64,
+    pub by_role: BTreeMap<String, i64>,
+    pub busiest_day: Option<String>,
+    pub busiest_day_count: i64,
+    #[serde(flatten)]
+    pub caps: JuryCaps,
+    pub over_cap: bool,
+}
+
+/// Hand `role` on `soutenance_id` from one jury member to another.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct SwapSuggestion {
+    pub soutenance_id: i32,
+    pub role: JuryRole,
+    pub from_jury_id: i32,
+    pub to_jury_id: i32,
+    /// Loads before the swap.
+    pub from_load: i64,
+    pub to_load: i64,
+}
+
+/// One sitting of a jury member, as far as scheduling is concerned.
+#[derive(Clone)]
+struct Seat {
+    soutenance_id: i32,
+    jury_id: i32,
+    role: JuryRole,
+    day: Option<String>,
+    start: Option<i32>,
+}
+
+fn default_caps(conn: &Connection) -> Result<JuryCaps, String> {
+    Ok(JuryCaps {
+        max_per_day: get_setting(conn, MAX_PER_DAY_KEY)?.and_then(|v| v.parse().ok()),
+        max_per_session: get_setting(conn, MAX_PER_SESSION_KEY)?.and_then(|v| v.parse().ok()),
+    })
+}
+
+fn caps_for(conn: &Connection, jury_id: i32, defaults: &JuryCaps) -> Result<JuryCaps, String> {
+    let (max_per_day, max_per_session): (Option<i64>, Option<i64>) = conn
+        .query_row("SELECT max_per_day, max_per_session FROM jury WHERE id = ?1", [jury_id], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .map_err(|e| format!("Jury {} not found: {}", jury_id, e))?;
+    Ok(JuryCaps {
+        max_per_day: max_per_day.or(defaults.max_per_day),
+        max_per_session: max_per_session.or(defaults.max_per_session),
+    })
+}
+
+/// Sitting roles held in a session, or in every session when `session_id` is `None`.
+fn seats(conn: &Connection, session_id: Option<i32>) -> Result<Vec<Seat>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT js.soutenance_id, js.jury_id, js.role, substr(s.date, 1, 10), s.hour
+            FROM jury_soutenance js JOIN soutenance s ON s.id = js.soutenance_id
+            WHERE (?1 IS NULL OR s.session_id = ?1)",
+        )
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([session_id], |row| {
+            Ok((
+                row.get::<_, i32>(0)?,
+                row.get::<_, i32>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+                row.get::<_, Option<String>>(4)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+    let mut seats = Vec::new();
+    for row in rows {
+        let (soutenance_id, jury_id, role, day, hour) = row.map_err(|e| e.to_string())?;
+        let role = JuryRole::parse(&role)?;
+        if role.is_member() {
+            seats.push(Seat {
+                soutenance_id,
+                jury_id,
+                role,
+                day: day.filter(|d| !d.is_empty()),
+                start: hour.and_then(|h| parse_minutes(&h).ok()),
+            });
+        }
+    }
+    Ok(seats)
+}
+
+/// Why `jury_id` cannot take `seat` on top of the sittings in `held`, if anything.
+fn seat_problem(seat: &Seat, jury_id: i32, held: &[&Seat], caps: &JuryCaps, duration: i32) -> Option<String> {
+    if held.iter().any(|other| other.soutenance_id == seat.soutenance_id) {
+        return Some("already sits on this defence".to_string());
+    }
+    if caps.max_per_session.is_some_and(|max| held.len() as i64 >= max) {
+        return Some(format!("jury {} already sits on {} defences this session", jury_id, held.len()));
+    }
+    if let Some(day) = &seat.day {
+        let same_day: Vec<&&Seat> = held.iter().filter(|other| other.day.as_ref() == Some(day)).collect();
+        if caps.max_per_day.is_some_and(|max| same_day.len() as i64 >= max) {
+            return Some(format!("jury {} already sits on {} defences on {}", jury_id, same_day.len(), day));
+        }
+        if let Some(start) = seat.start {
+            let clash = same_day
+                .iter()
+                .filter_map(|other| other.start)
+                .any(|other| start < other + duration && other < start + duration);
+            if clash {
+                return Some(format!("jury {} sits on another defence at that time on {}", jury_id, day));
+            }
+        }
+    }
+    None
+}
+
+/// Refuses an assignment that would exceed the jury member's caps or overlap another of
+/// their defences. Call after the assignment was written, in the same transaction.
+pub fn ensure_within_caps(conn: &Connection, jury_id: i32, soutenance_id: i32) -> Result<(), String> {
+    let session_id: Option<i32> = conn
+        .query_row("SELECT session_id FROM soutenance WHERE id = ?1", [soutenance_id], |row| row.get(0))
+        .map_err(|e| format!("Soutenance {} not found: {}", soutenance_id, e))?;
+    let all = seats(conn, session_id)?;
+    let Some(seat) = all.iter().find(|s| s.jury_id == jury_id && s.soutenance_id == soutenance_id) else {
+        // Guests of honour do not count.
+        return Ok(());
+    };
+    let held: Vec<&Seat> = all
+        .iter()
+        .filter(|s| s.jury_id == jury_id && s.soutenance_id != soutenance_id)
+        .collect();
+    let caps = caps_for(conn, jury_id, &default_caps(conn)?)?;
+    match seat_problem(seat, jury_id, &held, &caps, defence_duration(conn)?) {
+        Some(problem) => Err(format!("Cannot assign jury {}: {}", jury_id, problem)),
+        None => Ok(()),
+    }
+}
+
+/// Defences each jury member sits on in a session, guests of honour excluded.
+pub fn session_loads(conn: &Connection, session_id: Option<i32>) -> Result<HashMap<i32, i64>, String> {
+    let mut loads = HashMap::new();
+    for seat in seats(conn, session_id)? {
+        *loads.entry(seat.jury_id).or_insert(0) += 1;
+    }
+    Ok(loads)
+}
+
+/// Why `jury_id` could not sit on `soutenance_id` (caps, time clash), if anything.
+pub fn availability_problem(conn: &Connection, jury_id: i32, soutenance_id: i32) -> Result<Option<String>, String> {
+    let (session_id, day, hour): (Option<i32>, Option<String>, Option<String>) = conn
+        .query_row(
+            "SELECT session_id, substr(date, 1, 10), hour FROM soutenance WHERE id = ?1",
+            [soutenance_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .map_err(|e| format!("Soutenance {} not found: {}", soutenance_id, e))?;
+    let seat = Seat {
+        soutenance_id,
+        jury_id,
+        role: JuryRole::Examinateur,
+        day: day.filter(|d| !d.is_empty()),
+        start: hour.and_then(|h| parse_minutes(&h).ok()),
+    };
+    let all = seats(conn, session_id)?;
+    let held: Vec<&Seat> = all.iter().filter(|s| s.jury_id == jury_id).collect();
+    let caps = caps_for(conn, jury_id, &default_caps(conn)?)?;
+    Ok(seat_problem(&seat, jury_id, &held, &caps, defence_duration(conn)?))
+}
+
+pub fn get_jury_workload(conn: &Connection, session_id: Option<i32>) -> Result<Vec<JuryWorkload>, String> {
+    let session_id = resolve_session(conn, session_id)?;
+    let defaults = default_caps(conn)?;
+    let seats = seats(conn, session_id)?;
+
+    let mut stmt = conn
+        .prepare("SELECT id, firstname, lastname FROM jury WHERE deleted_at IS NULL ORDER BY lastname, firstname")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get::<_, i32>(0)?, row.get(1)?, row.get(2)?)))
+        .map_err(|e| e.to_string())?;
+    let mut workloads = Vec::new();
+    for row in rows {
+        let (jury_id, firstname, lastname) = row.map_err(|e| e.to_string())?;
+        let mut by_role = BTreeMap::new();
+        let mut by_day: BTreeMap<String, i64> = BTreeMap::new();
+        let mut total = 0;
+        for seat in seats.iter().filter(|s| s.jury_id == jury_id) {
+            total += 1;
+            *by_role.entry(seat.role.as_str().to_string()).or_insert(0) += 1;
+            if let Some(day) = &seat.day {
+                *by_day.entry(day.clone()).or_insert(0) += 1;
+            }
+        }
+        let busiest = by_day.into_iter().max_by_key(|(_, count)| *count);
+        let caps = caps_for(conn, jury_id, &defaults)?;
+        let busiest_day_count = busiest.as_ref().map(|(_, count)| *count).unwrap_or(0);
+        workloads.push(JuryWorkload {
+            jury_id,
+            firstname,
+            lastname,
+            total,
+            by_role,
+            busiest_day: busiest.map(|(day, _)| day),
+            busiest_day_count,
+            over_cap: caps.max_per_session.is_some_and(|max| total > max)
+                || caps.max_per_day.is_some_and(|max| busiest_day_count > max),
+            caps,
+        });
+    }
+    workloads.sort_by_key(|w| std::cmp::Reverse(w.total));
+    Ok(workloads)
+}
+
+/// Proposes moving sittings from the busiest jury members to the least busy ones until
+/// no move narrows the gap. Supervisors keep their defences; every move respects caps,
+/// time clashes and who may preside.
+pub fn suggest_jury_rebalancing(conn: &Connection, session_id: Option<i32>) -> Result<Vec<SwapSuggestion>, String> {
+    let session_id = resolve_session(conn, session_id)?;
+    let defaults = default_caps(conn)?;
+    let duration = defence_duration(conn)?;
+    let mut seats = seats(conn, session_id)?;
+
+    let mut stmt = conn
+        .prepare("SELECT id FROM jury WHERE deleted_at IS NULL")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([], |row| row.get::<_, i32>(0)).map_err(|e| e.to_string())?;
+    let mut caps = HashMap::new();
+    for jury_id in rows {
+        let jury_id = jury_id.map_err(|e| e.to_string())?;
+        caps.insert(jury_id, caps_for(conn, jury_id, &defaults)?);
+    }
+
+    // Presidents may not supervise the students they examine, unless the specialité allows it.
+    let mut barred_presidents: HashMap<i32, Vec<i32>> = HashMap::new();
+    for soutenance_id in seats.iter().map(|s| s.soutenance_id).collect::<std::collections::BTreeSet<_>>() {
+        let specialite_id: i32 = conn
+            .query_row("SELECT specialite_id FROM soutenance WHERE id = ?1", [soutenance_id], |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        if !rules_for(conn, specialite_id)?.supervisor_can_preside {
+            barred_presidents.insert(soutenance_id, supervisors(conn, soutenance_id)?);
+        }
+    }
+
+    let load = |seats: &[Seat], jury_id: i32| seats.iter().filter(|s| s.jury_id == jury_id).count() as i64;
+    let movable = |role: JuryRole| matches!(role, JuryRole::President | JuryRole::Rapporteur | JuryRole::Examinateur);
+
+    let mut suggestions = Vec::new();
+    while suggestions.len() < MAX_SUGGESTIONS {
+        let mut juries: Vec<(i32, i64)> = caps.keys().map(|jury_id| (*jury_id, load(&seats, *jury_id))).collect();
+        juries.sort_by_key(|(jury_id, load)| (std::cmp::Reverse(*load), *jury_id));
+
+        let mut best: Option<(usize, i32, i64, i64)> = None;
+        'search: for (from, from_load) in &juries {
+            for (to, to_load) in juries.iter().rev() {
+                if from_load - to_load < 2 {
+                    break;
+                }
+                let held: Vec<&Seat> = seats.iter().filter(|s| s.jury_id == *to).collect();
+                for (index, seat) in seats.iter().enumerate() {
+                    if seat.jury_id != *from || !movable(seat.role) {
+                        continue;
+                    }
+                    if seat.role == JuryRole::President
+                        && barred_presidents.get(&seat.soutenance_id).is_some_and(|barred| barred.contains(to))
+                    {
+                        continue;
+                    }
+                    if seat_problem(seat, *to, &held, &caps[to], duration).is_none()
+                        && assignment_conflicts(conn, *to, seat.soutenance_id)?.is_empty()
+                    {
+                        best = Some((index, *to, *from_load, *to_load));
+                        break 'search;
+                    }
+                }
+            }
+        }
+        let Some((index, to, from_load, to_load)) = best else {
+            break;
+        };
+        let seat = &mut seats[index];
+        suggestions.push(SwapSuggestion {
+            soutenance_id: seat.soutenance_id,
+            role: seat.role,
+            from_jury_id: seat.jury_id,
+            to_jury_id: to,
+            from_load,
+            to_load,
+        });
+        seat.jury_id = to;
+    }
+    Ok(suggestions)
+}
+
+/// Applies a suggested swap; it can be undone like any other change.
+pub fn apply_jury_swap(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    soutenance_id: i32,
+    from_jury_id: i32,
+    to_jury_id: i32,
+) -> Result<(), String> {
+    ensure_soutenance_open(conn, soutenance_id)?;
+    if jury_role(conn, soutenance_id, from_jury_id)?.is_none() {
+        return Err(format!("Jury {} does not sit on soutenance {}", from_jury_id, soutenance_id));
+    }
+    if jury_role(conn, soutenance_id, to_jury_id)?.is_some() {
+        return Err(format!("Jury {} already sits on soutenance {}", to_jury_id, soutenance_id));
+    }
+    let mut operation = Operation::new("apply_jury_swap");
+    operation.track(conn, "jury_soutenance", &[("jury_id", from_jury_id.into()), ("soutenance_id", soutenance_id.into())])?;
+    operation.track(conn, "jury_soutenance", &[("jury_id", to_jury_id.into()), ("soutenance_id", soutenance_id.into())])?;
+    conn.execute(
+        "UPDATE jury_soutenance SET jury_id = ?1 WHERE jury_id = ?2 AND soutenance_id = ?3",
+        params![to_jury_id, from_jury_id, soutenance_id],
+    )
+    .map_err(|e| format!("Failed to swap jury: {}", e))?;
+    ensure_attachable(conn, soutenance_id)?;
+    ensure_within_caps(conn, to_jury_id, soutenance_id)?;
+    ensure_no_conflict(conn, actor, to_jury_id, soutenance_id, None)?;
+    let role: String = conn
+        .query_row(
+            "SELECT role FROM jury_soutenance WHERE jury_id = ?1 AND soutenance_id = ?2",
+            [to_jury_id, soutenance_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+    let role = JuryRole::parse(&role)?;
+    notification::unassigned(conn, soutenance_id, (RecipientKind::Jury, from_jury_id))?;
+    notification::assigned(conn, soutenance_id, (RecipientKind::Jury, to_jury_id), Some(role.as_str()))?;
+    operation.finish(conn, actor)?;
+    Ok(())
+}
+
+pub fn get_jury_caps(conn: &Connection, jury_id: Option<i32>) -> Result<JuryCaps, String> {
+    let defaults = default_caps(conn)?;
+    match jury_id {
+        Some(jury_id) => caps_for(conn, jury_id, &defaults),
+        None => Ok(defaults),
+    }
+}
+
+/// Sets the caps of one jury member, or the defaults when `jury_id` is omitted. A jury
+/// member's `None` caps fall back to the defaults.
+pub fn set_jury_caps(
+    conn: &Connection,
+    actor: &Option<CurrentUser>,
+    jury_id: Option<i32>,
+    caps: JuryCaps,
+) -> Result<(), String> {
+    if caps.max_per_day.is_some_and(|max| max < 0) || caps.max_per_session.is_some_and(|max| max < 0) {
+        return Err("Caps cannot be negative".to_string());
+    }
+    match jury_id {
+        Some(jury_id) => {
+            let key = [("id", jury_id.into())];
+            let before = audit::snapshot(conn, "jury", &key)?;
+            conn.execute(
+                "UPDATE jury SET max_per_day = ?1, max_per_session = ?2 WHERE id = ?3",
+                params![caps.max_per_day, caps.max_per_session, jury_id],
+            )
+            .map_err(|e| format!("Failed to save caps: {}", e))?;
+            audit::record_change(conn, actor, "jury", &key, before)?;
+        }
+        None => {
+            for (name, value) in [(MAX_PER_DAY_KEY, caps.max_per_day), (MAX_PER_SESSION_KEY, caps.max_per_session)] {
+                let key = [("key", name.to_string().into())];
+                let before = audit::snapshot(conn, "settings", &key)?;
+                set_setting(conn, name, value.map(|v| v.to_string()).as_deref())?;
+                audit::record_change(conn, actor, "settings", &key, before)?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,181 @@
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params_from_iter, Connection, Row};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+/// Paging, sorting and filtering accepted by every list command. Omitted fields do not
+/// restrict anything; without `page_size` the whole list is returned in one page.
+#[derive(Deserialize, Serialize, JsonSchema, Clone, Debug, Default)]
+pub struct QueryOptions {
+    /// 1-based.
+    pub page: Option<i64>,
+    pub page_size: Option<i64>,
+    pub sort_by: Option<String>,
+    pub sort_dir: Option<SortDirection>,
+    /// Case-insensitive substring matched against the list's text columns.
+    pub text: Option<String>,
+    /// Inclusive `YYYY-MM-DD` bounds.
+    pub date_from: Option<String>,
+    pub date_to: Option<String>,
+    pub classroom_id: Option<i32>,
+    pub jury_id: Option<i32>,
+}
+
+impl QueryOptions {
+    /// Fails when a filter is set that the list cannot apply, rather than silently ignoring it.
+    pub fn ensure_supported(&self, list: &str, supported: &[&str]) -> Result<(), String> {
+        let requested = [
+            ("date_from", self.date_from.is_some()),
+            ("date_to", self.date_to.is_some()),
+            ("classroom_id", self.classroom_id.is_some()),
+            ("jury_id", self.jury_id.is_some()),
+        ];
+        for (filter, set) in requested {
+            if set && !supported.contains(&filter) {
+                return Err(format!("{} cannot be filtered by {}", list, filter));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+    pub page: i64,
+    pub page_size: Option<i64>,
+}
+
+/// Accumulates parameterised conditions for a list query. Conditions use anonymous `?`
+/// placeholders, bound in the order they were added.
+pub struct ListQuery {
+    from: String,
+    conditions: Vec<String>,
+    params: Vec<SqlValue>,
+}
+
+impl ListQuery {
+    pub fn new(from: &str) -> ListQuery {
+        ListQuery {
+            from: from.to_string(),
+            conditions: Vec::new(),
+            params: Vec::new(),
+        }
+    }
+
+    pub fn filter(&mut self, condition: &str, params: Vec<SqlValue>) -> &mut ListQuery {
+        self.conditions.push(format!("({})", condition));
+        self.params.extend(params);
+        self
+    }
+
+    pub fn filter_opt<T: Into<SqlValue>>(&mut self, condition: &str, value: Option<T>) -> &mut ListQuery {
+        if let Some(value) = value {
+            self.filter(condition, vec![value.into()]);
+        }
+        self
+    }
+
+    /// Matches `text` as a substring of any of `columns`.
+    pub fn text(&mut self, text: &Option<String>, columns: &[&str]) -> &mut ListQuery {
+        let Some(text) = text.as_ref().map(|t| t.trim()).filter(|t| !t.is_empty()) else {
+            return self;
+        };
+        let escaped = text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+        let pattern = SqlValue::Text(format!("%{}%", escaped));
+        let condition = columns
+            .iter()
+            .map(|column| format!("{} LIKE ? ESCAPE '\\'", column))
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        self.filter(&condition, vec![pattern; columns.len()])
+    }
+
+    fn where_clause(&self) -> String {
+        if self.conditions.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", self.conditions.join(" AND "))
+        }
+    }
+
+    /// Runs the count and the page query. `sorts` maps the accepted `sort_by` values to
+    /// comma-separated SQL columns; the first entry is the default order.
+    pub fn fetch<T, F>(
+        &self,
+        conn: &Connection,
+        select: &str,
+        options: &QueryOptions,
+        sorts: &[(&str, &str)],
+        map: F,
+    ) -> Result<Page<T>, String>
+    where
+        F: FnMut(&Row) -> rusqlite::Result<T>,
+    {
+        let where_clause = self.where_clause();
+        let total: i64 = conn
+            .query_row(
+                &format!("SELECT COUNT(*) {}{}", self.from, where_clause),
+                params_from_iter(self.params.iter()),
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Failed to count rows: {}", e))?;
+
+        let order = match &options.sort_by {
+            Some(field) => sorts
+                .iter()
+                .find(|(name, _)| name == field)
+                .map(|(_, sql)| *sql)
+                .ok_or_else(|| format!("Cannot sort by {}", field))?,
+            None => sorts.first().map(|(_, sql)| *sql).unwrap_or("1"),
+        };
+        let direction = match options.sort_dir.unwrap_or_default() {
+            SortDirection::Asc => "ASC",
+            SortDirection::Desc => "DESC",
+        };
+        let page = options.page.unwrap_or(1).max(1);
+        let page_size = options.page_size.filter(|size| *size > 0);
+        let limit = page_size.unwrap_or(-1);
+        let offset = match page_size {
+            Some(size) => (page - 1)
+                .checked_mul(size)
+                .ok_or_else(|| format!("Page {} of {} rows is out of range", page, size))?,
+            None => 0,
+        };
+
+        let mut params = self.params.clone();
+        params.push(SqlValue::Integer(limit));
+        params.push(SqlValue::Integer(offset));
+        let order = order
+            .split(", ")
+            .map(|column| format!("{} {}", column, direction))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut stmt = conn
+            .prepare(&format!(
+                "{} {}{} ORDER BY {} LIMIT ? OFFSET ?",
+                select, self.from, where_clause, order
+            ))
+            .map_err(|e| format!("Failed to prepare list query: {}", e))?;
+        let rows = stmt
+            .query_map(params_from_iter(params.iter()), map)
+            .map_err(|e| format!("Failed to run list query: {}", e))?;
+        let items: Result<Vec<T>, _> = rows.collect();
+
+        Ok(Page {
+            items: items.map_err(|e| format!("Failed to collect rows: {}", e))?,
+            total,
+            page,
+            page_size,
+        })
+    }
+}
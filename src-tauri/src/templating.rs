@@ -0,0 +1,366 @@
+//! A small template language for emails and documents.
+//!
+//! `{{ soutenance.date }}` prints a value, optionally through filters such as
+//! `{{ recipient.lastname | upper }}`. `{% if path %}`, `{% if not path %}`, `{% elif path %}`,
+//! `{% else %}` and `{% endif %}` test values; `{% for jury in soutenance.juries %}` ... `{% endfor %}`
+//! repeats over a list, with `loop.index`, `loop.first` and `loop.last` inside. A line
+//! holding nothing but a `{% %}` tag leaves no blank line behind.
+
+use serde_json::{Map, Value};
+
+const FILTERS: [&str; 3] = ["upper", "lower", "trim"];
+
+#[derive(Clone, Debug)]
+enum Node {
+    Text(String),
+    Value { path: Vec<String>, filters: Vec<String>, line: usize },
+    If { path: Vec<String>, negate: bool, line: usize, then: Vec<Node>, otherwise: Vec<Node> },
+    For { name: String, path: Vec<String>, line: usize, body: Vec<Node> },
+}
+
+enum Token {
+    Text(String),
+    Value(String, usize),
+    Tag(String, usize),
+}
+
+#[derive(Clone, Debug)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    let mut line = 1;
+    // Whether the next token starts a line, as it does after a tag that took its line.
+    let mut fresh_line = true;
+    loop {
+        let next = [rest.find("{{"), rest.find("{%")].into_iter().flatten().min();
+        let Some(start) = next else {
+            if !rest.is_empty() {
+                tokens.push(Token::Text(rest.to_string()));
+            }
+            return Ok(tokens);
+        };
+        let is_tag = rest[start..].starts_with("{%");
+        let close = if is_tag { "%}" } else { "}}" };
+        let mut text = &rest[..start];
+        let end = rest[start + 2..]
+            .find(close)
+            .ok_or_else(|| format!("Line {}: {} is never closed", line + text.matches('\n').count(), &rest[start..start + 2]))?
+            + start
+            + 2;
+        let inner = rest[start + 2..end].trim().to_string();
+        let mut after = &rest[end + 2..];
+        if is_tag {
+            // A tag alone on its line takes the line with it.
+            let line_start = text.rfind('\n').map(|i| i + 1).unwrap_or(0);
+            let at_line_start = text[line_start..].trim_matches([' ', '\t']).is_empty() && (line_start > 0 || fresh_line);
+            let line_end = after.find('\n');
+            let at_line_end = after[..line_end.unwrap_or(after.len())].trim_matches([' ', '\t', '\r']).is_empty();
+            if at_line_start && at_line_end {
+                line += text.matches('\n').count();
+                text = &text[..line_start];
+                after = line_end.map(|i| &after[i + 1..]).unwrap_or("");
+                if !text.is_empty() {
+                    tokens.push(Token::Text(text.to_string()));
+                }
+                tokens.push(Token::Tag(inner, line));
+                line += usize::from(line_end.is_some());
+                fresh_line = true;
+                rest = after;
+                continue;
+            }
+        }
+        line += text.matches('\n').count();
+        if !text.is_empty() {
+            tokens.push(Token::Text(text.to_string()));
+        }
+        tokens.push(if is_tag { Token::Tag(inner, line) } else { Token::Value(inner, line) });
+        fresh_line = false;
+        rest = after;
+    }
+}
+
+fn parse_path(expression: &str, line: usize) -> Result<Vec<String>, String> {
+    let path: Vec<String> = expression.split('.').map(|part| part.trim().to_string()).collect();
+    let valid = path.iter().all(|part| {
+        !part.is_empty() && part.chars().all(|c| c.is_alphanumeric() || c == '_')
+    });
+    if !valid {
+        return Err(format!("Line {}: \"{}\" is not a variable name", line, expression));
+    }
+    Ok(path)
+}
+
+/// `path` or `not path`.
+fn parse_condition(expression: &str, line: usize) -> Result<(Vec<String>, bool), String> {
+    match expression.trim().strip_prefix("not ") {
+        Some(path) => Ok((parse_path(path, line)?, true)),
+        None => Ok((parse_path(expression, line)?, false)),
+    }
+}
+
+/// Builds nodes until a tag starting with one of `ends` closes the block; returns that
+/// tag too, or `None` at the end of the template.
+fn parse_block(tokens: &mut std::vec::IntoIter<Token>, ends: &[&str]) -> Result<(Vec<Node>, Option<String>), String> {
+    let mut nodes = Vec::new();
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Text(text) => nodes.push(Node::Text(text)),
+            Token::Value(expression, line) => {
+                let mut parts = expression.split('|');
+                let path = parse_path(parts.next().unwrap_or_default(), line)?;
+                let filters: Vec<String> = parts.map(|filter| filter.trim().to_string()).collect();
+                if let Some(unknown) = filters.iter().find(|filter| !FILTERS.contains(&filter.as_str())) {
+                    return Err(format!("Line {}: unknown filter \"{}\" (expected one of {})", line, unknown, FILTERS.join(", ")));
+                }
+                nodes.push(Node::Value { path, filters, line });
+            }
+            Token::Tag(tag, line) => {
+                let (keyword, rest) = tag.split_once(char::is_whitespace).unwrap_or((tag.as_str(), ""));
+                if ends.contains(&keyword) {
+                    return Ok((nodes, Some(tag)));
+                }
+                match (keyword, rest.split_whitespace().collect::<Vec<_>>().as_slice()) {
+                    ("if", _) => nodes.push(parse_if(tokens, rest, line)?),
+                    ("for", [name, "in", expression]) => {
+                        let path = parse_path(expression, line)?;
+                        let (body, end) = parse_block(tokens, &["endfor"])?;
+                        if end.is_none() {
+                            return Err(format!("Line {}: {{% for %}} without {{% endfor %}}", line));
+                        }
+                        nodes.push(Node::For { name: name.to_string(), path, line, body });
+                    }
+                    _ => return Err(format!("Line {}: unexpected {{% {} %}}", line, tag)),
+                }
+            }
+        }
+    }
+    Ok((nodes, None))
+}
+
+/// The rest of an `if` whose condition is `condition`; an `elif` becomes a nested `if`
+/// in the `else` branch.
+fn parse_if(tokens: &mut std::vec::IntoIter<Token>, condition: &str, line: usize) -> Result<Node, String> {
+    let (path, negate) = parse_condition(condition, line)?;
+    let (then, end) = parse_block(tokens, &["elif", "else", "endif"])?;
+    let end = end.ok_or_else(|| format!("Line {}: {{% if %}} without {{% endif %}}", line))?;
+    let otherwise = match end.split_once(char::is_whitespace) {
+        Some(("elif", condition)) => vec![parse_if(tokens, condition, line)?],
+        _ if end == "else" => match parse_block(tokens, &["endif"])? {
+            (otherwise, Some(_)) => otherwise,
+            (_, None) => return Err(format!("Line {}: {{% if %}} without {{% endif %}}", line)),
+        },
+        _ if end == "endif" => Vec::new(),
+        _ => return Err(format!("Line {}: unexpected {{% {} %}}", line, end)),
+    };
+    Ok(Node::If { path, negate, line, then, otherwise })
+}
+
+enum Lookup<'a> {
+    Found(&'a Value),
+    /// The path went through a value that is not set, such as a room not chosen yet.
+    Missing,
+    Unknown,
+}
+
+struct Scope<'a> {
+    root: &'a Value,
+    locals: Vec<(String, Value)>,
+}
+
+impl<'a> Scope<'a> {
+    fn lookup(&self, path: &[String]) -> Lookup<'_> {
+        let first = &path[0];
+        let start = match self.locals.iter().rev().find(|(name, _)| name == first) {
+            Some((_, value)) => value,
+            None => match self.root.get(first) {
+                Some(value) => value,
+                None => return Lookup::Unknown,
+            },
+        };
+        let mut current = start;
+        for part in &path[1..] {
+            current = match current {
+                Value::Null => return Lookup::Missing,
+                Value::Object(map) => match map.get(part) {
+                    Some(value) => value,
+                    None => return Lookup::Unknown,
+                },
+                _ => return Lookup::Unknown,
+            };
+        }
+        Lookup::Found(current)
+    }
+}
+
+fn display(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        Value::Array(items) => items.iter().map(display).collect::<Vec<_>>().join(", "),
+        other => other.to_string(),
+    }
+}
+
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(flag) => *flag,
+        Value::String(text) => !text.is_empty(),
+        Value::Number(number) => number.as_f64() != Some(0.0),
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+    }
+}
+
+fn loop_value(index: usize, count: usize) -> Value {
+    let mut map = Map::new();
+    map.insert("index".to_string(), Value::from(index + 1));
+    map.insert("first".to_string(), Value::from(index == 0));
+    map.insert("last".to_string(), Value::from(index + 1 == count));
+    Value::Object(map)
+}
+
+fn unknown(path: &[String], line: usize) -> String {
+    format!("Line {}: unknown variable {}", line, path.join("."))
+}
+
+fn render_nodes(nodes: &[Node], scope: &mut Scope, out: &mut String) -> Result<(), String> {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Value { path, filters, line } => {
+                let mut text = match scope.lookup(path) {
+                    Lookup::Found(value) => display(value),
+                    Lookup::Missing => String::new(),
+                    Lookup::Unknown => return Err(unknown(path, *line)),
+                };
+                for filter in filters {
+                    text = match filter.as_str() {
+                        "upper" => text.to_uppercase(),
+                        "lower" => text.to_lowercase(),
+                        _ => text.trim().to_string(),
+                    };
+                }
+                out.push_str(&text);
+            }
+            Node::If { path, negate, line, then, otherwise } => {
+                let value = match scope.lookup(path) {
+                    Lookup::Found(value) => truthy(value),
+                    Lookup::Missing => false,
+                    Lookup::Unknown => return Err(unknown(path, *line)),
+                };
+                render_nodes(if value != *negate { then } else { otherwise }, scope, out)?;
+            }
+            Node::For { name, path, line, body } => {
+                let items = match scope.lookup(path) {
+                    Lookup::Found(Value::Array(items)) => items.clone(),
+                    Lookup::Found(Value::Null) | Lookup::Missing => Vec::new(),
+                    Lookup::Found(_) => return Err(format!("Line {}: {} is not a list", line, path.join("."))),
+                    Lookup::Unknown => return Err(unknown(path, *line)),
+                };
+                let count = items.len();
+                for (index, item) in items.into_iter().enumerate() {
+                    scope.locals.push(("loop".to_string(), loop_value(index, count)));
+                    scope.locals.push((name.clone(), item));
+                    let result = render_nodes(body, scope, out);
+                    scope.locals.truncate(scope.locals.len() - 2);
+                    result?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Walks every branch, and the first item of every list, looking for names `sample` lacks.
+fn check_nodes(nodes: &[Node], scope: &mut Scope, problems: &mut Vec<String>) {
+    for node in nodes {
+        match node {
+            Node::Text(_) => {}
+            Node::Value { path, line, .. } => {
+                if let Lookup::Unknown = scope.lookup(path) {
+                    problems.push(unknown(path, *line));
+                }
+            }
+            Node::If { path, line, then, otherwise, .. } => {
+                if let Lookup::Unknown = scope.lookup(path) {
+                    problems.push(unknown(path, *line));
+                }
+                check_nodes(then, scope, problems);
+                check_nodes(otherwise, scope, problems);
+            }
+            Node::For { name, path, line, body } => {
+                let item = match scope.lookup(path) {
+                    Lookup::Found(Value::Array(items)) => items.first().cloned().unwrap_or(Value::Null),
+                    Lookup::Found(Value::Null) | Lookup::Missing => Value::Null,
+                    Lookup::Found(_) => {
+                        problems.push(format!("Line {}: {} is not a list", line, path.join(".")));
+                        Value::Null
+                    }
+                    Lookup::Unknown => {
+                        problems.push(unknown(path, *line));
+                        Value::Null
+                    }
+                };
+                scope.locals.push(("loop".to_string(), loop_value(0, 1)));
+                scope.locals.push((name.clone(), item));
+                check_nodes(body, scope, problems);
+                scope.locals.truncate(scope.locals.len() - 2);
+            }
+        }
+    }
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Template, String> {
+        let mut tokens = tokenize(source)?.into_iter();
+        let (nodes, end) = parse_block(&mut tokens, &[])?;
+        if let Some(end) = end {
+            return Err(format!("Unexpected {{% {} %}}", end));
+        }
+        Ok(Template { nodes })
+    }
+
+    /// Fails on names the context does not have; values that are merely unset print
+    /// as nothing.
+    pub fn render(&self, context: &Value) -> Result<String, String> {
+        let mut out = String::new();
+        render_nodes(&self.nodes, &mut Scope { root: context, locals: Vec::new() }, &mut out)?;
+        Ok(out)
+    }
+
+    /// Variables used anywhere in the template that `sample` does not provide.
+    pub fn unknown_variables(&self, sample: &Value) -> Vec<String> {
+        let mut problems = Vec::new();
+        check_nodes(&self.nodes, &mut Scope { root: sample, locals: Vec::new() }, &mut problems);
+        problems
+    }
+}
+
+/// Every path a template can use with `sample`; list items appear as `list[].field`.
+pub fn variables(sample: &Value) -> Vec<String> {
+    fn walk(value: &Value, prefix: &str, out: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                for (key, child) in map {
+                    let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                    walk(child, &path, out);
+                }
+            }
+            Value::Array(items) => {
+                out.push(prefix.to_string());
+                if let Some(first) = items.first() {
+                    walk(first, &format!("{}[]", prefix), out);
+                }
+            }
+            _ => out.push(prefix.to_string()),
+        }
+    }
+    let mut out = Vec::new();
+    walk(sample, "", &mut out);
+    out
+}
@@ -0,0 +1,392 @@
+mod common;
+
+use common::{error, temp_path, Fixture, GENIE_INDUSTRIEL, GENIE_INFORMATIQUE};
+use gestion_soutenances_lib::backup_to;
+use gestion_soutenances_lib::models::audit::{self, AuditFilter};
+use gestion_soutenances_lib::models::expertise::{self, JuryProfile};
+use gestion_soutenances_lib::models::honoraria::{self, HonorariaRates, PaymentDetails};
+use gestion_soutenances_lib::models::jury_soutenance::JuryRole;
+use gestion_soutenances_lib::models::session::{self, SessionKind};
+use gestion_soutenances_lib::models::{archive, classroom, etudiant, jury, journal, search, settings, soutenance};
+
+fn filter(entity_type: &str, entity_id: Option<i32>) -> AuditFilter {
+    AuditFilter {
+        entity_type: Some(entity_type.to_string()),
+        entity_id: entity_id.map(|id| id.to_string()),
+        user_id: None,
+        from: None,
+        to: None,
+        limit: None,
+    }
+}
+
+#[test]
+fn sessions_are_created_per_academic_year() {
+    let fx = Fixture::new();
+    let annee = session::create_annee_universitaire(
+        &fx.conn,
+        &fx.actor,
+        "2030-2031".to_string(),
+        Some("2030-09-15".to_string()),
+        Some("2031-07-31".to_string()),
+    )
+    .unwrap();
+    assert!(session::get_all_annees_universitaires(&fx.conn).unwrap().iter().any(|a| a.label == "2030-2031"));
+
+    let principale = session::create_session(&fx.conn, &fx.actor, annee, "principale".to_string()).unwrap();
+    let rattrapage = session::create_session(&fx.conn, &fx.actor, annee, "rattrapage".to_string()).unwrap();
+    assert!(error(session::create_session(&fx.conn, &fx.actor, annee, "hiver".to_string())).contains("Unknown session kind"));
+
+    let sessions = session::get_all_sessions(&fx.conn, Some(annee)).unwrap();
+    assert_eq!(sessions.len(), 2);
+    assert_eq!(session::find_session(&fx.conn, rattrapage).unwrap().kind, SessionKind::Controle);
+
+    let active = session::set_active_session(&fx.conn, &fx.actor, principale).unwrap();
+    assert_eq!(active.annee_label, "2030-2031");
+    assert_eq!(session::get_active_session(&fx.conn).unwrap().unwrap().id, principale);
+    assert_eq!(fx.session(), principale);
+}
+
+#[test]
+fn closing_a_session_rolls_over_the_students_who_did_not_pass() {
+    let fx = Fixture::new();
+    let principale = fx.session();
+    let passed = fx.student("Amina", GENIE_INDUSTRIEL);
+    let failed = fx.student("Youssef", GENIE_INDUSTRIEL);
+    fx.student("Salma", GENIE_INFORMATIQUE);
+    let archived = fx.student("Nour", GENIE_INFORMATIQUE);
+    etudiant::delete_students(&fx.conn, &fx.actor, vec![archived]).unwrap();
+    etudiant::set_student_grade(&fx.conn, &fx.actor, passed, Some(14.0)).unwrap();
+    etudiant::set_student_grade(&fx.conn, &fx.actor, failed, Some(8.5)).unwrap();
+    let supervisor = fx.jury("Sami", "Ben Ali");
+    etudiant::set_student_supervisor(&fx.conn, &fx.actor, failed, Some(supervisor)).unwrap();
+    fx.conn
+        .execute("UPDATE etudiant SET email = 'youssef@univ.tn' WHERE id = ?1", [failed])
+        .unwrap();
+
+    let closing = session::close_session(&fx.conn, &fx.actor, principale, None).unwrap();
+    assert_eq!(closing.rolled_students, 2);
+    let controle = session::find_session(&fx.conn, closing.next_session_id).unwrap();
+    let carried: (Option<String>, Option<i32>) = fx
+        .conn
+        .query_row(
+            "SELECT email, supervisor_id FROM etudiant WHERE session_id = ?1 AND firstname = 'Youssef'",
+            [controle.id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(carried, (Some("youssef@univ.tn".to_string()), Some(supervisor)));
+    assert_eq!(controle.kind, SessionKind::Controle);
+    assert_eq!(controle.annee_id, session::find_session(&fx.conn, principale).unwrap().annee_id);
+    assert_eq!(fx.session(), controle.id);
+    assert!(session::find_session(&fx.conn, principale).unwrap().closed);
+
+    assert!(error(session::close_session(&fx.conn, &fx.actor, principale, None)).contains("already closed"));
+    assert!(error(session::close_session(&fx.conn, &fx.actor, controle.id, Some(controle.id)))
+        .contains("cannot be rolled over into itself"));
+}
+
+#[test]
+fn a_closed_session_can_no_longer_be_modified() {
+    let fx = Fixture::new();
+    let principale = fx.session();
+    let student = fx.student("Amina", GENIE_INDUSTRIEL);
+    session::close_session(&fx.conn, &fx.actor, principale, None).unwrap();
+
+    assert!(error(etudiant::set_student_grade(&fx.conn, &fx.actor, student, Some(12.0)))
+        .contains(&format!("Session {} is closed", principale)));
+    assert!(error(session::ensure_session_open(&fx.conn, Some(principale))).contains("can no longer be modified"));
+
+    session::set_active_session(&fx.conn, &fx.actor, principale).unwrap();
+    let created = fx.write(|conn, actor| {
+        soutenance::create_soutenance(
+            conn,
+            actor,
+            Some("2026-06-22".to_string()),
+            Some("09:00".to_string()),
+            GENIE_INDUSTRIEL,
+            None,
+            None,
+            None,
+            None,
+        )
+    });
+    assert!(error(created).contains("is closed"));
+}
+
+#[test]
+fn archived_rows_are_listed_and_restored() {
+    let fx = Fixture::new();
+    let amina = fx.student("Amina", GENIE_INDUSTRIEL);
+    let sami = fx.jury("Sami", "Trabelsi");
+    etudiant::delete_students(&fx.conn, &fx.actor, vec![amina]).unwrap();
+    jury::delete_jury(&fx.conn, &fx.actor, vec![sami]).unwrap();
+
+    let archived = archive::get_archive(&fx.conn, None).unwrap();
+    assert_eq!(archived.len(), 2);
+    let students = archive::get_archive(&fx.conn, Some("etudiant".to_string())).unwrap();
+    assert_eq!(students.len(), 1);
+    assert_eq!(students[0].id, amina);
+    assert!(students[0].label.contains("Amina"));
+
+    assert_eq!(archive::restore_archived(&fx.conn, &fx.actor, "etudiant".to_string(), vec![amina]).unwrap(), 1);
+    assert_eq!(archive::get_archive(&fx.conn, Some("etudiant".to_string())).unwrap().len(), 0);
+    assert!(etudiant::get_student(&fx.conn, amina).is_ok());
+    assert!(error(archive::restore_archived(&fx.conn, &fx.actor, "user".to_string(), vec![1]))
+        .contains("cannot be archived"));
+
+    let undone = journal::undo(&fx.conn, &fx.actor).unwrap().unwrap();
+    assert_eq!(undone.label, "restore_etudiant");
+    assert_eq!(archive::get_archive(&fx.conn, Some("etudiant".to_string())).unwrap().len(), 1);
+}
+
+#[test]
+fn purging_the_archive_removes_rows_and_their_assignments() {
+    let fx = Fixture::new();
+    let soutenance = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    let sami = fx.jury("Sami", "Trabelsi");
+    let leila = fx.jury("Leila", "Gharbi");
+    fx.seat(sami, soutenance, JuryRole::President);
+    fx.seat(leila, soutenance, JuryRole::Rapporteur);
+    jury::delete_jury(&fx.conn, &fx.actor, vec![sami]).unwrap();
+
+    assert_eq!(archive::purge_archive(&fx.conn, &fx.actor, Some(30)).unwrap(), 0);
+    assert_eq!(archive::purge_archive(&fx.conn, &fx.actor, Some(0)).unwrap(), 1);
+    assert_eq!(fx.count("jury"), 1);
+    assert_eq!(fx.count("jury_soutenance"), 1);
+    assert!(archive::get_archive(&fx.conn, None).unwrap().is_empty());
+}
+
+#[test]
+fn purging_the_archive_clears_references_to_purged_rows() {
+    let fx = Fixture::new();
+    let room = fx.classroom("B12", Some(30));
+    let soutenance = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, Some(room));
+    let supervisor = fx.jury("Sami", "Trabelsi");
+    let student = fx.student("Amina", GENIE_INDUSTRIEL);
+    etudiant::set_student_supervisor(&fx.conn, &fx.actor, student, Some(supervisor)).unwrap();
+    classroom::delete_classrooms(&fx.conn, &fx.actor, vec![room]).unwrap();
+    jury::delete_jury(&fx.conn, &fx.actor, vec![supervisor]).unwrap();
+
+    assert_eq!(archive::purge_archive(&fx.conn, &fx.actor, Some(0)).unwrap(), 2);
+    let dangling: i64 = fx
+        .conn
+        .query_row(
+            "SELECT (SELECT COUNT(*) FROM soutenance WHERE classroom_id = ?1)
+            + (SELECT COUNT(*) FROM etudiant WHERE supervisor_id = ?2)",
+            [room, supervisor],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(dangling, 0);
+    assert_eq!(fx.count("soutenance"), 1);
+    assert_eq!(audit::get_audit_log(&fx.conn, Some(filter("soutenance", Some(soutenance)))).unwrap()[0].action, "update");
+}
+
+#[test]
+fn the_retention_period_defaults_to_a_year() {
+    let fx = Fixture::new();
+    assert_eq!(archive::get_archive_retention_days(&fx.conn).unwrap(), 365);
+    archive::set_archive_retention_days(&fx.conn, &fx.actor, 90).unwrap();
+    assert_eq!(archive::get_archive_retention_days(&fx.conn).unwrap(), 90);
+    assert!(archive::set_archive_retention_days(&fx.conn, &fx.actor, -1).is_err());
+}
+
+#[test]
+fn changes_are_recorded_in_the_audit_log() {
+    let fx = Fixture::new();
+    let amina = fx.student("Amina", GENIE_INDUSTRIEL);
+    etudiant::set_student_grade(&fx.conn, &fx.actor, amina, Some(12.0)).unwrap();
+    etudiant::delete_students(&fx.conn, &fx.actor, vec![amina]).unwrap();
+
+    let entries = audit::get_audit_log(&fx.conn, Some(filter("etudiant", Some(amina)))).unwrap();
+    let actions: Vec<&str> = entries.iter().map(|e| e.action.as_str()).collect();
+    assert_eq!(actions, ["update", "update", "create"]);
+    assert!(entries.iter().all(|e| e.user_email.as_deref() == Some("admin@example.com")));
+    assert_eq!(entries[1].changes["grade"]["after"], 12.0);
+
+    let limited = AuditFilter { limit: Some(1), ..filter("etudiant", None) };
+    assert_eq!(audit::get_audit_log(&fx.conn, Some(limited)).unwrap().len(), 1);
+    assert!(audit::get_audit_log(&fx.conn, Some(filter("classroom", None))).unwrap().is_empty());
+}
+
+#[test]
+fn the_audit_log_is_exported_as_csv() {
+    let fx = Fixture::new();
+    fx.student("Amina", GENIE_INDUSTRIEL);
+    fx.student("Youssef", GENIE_INDUSTRIEL);
+    let path = temp_path("audit.csv");
+
+    let written = audit::export_audit_log_csv(&fx.conn, path.to_str().unwrap(), Some(filter("etudiant", None))).unwrap();
+    assert_eq!(written, 2);
+    let csv = std::fs::read_to_string(&path).unwrap();
+    assert!(csv.starts_with("id,timestamp,user_id,user_email,action,entity_type,entity_id,changes"));
+    assert_eq!(csv.lines().count(), 3);
+}
+
+#[test]
+fn operations_are_undone_and_redone_in_order() {
+    let fx = Fixture::new();
+    let amina = fx.student("Amina", GENIE_INDUSTRIEL);
+    let youssef = fx.student("Youssef", GENIE_INDUSTRIEL);
+    etudiant::delete_students(&fx.conn, &fx.actor, vec![amina]).unwrap();
+    etudiant::delete_students(&fx.conn, &fx.actor, vec![youssef]).unwrap();
+
+    let history = journal::get_operation_history(&fx.conn, &fx.actor).unwrap();
+    assert_eq!(history.len(), 2);
+    assert!(history.iter().all(|op| !op.undone));
+
+    journal::undo(&fx.conn, &fx.actor).unwrap().unwrap();
+    assert!(etudiant::get_student(&fx.conn, youssef).is_ok());
+    assert_eq!(archive::get_archive(&fx.conn, None).unwrap().len(), 1);
+    journal::undo(&fx.conn, &fx.actor).unwrap().unwrap();
+    assert!(archive::get_archive(&fx.conn, None).unwrap().is_empty());
+    assert!(journal::undo(&fx.conn, &fx.actor).unwrap().is_none());
+
+    let redone = journal::redo(&fx.conn, &fx.actor).unwrap().unwrap();
+    assert_eq!(redone.id, history[1].id);
+    assert_eq!(archive::get_archive(&fx.conn, None).unwrap()[0].id, amina);
+}
+
+#[test]
+fn payment_details_and_rates_are_validated() {
+    let fx = Fixture::new();
+    let sami = fx.jury("Sami", "Trabelsi");
+    let details = PaymentDetails {
+        affiliation: Some("ENIT".to_string()),
+        payment_reference: Some("TN59 1000 6035 1835 9847 8831".to_string()),
+        rate: Some(60.0),
+        travel_rate: None,
+    };
+    honoraria::set_jury_payment(&fx.conn, &fx.actor, sami, details).unwrap();
+    assert_eq!(honoraria::get_jury_payment(&fx.conn, sami).unwrap().affiliation.as_deref(), Some("ENIT"));
+    let negative = PaymentDetails { rate: Some(-1.0), ..PaymentDetails::default() };
+    assert!(error(honoraria::set_jury_payment(&fx.conn, &fx.actor, sami, negative)).contains("negative"));
+
+    let defaults = honoraria::get_honoraria_rates(&fx.conn).unwrap();
+    assert_eq!((defaults.rate, defaults.currency.as_str()), (0.0, "TND"));
+    let rates = HonorariaRates { rate: 50.0, travel_rate: 20.0, currency: " eur ".to_string() };
+    honoraria::set_honoraria_rates(&fx.conn, &fx.actor, rates).unwrap();
+    assert_eq!(honoraria::get_honoraria_rates(&fx.conn).unwrap().currency, "EUR");
+    let negative = HonorariaRates { rate: -5.0, travel_rate: 0.0, currency: "TND".to_string() };
+    assert!(honoraria::set_honoraria_rates(&fx.conn, &fx.actor, negative).is_err());
+}
+
+#[test]
+fn honoraria_pay_sitting_members_per_defence_and_travel_day() {
+    let fx = Fixture::new();
+    let monday = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    let monday_later = fx.soutenance("2026-06-22 11:00", GENIE_INDUSTRIEL, None);
+    let tuesday = fx.soutenance("2026-06-23 09:00", GENIE_INDUSTRIEL, None);
+    let internal = fx.jury("Sami", "Trabelsi");
+    let external = fx.jury("Nadia", "Hamdi");
+    let guest = fx.jury("Karim", "Jebali");
+    expertise::set_jury_profile(&fx.conn, &fx.actor, external, JuryProfile { external: true, ..JuryProfile::default() })
+        .unwrap();
+    honoraria::set_honoraria_rates(
+        &fx.conn,
+        &fx.actor,
+        HonorariaRates { rate: 50.0, travel_rate: 20.0, currency: "TND".to_string() },
+    )
+    .unwrap();
+    for id in [monday, monday_later, tuesday] {
+        fx.seat(external, id, JuryRole::Examinateur);
+        fx.seat(internal, id, JuryRole::President);
+    }
+    fx.seat(guest, monday, JuryRole::InviteHonneur);
+
+    let summary = honoraria::compute_honoraria(&fx.conn, None).unwrap();
+    assert_eq!(summary.members.len(), 2);
+    let nadia = summary.members.iter().find(|m| m.jury_id == external).unwrap();
+    assert_eq!((nadia.defences.len(), nadia.travel_days), (3, 2));
+    assert_eq!((nadia.fees, nadia.travel, nadia.total), (150.0, 40.0, 190.0));
+    let sami = summary.members.iter().find(|m| m.jury_id == internal).unwrap();
+    assert_eq!(sami.total, 0.0);
+
+    honoraria::set_jury_payment(
+        &fx.conn,
+        &fx.actor,
+        internal,
+        PaymentDetails { rate: Some(30.0), ..PaymentDetails::default() },
+    )
+    .unwrap();
+    let summary = honoraria::compute_honoraria(&fx.conn, None).unwrap();
+    assert_eq!((summary.total_fees, summary.total_travel, summary.total), (240.0, 40.0, 280.0));
+}
+
+#[test]
+fn honoraria_are_exported() {
+    let fx = Fixture::new();
+    let soutenance = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    let paid = fx.jury("Sami", "Trabelsi");
+    let unpaid = fx.jury("Leila", "Gharbi");
+    let absent = fx.jury("Karim", "Jebali");
+    fx.seat(paid, soutenance, JuryRole::President);
+    fx.seat(unpaid, soutenance, JuryRole::Rapporteur);
+    honoraria::set_jury_payment(&fx.conn, &fx.actor, paid, PaymentDetails { rate: Some(40.0), ..PaymentDetails::default() })
+        .unwrap();
+
+    let csv = temp_path("honoraria.csv");
+    assert_eq!(honoraria::export_honoraria_csv(&fx.conn, csv.display().to_string(), None).unwrap(), 1);
+    let pdf = temp_path("honoraria.pdf");
+    honoraria::export_honoraria_pdf(&fx.conn, pdf.display().to_string(), None).unwrap();
+    assert!(std::fs::read(&pdf).unwrap().starts_with(b"%PDF"));
+
+    let statement = temp_path("statement.csv");
+    assert_eq!(
+        honoraria::export_payment_statement_csv(&fx.conn, statement.display().to_string(), paid, None).unwrap(),
+        1
+    );
+    let lines: Vec<String> = std::fs::read_to_string(&statement).unwrap().lines().map(str::to_string).collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].ends_with("role,fee,currency"));
+    assert!(lines[1].ends_with(",40.000,TND"));
+    let statement_pdf = temp_path("statement.pdf");
+    honoraria::export_payment_statement_pdf(&fx.conn, statement_pdf.display().to_string(), paid, None).unwrap();
+    assert!(statement_pdf.exists());
+    assert!(error(honoraria::export_payment_statement_csv(&fx.conn, statement.display().to_string(), absent, None))
+        .contains("does not sit on any defence"));
+}
+
+#[test]
+fn search_finds_people_by_prefix_without_accents() {
+    let fx = Fixture::new();
+    let helene = fx.student("Hélène", GENIE_INDUSTRIEL);
+    let sami = fx.jury("Sami", "Trabelsi");
+    fx.pfe(GENIE_INFORMATIQUE, "Détection de fraude", Some("Sofrecom"));
+
+    let results = search::global_search(&fx.conn, "helen".to_string(), None, None).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!((results[0].kind.as_str(), results[0].id), ("etudiant", helene));
+
+    let results = search::global_search(&fx.conn, "trab".to_string(), Some(vec!["jury".to_string()]), None).unwrap();
+    assert_eq!((results[0].kind.as_str(), results[0].id), ("jury", sami));
+    assert!(search::global_search(&fx.conn, "trab".to_string(), Some(vec!["pfe".to_string()]), None)
+        .unwrap()
+        .is_empty());
+    assert_eq!(search::global_search(&fx.conn, "detection".to_string(), None, None).unwrap()[0].kind, "pfe");
+    assert!(search::global_search(&fx.conn, "  ".to_string(), None, None).unwrap().is_empty());
+}
+
+#[test]
+fn settings_are_stored_and_cleared() {
+    let fx = Fixture::new();
+    assert_eq!(settings::get_setting(&fx.conn, "test.key").unwrap(), None);
+    settings::set_setting(&fx.conn, "test.key", Some("value")).unwrap();
+    assert_eq!(settings::get_setting(&fx.conn, "test.key").unwrap().as_deref(), Some("value"));
+    settings::set_setting(&fx.conn, "test.key", None).unwrap();
+    assert_eq!(settings::get_setting(&fx.conn, "test.key").unwrap(), None);
+}
+
+#[test]
+fn a_backup_is_a_complete_copy_and_never_overwrites() {
+    let fx = Fixture::new();
+    fx.student("Amina", GENIE_INDUSTRIEL);
+    let path = temp_path("backup.db");
+
+    backup_to(&fx.conn, &path).unwrap();
+    let copy = rusqlite::Connection::open(&path).unwrap();
+    let students: i64 = copy.query_row("SELECT COUNT(*) FROM etudiant", [], |row| row.get(0)).unwrap();
+    assert_eq!(students, 1);
+    assert!(error(backup_to(&fx.conn, &path)).contains("already exists"));
+}
@@ -0,0 +1,185 @@
+//! Shared setup for the integration tests: an in-memory database with the full schema and
+//! builders for the rows most tests start from.
+#![allow(dead_code)]
+
+use gestion_soutenances_lib::models::classroom::{self, ClassroomDetails};
+use gestion_soutenances_lib::models::etudiant::{self, NewStudent};
+use gestion_soutenances_lib::models::invite::{self, NewInvitee};
+use gestion_soutenances_lib::models::jury::{self, NewJury};
+use gestion_soutenances_lib::models::jury_soutenance::{self, JuryRole};
+use gestion_soutenances_lib::models::pfe;
+use gestion_soutenances_lib::models::session;
+use gestion_soutenances_lib::models::soutenance;
+use gestion_soutenances_lib::models::user::CurrentUser;
+use gestion_soutenances_lib::open_database;
+use rusqlite::Connection;
+use std::path::{Path, PathBuf};
+
+/// Seeded by the schema: 'Licence Génie Industriel' and 'Licence Génie Informatique'.
+pub const GENIE_INDUSTRIEL: i32 = 1;
+pub const GENIE_INFORMATIQUE: i32 = 2;
+
+pub struct Fixture {
+    pub conn: Connection,
+    /// The seeded admin account, signed in with a fixed session token.
+    pub actor: Option<CurrentUser>,
+}
+
+impl Fixture {
+    pub fn new() -> Fixture {
+        Fixture {
+            conn: open_database(Path::new(":memory:")),
+            actor: Some(CurrentUser {
+                id: 1,
+                username: "Admin".to_string(),
+                email: "admin@example.com".to_string(),
+                token: "fixture-session".to_string(),
+            }),
+        }
+    }
+
+    /// Runs `f` the way the commands do: in a transaction kept only if `f` succeeds.
+    pub fn write<T>(&self, f: impl FnOnce(&Connection, &Option<CurrentUser>) -> Result<T, String>) -> Result<T, String> {
+        let tx = self.conn.unchecked_transaction().unwrap();
+        let value = f(&tx, &self.actor)?;
+        tx.commit().unwrap();
+        Ok(value)
+    }
+
+    /// The session new rows are attached to by default.
+    pub fn session(&self) -> i32 {
+        session::active_session_id(&self.conn).unwrap().expect("no active session")
+    }
+
+    pub fn student(&self, firstname: &str, specialite_id: i32) -> i32 {
+        etudiant::create_student(
+            &self.conn,
+            &self.actor,
+            &NewStudent {
+                firstname: firstname.to_string(),
+                lastname: "Etudiant".to_string(),
+                address: "Tunis".to_string(),
+                specialite_id,
+                soutenance_id: None,
+                session_id: None,
+                email: Some(format!("{}@etudiant.tn", firstname.to_lowercase())),
+            },
+        )
+        .unwrap()
+    }
+
+    pub fn jury(&self, firstname: &str, lastname: &str) -> i32 {
+        jury::create_jury(
+            &self.conn,
+            &self.actor,
+            NewJury {
+                firstname: firstname.to_string(),
+                lastname: lastname.to_string(),
+                email: format!("{}.{}@univ.tn", firstname, lastname).to_lowercase().replace(' ', ""),
+            },
+        )
+        .unwrap()
+    }
+
+    pub fn invitee(&self, firstname: &str) -> i32 {
+        invite::create_invite(
+            &self.conn,
+            &self.actor,
+            NewInvitee {
+                firstname: firstname.to_string(),
+                lastname: "Invite".to_string(),
+                email: format!("{}@entreprise.tn", firstname.to_lowercase()),
+            },
+        )
+        .unwrap()
+    }
+
+    pub fn classroom(&self, name: &str, capacity: Option<i32>) -> i32 {
+        classroom::create_classroom(
+            &self.conn,
+            &self.actor,
+            name.to_string(),
+            Some(ClassroomDetails {
+                capacity,
+                building: Some("A".to_string()),
+                floor: Some("1".to_string()),
+                equipment: vec!["projector".to_string()],
+            }),
+        )
+        .unwrap()
+    }
+
+    pub fn pfe(&self, specialite_id: i32, title: &str, company: Option<&str>) -> i32 {
+        pfe::create_pfe(
+            &self.conn,
+            &self.actor,
+            specialite_id,
+            Some(title.to_string()),
+            None,
+            company.map(str::to_string),
+        )
+        .unwrap()
+    }
+
+    /// A soutenance in the active session; `when` is "YYYY-MM-DD HH:MM".
+    pub fn soutenance(&self, when: &str, specialite_id: i32, classroom_id: Option<i32>) -> i32 {
+        let (date, hour) = when.split_once(' ').expect("expected a date and an hour");
+        soutenance::create_soutenance(
+            &self.conn,
+            &self.actor,
+            Some(date.to_string()),
+            Some(hour.to_string()),
+            specialite_id,
+            classroom_id,
+            Some(format!("Projet du {}", date)),
+            None,
+            None,
+        )
+        .unwrap()
+        .id
+    }
+
+    pub fn seat(&self, jury_id: i32, soutenance_id: i32, role: JuryRole) {
+        jury_soutenance::create_jury_soutenance(
+            &self.conn,
+            &self.actor,
+            jury_id,
+            soutenance_id,
+            role.as_str().to_string(),
+            None,
+        )
+        .unwrap();
+    }
+
+    pub fn assign(&self, student_id: i32, soutenance_id: i32) {
+        etudiant::assign_student(&self.conn, &self.actor, student_id, soutenance_id).unwrap();
+    }
+
+    /// Today's date as SQLite sees it, for check-ins that only work on the day.
+    pub fn today(&self) -> String {
+        self.conn
+            .query_row("SELECT date('now', 'localtime')", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    pub fn count(&self, table: &str) -> i64 {
+        self.conn
+            .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0))
+            .unwrap()
+    }
+}
+
+/// The error of a call expected to fail.
+pub fn error<T>(result: Result<T, String>) -> String {
+    match result {
+        Ok(_) => panic!("expected an error"),
+        Err(e) => e,
+    }
+}
+
+/// A path in the temporary directory, unique to this test process, removed if it exists.
+pub fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("gestion-soutenances-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_file(&path);
+    path
+}
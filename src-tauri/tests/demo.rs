@@ -0,0 +1,132 @@
+mod common;
+
+use common::{error, Fixture, GENIE_INDUSTRIEL, GENIE_INFORMATIQUE};
+use gestion_soutenances_lib::models::audit::{self, AuditFilter};
+use gestion_soutenances_lib::models::composition;
+use gestion_soutenances_lib::models::conflict;
+use gestion_soutenances_lib::models::demo::{self, DemoOptions, DemoSummary};
+use gestion_soutenances_lib::models::notification;
+use gestion_soutenances_lib::models::search;
+use gestion_soutenances_lib::models::soutenance;
+
+fn options(seed: u64) -> DemoOptions {
+    DemoOptions {
+        seed,
+        students_per_specialite: Some(4),
+        specialite_ids: Some(vec![GENIE_INDUSTRIEL, GENIE_INFORMATIQUE]),
+        classrooms: Some(2),
+        juries: None,
+        start_date: Some("2026-06-22".to_string()),
+        session_id: None,
+    }
+}
+
+/// Every generated row in a comparable form.
+fn contents(fx: &Fixture) -> Vec<String> {
+    let mut stmt = fx
+        .conn
+        .prepare(
+            "SELECT firstname || ' ' || lastname || ' ' || address || ' ' || IFNULL(grade, '') FROM etudiant
+            UNION ALL SELECT firstname || ' ' || lastname || ' ' || email || ' ' || keywords FROM jury
+            UNION ALL SELECT date || ' ' || hour || ' ' || classroom_id || ' ' || pfe FROM soutenance
+            UNION ALL SELECT jury_id || ' ' || soutenance_id || ' ' || role FROM jury_soutenance",
+        )
+        .unwrap();
+    let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+    rows.collect::<Result<_, _>>().unwrap()
+}
+
+#[test]
+fn the_same_seed_produces_the_same_data() {
+    let (first, second, other) = (Fixture::new(), Fixture::new(), Fixture::new());
+    demo::generate_demo_data(&first.conn, &first.actor, options(42)).unwrap();
+    demo::generate_demo_data(&second.conn, &second.actor, options(42)).unwrap();
+    demo::generate_demo_data(&other.conn, &other.actor, options(7)).unwrap();
+
+    assert!(!contents(&first).is_empty());
+    assert_eq!(contents(&first), contents(&second));
+    assert_ne!(contents(&first), contents(&other));
+}
+
+#[test]
+fn the_session_is_fully_scheduled_and_graded() {
+    let fx = Fixture::new();
+    let summary = demo::generate_demo_data(&fx.conn, &fx.actor, options(1)).unwrap();
+    assert_eq!(summary.students, 8);
+    assert_eq!((summary.pfes, summary.soutenances, summary.classrooms), (8, 8, 2));
+    assert_eq!(demo::get_demo_data(&fx.conn).unwrap(), summary);
+
+    let unscheduled: i64 = fx
+        .conn
+        .query_row(
+            "SELECT COUNT(*) FROM etudiant WHERE soutenance_id IS NULL OR grade IS NULL OR supervisor_id IS NULL
+            OR session_id != ?1",
+            [fx.session()],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(unscheduled, 0);
+    let weekend: i64 = fx
+        .conn
+        .query_row("SELECT COUNT(*) FROM soutenance WHERE strftime('%w', date) IN ('0', '6')", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(weekend, 0);
+    assert!(composition::get_composition_violations(&fx.conn, None, None).unwrap().is_empty());
+    assert!(conflict::get_assignment_conflicts(&fx.conn, None).unwrap().is_empty());
+
+    // Juries have a profile to match projects against, and nobody is emailed.
+    let profiled: i64 = fx
+        .conn
+        .query_row("SELECT COUNT(*) FROM jury WHERE keywords != '[]' AND department IS NOT NULL", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(profiled as usize, summary.juries);
+    assert!(notification::get_pending_notifications(&fx.conn, None).unwrap().is_empty());
+    assert_eq!(fx.count("notification_preference") as usize, summary.juries + summary.students);
+}
+
+#[test]
+fn demo_data_is_generated_once_until_purged() {
+    let fx = Fixture::new();
+    let real_student = fx.student("Amina", GENIE_INDUSTRIEL);
+    let real_jury = fx.jury("Sami", "Trabelsi");
+    demo::generate_demo_data(&fx.conn, &fx.actor, options(3)).unwrap();
+    assert!(error(demo::generate_demo_data(&fx.conn, &fx.actor, options(3))).contains("purge it first"));
+
+    assert!(demo::purge_demo_data(&fx.conn, &fx.actor).unwrap() > 0);
+    assert_eq!(demo::get_demo_data(&fx.conn).unwrap(), DemoSummary::default());
+    assert_eq!((fx.count("etudiant"), fx.count("jury")), (1, 1));
+    for table in ["soutenance", "jury_soutenance", "pfe", "classroom", "notification_preference", "reminder"] {
+        assert_eq!(fx.count(table), 0, "{} was not purged", table);
+    }
+    assert_eq!(search::global_search(&fx.conn, "Amina".to_string(), None, None).unwrap()[0].id, real_student);
+    assert_eq!(search::global_search(&fx.conn, "Sami".to_string(), None, None).unwrap()[0].id, real_jury);
+
+    demo::generate_demo_data(&fx.conn, &fx.actor, options(3)).unwrap();
+}
+
+#[test]
+fn real_soutenances_leave_a_purged_demo_classroom() {
+    let fx = Fixture::new();
+    demo::generate_demo_data(&fx.conn, &fx.actor, options(4)).unwrap();
+    let room: i32 = fx.conn.query_row("SELECT MIN(id) FROM classroom", [], |row| row.get(0)).unwrap();
+    let real = fx.soutenance("2026-09-14 09:00", GENIE_INDUSTRIEL, Some(room));
+
+    demo::purge_demo_data(&fx.conn, &fx.actor).unwrap();
+    assert_eq!(fx.count("classroom"), 0);
+    assert!(soutenance::get_soutenance(&fx.conn, real).unwrap().classroom.is_none());
+    let filter = AuditFilter {
+        entity_type: Some("soutenance".to_string()),
+        entity_id: Some(real.to_string()),
+        ..Default::default()
+    };
+    let entries = audit::get_audit_log(&fx.conn, Some(filter)).unwrap();
+    assert!(entries.iter().any(|entry| entry.changes.get("classroom_id").is_some()));
+}
+
+#[test]
+fn demo_data_needs_enough_juries() {
+    let fx = Fixture::new();
+    let result = fx.write(|conn, actor| demo::generate_demo_data(conn, actor, DemoOptions { juries: Some(3), ..options(5) }));
+    assert!(error(result).contains("Not enough jury members"));
+    assert_eq!(fx.count("demo_row"), 0);
+}
@@ -0,0 +1,422 @@
+mod common;
+
+use common::{error, Fixture, GENIE_INDUSTRIEL};
+use gestion_soutenances_lib::models::composition::{self, CompositionRules};
+use gestion_soutenances_lib::models::conflict::{self, ConflictKind, NewConflict};
+use gestion_soutenances_lib::models::etudiant;
+use gestion_soutenances_lib::models::expertise::{self, JuryGrade, JuryProfile, ReviewerRequest};
+use gestion_soutenances_lib::models::journal;
+use gestion_soutenances_lib::models::jury_soutenance::{self, JuryRole};
+use gestion_soutenances_lib::models::notification;
+use gestion_soutenances_lib::models::soutenance;
+use gestion_soutenances_lib::models::workload::{self, JuryCaps};
+
+fn conflict(jury_id: i32, kind: ConflictKind) -> NewConflict {
+    NewConflict {
+        jury_id,
+        kind,
+        etudiant_id: None,
+        company: None,
+        other_jury_id: None,
+        reason: None,
+    }
+}
+
+fn reviewers(title: &str) -> ReviewerRequest {
+    ReviewerRequest {
+        pfe_id: None,
+        title: Some(title.to_string()),
+        keywords: Vec::new(),
+        specialite_id: None,
+        soutenance_id: None,
+        exclude: Vec::new(),
+        limit: None,
+    }
+}
+
+fn profile(keywords: &[&str], department: Option<&str>) -> JuryProfile {
+    JuryProfile {
+        keywords: keywords.iter().map(|k| k.to_string()).collect(),
+        department: department.map(str::to_string),
+        grade: None,
+        external: false,
+    }
+}
+
+fn soutenance_juries(fx: &Fixture, id: i32) -> usize {
+    soutenance::get_soutenance_jurys(&fx.conn, id).unwrap().len()
+}
+
+#[test]
+fn composition_rules_default_and_are_validated() {
+    let fx = Fixture::new();
+    let rules = composition::get_composition_rules(&fx.conn, GENIE_INDUSTRIEL).unwrap();
+    assert_eq!((rules.min_size, rules.max_size, rules.presidents), (3, Some(5), 1));
+
+    let mut backwards = CompositionRules::default_for(GENIE_INDUSTRIEL);
+    backwards.max_size = Some(2);
+    assert!(error(composition::set_composition_rules(&fx.conn, &fx.actor, backwards)).contains("below the minimum"));
+    let mut negative = CompositionRules::default_for(GENIE_INDUSTRIEL);
+    negative.min_rapporteurs = -1;
+    assert!(composition::set_composition_rules(&fx.conn, &fx.actor, negative).is_err());
+
+    let mut smaller = CompositionRules::default_for(GENIE_INDUSTRIEL);
+    smaller.min_size = 2;
+    composition::set_composition_rules(&fx.conn, &fx.actor, smaller).unwrap();
+    assert_eq!(composition::get_composition_rules(&fx.conn, GENIE_INDUSTRIEL).unwrap().min_size, 2);
+}
+
+#[test]
+fn an_incomplete_jury_is_reported() {
+    let fx = Fixture::new();
+    let id = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    fx.seat(fx.jury("Sami", "Ben Ali"), id, JuryRole::President);
+    fx.seat(fx.jury("Leila", "Trabelsi"), id, JuryRole::Examinateur);
+    fx.seat(fx.jury("Omar", "Invite"), id, JuryRole::InviteHonneur);
+
+    let rules: Vec<_> = composition::get_soutenance_composition(&fx.conn, id)
+        .unwrap()
+        .into_iter()
+        .map(|v| v.rule)
+        .collect();
+    assert_eq!(rules, ["min_size", "min_rapporteurs"]);
+    assert_eq!(composition::get_composition_violations(&fx.conn, None, None).unwrap().len(), 2);
+}
+
+#[test]
+fn a_second_president_is_refused() {
+    let fx = Fixture::new();
+    let id = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    fx.seat(fx.jury("Sami", "Ben Ali"), id, JuryRole::President);
+    let other = fx.jury("Leila", "Trabelsi");
+
+    let message = error(fx.write(|conn, actor| {
+        jury_soutenance::create_jury_soutenance(conn, actor, other, id, "president".to_string(), None)
+    }));
+    assert!(message.contains("2 presidents, 1 expected"), "{}", message);
+    assert_eq!(soutenance_juries(&fx, id), 1);
+}
+
+#[test]
+fn a_supervisor_cannot_preside() {
+    let fx = Fixture::new();
+    let id = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    let supervisor = fx.jury("Sami", "Ben Ali");
+    let student = fx.student("Amira", GENIE_INDUSTRIEL);
+    fx.assign(student, id);
+    etudiant::set_student_supervisor(&fx.conn, &fx.actor, student, Some(supervisor)).unwrap();
+
+    let message = error(fx.write(|conn, actor| {
+        jury_soutenance::create_jury_soutenance(conn, actor, supervisor, id, "president".to_string(), None)
+    }));
+    assert!(message.contains("supervisor cannot preside"), "{}", message);
+    fx.seat(supervisor, id, JuryRole::EncadrantAcademique);
+
+    // Moving their student onto a defence they preside is refused as well.
+    let presided = fx.soutenance("2026-06-23 09:00", GENIE_INDUSTRIEL, None);
+    fx.seat(supervisor, presided, JuryRole::President);
+    let message = error(fx.write(|conn, actor| etudiant::assign_student(conn, actor, student, presided)));
+    assert!(message.contains("supervisor cannot preside"), "{}", message);
+    assert_eq!(etudiant::get_student(&fx.conn, student).unwrap().soutenance_id, Some(id));
+}
+
+#[test]
+fn conflicts_need_a_target() {
+    let fx = Fixture::new();
+    let jury = fx.jury("Sami", "Ben Ali");
+
+    assert!(error(conflict::declare_jury_conflict(&fx.conn, &fx.actor, conflict(jury, ConflictKind::Company))).contains("needs the company"));
+    let mut itself = conflict(jury, ConflictKind::Jury);
+    itself.other_jury_id = Some(jury);
+    assert!(error(conflict::declare_jury_conflict(&fx.conn, &fx.actor, itself)).contains("themselves"));
+
+    let mut company = conflict(jury, ConflictKind::Company);
+    company.company = Some("  Poulina ".to_string());
+    let id = conflict::declare_jury_conflict(&fx.conn, &fx.actor, company).unwrap();
+    let declared = conflict::get_jury_conflicts(&fx.conn, Some(jury)).unwrap();
+    assert_eq!(declared[0].company.as_deref(), Some("Poulina"));
+
+    conflict::delete_jury_conflict(&fx.conn, &fx.actor, id).unwrap();
+    assert!(conflict::get_jury_conflicts(&fx.conn, None).unwrap().is_empty());
+}
+
+#[test]
+fn a_conflicted_assignment_needs_a_justification() {
+    let fx = Fixture::new();
+    let id = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    let jury = fx.jury("Sami", "Ben Ali");
+    let student = fx.student("Amira", GENIE_INDUSTRIEL);
+    fx.assign(student, id);
+    let mut declared = conflict(jury, ConflictKind::Student);
+    declared.etudiant_id = Some(student);
+    conflict::declare_jury_conflict(&fx.conn, &fx.actor, declared).unwrap();
+
+    let message = error(fx.write(|conn, actor| {
+        jury_soutenance::create_jury_soutenance(conn, actor, jury, id, "examinateur".to_string(), None)
+    }));
+    assert!(message.contains("conflict of interest with student Amira Etudiant"), "{}", message);
+    assert_eq!(soutenance_juries(&fx, id), 0);
+
+    fx.write(|conn, actor| {
+        jury_soutenance::create_jury_soutenance(conn, actor, jury, id, "examinateur".to_string(), Some("Seul spécialiste".to_string()))
+    })
+    .unwrap();
+    let overrides = conflict::get_conflict_overrides(&fx.conn, Some(id)).unwrap();
+    assert_eq!(overrides[0].justification, "Seul spécialiste");
+    assert_eq!(overrides[0].user_email.as_deref(), Some("admin@example.com"));
+
+    let flagged = conflict::get_assignment_conflicts(&fx.conn, None).unwrap();
+    assert_eq!(flagged.len(), 1);
+    assert_eq!(flagged[0].justification.as_deref(), Some("Seul spécialiste"));
+}
+
+#[test]
+fn students_and_projects_cannot_bring_a_conflict_to_a_seated_jury() {
+    let fx = Fixture::new();
+    let id = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    let sami = fx.jury("Sami", "Ben Ali");
+    fx.seat(sami, id, JuryRole::Examinateur);
+    let amira = fx.student("Amira", GENIE_INDUSTRIEL);
+    let mut declared = conflict(sami, ConflictKind::Student);
+    declared.etudiant_id = Some(amira);
+    conflict::declare_jury_conflict(&fx.conn, &fx.actor, declared).unwrap();
+
+    let message = error(fx.write(|conn, actor| etudiant::assign_student(conn, actor, amira, id)));
+    assert!(message.contains("conflict of interest with student Amira Etudiant"), "{}", message);
+    assert_eq!(etudiant::get_student(&fx.conn, amira).unwrap().soutenance_id, None);
+
+    // A conflict already waived with a justification does not block later moves.
+    let waived = fx.soutenance("2026-06-23 09:00", GENIE_INDUSTRIEL, None);
+    fx.assign(amira, waived);
+    jury_soutenance::create_jury_soutenance(&fx.conn, &fx.actor, sami, waived, "examinateur".to_string(), Some("Seul spécialiste".to_string()))
+        .unwrap();
+    let omar = fx.student("Omar", GENIE_INDUSTRIEL);
+    etudiant::assign_student(&fx.conn, &fx.actor, omar, waived).unwrap();
+
+    fx.pfe(GENIE_INDUSTRIEL, "Optimisation logistique", Some("Poulina"));
+    let mut company = conflict(sami, ConflictKind::Company);
+    company.company = Some("Poulina".to_string());
+    conflict::declare_jury_conflict(&fx.conn, &fx.actor, company).unwrap();
+    let message = error(fx.write(|conn, actor| {
+        soutenance::update_soutenance(
+            conn,
+            actor,
+            id,
+            Some("2026-06-22".to_string()),
+            Some("09:00".to_string()),
+            GENIE_INDUSTRIEL,
+            None,
+            Some("Optimisation logistique".to_string()),
+        )
+    }));
+    assert!(message.contains("conflict of interest with company Poulina"), "{}", message);
+}
+
+#[test]
+fn company_and_jury_conflicts_apply_to_the_right_defences() {
+    let fx = Fixture::new();
+    fx.pfe(GENIE_INDUSTRIEL, "Projet du 2026-06-22", Some("Poulina"));
+    let at_poulina = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    let elsewhere = fx.soutenance("2026-06-23 09:00", GENIE_INDUSTRIEL, None);
+    let sami = fx.jury("Sami", "Ben Ali");
+    let leila = fx.jury("Leila", "Trabelsi");
+
+    let mut company = conflict(sami, ConflictKind::Company);
+    company.company = Some("poulina".to_string());
+    conflict::declare_jury_conflict(&fx.conn, &fx.actor, company).unwrap();
+    let mut colleague = conflict(leila, ConflictKind::Jury);
+    colleague.other_jury_id = Some(sami);
+    conflict::declare_jury_conflict(&fx.conn, &fx.actor, colleague).unwrap();
+
+    assert_eq!(
+        conflict::assignment_conflicts(&fx.conn, sami, at_poulina).unwrap(),
+        ["conflict of interest with company Poulina"]
+    );
+    fx.seat(sami, elsewhere, JuryRole::Examinateur);
+    // Declared by Leila, but it binds Sami too.
+    assert!(conflict::assignment_conflicts(&fx.conn, leila, elsewhere).unwrap()[0].contains("Sami Ben Ali"));
+    assert!(conflict::assignment_conflicts(&fx.conn, leila, at_poulina).unwrap().is_empty());
+}
+
+#[test]
+fn a_jury_member_cannot_sit_twice_at_once() {
+    let fx = Fixture::new();
+    let first = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    let overlapping = fx.soutenance("2026-06-22 09:30", GENIE_INDUSTRIEL, None);
+    let after = fx.soutenance("2026-06-22 10:00", GENIE_INDUSTRIEL, None);
+    let jury = fx.jury("Sami", "Ben Ali");
+    fx.seat(jury, first, JuryRole::Examinateur);
+
+    let message = error(fx.write(|conn, actor| {
+        jury_soutenance::create_jury_soutenance(conn, actor, jury, overlapping, "examinateur".to_string(), None)
+    }));
+    assert_eq!(
+        message,
+        format!("Cannot assign jury {}: jury {} sits on another defence at that time on 2026-06-22", jury, jury)
+    );
+    fx.seat(jury, after, JuryRole::Examinateur);
+}
+
+#[test]
+fn caps_limit_the_defences_per_day() {
+    let fx = Fixture::new();
+    let jury = fx.jury("Sami", "Ben Ali");
+    assert!(workload::set_jury_caps(&fx.conn, &fx.actor, None, JuryCaps { max_per_day: Some(-1), max_per_session: None }).is_err());
+    workload::set_jury_caps(&fx.conn, &fx.actor, None, JuryCaps { max_per_day: Some(1), max_per_session: None }).unwrap();
+    assert_eq!(workload::get_jury_caps(&fx.conn, Some(jury)).unwrap().max_per_day, Some(1));
+
+    let morning = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    let afternoon = fx.soutenance("2026-06-22 14:00", GENIE_INDUSTRIEL, None);
+    fx.seat(jury, morning, JuryRole::Examinateur);
+    let message = error(fx.write(|conn, actor| {
+        jury_soutenance::create_jury_soutenance(conn, actor, jury, afternoon, "examinateur".to_string(), None)
+    }));
+    assert!(message.contains("already sits on 1 defences on 2026-06-22"), "{}", message);
+
+    // The member's own cap overrides the default.
+    workload::set_jury_caps(&fx.conn, &fx.actor, Some(jury), JuryCaps { max_per_day: Some(2), max_per_session: None }).unwrap();
+    fx.seat(jury, afternoon, JuryRole::Examinateur);
+}
+
+#[test]
+fn rescheduling_cannot_push_a_member_over_the_cap() {
+    let fx = Fixture::new();
+    let jury = fx.jury("Sami", "Ben Ali");
+    workload::set_jury_caps(&fx.conn, &fx.actor, None, JuryCaps { max_per_day: Some(1), max_per_session: None }).unwrap();
+    let monday = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    let tuesday = fx.soutenance("2026-06-23 14:00", GENIE_INDUSTRIEL, None);
+    fx.seat(jury, monday, JuryRole::Examinateur);
+    fx.seat(jury, tuesday, JuryRole::Examinateur);
+
+    let message = error(fx.write(|conn, actor| {
+        soutenance::update_soutenance(
+            conn,
+            actor,
+            tuesday,
+            Some("2026-06-22".to_string()),
+            Some("14:00".to_string()),
+            GENIE_INDUSTRIEL,
+            None,
+            Some("Projet du 2026-06-23".to_string()),
+        )
+    }));
+    assert!(message.contains("already sits on 1 defences on 2026-06-22"), "{}", message);
+    assert_eq!(soutenance::get_soutenance(&fx.conn, tuesday).unwrap().date.as_deref(), Some("2026-06-23"));
+}
+
+#[test]
+fn the_workload_counts_sitting_roles() {
+    let fx = Fixture::new();
+    let sami = fx.jury("Sami", "Ben Ali");
+    let leila = fx.jury("Leila", "Trabelsi");
+    let first = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    let second = fx.soutenance("2026-06-22 11:00", GENIE_INDUSTRIEL, None);
+    fx.seat(sami, first, JuryRole::President);
+    fx.seat(sami, second, JuryRole::Rapporteur);
+    fx.seat(leila, first, JuryRole::InviteHonneur);
+
+    let loads = workload::get_jury_workload(&fx.conn, None).unwrap();
+    assert_eq!((loads[0].jury_id, loads[0].total), (sami, 2));
+    assert_eq!(loads[0].busiest_day.as_deref(), Some("2026-06-22"));
+    assert_eq!(loads[0].by_role.get("president"), Some(&1));
+    assert_eq!((loads[1].jury_id, loads[1].total), (leila, 0));
+}
+
+#[test]
+fn rebalancing_moves_a_sitting_to_the_least_busy_member() {
+    let fx = Fixture::new();
+    let busy = fx.jury("Sami", "Ben Ali");
+    let idle = fx.jury("Leila", "Trabelsi");
+    let ids: Vec<i32> = ["2026-06-22", "2026-06-23", "2026-06-24"]
+        .iter()
+        .map(|day| fx.soutenance(&format!("{} 09:00", day), GENIE_INDUSTRIEL, None))
+        .collect();
+    for id in &ids {
+        fx.seat(busy, *id, JuryRole::Examinateur);
+    }
+
+    let suggestions = workload::suggest_jury_rebalancing(&fx.conn, None).unwrap();
+    assert_eq!(suggestions.len(), 1);
+    let swap = &suggestions[0];
+    assert_eq!((swap.from_jury_id, swap.to_jury_id, swap.from_load, swap.to_load), (busy, idle, 3, 0));
+
+    workload::apply_jury_swap(&fx.conn, &fx.actor, swap.soutenance_id, busy, idle).unwrap();
+    let pending = notification::get_pending_notifications(&fx.conn, Some(swap.soutenance_id)).unwrap();
+    let role = pending.iter().find(|p| p.recipient_id == idle && p.field == "role").expect("no role notification");
+    assert_eq!(role.new_value.as_deref(), Some("examinateur"));
+    assert!(workload::suggest_jury_rebalancing(&fx.conn, None).unwrap().is_empty());
+    assert!(error(workload::apply_jury_swap(&fx.conn, &fx.actor, swap.soutenance_id, busy, idle)).contains("does not sit on"));
+    let other = ids.iter().copied().find(|id| *id != swap.soutenance_id).unwrap();
+    fx.seat(idle, other, JuryRole::Rapporteur);
+    assert!(error(workload::apply_jury_swap(&fx.conn, &fx.actor, other, busy, idle)).contains("already sits on"));
+
+    journal::undo(&fx.conn, &fx.actor).unwrap();
+    assert_eq!(jury_soutenance::jury_role(&fx.conn, swap.soutenance_id, busy).unwrap(), Some(JuryRole::Examinateur));
+}
+
+#[test]
+fn jury_profiles_are_normalised() {
+    let fx = Fixture::new();
+    let jury = fx.jury("Sami", "Ben Ali");
+    let mut saved = profile(&[" Logistique", "supply chain", "logistique", ""], Some("  Génie Industriel "));
+    saved.grade = Some(JuryGrade::MC);
+    let saved = expertise::set_jury_profile(&fx.conn, &fx.actor, jury, saved).unwrap();
+
+    assert_eq!(saved.keywords, ["logistique", "supply chain"]);
+    assert_eq!(saved.department.as_deref(), Some("Génie Industriel"));
+    assert_eq!(expertise::get_jury_profile(&fx.conn, jury).unwrap().grade, Some(JuryGrade::MC));
+    assert!(JuryGrade::parse("assistant").is_err());
+}
+
+#[test]
+fn reviewers_are_ranked_by_topic_and_availability() {
+    let fx = Fixture::new();
+    let expert = fx.jury("Sami", "Ben Ali");
+    let colleague = fx.jury("Leila", "Trabelsi");
+    let busy = fx.jury("Mehdi", "Gharbi");
+    let supervisor = fx.jury("Nour", "Hammami");
+    expertise::set_jury_profile(&fx.conn, &fx.actor, expert, profile(&["logistique", "supply chain"], None)).unwrap();
+    expertise::set_jury_profile(&fx.conn, &fx.actor, colleague, profile(&[], Some("Industriel"))).unwrap();
+    expertise::set_jury_profile(&fx.conn, &fx.actor, busy, profile(&["logistique"], None)).unwrap();
+    expertise::set_jury_profile(&fx.conn, &fx.actor, supervisor, profile(&["logistique"], None)).unwrap();
+
+    let id = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    let student = fx.student("Amira", GENIE_INDUSTRIEL);
+    fx.assign(student, id);
+    etudiant::set_student_supervisor(&fx.conn, &fx.actor, student, Some(supervisor)).unwrap();
+    let clash = fx.soutenance("2026-06-22 09:30", GENIE_INDUSTRIEL, None);
+    fx.seat(busy, clash, JuryRole::Examinateur);
+
+    let mut request = reviewers("Optimisation de la supply chain et logistique");
+    request.soutenance_id = Some(id);
+    let ranked = expertise::suggest_reviewers(&fx.conn, request).unwrap();
+    let order: Vec<i32> = ranked.iter().map(|c| c.jury_id).collect();
+    assert_eq!(order, [expert, colleague, busy]);
+    assert_eq!(ranked[0].matched_keywords, ["logistique", "supply chain"]);
+    assert!(ranked[2].unavailable.is_some());
+
+    let mut limited = reviewers("logistique");
+    limited.limit = Some(1);
+    assert_eq!(expertise::suggest_reviewers(&fx.conn, limited).unwrap().len(), 1);
+}
+
+#[test]
+fn reviewers_for_a_pfe_leave_out_its_supervisors() {
+    let fx = Fixture::new();
+    let expert = fx.jury("Sami", "Ben Ali");
+    let supervisor = fx.jury("Nour", "Hammami");
+    expertise::set_jury_profile(&fx.conn, &fx.actor, expert, profile(&["logistique"], None)).unwrap();
+    expertise::set_jury_profile(&fx.conn, &fx.actor, supervisor, profile(&["logistique"], None)).unwrap();
+
+    let pfe = fx.pfe(GENIE_INDUSTRIEL, "Projet du 2026-06-22", None);
+    let id = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    let student = fx.student("Amira", GENIE_INDUSTRIEL);
+    fx.assign(student, id);
+    etudiant::set_student_supervisor(&fx.conn, &fx.actor, student, Some(supervisor)).unwrap();
+
+    let mut request = reviewers("logistique");
+    request.pfe_id = Some(pfe);
+    let ranked = expertise::suggest_reviewers(&fx.conn, request).unwrap();
+    let order: Vec<i32> = ranked.iter().map(|c| c.jury_id).collect();
+    assert_eq!(order, [expert]);
+}
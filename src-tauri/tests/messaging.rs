@@ -0,0 +1,308 @@
+mod common;
+
+use common::{error, temp_path, Fixture, GENIE_INDUSTRIEL, GENIE_INFORMATIQUE};
+use gestion_soutenances_lib::models::badge::{self, HolderKind};
+use gestion_soutenances_lib::models::invite_soutenance;
+use gestion_soutenances_lib::models::jury_soutenance::JuryRole;
+use gestion_soutenances_lib::models::notification::{self, RecipientKind};
+use gestion_soutenances_lib::models::outbox::{self, OutboxStatus};
+use gestion_soutenances_lib::models::reminder::{self, ReminderStatus};
+use gestion_soutenances_lib::models::soutenance;
+use gestion_soutenances_lib::models::template::{self, Language, TemplateInput, TemplateKind};
+
+fn invite(fx: &Fixture, invitee: i32, soutenance_id: i32) -> String {
+    invite_soutenance::create_invite_soutenance(&fx.conn, &fx.actor, invitee, soutenance_id).unwrap();
+    invite_soutenance::get_invite_soutenance(&fx.conn, invitee, soutenance_id).unwrap().rsvp_token
+}
+
+fn move_to(fx: &Fixture, id: i32, hour: &str) {
+    soutenance::update_soutenance(
+        &fx.conn,
+        &fx.actor,
+        id,
+        Some("2026-06-22".to_string()),
+        Some(hour.to_string()),
+        GENIE_INDUSTRIEL,
+        None,
+        Some("Projet du 2026-06-22".to_string()),
+    )
+    .unwrap();
+}
+
+fn template(kind: TemplateKind, specialite_id: Option<i32>, subject: &str, body: &str) -> TemplateInput {
+    TemplateInput {
+        kind,
+        language: Language::Fr,
+        specialite_id,
+        subject: subject.to_string(),
+        body: body.to_string(),
+    }
+}
+
+#[test]
+fn changes_are_batched_into_one_message_per_recipient() {
+    let fx = Fixture::new();
+    let id = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    let jury = fx.jury("Sami", "Ben Ali");
+    fx.seat(jury, id, JuryRole::President);
+    notification::flush_pending_notifications(&fx.conn).unwrap();
+
+    move_to(&fx, id, "10:00");
+    move_to(&fx, id, "11:00");
+    let pending = notification::get_pending_notifications(&fx.conn, Some(id)).unwrap();
+    assert_eq!(pending.len(), 2);
+    assert_eq!((pending[0].field.as_str(), pending[0].recipient_kind), ("hour", RecipientKind::Jury));
+
+    assert_eq!(notification::flush_pending_notifications(&fx.conn).unwrap(), 1);
+    assert!(notification::get_pending_notifications(&fx.conn, None).unwrap().is_empty());
+    let messages = outbox::get_outbox(&fx.conn, Some(id), Some(OutboxStatus::Queued)).unwrap();
+    let change = messages.iter().find(|m| m.template == "soutenance_change").expect("no change message");
+    assert_eq!(change.email, "sami.benali@univ.tn");
+    assert!(change.body.contains("09:00") && change.body.contains("11:00"), "{}", change.body);
+}
+
+#[test]
+fn changes_that_cancel_out_send_nothing() {
+    let fx = Fixture::new();
+    let id = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    fx.seat(fx.jury("Sami", "Ben Ali"), id, JuryRole::President);
+    notification::flush_pending_notifications(&fx.conn).unwrap();
+    let before = outbox::get_outbox(&fx.conn, None, None).unwrap().len();
+
+    move_to(&fx, id, "10:00");
+    move_to(&fx, id, "09:00");
+    assert_eq!(notification::flush_pending_notifications(&fx.conn).unwrap(), 0);
+    assert_eq!(outbox::get_outbox(&fx.conn, None, None).unwrap().len(), before);
+}
+
+#[test]
+fn muted_recipients_and_discarded_changes_are_not_notified() {
+    let fx = Fixture::new();
+    let id = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    let jury = fx.jury("Sami", "Ben Ali");
+    assert!(error(notification::set_notification_preference(&fx.conn, &fx.actor, RecipientKind::Jury, 999, true)).contains("not found"));
+    notification::set_notification_preference(&fx.conn, &fx.actor, RecipientKind::Jury, jury, true).unwrap();
+    assert!(notification::get_notification_preference(&fx.conn, RecipientKind::Jury, jury).unwrap().muted);
+
+    fx.seat(jury, id, JuryRole::President);
+    assert_eq!(notification::flush_pending_notifications(&fx.conn).unwrap(), 0);
+
+    let student = fx.student("Amira", GENIE_INDUSTRIEL);
+    fx.assign(student, id);
+    move_to(&fx, id, "10:00");
+    assert!(notification::discard_pending_notifications(&fx.conn, id).unwrap() > 0);
+    assert_eq!(notification::flush_pending_notifications(&fx.conn).unwrap(), 0);
+}
+
+#[test]
+fn convocations_and_invitations_are_queued_once() {
+    let fx = Fixture::new();
+    let id = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    fx.seat(fx.jury("Sami", "Ben Ali"), id, JuryRole::President);
+    let karim = fx.invitee("Karim");
+    let token = invite(&fx, karim, id);
+    let nadia = fx.invitee("Nadia");
+    let declined = invite(&fx, nadia, id);
+    invite_soutenance::respond_to_invitation(&fx.conn, &fx.actor, declined, false).unwrap();
+
+    let previews = outbox::preview_soutenance_notifications(&fx.conn, id).unwrap();
+    let templates: Vec<_> = previews.iter().map(|p| (p.template.as_str(), p.recipient_id)).collect();
+    assert_eq!(templates.len(), 2);
+    assert_eq!(templates[1], ("invitation", karim));
+    assert!(previews[0].body.contains("en tant que président"), "{}", previews[0].body);
+    assert!(previews[1].body.contains(&token));
+
+    assert_eq!(outbox::enqueue_soutenance_notifications(&fx.conn, &fx.actor, id).unwrap(), 2);
+    assert_eq!(outbox::enqueue_soutenance_notifications(&fx.conn, &fx.actor, id).unwrap(), 0);
+}
+
+#[test]
+fn outbox_messages_are_cancelled_and_resent() {
+    let fx = Fixture::new();
+    let id = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    fx.seat(fx.jury("Sami", "Ben Ali"), id, JuryRole::President);
+    outbox::enqueue_soutenance_notifications(&fx.conn, &fx.actor, id).unwrap();
+    let queued = outbox::get_outbox(&fx.conn, Some(id), Some(OutboxStatus::Queued)).unwrap();
+    let message = queued.iter().find(|m| m.template == "jury_convocation").unwrap().id;
+
+    assert!(error(outbox::resend_outbox_message(&fx.conn, &fx.actor, message)).contains("not been sent"));
+    outbox::cancel_outbox_message(&fx.conn, &fx.actor, message).unwrap();
+    assert!(error(outbox::cancel_outbox_message(&fx.conn, &fx.actor, message)).contains("this one is cancelled"));
+
+    let copy = outbox::resend_outbox_message(&fx.conn, &fx.actor, message).unwrap();
+    assert_ne!(copy, message);
+    assert!(outbox::cancel_soutenance_notifications(&fx.conn, &fx.actor, id).unwrap() >= 1);
+    assert!(outbox::get_outbox(&fx.conn, Some(id), Some(OutboxStatus::Queued)).unwrap().is_empty());
+}
+
+#[test]
+fn nothing_is_sent_until_smtp_is_configured() {
+    let fx = Fixture::new();
+    let id = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    fx.seat(fx.jury("Sami", "Ben Ali"), id, JuryRole::President);
+    outbox::enqueue_soutenance_notifications(&fx.conn, &fx.actor, id).unwrap();
+
+    assert!(error(outbox::smtp_config(&fx.conn)).contains("SMTP is not configured"));
+    assert!(outbox::get_smtp_settings(&fx.conn).unwrap().host.is_none());
+    assert_eq!(outbox::flush_outbox(&fx.conn).unwrap(), 0);
+    assert!(!outbox::get_outbox(&fx.conn, Some(id), Some(OutboxStatus::Queued)).unwrap().is_empty());
+}
+
+#[test]
+fn reminders_follow_the_offsets() {
+    let fx = Fixture::new();
+    assert_eq!(reminder::get_reminder_offsets(&fx.conn).unwrap(), [7 * 24 * 60, 24 * 60, 120]);
+    assert!(error(reminder::set_reminder_offsets(&fx.conn, &fx.actor, vec![0])).contains("at least one minute"));
+    reminder::set_reminder_offsets(&fx.conn, &fx.actor, vec![60, 24 * 60, 60]).unwrap();
+    assert_eq!(reminder::get_reminder_offsets(&fx.conn).unwrap(), [24 * 60, 60]);
+
+    let id = fx.soutenance("2099-06-22 09:00", GENIE_INDUSTRIEL, None);
+    fx.seat(fx.jury("Sami", "Ben Ali"), id, JuryRole::President);
+    fx.assign(fx.student("Amira", GENIE_INDUSTRIEL), id);
+
+    let upcoming = reminder::get_upcoming_reminders(&fx.conn, Some(id)).unwrap();
+    assert_eq!(upcoming.len(), 4);
+    assert_eq!(upcoming[0].due_at, "2099-06-21 09:00:00");
+    assert_eq!(upcoming[3].due_at, "2099-06-22 08:00:00");
+    assert!(upcoming.iter().all(|r| r.status == ReminderStatus::Pending));
+}
+
+#[test]
+fn cancelled_reminders_stay_cancelled() {
+    let fx = Fixture::new();
+    let id = fx.soutenance("2099-06-22 09:00", GENIE_INDUSTRIEL, None);
+    fx.seat(fx.jury("Sami", "Ben Ali"), id, JuryRole::President);
+    let first = reminder::get_upcoming_reminders(&fx.conn, Some(id)).unwrap()[0].id;
+
+    reminder::cancel_reminder(&fx.conn, &fx.actor, first).unwrap();
+    assert!(error(reminder::cancel_reminder(&fx.conn, &fx.actor, first)).contains("this one is cancelled"));
+    let upcoming = reminder::get_upcoming_reminders(&fx.conn, Some(id)).unwrap();
+    assert_eq!(upcoming.len(), 2);
+    assert!(upcoming.iter().all(|r| r.id != first));
+
+    assert_eq!(reminder::cancel_soutenance_reminders(&fx.conn, &fx.actor, id).unwrap(), 2);
+    assert!(reminder::get_upcoming_reminders(&fx.conn, Some(id)).unwrap().is_empty());
+}
+
+#[test]
+fn templates_are_validated_before_they_are_saved() {
+    let fx = Fixture::new();
+    let no_subject = template(TemplateKind::Reminder, None, " ", "Bonjour");
+    assert!(error(template::save_template(&fx.conn, &fx.actor, no_subject)).contains("needs a subject"));
+    let unknown = template(TemplateKind::Reminder, None, "Rappel", "Bonjour {{ recipient.surname }}");
+    assert!(error(template::save_template(&fx.conn, &fx.actor, unknown)).starts_with("Invalid template: body"));
+
+    let problems = template::validate_template(TemplateKind::Reminder, Language::Fr, "{% if %}".to_string(), String::new());
+    assert_eq!(problems.len(), 1);
+    assert!(problems[0].starts_with("subject"));
+    assert!(template::get_template_variables(TemplateKind::Reminder).contains(&"recipient.firstname".to_string()));
+}
+
+#[test]
+fn a_specialite_override_takes_precedence() {
+    let fx = Fixture::new();
+    let default = template(TemplateKind::Reminder, None, "Rappel", "Bonjour {{ recipient.firstname }}");
+    let default = template::save_template(&fx.conn, &fx.actor, default).unwrap();
+    let override_ = template(TemplateKind::Reminder, Some(GENIE_INFORMATIQUE), "Rappel GI", "Salut {{ recipient.firstname }}");
+    let override_ = template::save_template(&fx.conn, &fx.actor, override_).unwrap();
+
+    let effective = |specialite_id| template::get_effective_template(&fx.conn, TemplateKind::Reminder, Language::Fr, specialite_id).unwrap();
+    assert_eq!(effective(Some(GENIE_INFORMATIQUE)).id, Some(override_));
+    assert_eq!(effective(Some(GENIE_INDUSTRIEL)).id, Some(default));
+
+    let resaved = template(TemplateKind::Reminder, None, "Rappel", "Bonsoir {{ recipient.firstname }}");
+    assert_eq!(template::save_template(&fx.conn, &fx.actor, resaved).unwrap(), default);
+    assert_eq!(template::get_templates(&fx.conn, Some(TemplateKind::Reminder)).unwrap().len(), 2);
+
+    template::delete_template(&fx.conn, &fx.actor, default).unwrap();
+    assert_eq!(effective(Some(GENIE_INDUSTRIEL)).id, None);
+}
+
+#[test]
+fn previews_use_sample_data_in_the_chosen_language() {
+    let fx = Fixture::new();
+    let draft = template::preview_template(
+        &fx.conn,
+        TemplateKind::Reminder,
+        Language::Fr,
+        None,
+        Some("Rappel pour {{ recipient.firstname }}".to_string()),
+        None,
+    )
+    .unwrap();
+    assert!(!draft.subject.contains("{{"));
+
+    template::set_template_language(&fx.conn, &fx.actor, Language::En).unwrap();
+    assert_eq!(template::get_template_language(&fx.conn).unwrap(), Language::En);
+    let id = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    fx.seat(fx.jury("Sami", "Ben Ali"), id, JuryRole::President);
+    let previews = outbox::preview_soutenance_notifications(&fx.conn, id).unwrap();
+    assert!(previews[0].subject.starts_with("Notice: defence on 2026-06-22"), "{}", previews[0].subject);
+    assert!(previews[0].body.contains("as chair"), "{}", previews[0].body);
+}
+
+#[test]
+fn the_pv_is_written_right_to_left_in_arabic() {
+    let fx = Fixture::new();
+    let id = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    fx.seat(fx.jury("Sami", "Ben Ali"), id, JuryRole::President);
+    let path = temp_path("pv.html");
+
+    template::export_pv(&fx.conn, path.to_string_lossy().into_owned(), id, Some(Language::Ar)).unwrap();
+    let html = std::fs::read_to_string(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert!(html.contains(r#"<html lang="ar" dir="rtl">"#));
+    assert!(html.contains("Sami Ben Ali"));
+}
+
+#[test]
+fn badge_tokens_are_signed() {
+    let fx = Fixture::new();
+    let token = badge::sign(&fx.conn, 7, HolderKind::Jury, 3).unwrap();
+    assert_eq!(badge::verify(&fx.conn, &token).unwrap(), (7, HolderKind::Jury, 3));
+
+    let forged = token.replacen("7.", "8.", 1);
+    assert_eq!(error(badge::verify(&fx.conn, &forged)), "Invalid badge");
+    assert!(badge::verify(&fx.conn, "not a badge").is_err());
+
+    badge::rotate_badge_key(&fx.conn).unwrap();
+    assert!(badge::verify(&fx.conn, &token).is_err());
+}
+
+#[test]
+fn badges_check_people_in_on_the_day() {
+    let fx = Fixture::new();
+    let today = fx.soutenance(&format!("{} 09:00", fx.today()), GENIE_INDUSTRIEL, None);
+    let other = fx.soutenance("2099-06-22 09:00", GENIE_INDUSTRIEL, None);
+    fx.seat(fx.jury("Sami", "Ben Ali"), today, JuryRole::President);
+    let karim = fx.invitee("Karim");
+    invite(&fx, karim, today);
+    let nadia = fx.invitee("Nadia");
+    let declined = invite(&fx, nadia, today);
+    invite_soutenance::respond_to_invitation(&fx.conn, &fx.actor, declined, false).unwrap();
+
+    let badges = badge::get_soutenance_badges(&fx.conn, today).unwrap();
+    let holders: Vec<_> = badges.iter().map(|b| (b.name.as_str(), b.role.as_str())).collect();
+    assert_eq!(holders, [("Sami Ben Ali", "Président"), ("Karim Invite", "Invité")]);
+    assert!(badges[0].qr_svg.starts_with("<svg"));
+
+    let karim_badge = badges[1].token.clone();
+    assert!(error(badge::check_in_badge(&fx.conn, &fx.actor, karim_badge.clone(), other)).contains("is for soutenance"));
+    let entry = badge::check_in_badge(&fx.conn, &fx.actor, karim_badge.clone(), today).unwrap();
+    assert!(entry.arrived_at.is_some());
+    assert!(error(badge::check_in_badge(&fx.conn, &fx.actor, karim_badge, today)).contains("already used"));
+    assert_eq!(
+        invite_soutenance::get_invite_soutenance(&fx.conn, karim, today).unwrap().status,
+        invite_soutenance::InvitationStatus::Attended
+    );
+
+    let arrived: Vec<_> = badge::get_attendance_list(&fx.conn, today)
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.arrived_at.is_some())
+        .collect();
+    assert_eq!(arrived, [false, true]);
+    let path = temp_path("attendance.csv");
+    assert_eq!(badge::export_attendance_csv(&fx.conn, path.to_string_lossy().into_owned(), today).unwrap(), 2);
+    let _ = std::fs::remove_file(&path);
+}
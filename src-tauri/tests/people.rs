@@ -0,0 +1,413 @@
+mod common;
+
+use common::{error, temp_path, Fixture, GENIE_INDUSTRIEL, GENIE_INFORMATIQUE};
+use gestion_soutenances_lib::models::etudiant::{self, NewStudent};
+use gestion_soutenances_lib::models::invite::{self, NewInvitee};
+use gestion_soutenances_lib::models::jury::{self, NewJury};
+use gestion_soutenances_lib::models::jury_soutenance::JuryRole;
+use gestion_soutenances_lib::models::{pfe, specialite, user};
+use gestion_soutenances_lib::query::{QueryOptions, SortDirection};
+
+#[test]
+fn students_are_created_in_the_active_session() {
+    let fx = Fixture::new();
+    let id = fx.student("Amina", GENIE_INDUSTRIEL);
+
+    let student = etudiant::get_student(&fx.conn, id).unwrap();
+    assert_eq!(student.firstname, "Amina");
+    assert_eq!(student.session_id, Some(fx.session()));
+    assert_eq!(student.email.as_deref(), Some("amina@etudiant.tn"));
+    assert_eq!(student.soutenance_id, None);
+}
+
+#[test]
+fn updating_a_student_returns_the_saved_row() {
+    let fx = Fixture::new();
+    let id = fx.student("Amina", GENIE_INDUSTRIEL);
+    let soutenance = fx.soutenance("2026-06-20 09:00", GENIE_INFORMATIQUE, None);
+
+    let updated = etudiant::update_student(
+        &fx.conn,
+        &fx.actor,
+        id,
+        "Amina".to_string(),
+        "Ben Salah".to_string(),
+        "Sfax".to_string(),
+        GENIE_INFORMATIQUE,
+        soutenance,
+        None,
+    )
+    .unwrap();
+    assert_eq!(updated.lastname, "Ben Salah");
+    assert_eq!(updated.specialite_id, GENIE_INFORMATIQUE);
+    assert_eq!(updated.soutenance_id, Some(soutenance));
+    assert_eq!(updated.email, None);
+}
+
+#[test]
+fn grades_must_be_between_0_and_20() {
+    let fx = Fixture::new();
+    let id = fx.student("Amina", GENIE_INDUSTRIEL);
+
+    assert_eq!(etudiant::set_student_grade(&fx.conn, &fx.actor, id, Some(15.5)).unwrap().grade, Some(15.5));
+    assert!(error(etudiant::set_student_grade(&fx.conn, &fx.actor, id, Some(20.5))).contains("between 0 and 20"));
+    assert_eq!(etudiant::set_student_grade(&fx.conn, &fx.actor, id, None).unwrap().grade, None);
+}
+
+#[test]
+fn supervisors_are_recorded_on_the_student() {
+    let fx = Fixture::new();
+    let id = fx.student("Amina", GENIE_INDUSTRIEL);
+    let supervisor = fx.jury("Ali", "Trabelsi");
+
+    let student = etudiant::set_student_supervisor(&fx.conn, &fx.actor, id, Some(supervisor)).unwrap();
+    assert_eq!(student.supervisor_id, Some(supervisor));
+}
+
+#[test]
+fn students_are_listed_per_specialite_with_paging() {
+    let fx = Fixture::new();
+    for name in ["Amina", "Omar", "Yasmine"] {
+        fx.student(name, GENIE_INDUSTRIEL);
+    }
+    fx.student("Karim", GENIE_INFORMATIQUE);
+
+    let page = etudiant::get_specialite_students(
+        &fx.conn,
+        GENIE_INDUSTRIEL,
+        None,
+        Some(QueryOptions {
+            page: Some(1),
+            page_size: Some(2),
+            sort_by: Some("firstname".to_string()),
+            sort_dir: Some(SortDirection::Desc),
+            ..Default::default()
+        }),
+    )
+    .unwrap();
+    assert_eq!(page.total, 3);
+    let names: Vec<_> = page.items.iter().map(|s| s.firstname.as_str()).collect();
+    assert_eq!(names, ["Yasmine", "Omar"]);
+
+    let by_department = etudiant::get_students_by_department(
+        &fx.conn,
+        GENIE_INFORMATIQUE,
+        None,
+        Some(QueryOptions {
+            text: Some("kar".to_string()),
+            ..Default::default()
+        }),
+    )
+    .unwrap();
+    assert_eq!(by_department.total, 1);
+
+    let unsupported = QueryOptions {
+        jury_id: Some(1),
+        ..Default::default()
+    };
+    assert!(error(etudiant::get_specialite_students(&fx.conn, GENIE_INDUSTRIEL, None, Some(unsupported))).contains("jury_id"));
+
+    let too_far = QueryOptions {
+        page: Some(i64::MAX),
+        page_size: Some(2),
+        ..Default::default()
+    };
+    assert!(error(etudiant::get_specialite_students(&fx.conn, GENIE_INDUSTRIEL, None, Some(too_far))).contains("out of range"));
+}
+
+#[test]
+fn deleted_students_are_hidden_from_lists() {
+    let fx = Fixture::new();
+    let kept = fx.student("Amina", GENIE_INDUSTRIEL);
+    let deleted = fx.student("Omar", GENIE_INDUSTRIEL);
+
+    etudiant::delete_students(&fx.conn, &fx.actor, vec![deleted]).unwrap();
+
+    let page = etudiant::get_specialite_students(&fx.conn, GENIE_INDUSTRIEL, None, None).unwrap();
+    assert_eq!(page.items.iter().map(|s| s.id).collect::<Vec<_>>(), [kept]);
+    assert!(etudiant::get_student(&fx.conn, deleted).unwrap().deleted_at.is_some());
+}
+
+#[test]
+fn students_are_imported_from_csv() {
+    let fx = Fixture::new();
+    let path = temp_path("students.csv");
+    std::fs::write(
+        &path,
+        "\u{feff}firstname,lastname,specialite_id,email\r\nAmina,\"Ben Salah, Jr\",1,amina@etudiant.tn\r\nOmar,Jaziri,2,\r\n\r\n",
+    )
+    .unwrap();
+
+    let count = fx
+        .write(|conn, actor| etudiant::import_students_csv(conn, actor, &path.to_string_lossy(), None))
+        .unwrap();
+    assert_eq!(count, 2);
+    let page = etudiant::get_specialite_students(&fx.conn, GENIE_INDUSTRIEL, None, None).unwrap();
+    assert_eq!(page.items[0].lastname, "Ben Salah, Jr");
+    assert_eq!(page.items[0].email.as_deref(), Some("amina@etudiant.tn"));
+}
+
+#[test]
+fn a_bad_csv_row_imports_nothing() {
+    let fx = Fixture::new();
+    let path = temp_path("bad-students.csv");
+    std::fs::write(&path, "firstname,lastname,specialite_id\nAmina,Ben Salah,1\nOmar,Jaziri,two\n").unwrap();
+
+    let message = error(fx.write(|conn, actor| etudiant::import_students_csv(conn, actor, &path.to_string_lossy(), None)));
+    assert!(message.contains("Row 3"), "{}", message);
+    assert_eq!(fx.count("etudiant"), 0);
+
+    std::fs::write(&path, "firstname,specialite_id\nAmina,1\n").unwrap();
+    assert!(error(etudiant::read_students_csv(&path.to_string_lossy(), None)).contains("lastname"));
+}
+
+#[test]
+fn assigning_an_unknown_student_fails() {
+    let fx = Fixture::new();
+    let soutenance = fx.soutenance("2026-06-20 09:00", GENIE_INDUSTRIEL, None);
+
+    let message = error(etudiant::assign_student(&fx.conn, &fx.actor, 404, soutenance));
+    assert!(message.contains("Student 404 not found"), "{}", message);
+}
+
+#[test]
+fn get_jury_reads_the_email_column() {
+    let fx = Fixture::new();
+    let id = fx.jury("Ali", "Trabelsi");
+
+    let jury = jury::get_jury(&fx.conn, id).unwrap();
+    assert_eq!(jury.email, "ali.trabelsi@univ.tn");
+    assert_eq!(jury.deleted_at, None);
+}
+
+#[test]
+fn jury_emails_are_unique() {
+    let fx = Fixture::new();
+    let ali = fx.jury("Ali", "Trabelsi");
+    let sara = fx.jury("Sara", "Kammoun");
+
+    let taken = NewJury {
+        firstname: "Sara".to_string(),
+        lastname: "Kammoun".to_string(),
+        email: "ali.trabelsi@univ.tn".to_string(),
+    };
+    assert_eq!(jury::update_jury(&fx.conn, &fx.actor, sara, taken).unwrap(), "Email already exists for another jury");
+    assert_eq!(jury::get_jury(&fx.conn, sara).unwrap().email, "sara.kammoun@univ.tn");
+
+    let renamed = NewJury {
+        firstname: "Ali".to_string(),
+        lastname: "Trabelsi".to_string(),
+        email: "a.trabelsi@univ.tn".to_string(),
+    };
+    jury::update_jury(&fx.conn, &fx.actor, ali, renamed).unwrap();
+    assert_eq!(jury::get_jury(&fx.conn, ali).unwrap().email, "a.trabelsi@univ.tn");
+
+    let duplicate = NewJury {
+        firstname: "Autre".to_string(),
+        lastname: "Personne".to_string(),
+        email: "a.trabelsi@univ.tn".to_string(),
+    };
+    assert!(jury::create_jury(&fx.conn, &fx.actor, duplicate).is_err());
+}
+
+#[test]
+fn juries_are_listed_and_archived() {
+    let fx = Fixture::new();
+    let ali = fx.jury("Ali", "Trabelsi");
+    let sara = fx.jury("Sara", "Kammoun");
+
+    jury::delete_jury(&fx.conn, &fx.actor, vec![]).unwrap();
+    jury::delete_jury(&fx.conn, &fx.actor, vec![ali]).unwrap();
+
+    let page = jury::get_all_jury(&fx.conn, None).unwrap();
+    assert_eq!(page.items.iter().map(|j| j.id).collect::<Vec<_>>(), [sara]);
+    assert!(jury::get_jury(&fx.conn, ali).unwrap().deleted_at.is_some());
+}
+
+#[test]
+fn jury_soutenances_come_with_their_role() {
+    let fx = Fixture::new();
+    let ali = fx.jury("Ali", "Trabelsi");
+    let soutenance = fx.soutenance("2026-06-20 09:00", GENIE_INDUSTRIEL, None);
+    fx.seat(ali, soutenance, JuryRole::Rapporteur);
+
+    let soutenances = jury::get_jury_soutenances(&fx.conn, ali, None).unwrap();
+    assert_eq!(soutenances, [(soutenance, "rapporteur".to_string())]);
+}
+
+#[test]
+fn get_invite_reads_the_email_column() {
+    let fx = Fixture::new();
+    let id = fx.invitee("Nadia");
+
+    assert_eq!(invite::get_invite(&fx.conn, id).unwrap().email, "nadia@entreprise.tn");
+}
+
+#[test]
+fn invitees_are_updated_listed_and_archived() {
+    let fx = Fixture::new();
+    let nadia = fx.invitee("Nadia");
+    let sami = fx.invitee("Sami");
+
+    let taken = NewInvitee {
+        firstname: "Sami".to_string(),
+        lastname: "Invite".to_string(),
+        email: "nadia@entreprise.tn".to_string(),
+    };
+    assert_eq!(invite::update_invite(&fx.conn, &fx.actor, sami, taken).unwrap(), "Email already exists for another invite");
+
+    let renamed = NewInvitee {
+        firstname: "Sami".to_string(),
+        lastname: "Gharbi".to_string(),
+        email: "sami.gharbi@entreprise.tn".to_string(),
+    };
+    invite::update_invite(&fx.conn, &fx.actor, sami, renamed).unwrap();
+    assert_eq!(invite::get_invite(&fx.conn, sami).unwrap().lastname, "Gharbi");
+
+    invite::delete_invite(&fx.conn, &fx.actor, vec![nadia]).unwrap();
+    let page = invite::get_all_invite(
+        &fx.conn,
+        Some(QueryOptions {
+            text: Some("gharbi".to_string()),
+            ..Default::default()
+        }),
+    )
+    .unwrap();
+    assert_eq!(page.items.iter().map(|i| i.id).collect::<Vec<_>>(), [sami]);
+    assert_eq!(invite::get_all_invite(&fx.conn, None).unwrap().total, 1);
+}
+
+#[test]
+fn get_invite_soutenances_lists_the_defences_of_an_invitee() {
+    let fx = Fixture::new();
+    let nadia = fx.invitee("Nadia");
+    let first = fx.soutenance("2026-06-20 09:00", GENIE_INDUSTRIEL, None);
+    let second = fx.soutenance("2026-06-21 09:00", GENIE_INDUSTRIEL, None);
+    for soutenance in [first, second] {
+        gestion_soutenances_lib::models::invite_soutenance::create_invite_soutenance(&fx.conn, &fx.actor, nadia, soutenance)
+            .unwrap();
+    }
+
+    let mut soutenances = invite::get_invite_soutenances(&fx.conn, nadia, None).unwrap();
+    soutenances.sort();
+    assert_eq!(soutenances, [first, second]);
+}
+
+#[test]
+fn users_are_stored_under_their_username() {
+    let fx = Fixture::new();
+    let id = user::create_user(
+        &fx.conn,
+        &fx.actor,
+        "Secrétariat".to_string(),
+        "secretariat@univ.tn".to_string(),
+        "s3cret".to_string(),
+    )
+    .unwrap();
+
+    let created = user::get_user(&fx.conn, id).unwrap();
+    assert_eq!(created.name, "Secrétariat");
+    assert_eq!(created.password, user::hash_password("s3cret"));
+
+    user::update_user(
+        &fx.conn,
+        &fx.actor,
+        id,
+        "Scolarité".to_string(),
+        "scolarite@univ.tn".to_string(),
+        "n3w".to_string(),
+    )
+    .unwrap();
+    let updated = user::get_user(&fx.conn, id).unwrap();
+    assert_eq!((updated.name.as_str(), updated.email.as_str()), ("Scolarité", "scolarite@univ.tn"));
+
+    user::delete_user(&fx.conn, &fx.actor, id).unwrap();
+    assert!(user::get_user(&fx.conn, id).is_err());
+}
+
+#[test]
+fn created_users_and_the_seeded_admin_can_sign_in() {
+    let fx = Fixture::new();
+    user::create_user(
+        &fx.conn,
+        &fx.actor,
+        "Secrétariat".to_string(),
+        "secretariat@univ.tn".to_string(),
+        "s3cret".to_string(),
+    )
+    .unwrap();
+
+    let signed_in = user::authenticate(&fx.conn, "secretariat@univ.tn", "s3cret").unwrap().unwrap();
+    assert_eq!(signed_in.username, "Secrétariat");
+    assert!(user::authenticate(&fx.conn, "secretariat@univ.tn", "wrong").unwrap().is_none());
+    let stored = user::hash_password("s3cret");
+    assert!(user::authenticate(&fx.conn, "secretariat@univ.tn", &stored).unwrap().is_none());
+
+    let admin = user::authenticate(&fx.conn, "admin@example.com", "admin").unwrap().unwrap();
+    let again = user::authenticate(&fx.conn, "admin@example.com", "admin").unwrap().unwrap();
+    assert_eq!(admin.id, 1);
+    let admin_password: String = fx
+        .conn
+        .query_row("SELECT password FROM users WHERE id = 1", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(admin_password, user::hash_password("admin"));
+    assert_ne!(admin.token, again.token);
+}
+
+#[test]
+fn pfes_are_created_updated_and_deleted() {
+    let fx = Fixture::new();
+    let id = fx.pfe(GENIE_INFORMATIQUE, "Détection de fraude", Some("Vermeg"));
+
+    pfe::update_pfe(
+        &fx.conn,
+        &fx.actor,
+        id,
+        GENIE_INFORMATIQUE,
+        Some("Détection de fraude bancaire".to_string()),
+        Some("Apprentissage supervisé".to_string()),
+        Some("Vermeg".to_string()),
+    )
+    .unwrap();
+    let saved = pfe::get_pfe(&fx.conn, id).unwrap();
+    assert_eq!(saved.title.as_deref(), Some("Détection de fraude bancaire"));
+    assert_eq!(saved.resume.as_deref(), Some("Apprentissage supervisé"));
+    assert_eq!(specialite::get_specialite_pfes(&fx.conn, GENIE_INFORMATIQUE).unwrap(), [id]);
+
+    pfe::delete_pfe(&fx.conn, &fx.actor, id).unwrap();
+    assert!(pfe::get_pfe(&fx.conn, id).is_err());
+}
+
+#[test]
+fn specialites_are_seeded_and_can_be_added() {
+    let fx = Fixture::new();
+    assert_eq!(specialite::get_specialite(&fx.conn, GENIE_INDUSTRIEL).unwrap().name, "Licence Génie Industriel");
+
+    let id = specialite::create_specialite(&fx.conn, &fx.actor).unwrap();
+    assert_eq!(specialite::get_specialite(&fx.conn, id).unwrap().name, "");
+
+    specialite::delete_specialite(&fx.conn, &fx.actor, id).unwrap();
+    assert!(specialite::get_specialite(&fx.conn, id).is_err());
+}
+
+#[test]
+fn students_can_be_created_with_a_soutenance() {
+    let fx = Fixture::new();
+    let soutenance = fx.soutenance("2026-06-20 09:00", GENIE_INDUSTRIEL, None);
+
+    let id = etudiant::create_student(
+        &fx.conn,
+        &fx.actor,
+        &NewStudent {
+            firstname: "Amina".to_string(),
+            lastname: "Ben Salah".to_string(),
+            address: String::new(),
+            specialite_id: GENIE_INDUSTRIEL,
+            soutenance_id: Some(soutenance),
+            session_id: None,
+            email: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(etudiant::get_student(&fx.conn, id).unwrap().soutenance_id, Some(soutenance));
+}
@@ -0,0 +1,272 @@
+mod common;
+
+use common::{error, Fixture, GENIE_INDUSTRIEL};
+use gestion_soutenances_lib::models::classroom::{self, ClassroomDetails, NewClosure, OpeningHours, RoomRequest};
+use gestion_soutenances_lib::models::occupancy;
+use gestion_soutenances_lib::query::QueryOptions;
+
+// 2026-06-22 is a Monday.
+const MONDAY: i32 = 1;
+
+fn weekdays(opens: &str, closes: &str) -> Vec<OpeningHours> {
+    (1..=5)
+        .map(|weekday| OpeningHours {
+            weekday,
+            opens: opens.to_string(),
+            closes: closes.to_string(),
+        })
+        .collect()
+}
+
+fn request(date: &str, hour: &str, attendees: Option<i32>) -> RoomRequest {
+    RoomRequest {
+        date: date.to_string(),
+        hour: hour.to_string(),
+        attendees,
+        soutenance_id: None,
+        equipment: Vec::new(),
+    }
+}
+
+#[test]
+fn classrooms_keep_their_details() {
+    let fx = Fixture::new();
+    let id = classroom::create_classroom(
+        &fx.conn,
+        &fx.actor,
+        "Amphi A".to_string(),
+        Some(ClassroomDetails {
+            capacity: Some(120),
+            building: Some("Bloc A".to_string()),
+            floor: None,
+            equipment: vec![" Projector".to_string(), "micro".to_string(), "projector".to_string()],
+        }),
+    )
+    .unwrap();
+
+    let room = classroom::get_classroom(&fx.conn, id).unwrap();
+    assert_eq!(room.name, "Amphi A");
+    assert_eq!(room.capacity, Some(120));
+    assert_eq!(room.equipment, ["micro", "projector"]);
+
+    classroom::update_classroom(&fx.conn, &fx.actor, id, "Amphi B".to_string(), None).unwrap();
+    let renamed = classroom::get_classroom(&fx.conn, id).unwrap();
+    assert_eq!((renamed.name.as_str(), renamed.capacity), ("Amphi B", Some(120)));
+
+    classroom::update_classroom(&fx.conn, &fx.actor, id, "Amphi B".to_string(), Some(ClassroomDetails::default())).unwrap();
+    let cleared = classroom::get_classroom(&fx.conn, id).unwrap();
+    assert_eq!(cleared.capacity, None);
+    assert!(cleared.equipment.is_empty());
+}
+
+#[test]
+fn archived_classrooms_are_left_out_of_the_list() {
+    let fx = Fixture::new();
+    let a = fx.classroom("Salle 1", Some(30));
+    let b = fx.classroom("Salle 2", Some(40));
+
+    classroom::delete_classrooms(&fx.conn, &fx.actor, vec![a]).unwrap();
+
+    let page = classroom::get_all_classrooms(&fx.conn, None).unwrap();
+    assert_eq!(page.items.iter().map(|room| room.id).collect::<Vec<_>>(), [b]);
+    assert!(classroom::get_classroom(&fx.conn, a).unwrap().deleted_at.is_some());
+
+    let filtered = QueryOptions {
+        classroom_id: Some(b),
+        ..Default::default()
+    };
+    assert!(classroom::get_all_classrooms(&fx.conn, Some(filtered)).is_err());
+}
+
+#[test]
+fn classroom_soutenances_are_listed() {
+    let fx = Fixture::new();
+    let room = fx.classroom("Salle 1", Some(30));
+    let soutenance = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, Some(room));
+    fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+
+    assert_eq!(classroom::get_classroom_soutenances(&fx.conn, room, None).unwrap(), [soutenance]);
+}
+
+#[test]
+fn opening_hours_are_validated_and_normalised() {
+    let fx = Fixture::new();
+    let room = fx.classroom("Salle 1", Some(30));
+
+    let invalid = vec![OpeningHours {
+        weekday: 7,
+        opens: "08:00".to_string(),
+        closes: "12:00".to_string(),
+    }];
+    assert!(error(classroom::set_classroom_opening_hours(&fx.conn, &fx.actor, room, invalid)).contains("Invalid weekday"));
+    let backwards = vec![OpeningHours {
+        weekday: MONDAY,
+        opens: "14:00".to_string(),
+        closes: "08:00".to_string(),
+    }];
+    assert!(error(classroom::set_classroom_opening_hours(&fx.conn, &fx.actor, room, backwards)).contains("must be before"));
+    let mut twice = weekdays("08:00", "12:00");
+    twice.push(twice[0].clone());
+    assert!(error(classroom::set_classroom_opening_hours(&fx.conn, &fx.actor, room, twice)).contains("twice"));
+
+    classroom::set_classroom_opening_hours(&fx.conn, &fx.actor, room, weekdays("8:00", "17:30")).unwrap();
+    let hours = classroom::get_classroom_opening_hours(&fx.conn, room).unwrap();
+    assert_eq!(hours.len(), 5);
+    assert_eq!((hours[0].weekday, hours[0].opens.as_str()), (MONDAY, "08:00"));
+}
+
+#[test]
+fn closures_are_added_and_removed() {
+    let fx = Fixture::new();
+    let room = fx.classroom("Salle 1", Some(30));
+
+    let backwards = NewClosure {
+        classroom_id: room,
+        start_date: "2026-06-25".to_string(),
+        end_date: "2026-06-22".to_string(),
+        reason: None,
+    };
+    assert!(error(classroom::add_classroom_closure(&fx.conn, &fx.actor, backwards)).contains("before it starts"));
+
+    let id = classroom::add_classroom_closure(
+        &fx.conn,
+        &fx.actor,
+        NewClosure {
+            classroom_id: room,
+            start_date: "2026-06-22".to_string(),
+            end_date: "2026-06-23".to_string(),
+            reason: Some("Travaux".to_string()),
+        },
+    )
+    .unwrap();
+    let closures = classroom::get_classroom_closures(&fx.conn, room).unwrap();
+    assert_eq!(closures.len(), 1);
+    assert_eq!(closures[0].reason.as_deref(), Some("Travaux"));
+
+    classroom::delete_classroom_closure(&fx.conn, &fx.actor, id).unwrap();
+    assert!(classroom::get_classroom_closures(&fx.conn, room).unwrap().is_empty());
+}
+
+#[test]
+fn room_conflicts_cover_closures_hours_and_bookings() {
+    let fx = Fixture::new();
+    let room = fx.classroom("Salle 1", Some(30));
+    classroom::set_classroom_opening_hours(&fx.conn, &fx.actor, room, weekdays("08:00", "17:00")).unwrap();
+    let booked = fx.soutenance("2026-06-22 10:00", GENIE_INDUSTRIEL, Some(room));
+
+    let overlap = classroom::room_conflicts(&fx.conn, room, "2026-06-22", "10:30", None).unwrap();
+    assert_eq!(overlap, [format!("The room is already booked at 10:00 by soutenance {}", booked)]);
+    assert!(classroom::room_conflicts(&fx.conn, room, "2026-06-22", "10:30", Some(booked)).unwrap().is_empty());
+    assert!(classroom::room_conflicts(&fx.conn, room, "2026-06-22", "11:00", None).unwrap().is_empty());
+
+    let late = classroom::room_conflicts(&fx.conn, room, "2026-06-22", "16:30", None).unwrap();
+    assert!(late[0].contains("outside the opening hours"), "{:?}", late);
+    let saturday = classroom::room_conflicts(&fx.conn, room, "2026-06-20", "09:00", None).unwrap();
+    assert_eq!(saturday, ["The room is not open on 2026-06-20"]);
+    assert!(classroom::room_conflicts(&fx.conn, room, "22/06/2026", "09:00", None).is_err());
+}
+
+#[test]
+fn a_second_booking_in_the_same_room_is_saved_with_a_warning() {
+    let fx = Fixture::new();
+    let room = fx.classroom("Salle 1", Some(2));
+    fx.soutenance("2026-06-22 10:00", GENIE_INDUSTRIEL, Some(room));
+
+    let second = gestion_soutenances_lib::models::soutenance::create_soutenance(
+        &fx.conn,
+        &fx.actor,
+        Some("2026-06-22".to_string()),
+        Some("10:30".to_string()),
+        GENIE_INDUSTRIEL,
+        Some(room),
+        None,
+        None,
+        Some(5),
+    )
+    .unwrap();
+    assert_eq!(second.warnings.len(), 2, "{:?}", second.warnings);
+    assert!(second.warnings[0].contains("holds 2 people but 5 are expected"));
+}
+
+#[test]
+fn available_classrooms_fit_the_request() {
+    let fx = Fixture::new();
+    let small = fx.classroom("Petite salle", Some(10));
+    let large = fx.classroom("Amphi", Some(100));
+    let unknown = classroom::create_classroom(&fx.conn, &fx.actor, "Labo".to_string(), None).unwrap();
+    let busy = fx.classroom("Salle occupée", Some(50));
+    fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, Some(busy));
+
+    let ids = |rooms: Vec<classroom::Salle>| rooms.into_iter().map(|room| room.id).collect::<Vec<_>>();
+    let any = classroom::find_available_classrooms(&fx.conn, request("2026-06-22", "09:00", Some(5))).unwrap();
+    assert_eq!(ids(any), [small, large, unknown]);
+
+    let big = classroom::find_available_classrooms(&fx.conn, request("2026-06-22", "09:00", Some(20))).unwrap();
+    assert_eq!(ids(big), [large, unknown]);
+
+    let mut projector = request("2026-06-22", "09:00", None);
+    projector.equipment = vec!["Projector".to_string()];
+    assert_eq!(ids(classroom::find_available_classrooms(&fx.conn, projector).unwrap()), [small, large]);
+}
+
+#[test]
+fn the_agenda_shows_defences_and_free_gaps() {
+    let fx = Fixture::new();
+    let room = fx.classroom("Salle 1", Some(30));
+    classroom::set_classroom_opening_hours(&fx.conn, &fx.actor, room, weekdays("08:00", "12:00")).unwrap();
+    let soutenance = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, Some(room));
+
+    let agenda = occupancy::get_classroom_agenda(&fx.conn, room, "2026-06-22".to_string(), "2026-06-23".to_string(), None).unwrap();
+    assert_eq!(agenda.days.len(), 2);
+    let monday = &agenda.days[0];
+    let blocks: Vec<_> = monday
+        .blocks
+        .iter()
+        .map(|b| (b.start.as_str(), b.end.as_str(), b.soutenance_id))
+        .collect();
+    assert_eq!(blocks, [("08:00", "09:00", None), ("09:00", "10:00", Some(soutenance)), ("10:00", "12:00", None)]);
+    assert_eq!((monday.open_minutes, monday.occupied_minutes), (240, 60));
+
+    assert!(occupancy::get_classroom_agenda(&fx.conn, room, "2026-06-23".to_string(), "2026-06-22".to_string(), None).is_err());
+}
+
+#[test]
+fn utilisation_adds_up_rooms_and_days() {
+    let fx = Fixture::new();
+    let a = fx.classroom("A", Some(30));
+    let b = fx.classroom("B", Some(30));
+    fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, Some(a));
+    fx.soutenance("2026-06-22 09:30", GENIE_INDUSTRIEL, Some(b));
+    classroom::add_classroom_closure(
+        &fx.conn,
+        &fx.actor,
+        NewClosure {
+            classroom_id: b,
+            start_date: "2026-06-23".to_string(),
+            end_date: "2026-06-23".to_string(),
+            reason: None,
+        },
+    )
+    .unwrap();
+
+    let report =
+        occupancy::get_classroom_utilisation(&fx.conn, "2026-06-22".to_string(), "2026-06-23".to_string(), None, None).unwrap();
+    assert_eq!(report.rooms.iter().map(|r| r.defences).collect::<Vec<_>>(), [1, 1]);
+    assert_eq!((report.days[0].rooms_used, report.days[0].peak_rooms), (2, 2));
+    // Nominal 08:00-18:00 on both days, except B closed on the 23rd.
+    assert_eq!(report.total.open_minutes, 3 * 600);
+    assert_eq!(report.total.occupied_minutes, 120);
+
+    // Defences running past closing time or on a closed day do not count beyond the hours.
+    fx.soutenance("2026-06-22 17:30", GENIE_INDUSTRIEL, Some(a));
+    fx.soutenance("2026-06-23 10:00", GENIE_INDUSTRIEL, Some(b));
+    let report =
+        occupancy::get_classroom_utilisation(&fx.conn, "2026-06-22".to_string(), "2026-06-23".to_string(), None, None).unwrap();
+    assert_eq!(report.total.occupied_minutes, 150);
+    assert!(report.rooms.iter().all(|r| r.usage.rate <= 1.0));
+
+    let only_a =
+        occupancy::get_classroom_utilisation(&fx.conn, "2026-06-22".to_string(), "2026-06-22".to_string(), Some(vec![a]), None)
+            .unwrap();
+    assert_eq!(only_a.rooms.len(), 1);
+}
@@ -0,0 +1,381 @@
+mod common;
+
+use common::{error, temp_path, Fixture, GENIE_INDUSTRIEL, GENIE_INFORMATIQUE};
+use gestion_soutenances_lib::models::etudiant;
+use gestion_soutenances_lib::models::invite_soutenance::{self, InvitationStatus};
+use gestion_soutenances_lib::models::journal;
+use gestion_soutenances_lib::models::jury_soutenance::{self, JuryRole};
+use gestion_soutenances_lib::models::soutenance::{self, JuryAssignment, SoutenancePlan};
+use gestion_soutenances_lib::query::QueryOptions;
+
+fn plan(date: &str, hour: &str) -> SoutenancePlan {
+    SoutenancePlan {
+        date: Some(date.to_string()),
+        hour: Some(hour.to_string()),
+        specialite_id: GENIE_INDUSTRIEL,
+        classroom_id: None,
+        pfe: Some("Optimisation logistique".to_string()),
+        session_id: None,
+        expected_attendees: None,
+        juries: Vec::new(),
+        invitee_ids: Vec::new(),
+        student_ids: Vec::new(),
+    }
+}
+
+fn seat(jury_id: i32, role: JuryRole) -> JuryAssignment {
+    JuryAssignment {
+        jury_id,
+        role,
+        justification: None,
+    }
+}
+
+#[test]
+fn a_soutenance_is_read_back_with_its_participants() {
+    let fx = Fixture::new();
+    let room = fx.classroom("Salle 1", Some(30));
+    let id = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, Some(room));
+    let president = fx.jury("Sami", "Ben Ali");
+    let student = fx.student("Amira", GENIE_INDUSTRIEL);
+    let invitee = fx.invitee("Karim");
+    fx.seat(president, id, JuryRole::President);
+    fx.assign(student, id);
+    invite_soutenance::create_invite_soutenance(&fx.conn, &fx.actor, invitee, id).unwrap();
+
+    let found = soutenance::get_soutenance(&fx.conn, id).unwrap();
+    assert_eq!(found.session_id, Some(fx.session()));
+    assert_eq!(found.classroom.map(|c| c.id), Some(room));
+    assert_eq!(found.juries.len(), 1);
+    assert_eq!((found.juries[0].jury.id, found.juries[0].role), (president, JuryRole::President));
+    assert_eq!(found.students.iter().map(|s| s.id).collect::<Vec<_>>(), [student]);
+    assert_eq!(found.invitees.iter().map(|i| i.id).collect::<Vec<_>>(), [invitee]);
+
+    assert_eq!(soutenance::get_soutenance_students(&fx.conn, id).unwrap(), [student]);
+    assert_eq!(soutenance::get_soutenance_jurys(&fx.conn, id).unwrap(), [(president, "president".to_string())]);
+    assert_eq!(soutenance::get_soutenance_invites(&fx.conn, id).unwrap(), [invitee]);
+}
+
+#[test]
+fn updating_a_soutenance_moves_it() {
+    let fx = Fixture::new();
+    let id = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    let room = fx.classroom("Salle 2", None);
+
+    soutenance::update_soutenance(
+        &fx.conn,
+        &fx.actor,
+        id,
+        Some("2026-06-23".to_string()),
+        Some("14:00".to_string()),
+        GENIE_INFORMATIQUE,
+        Some(room),
+        Some("Nouveau titre".to_string()),
+    )
+    .unwrap();
+
+    let moved = soutenance::get_soutenance(&fx.conn, id).unwrap();
+    assert_eq!((moved.date.as_deref(), moved.hour.as_deref()), (Some("2026-06-23"), Some("14:00")));
+    assert_eq!((moved.specialite_id, moved.pfe.as_deref()), (GENIE_INFORMATIQUE, Some("Nouveau titre")));
+    assert_eq!(moved.classroom.map(|c| c.id), Some(room));
+}
+
+#[test]
+fn deleting_a_soutenance_can_be_undone() {
+    let fx = Fixture::new();
+    let id = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    let jury = fx.jury("Sami", "Ben Ali");
+    let student = fx.student("Amira", GENIE_INDUSTRIEL);
+    fx.seat(jury, id, JuryRole::Examinateur);
+    fx.assign(student, id);
+
+    soutenance::delete_soutenance(&fx.conn, &fx.actor, id).unwrap();
+    assert!(soutenance::get_soutenance(&fx.conn, id).is_err());
+    assert_eq!(fx.count("jury_soutenance"), 0);
+    assert!(soutenance::get_soutenance_students(&fx.conn, id).unwrap().is_empty());
+
+    let undone = journal::undo(&fx.conn, &fx.actor).unwrap().expect("nothing to undo");
+    assert_eq!(undone.label, "delete_soutenance");
+    let restored = soutenance::get_soutenance(&fx.conn, id).unwrap();
+    assert_eq!(restored.juries.len(), 1);
+    assert_eq!(restored.students.iter().map(|s| s.id).collect::<Vec<_>>(), [student]);
+}
+
+#[test]
+fn a_soutenance_is_created_with_its_participants_in_one_step() {
+    let fx = Fixture::new();
+    let juries = [fx.jury("Sami", "Ben Ali"), fx.jury("Leila", "Trabelsi"), fx.jury("Mehdi", "Gharbi")];
+    let student = fx.student("Amira", GENIE_INDUSTRIEL);
+    let invitee = fx.invitee("Karim");
+
+    let mut full = plan("2026-06-22", "09:00");
+    full.juries = vec![
+        seat(juries[0], JuryRole::President),
+        seat(juries[1], JuryRole::Rapporteur),
+        seat(juries[2], JuryRole::Examinateur),
+    ];
+    full.invitee_ids = vec![invitee];
+    full.student_ids = vec![student];
+    let created = fx.write(|conn, actor| soutenance::create_soutenance_with_participants(conn, actor, full)).unwrap();
+    assert!(created.warnings.is_empty(), "{:?}", created.warnings);
+    assert_eq!((created.juries.len(), created.invitees.len(), created.students.len()), (3, 1, 1));
+
+    let mut incomplete = plan("2026-06-23", "09:00");
+    incomplete.juries = vec![seat(juries[0], JuryRole::President)];
+    let created = fx.write(|conn, actor| soutenance::create_soutenance_with_participants(conn, actor, incomplete)).unwrap();
+    assert!(created.warnings.iter().any(|w| w.contains("at least 3 are required")), "{:?}", created.warnings);
+}
+
+#[test]
+fn a_soutenance_with_a_bad_participant_leaves_nothing_behind() {
+    let fx = Fixture::new();
+    let jury = fx.jury("Sami", "Ben Ali");
+    let before = fx.count("soutenance");
+
+    let mut bad = plan("2026-06-22", "09:00");
+    bad.juries = vec![seat(jury, JuryRole::President)];
+    bad.student_ids = vec![999];
+    let message = error(fx.write(|conn, actor| soutenance::create_soutenance_with_participants(conn, actor, bad)));
+    assert!(message.starts_with("Student 999"), "{}", message);
+    assert_eq!(fx.count("soutenance"), before);
+    assert_eq!(fx.count("jury_soutenance"), 0);
+}
+
+#[test]
+fn a_soutenance_planned_in_one_step_checks_the_jury_against_its_students() {
+    let fx = Fixture::new();
+    let supervisor = fx.jury("Sami", "Ben Ali");
+    let student = fx.student("Amira", GENIE_INDUSTRIEL);
+    etudiant::set_student_supervisor(&fx.conn, &fx.actor, student, Some(supervisor)).unwrap();
+    let before = fx.count("soutenance");
+
+    let mut presided = plan("2026-06-22", "09:00");
+    presided.juries = vec![seat(supervisor, JuryRole::President)];
+    presided.student_ids = vec![student];
+    let message = error(fx.write(|conn, actor| soutenance::create_soutenance_with_participants(conn, actor, presided)));
+    assert!(message.contains("supervisor cannot preside"), "{}", message);
+    assert_eq!(fx.count("soutenance"), before);
+}
+
+#[test]
+fn a_room_too_small_for_the_participants_is_reported() {
+    let fx = Fixture::new();
+    let room = fx.classroom("Bureau", Some(2));
+    let juries = [fx.jury("Sami", "Ben Ali"), fx.jury("Leila", "Trabelsi"), fx.jury("Mehdi", "Gharbi")];
+
+    let mut crowded = plan("2026-06-22", "09:00");
+    crowded.classroom_id = Some(room);
+    crowded.juries = vec![
+        seat(juries[0], JuryRole::President),
+        seat(juries[1], JuryRole::Rapporteur),
+        seat(juries[2], JuryRole::Examinateur),
+    ];
+    let created = fx.write(|conn, actor| soutenance::create_soutenance_with_participants(conn, actor, crowded)).unwrap();
+    assert!(created.warnings.iter().any(|w| w.contains("holds 2 people but 3 are expected")), "{:?}", created.warnings);
+}
+
+#[test]
+fn specialite_soutenances_are_filtered() {
+    let fx = Fixture::new();
+    let room = fx.classroom("Salle 1", None);
+    let jury = fx.jury("Sami", "Ben Ali");
+    let early = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, Some(room));
+    let late = fx.soutenance("2026-06-25 09:00", GENIE_INDUSTRIEL, None);
+    fx.soutenance("2026-06-22 11:00", GENIE_INFORMATIQUE, None);
+    fx.seat(jury, late, JuryRole::Examinateur);
+
+    let ids = |options: QueryOptions| {
+        soutenance::get_specialite_soutenances(&fx.conn, GENIE_INDUSTRIEL, None, Some(options))
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|s| s.id)
+            .collect::<Vec<_>>()
+    };
+    let mut all = ids(QueryOptions::default());
+    all.sort();
+    assert_eq!(all, [early, late]);
+    assert_eq!(
+        ids(QueryOptions {
+            date_from: Some("2026-06-24".to_string()),
+            ..Default::default()
+        }),
+        [late]
+    );
+    assert_eq!(
+        ids(QueryOptions {
+            classroom_id: Some(room),
+            ..Default::default()
+        }),
+        [early]
+    );
+    assert_eq!(
+        ids(QueryOptions {
+            jury_id: Some(jury),
+            ..Default::default()
+        }),
+        [late]
+    );
+}
+
+#[test]
+fn the_planning_is_exported_as_csv() {
+    let fx = Fixture::new();
+    let id = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    fx.assign(fx.student("Amira", GENIE_INDUSTRIEL), id);
+    fx.seat(fx.jury("Sami", "Ben Ali"), id, JuryRole::President);
+    let path = temp_path("planning.csv");
+
+    assert_eq!(soutenance::write_planning_csv(&fx.conn, path.to_str().unwrap(), None).unwrap(), 1);
+    let csv = std::fs::read_to_string(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+    assert!(csv.starts_with("id,date,hour,classroom,specialite,pfe,students,jury,invitees"), "{}", csv);
+    assert!(csv.contains("Amira Etudiant"));
+    assert!(csv.contains("Sami Ben Ali (Président)"));
+}
+
+#[test]
+fn the_defence_duration_must_be_positive() {
+    let fx = Fixture::new();
+    assert!(error(soutenance::set_defence_duration(&fx.conn, &fx.actor, 0)).contains("at least one minute"));
+
+    soutenance::set_defence_duration(&fx.conn, &fx.actor, 45).unwrap();
+    assert_eq!(soutenance::get_defence_duration(&fx.conn).unwrap(), 45);
+}
+
+#[test]
+fn jury_roles_accept_labels_and_the_legacy_member() {
+    assert_eq!(JuryRole::parse("Président").unwrap(), JuryRole::President);
+    assert_eq!(JuryRole::parse("member").unwrap(), JuryRole::Examinateur);
+    assert_eq!(JuryRole::parse("Invité d'honneur").unwrap(), JuryRole::InviteHonneur);
+    assert!(JuryRole::parse("secretaire").unwrap_err().contains("Unknown jury role"));
+    assert_eq!(jury_soutenance::get_jury_roles().len(), JuryRole::ALL.len());
+}
+
+#[test]
+fn a_jury_member_changes_role_and_leaves() {
+    let fx = Fixture::new();
+    let id = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    let jury = fx.jury("Sami", "Ben Ali");
+    fx.seat(jury, id, JuryRole::Examinateur);
+
+    jury_soutenance::update_jury_soutenance(&fx.conn, &fx.actor, jury, id, "rapporteur".to_string()).unwrap();
+    assert_eq!(jury_soutenance::get_jury_soutenance(&fx.conn, jury, id).unwrap().role, JuryRole::Rapporteur);
+
+    jury_soutenance::delete_jury_soutenance(&fx.conn, &fx.actor, jury, id).unwrap();
+    assert!(jury_soutenance::get_jury_soutenance(&fx.conn, jury, id).is_err());
+    journal::undo(&fx.conn, &fx.actor).unwrap();
+    assert_eq!(jury_soutenance::get_jury_soutenance(&fx.conn, jury, id).unwrap().role, JuryRole::Rapporteur);
+}
+
+#[test]
+fn invitees_answer_with_their_token() {
+    let fx = Fixture::new();
+    let id = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    let karim = fx.invitee("Karim");
+    let nadia = fx.invitee("Nadia");
+    invite_soutenance::create_invite_soutenance(&fx.conn, &fx.actor, karim, id).unwrap();
+    invite_soutenance::create_invite_soutenance(&fx.conn, &fx.actor, nadia, id).unwrap();
+
+    let invitation = invite_soutenance::get_invite_soutenance(&fx.conn, karim, id).unwrap();
+    assert_eq!(invitation.status, InvitationStatus::Pending);
+    assert_eq!(invitation.rsvp_token.len(), 32);
+
+    let answered = invite_soutenance::respond_to_invitation(&fx.conn, &fx.actor, invitation.rsvp_token.clone(), false).unwrap();
+    assert_eq!(answered.status, InvitationStatus::Declined);
+    assert!(answered.responded_at.is_some());
+    assert!(invite_soutenance::respond_to_invitation(&fx.conn, &fx.actor, "unknown".to_string(), true).is_err());
+
+    let counts = invite_soutenance::get_invitation_counts(&fx.conn, id).unwrap();
+    assert_eq!((counts.pending, counts.declined, counts.total), (1, 1, 2));
+    assert_eq!(invite_soutenance::get_soutenance_invitations(&fx.conn, id).unwrap().len(), 2);
+}
+
+#[test]
+fn a_regenerated_token_replaces_the_old_one() {
+    let fx = Fixture::new();
+    let id = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    let karim = fx.invitee("Karim");
+    invite_soutenance::create_invite_soutenance(&fx.conn, &fx.actor, karim, id).unwrap();
+    let old = invite_soutenance::get_invite_soutenance(&fx.conn, karim, id).unwrap().rsvp_token;
+
+    let new = invite_soutenance::regenerate_rsvp_token(&fx.conn, &fx.actor, karim, id).unwrap();
+    assert_ne!(old, new);
+    assert!(invite_soutenance::respond_to_invitation(&fx.conn, &fx.actor, old, true).is_err());
+    invite_soutenance::respond_to_invitation(&fx.conn, &fx.actor, new, true).unwrap();
+
+    let nobody = fx.invitee("Nadia");
+    assert!(error(invite_soutenance::regenerate_rsvp_token(&fx.conn, &fx.actor, nobody, id)).contains("is not invited"));
+}
+
+#[test]
+fn invitees_check_in_once_on_the_day() {
+    let fx = Fixture::new();
+    let today = fx.soutenance(&format!("{} 09:00", fx.today()), GENIE_INDUSTRIEL, None);
+    let later = fx.soutenance("2099-06-22 09:00", GENIE_INDUSTRIEL, None);
+    let karim = fx.invitee("Karim");
+    invite_soutenance::create_invite_soutenance(&fx.conn, &fx.actor, karim, today).unwrap();
+    invite_soutenance::create_invite_soutenance(&fx.conn, &fx.actor, karim, later).unwrap();
+    let token = |id| invite_soutenance::get_invite_soutenance(&fx.conn, karim, id).unwrap().rsvp_token;
+
+    assert!(error(invite_soutenance::check_in_invitation(&fx.conn, &fx.actor, token(later))).contains("only open on that day"));
+
+    let checked_in = invite_soutenance::check_in_invitation(&fx.conn, &fx.actor, token(today)).unwrap();
+    assert_eq!(checked_in.status, InvitationStatus::Attended);
+    assert!(error(invite_soutenance::check_in_invitation(&fx.conn, &fx.actor, token(today))).starts_with("Already checked in"));
+    assert!(error(invite_soutenance::respond_to_invitation(&fx.conn, &fx.actor, token(today), false)).contains("already attended"));
+}
+
+#[test]
+fn check_in_ignores_a_time_stored_with_the_date() {
+    let fx = Fixture::new();
+    let id = fx.soutenance(&format!("{} 09:00", fx.today()), GENIE_INDUSTRIEL, None);
+    fx.conn
+        .execute("UPDATE soutenance SET date = date || ' 09:00:00' WHERE id = ?1", [id])
+        .unwrap();
+    let karim = fx.invitee("Karim");
+    invite_soutenance::create_invite_soutenance(&fx.conn, &fx.actor, karim, id).unwrap();
+    let token = invite_soutenance::get_invite_soutenance(&fx.conn, karim, id).unwrap().rsvp_token;
+
+    let checked_in = invite_soutenance::check_in_invitation(&fx.conn, &fx.actor, token).unwrap();
+    assert_eq!(checked_in.status, InvitationStatus::Attended);
+}
+
+#[test]
+fn an_invitation_is_withdrawn() {
+    let fx = Fixture::new();
+    let id = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    let karim = fx.invitee("Karim");
+    invite_soutenance::create_invite_soutenance(&fx.conn, &fx.actor, karim, id).unwrap();
+
+    invite_soutenance::delete_invite_soutenance(&fx.conn, &fx.actor, karim, id).unwrap();
+    assert!(invite_soutenance::get_soutenance_invitations(&fx.conn, id).unwrap().is_empty());
+}
+
+#[test]
+fn rsvp_tokens_stay_out_of_the_audit_log_and_the_journal() {
+    let fx = Fixture::new();
+    let id = fx.soutenance("2026-06-22 09:00", GENIE_INDUSTRIEL, None);
+    let karim = fx.invitee("Karim");
+    invite_soutenance::create_invite_soutenance(&fx.conn, &fx.actor, karim, id).unwrap();
+    let token = invite_soutenance::get_soutenance_invitations(&fx.conn, id).unwrap()[0].rsvp_token.clone();
+    invite_soutenance::delete_invite_soutenance(&fx.conn, &fx.actor, karim, id).unwrap();
+
+    let leaked: i64 = fx
+        .conn
+        .query_row(
+            "SELECT (SELECT COUNT(*) FROM audit_log WHERE IFNULL(before_json, '') || IFNULL(after_json, '') || changes_json LIKE ?1)
+            + (SELECT COUNT(*) FROM operation_journal_entry WHERE IFNULL(before_json, '') || IFNULL(after_json, '') LIKE ?1)",
+            [format!("%{}%", token)],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(leaked, 0);
+
+    // Undoing the withdrawal brings the invitation back with a new token.
+    journal::undo(&fx.conn, &fx.actor).unwrap().expect("nothing to undo");
+    let restored = invite_soutenance::get_soutenance_invitations(&fx.conn, id).unwrap();
+    assert_eq!(restored.len(), 1);
+    assert!(!restored[0].rsvp_token.is_empty());
+    assert_ne!(restored[0].rsvp_token, token);
+}
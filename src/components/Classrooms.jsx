@@ -5,7 +5,7 @@ const Classrooms = () => {
   const [classrooms, setClassrooms] = useState([]); // State to store classrooms
   const [selectedIds, setSelectedIds] = useState([]); // State to store selected classroom IDs
   const [showModal, setShowModal] = useState(false); // State to control modal visibility
-  const [formData, setFormData] = useState({ id: null, name: ''}); // State for form data
+  const [formData, setFormData] = useState({ id: null, name: '', capacity: '', building: '', floor: '', equipment: '' }); // State for form data
   const [isEditMode, setIsEditMode] = useState(false); // State to track if modal is in edit mode
 
   // Fetch classrooms from the Tauri backend
@@ -16,7 +16,7 @@ const Classrooms = () => {
   const fetchClassrooms = async () => {
     try {
       const response = await invoke('get_all_classrooms');
-      setClassrooms(response);
+      setClassrooms(response.items);
     } catch (error) {
       console.error('Error fetching classrooms:', error);
     }
@@ -50,14 +50,20 @@ const Classrooms = () => {
 
   // Handle opening the modal for adding a new classroom
   const handleAdd = () => {
-    setFormData({ id: null, name: ''}); // Reset form data
+    setFormData({ id: null, name: '', capacity: '', building: '', floor: '', equipment: '' }); // Reset form data
     setIsEditMode(false); // Set to add mode
     setShowModal(true); // Show the modal
   };
 
   // Handle opening the modal for updating a classroom
   const handleEdit = (classroom) => {
-    setFormData(classroom); // Set form data to the selected classroom
+    setFormData({
+      ...classroom,
+      capacity: classroom.capacity ?? '',
+      building: classroom.building ?? '',
+      floor: classroom.floor ?? '',
+      equipment: classroom.equipment.join(', '),
+    }); // Set form data to the selected classroom
     setIsEditMode(true); // Set to edit mode
     setShowModal(true); // Show the modal
   };
@@ -65,16 +71,23 @@ const Classrooms = () => {
   // Handle form submission (add or update)
   const handleSubmit = async (e) => {
     e.preventDefault();
+    const details = {
+      capacity: formData.capacity === '' ? null : parseInt(formData.capacity),
+      building: formData.building || null,
+      floor: formData.floor || null,
+      equipment: formData.equipment.split(',').map((tag) => tag.trim()).filter((tag) => tag),
+    };
     try {
       if (isEditMode) {
         // Update existing classroom
         await invoke('update_classroom', { 
           id: formData.id,
-          name: formData.name 
+          name: formData.name,
+          details
         });
       } else {
         // Add new classroom
-        await invoke('create_classroom', { name: formData.name });
+        await invoke('create_classroom', { name: formData.name, details });
       }
       setShowModal(false); // Close the modal
       fetchClassrooms(); // Refresh the classrooms list
@@ -125,6 +138,9 @@ const Classrooms = () => {
             </th>
             <th scope="col">ID</th>
             <th scope="col">Nom</th>
+            <th scope="col">Capacité</th>
+            <th scope="col">Bâtiment</th>
+            <th scope="col">Équipements</th>
             <th scope="col">Action</th>
           </tr>
         </thead>
@@ -141,6 +157,9 @@ const Classrooms = () => {
               </td>
               <th>{classroom.id}</th>
               <td>{classroom.name}</td>
+              <td>{classroom.capacity ?? '-'}</td>
+              <td>{[classroom.building, classroom.floor].filter((part) => part).join(' / ')}</td>
+              <td>{classroom.equipment.join(', ')}</td>
               <td>
                 <button className="btn btn-primary" onClick={() => handleEdit(classroom)}>
                   Modifier
@@ -174,6 +193,54 @@ const Classrooms = () => {
                       required
                     />
                   </div>
+                  <div className="mb-3">
+                    <label htmlFor="capacity" className="form-label">Capacité</label>
+                    <input
+                      type="number"
+                      min="0"
+                      className="form-control"
+                      id="capacity"
+                      name="capacity"
+                      value={formData.capacity}
+                      onChange={handleInputChange}
+                    />
+                  </div>
+                  <div className="row mb-3">
+                    <div className="col">
+                      <label htmlFor="building" className="form-label">Bâtiment</label>
+                      <input
+                        type="text"
+                        className="form-control"
+                        id="building"
+                        name="building"
+                        value={formData.building}
+                        onChange={handleInputChange}
+                      />
+                    </div>
+                    <div className="col">
+                      <label htmlFor="floor" className="form-label">Étage</label>
+                      <input
+                        type="text"
+                        className="form-control"
+                        id="floor"
+                        name="floor"
+                        value={formData.floor}
+                        onChange={handleInputChange}
+                      />
+                    </div>
+                  </div>
+                  <div className="mb-3">
+                    <label htmlFor="equipment" className="form-label">Équipements</label>
+                    <input
+                      type="text"
+                      className="form-control"
+                      id="equipment"
+                      name="equipment"
+                      placeholder="projector, videoconference, accessibility"
+                      value={formData.equipment}
+                      onChange={handleInputChange}
+                    />
+                  </div>
                   <button type="submit" className="btn btn-primary">
                     {isEditMode ? 'Modifier' : 'Ajouter'}
                   </button>
@@ -8,8 +8,7 @@ const Defences = () => {
   const { id } = useParams(); // Get the department ID from the URL
   const [department, setDepartment] = useState({ name: '' });
 
-  const [defences, setDefences] = useState([]); // All defences
-  const [filteredDefences, setFilteredDefences] = useState([]); // Filtered defences
+  const [filteredDefences, setFilteredDefences] = useState([]); // Defences matching the filter
   const [classrooms, setClassrooms] = useState([]);
   const [students, setStudents] = useState([]);
   const [juries, setJuries] = useState([]);
@@ -19,6 +18,8 @@ const Defences = () => {
   const [selectedDate, setSelectedDate] = useState('');
   const [selectedClassroom, setSelectedClassroom] = useState('');
   const [selectedJuries, setSelectedJuries] = useState([]);
+  const [juryRoles, setJuryRoles] = useState({}); // Role chosen for each selected jury, by id
+  const [roleOptions, setRoleOptions] = useState([]);
   const [selectedInvitees, setSelectedInvitees] = useState([]);
   const [selectedStudents, setSelectedStudents] = useState([]);
   const [date, setDate] = useState('');
@@ -34,6 +35,7 @@ const Defences = () => {
     fetchDefences();
     fetchClassrooms();
     fetchJuries();
+    fetchRoleOptions();
     fetchInvitees();
     fetchStudents();
   }, [id, showModal]);
@@ -43,7 +45,7 @@ const Defences = () => {
     try {
       // Get all students for the specialite
       const response = await invoke('get_specialite_students', { specialiteId: parseInt(id) });
-      setStudents(response);
+      setStudents(response.items);
     } catch (error) {
       console.error('Error fetching students:', error);
     }
@@ -58,11 +60,16 @@ const Defences = () => {
     }
   };
 
+  // Filtering happens in the backend
   const fetchDefences = async () => {
     try {
-      const response = await invoke('get_specialite_soutenances', { specialiteId: parseInt(id) });
-      setDefences(response);
-      setFilteredDefences(response); // Initialize filtered defences with all defences
+      const options = {
+        date_from: selectedDate || null,
+        date_to: selectedDate || null,
+        classroom_id: selectedClassroom ? parseInt(selectedClassroom) : null,
+      };
+      const response = await invoke('get_specialite_soutenances', { specialiteId: parseInt(id), options });
+      setFilteredDefences(response.items);
     } catch (error) {
       console.error('Error fetching defences:', error);
     }
@@ -71,7 +78,7 @@ const Defences = () => {
   const fetchClassrooms = async () => {
     try {
       const response = await invoke('get_all_classrooms');
-      setClassrooms(response);
+      setClassrooms(response.items);
     } catch (error) {
       console.error('Error fetching classrooms:', error);
     }
@@ -80,33 +87,33 @@ const Defences = () => {
   const fetchJuries = async () => {
     try {
       const response = await invoke('get_all_jury');
-      setJuries(response);
+      setJuries(response.items);
     } catch (error) {
       console.error('Error fetching juries:', error);
     }
   };
 
+  const fetchRoleOptions = async () => {
+    try {
+      const response = await invoke('get_jury_roles');
+      setRoleOptions(response);
+    } catch (error) {
+      console.error('Error fetching jury roles:', error);
+    }
+  };
+
   const fetchInvitees = async () => {
     try {
       const response = await invoke('get_all_invite');
-      setInvitees(response);
+      setInvitees(response.items);
     } catch (error) {
       console.error('Error fetching invitees:', error);
     }
   };
 
-  // Handle filtering defences on the frontend
   const handleFilterSubmit = (e) => {
     e.preventDefault();
-
-    const filtered = defences.filter((defence) => {
-      const matchesDate = selectedDate ? defence.date?.substring(0, 10) === selectedDate : true;
-      // Ensure selectedClassroom is an ID for comparison, or defence.classroom exists
-      const matchesClassroom = selectedClassroom ? (defence.classroom && defence.classroom.id === parseInt(selectedClassroom)) : true;
-      return matchesDate && matchesClassroom;
-    });
-
-    setFilteredDefences(filtered);
+    fetchDefences();
   };
 
   const handleDelete = async () => {
@@ -146,11 +153,15 @@ const Defences = () => {
         classroomId: selectedClassroom && selectedClassroom.id ? parseInt(selectedClassroom.id) : null,
         date,
         hour,
-        pfe: projectName
+        pfe: projectName,
+        expectedAttendees: selectedStudents.length + selectedJuries.length + selectedInvitees.length
       };
   
       // newSoutenance will now have the full structure (classroom populated, others empty arrays)
       const newSoutenance = await invoke('create_soutenance', soutenanceData);
+      if (newSoutenance.warnings?.length) {
+        await message(newSoutenance.warnings.join('\n'), { title: 'Attention', kind: 'warning' });
+      }
       // Update students with the new soutenance_id
       for (const student of selectedStudents) {
         console.log("new soutenance", newSoutenance.id);
@@ -159,6 +170,7 @@ const Defences = () => {
           firstname: student.firstname,
           lastname: student.lastname,
           address: student.address,
+          email: student.email,
           specialiteId: student.specialite_id,
           soutenanceId: newSoutenance.id
         });
@@ -167,11 +179,20 @@ const Defences = () => {
 
       // Create jury-soutenance relationships
       for (const jury of selectedJuries) {
-        await invoke('create_jury_soutenance', {
+        const assignment = {
           juryId: jury.id,
           soutenanceId: newSoutenance.id,
-          role: 'member' // You might want to make this configurable
-        });
+          role: juryRoles[jury.id] || 'examinateur'
+        };
+        try {
+          await invoke('create_jury_soutenance', assignment);
+        } catch (err) {
+          // Declared conflicts of interest can be overridden with a justification.
+          if (!String(err).includes('justification')) throw err;
+          const justification = window.prompt(`${err}\n\nJustification :`);
+          if (!justification) throw err;
+          await invoke('create_jury_soutenance', { ...assignment, justification });
+        }
       }
 
       // Create invite-soutenance relationships
@@ -182,17 +203,24 @@ const Defences = () => {
         });
       }
 
+      const violations = await invoke('get_soutenance_composition', { soutenanceId: newSoutenance.id });
+      if (violations.length) {
+        await message(violations.map((v) => v.message).join('\n'), { title: 'Composition du jury', kind: 'warning' });
+      }
+
       setProjectName("");
       setDate("");
       setHour("");
       setSelectedStudents([]);
       setSelectedJuries([]);
+      setJuryRoles({});
       setSelectedInvitees([]);
       setSelectedClassroom('');
       setShowModal(false); // Close the modal
       fetchDefences(); // Refresh the list
     } catch (error) {
       console.error('Error creating defence:', error);
+      await message(String(error), { title: 'Erreur', kind: 'error' });
     }
   };
 
@@ -293,6 +321,20 @@ const Defences = () => {
                         label: `${item.firstname} ${item.lastname}`,
                       }))}
                     />
+                    {selectedJuries.map((jury) => (
+                      <div key={jury.id} className="d-flex align-items-center mt-2">
+                        <span className="me-2 flex-grow-1">{jury.firstname} {jury.lastname}</span>
+                        <select
+                          className="form-select form-select-sm w-auto"
+                          value={juryRoles[jury.id] || 'examinateur'}
+                          onChange={(e) => setJuryRoles({ ...juryRoles, [jury.id]: e.target.value })}
+                        >
+                          {roleOptions.map((role) => (
+                            <option key={role.value} value={role.value}>{role.label}</option>
+                          ))}
+                        </select>
+                      </div>
+                    ))}
                   </div>
                   <div className="mb-3">
                     <label className="form-label">Invitées</label>
@@ -460,6 +502,7 @@ const Defences = () => {
                   {defence.juries?.map((jury) => (
                     <div key={jury.id}>
                       {jury.firstname} <span style={{ textTransform: 'uppercase' }}>{jury.lastname}</span>
+                      {' '}<small className="text-muted">({roleOptions.find((role) => role.value === jury.role)?.label || jury.role})</small>
                     </div>
                   ))}
                 </td>
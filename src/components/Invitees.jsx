@@ -19,7 +19,7 @@ const Invitees = () => {
   const fetchInvites = async () => {
     try {
       const response = await invoke("get_all_invite"); // Fetch invitees from Tauri
-      setInvites(response); // Update state with the response
+      setInvites(response.items); // Update state with the response
     } catch (error) {
       console.error('Error fetching invitees:', error);
     }
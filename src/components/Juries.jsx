@@ -19,7 +19,7 @@ const Juries2 = () => {
   const fetchJuries = async () => {
     try {
       const response = await invoke("get_all_jury"); // Fetch juries from Tauri
-      setJuries(response); // Update state with the response
+      setJuries(response.items); // Update state with the response
     } catch (error) {
       console.error('Error fetching juries:', error);
     }
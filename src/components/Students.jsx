@@ -14,6 +14,7 @@ const Students = () => {
     firstname: '', 
     lastname: '', 
     address: '', 
+    email: '',
     specialite_id: parseInt(id) 
   }); // State for form data
   const [isEditMode, setIsEditMode] = useState(false); // State to track if modal is in edit mode
@@ -36,7 +37,7 @@ const Students = () => {
   const fetchStudents = async () => {
     try {
       const response = await invoke('get_students_by_department', { departmentId: parseInt(id) });
-      setStudents(response);
+      setStudents(response.items);
     } catch (error) {
       console.error('Error fetching students:', error);
     }
@@ -77,6 +78,7 @@ const Students = () => {
       firstname: '', 
       lastname: '', 
       address: '', 
+      email: '',
       specialite_id: parseInt(id) 
     }); // Reset form data
     setIsEditMode(false); // Set to add mode
@@ -85,7 +87,7 @@ const Students = () => {
 
   // Handle opening the modal for updating a student
   const handleEdit = (student) => {
-    setFormData(student); // Set form data to the selected student
+    setFormData({ ...student, email: student.email || '' }); // Set form data to the selected student
     setIsEditMode(true); // Set to edit mode
     setShowModal(true); // Show the modal
   };
@@ -100,7 +102,8 @@ const Students = () => {
           id: formData.id,
           firstname: formData.firstname,
           lastname: formData.lastname,
-          address: formData.address
+          address: formData.address,
+          email: formData.email || null
         });
       } else {
         // Add new student
@@ -108,6 +111,7 @@ const Students = () => {
           firstname: formData.firstname,
           lastname: formData.lastname,
           address: formData.address,
+          email: formData.email || null,
           specialiteId: parseInt(id)
         });
       }
@@ -237,6 +241,17 @@ const Students = () => {
                       required
                     />
                   </div>
+                  <div className="mb-3">
+                    <label htmlFor="email" className="form-label">Email</label>
+                    <input
+                      type="email"
+                      className="form-control"
+                      id="email"
+                      name="email"
+                      value={formData.email}
+                      onChange={handleInputChange}
+                    />
+                  </div>
                   <button type="submit" className="btn btn-primary">
                     {isEditMode ? 'Modifier' : 'Ajouter'}
                   </button>
@@ -1,6 +1,7 @@
 import React, { useState } from 'react';
 import { Link, Outlet, useNavigate } from 'react-router-dom';
 import { confirm } from '@tauri-apps/plugin-dialog';
+import { invoke } from '@tauri-apps/api/core';
 
 const Dashboard = () => {
   const [isSidebarOpen, setIsSidebarOpen] = useState(false);
@@ -21,10 +22,8 @@ const Dashboard = () => {
     });
     if (confirmation) {
       try {
-        // Optionally call a Tauri logout command (uncomment if implemented in Rust)
-        // await invoke('logout', { token: localStorage.getItem('token') });
-        
-        // For now, just clear the token and redirect
+        // Forget the backend user so later changes are no longer attributed to them
+        await invoke('logout');
         localStorage.removeItem('token');
         navigate("/login");
       } catch (error) {